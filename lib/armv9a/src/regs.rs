@@ -425,6 +425,8 @@ define_sys_register!(S3_4_C14_C0_6); // CNTPOFF_EL2
 define_sys_register!(CNTP_CVAL_EL0);
 define_sys_register!(CNTP_CTL_EL0);
 define_sys_register!(CNTVCT_EL0);
+define_sys_register!(CNTPCT_EL0);
+define_sys_register!(PMCCNTR_EL0);
 define_sys_register!(CNTV_TVAL_EL0);
 
 macro_rules! define_iss_id {
@@ -488,6 +490,7 @@ define_sys_register!(ID_AA64AFR1_EL1);
 define_iss_id!(ISS_ID_AA64AFR1_EL1, 3, 0, 0, 5, 5);
 
 define_sys_register!(ID_AA64ISAR0_EL1);
+define_bits!(AA64ISAR0, RNDR[63 - 60]);
 define_iss_id!(ISS_ID_AA64ISAR0_EL1, 3, 0, 0, 6, 0);
 
 define_sys_register!(ID_AA64ISAR1_EL1);
@@ -3,6 +3,7 @@ pub mod verifier;
 
 use ciborium::de;
 use coset::CoseSign1;
+use sha2::{Digest, Sha256};
 use std::default::Default;
 use std::fmt::Debug;
 
@@ -31,6 +32,10 @@ const CCA_REALM_PUB_KEY: u32 = 44237;
 const CCA_REALM_INITIAL_MEASUREMENT: u32 = 44238;
 const CCA_REALM_EXTENSIBLE_MEASUREMENTS: u32 = 44239;
 const CCA_REALM_PUB_KEY_HASH_ALGO_ID: u32 = 44240;
+// Islet's own claims, present only when the RMM is built to report them
+const CCA_REALM_COMMITTED_RAM: u32 = 44300;
+// and present only in the token of a debuggable realm
+const CCA_REALM_DEBUG: u32 = 44301;
 
 /* Software components */
 const CCA_SW_COMP_TITLE: u32 = 1;
@@ -40,14 +45,14 @@ const CCA_SW_COMP_SIGNER_ID: u32 = 5;
 const CCA_SW_COMP_HASH_ALGORITHM: u32 = 6;
 
 /* Counts */
-const CLAIM_COUNT_REALM_TOKEN: usize = 6;
+const CLAIM_COUNT_REALM_TOKEN: usize = 8;
 const CLAIM_COUNT_COSE_SIGN1_WRAPPER: usize = 3;
 const CLAIM_COUNT_PLATFORM_TOKEN: usize = 8;
 const CLAIM_COUNT_REALM_EXTENSIBLE_MEASUREMENTS: usize = 4;
 const CLAIM_COUNT_SW_COMPONENT: usize = 5;
 const MAX_SW_COMPONENT_COUNT: usize = 32;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ClaimData
 {
     Bool(bool),
@@ -75,39 +80,6 @@ impl ClaimData
     {
         ClaimData::Text(String::new())
     }
-
-    fn get_bool(&self) -> bool
-    {
-        if let ClaimData::Bool(b) = self {
-            return *b;
-        } else {
-            panic!("ClaimData is not Bool");
-        }
-    }
-    fn get_int64(&self) -> i64
-    {
-        if let ClaimData::Int64(i) = self {
-            return *i;
-        } else {
-            panic!("ClaimData is not Int64");
-        }
-    }
-    fn get_bstr(&self) -> &[u8]
-    {
-        if let ClaimData::Bstr(d) = self {
-            return d;
-        } else {
-            panic!("ClaimData is not Bstr");
-        }
-    }
-    fn get_text(&self) -> &str
-    {
-        if let ClaimData::Text(s) = self {
-            return s;
-        } else {
-            panic!("ClaimData is not Text");
-        }
-    }
 }
 
 impl Default for ClaimData
@@ -149,6 +121,61 @@ pub struct SwComponent
     pub claims: [Claim; CLAIM_COUNT_SW_COMPONENT],
 }
 
+/// The security lifecycle state of the platform, i.e., the PSA major state (bits[15:8])
+/// of the lifecycle claim. The minor state (bits[7:0]) is implementation defined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecurityLifecycle
+{
+    Unknown,
+    AssemblyAndTest,
+    PsaRotProvisioning,
+    Secured,
+    NonPsaRotDebug,
+    RecoverablePsaRotDebug,
+    Decommissioned,
+}
+
+impl SecurityLifecycle
+{
+    pub fn from_claim(value: i64) -> Option<Self>
+    {
+        match value {
+            0x0000..=0x00ff => Some(Self::Unknown),
+            0x1000..=0x10ff => Some(Self::AssemblyAndTest),
+            0x2000..=0x20ff => Some(Self::PsaRotProvisioning),
+            0x3000..=0x30ff => Some(Self::Secured),
+            0x4000..=0x40ff => Some(Self::NonPsaRotDebug),
+            0x5000..=0x50ff => Some(Self::RecoverablePsaRotDebug),
+            0x6000..=0x60ff => Some(Self::Decommissioned),
+            _ => None,
+        }
+    }
+
+    // The progress towards production, which the debug and decommissioned states have left
+    fn rank(&self) -> Option<u8>
+    {
+        match self {
+            Self::Unknown => Some(0),
+            Self::AssemblyAndTest => Some(1),
+            Self::PsaRotProvisioning => Some(2),
+            Self::Secured => Some(3),
+            Self::NonPsaRotDebug | Self::RecoverablePsaRotDebug | Self::Decommissioned => None,
+        }
+    }
+
+    /// Returns whether the platform has made it at least as far as `min` towards production.
+    ///
+    /// A platform in a debug or decommissioned state meets no minimum,
+    /// as its security can no longer be relied on.
+    pub fn satisfies(&self, min: SecurityLifecycle) -> bool
+    {
+        match (self.rank(), min.rank()) {
+            (Some(state), Some(min)) => state >= min,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct AttestationClaims
 {
@@ -195,6 +222,82 @@ impl AttestationClaims
             .or(find(&self.plat_token_claims))
     }
 
+    /// Returns the URL or identifier of the verification service of the platform,
+    /// where a relying party can fetch the endorsements, if the token carries it.
+    pub fn verification_service(&self) -> Option<&str>
+    {
+        match self.claim("Verification service")? {
+            Claim {
+                present: true,
+                data: ClaimData::Text(service),
+                ..
+            } => Some(service),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the realm was created with debug access,
+    /// which a verifier should refuse to trust in production.
+    pub fn realm_debug(&self) -> bool
+    {
+        matches!(
+            self.claim("Realm debug"),
+            Some(Claim {
+                present: true,
+                data: ClaimData::Bool(true),
+                ..
+            })
+        )
+    }
+
+    /// Returns the first 8 bytes of the SHA-256 hash of the realm public key,
+    /// which tells the tokens signed before and after a rotation of the RAK apart.
+    ///
+    /// NOTE: this is a fingerprint of the key rather than a counter,
+    /// so a newer key doesn't have a greater value.
+    pub fn key_generation(&self) -> Option<u64>
+    {
+        match self.claim("Realm signing public key")? {
+            Claim {
+                present: true,
+                data: ClaimData::Bstr(key),
+                ..
+            } => {
+                let hash = Sha256::digest(key);
+                Some(u64::from_be_bytes(hash[..8].try_into().ok()?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the security lifecycle state of the platform,
+    /// unless the lifecycle claim is missing or out of the defined states.
+    pub fn security_lifecycle(&self) -> Option<SecurityLifecycle>
+    {
+        match self.claim("Lifecycle")? {
+            Claim {
+                present: true,
+                data: ClaimData::Int64(value),
+                ..
+            } => SecurityLifecycle::from_claim(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of granules whose RIPAS was RAM at attestation time,
+    /// unless the RMM doesn't report it.
+    pub fn committed_ram(&self) -> Option<u64>
+    {
+        match self.claim("Realm committed RAM")? {
+            Claim {
+                present: true,
+                data: ClaimData::Int64(value),
+                ..
+            } => u64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
     // For simulation on x86 system.
     pub fn claim_mut<'a>(&'a mut self, title: &'static str) -> Option<&'a mut Claim>
     {
@@ -262,6 +365,20 @@ impl AttestationClaims
             "Realm initial measurement",
             false,
         );
+        claims.realm_token_claims[6].init(
+            false,
+            ClaimData::new_bool(),
+            CCA_REALM_DEBUG,
+            "Realm debug",
+            false,
+        );
+        claims.realm_token_claims[7].init(
+            false,
+            ClaimData::new_int64(),
+            CCA_REALM_COMMITTED_RAM,
+            "Realm committed RAM",
+            false,
+        );
 
         Self::init_cose_sign1_claims(&mut claims.plat_cose_sign1_wrapper);
 
@@ -384,6 +501,7 @@ pub enum TokenError
     InvalidTag(&'static str),
     InvalidTokenFormat(&'static str),
     Signature,
+    NonCanonicalCbor,
     Ciborium(de::Error<std::io::Error>),
     Coset(coset::CoseError),
     Ecdsa(ecdsa::Error),
@@ -1,6 +1,6 @@
 use super::*;
 use ciborium::{de, value::Value};
-use coset::{AsCborValue, CoseSign1};
+use coset::{AsCborValue, CoseSign1, TaggedCborSerializable};
 
 fn unpack_i64(val: &Value) -> Result<i64, TokenError>
 {
@@ -97,6 +97,21 @@ fn get_claim(val: Value, claim: &mut Claim) -> Result<(), TokenError>
     Ok(())
 }
 
+/// Returns the next element of a CBOR array or map which has been checked to have it.
+fn next_elem<T>(iter: &mut impl Iterator<Item = T>, err: &'static str) -> Result<T, TokenError>
+{
+    iter.next().ok_or(TokenError::InvalidTokenFormat(err))
+}
+
+/// Returns the bytes of the claim, which must be present.
+fn claim_bstr<'a>(claim: &'a Claim, err: &'static str) -> Result<&'a [u8], TokenError>
+{
+    match &claim.data {
+        ClaimData::Bstr(data) if claim.present => Ok(data),
+        _ => Err(TokenError::InvalidTokenFormat(err)),
+    }
+}
+
 fn find_claim(claims: &mut [Claim], key: i64) -> Option<&mut Claim>
 {
     for elem in claims {
@@ -132,7 +147,10 @@ fn get_claims_from_map(
 
 fn verify_realm_token(attest_claims: &mut AttestationClaims) -> Result<(), TokenError>
 {
-    let realm_payload = attest_claims.realm_cose_sign1_wrapper[1].data.get_bstr();
+    let realm_payload = claim_bstr(
+        &attest_claims.realm_cose_sign1_wrapper[1],
+        "no realm payload",
+    )?;
     let val = de::from_reader(&realm_payload[..])?;
     let map = unpack_map(val, "realm token not a map")?;
 
@@ -144,7 +162,7 @@ fn verify_realm_token(attest_claims: &mut AttestationClaims) -> Result<(), Token
         return Err(TokenError::InvalidTokenFormat("no rems"));
     }
 
-    let rems = rest.into_iter().next().unwrap();
+    let rems = next_elem(&mut rest.into_iter(), "no rems")?;
     let rems = unpack_keyed_array(rems, CCA_REALM_EXTENSIBLE_MEASUREMENTS, "rems array")?;
 
     if rems.len() != CLAIM_COUNT_REALM_EXTENSIBLE_MEASUREMENTS {
@@ -165,7 +183,10 @@ fn verify_realm_token(attest_claims: &mut AttestationClaims) -> Result<(), Token
 
 fn verify_platform_token(attest_claims: &mut AttestationClaims) -> Result<(), TokenError>
 {
-    let platform_payload = attest_claims.plat_cose_sign1_wrapper[1].data.get_bstr();
+    let platform_payload = claim_bstr(
+        &attest_claims.plat_cose_sign1_wrapper[1],
+        "no platform payload",
+    )?;
     let val = de::from_reader(&platform_payload[..])?;
     let map = unpack_map(val, "platform token not a map")?;
 
@@ -177,7 +198,7 @@ fn verify_platform_token(attest_claims: &mut AttestationClaims) -> Result<(), To
         return Err(TokenError::InvalidTokenFormat("no sw components"));
     }
 
-    let sw_components = rest.into_iter().next().unwrap();
+    let sw_components = next_elem(&mut rest.into_iter(), "no sw components")?;
     let sw_components =
         unpack_keyed_array(sw_components, CCA_PLAT_SW_COMPONENTS, "sw components array")?;
 
@@ -228,13 +249,22 @@ fn verify_token_sign1(
     let mut iter = vec.into_iter();
 
     // Protected header
-    get_claim(iter.next().unwrap(), &mut cose_sign1_wrapper[0])?;
+    get_claim(
+        next_elem(&mut iter, "no protected header")?,
+        &mut cose_sign1_wrapper[0],
+    )?;
     // Unprotected header, map, may me empty (ignored)
-    iter.next().unwrap();
+    next_elem(&mut iter, "no unprotected header")?;
     // Payload
-    get_claim(iter.next().unwrap(), &mut cose_sign1_wrapper[1])?;
+    get_claim(
+        next_elem(&mut iter, "no payload")?,
+        &mut cose_sign1_wrapper[1],
+    )?;
     // Signature
-    get_claim(iter.next().unwrap(), &mut cose_sign1_wrapper[2])?;
+    get_claim(
+        next_elem(&mut iter, "no signature")?,
+        &mut cose_sign1_wrapper[2],
+    )?;
 
     Ok(())
 }
@@ -252,10 +282,13 @@ fn verify_cca_token(buf: &[u8]) -> Result<(Vec<u8>, Vec<u8>), TokenError>
     }
 
     let mut iter = map.into_iter();
-    let platform =
-        unpack_keyed_bytes(iter.next().unwrap(), CCA_PLAT_TOKEN, "platform token bytes")?;
+    let platform = unpack_keyed_bytes(
+        next_elem(&mut iter, "no platform token")?,
+        CCA_PLAT_TOKEN,
+        "platform token bytes",
+    )?;
     let realm = unpack_keyed_bytes(
-        iter.next().unwrap(),
+        next_elem(&mut iter, "no realm token")?,
         CCA_REALM_DELEGATED_TOKEN,
         "realm token bytes",
     )?;
@@ -263,11 +296,81 @@ fn verify_cca_token(buf: &[u8]) -> Result<(Vec<u8>, Vec<u8>), TokenError>
     Ok((platform, realm))
 }
 
-pub fn verify_token(buf: &[u8]) -> Result<AttestationClaims, TokenError>
+fn verify_cca_platform_token(buf: &[u8]) -> Result<Vec<u8>, TokenError>
+{
+    let val = de::from_reader(buf)?;
+    let data = unpack_tag(val, TAG_CCA_TOKEN, "cca token tag")?;
+    let map = unpack_map(data, "cca token not a map")?;
+
+    if map.len() != 1 {
+        return Err(TokenError::InvalidTokenFormat("wrong plat token count"));
+    }
+
+    unpack_keyed_bytes(
+        next_elem(&mut map.into_iter(), "no platform token")?,
+        CCA_PLAT_TOKEN,
+        "platform token bytes",
+    )
+}
+
+/// Splits the CCA token into the platform and the realm token
+/// without verifying them.
+pub fn split_token(buf: &[u8]) -> Result<(Vec<u8>, Vec<u8>), TokenError>
+{
+    verify_cca_token(buf)
+}
+
+/// Returns the platform token of the CCA token which carries only the platform token
+/// without verifying it.
+pub fn split_platform_token(buf: &[u8]) -> Result<Vec<u8>, TokenError>
+{
+    verify_cca_platform_token(buf)
+}
+
+/// Milestones of the token verification, reported in this order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Milestone
+{
+    /// The CCA token is split into the platform and the realm token.
+    HeaderParsed
+    {
+        bytes: usize
+    },
+    /// The claims of both tokens are extracted.
+    ClaimsExtracted
+    {
+        claims: usize
+    },
+    /// The signature of the realm token is verified.
+    SignatureVerified,
+}
+
+fn count_claims(attest_claims: &AttestationClaims) -> usize
+{
+    let sw_claims = attest_claims
+        .sw_component_claims
+        .iter()
+        .filter(|component| component.present)
+        .flat_map(|component| component.claims.iter());
+    attest_claims
+        .realm_token_claims
+        .iter()
+        .chain(attest_claims.realm_measurement_claims.iter())
+        .chain(attest_claims.plat_token_claims.iter())
+        .chain(sw_claims)
+        .filter(|claim| claim.present)
+        .count()
+}
+
+fn parse_cca_token(
+    buf: &[u8],
+    progress: &mut dyn FnMut(Milestone),
+) -> Result<(AttestationClaims, Vec<u8>), TokenError>
 {
     let mut attest_claims = AttestationClaims::new();
 
     let (platform_token, realm_token) = verify_cca_token(&buf)?;
+    progress(Milestone::HeaderParsed { bytes: buf.len() });
 
     verify_token_sign1(
         &realm_token.clone(),
@@ -282,12 +385,203 @@ pub fn verify_token(buf: &[u8]) -> Result<AttestationClaims, TokenError>
 
     verify_realm_token(&mut attest_claims)?;
     verify_platform_token(&mut attest_claims)?;
+    progress(Milestone::ClaimsExtracted {
+        claims: count_claims(&attest_claims),
+    });
+
+    Ok((attest_claims, realm_token))
+}
+
+/// Parses the claims of the CCA token without verifying the signatures.
+pub fn parse_token(buf: &[u8]) -> Result<AttestationClaims, TokenError>
+{
+    parse_cca_token(buf, &mut |_| {}).map(|(attest_claims, _realm_token)| attest_claims)
+}
+
+pub fn verify_token(buf: &[u8]) -> Result<AttestationClaims, TokenError>
+{
+    verify_token_with_progress(buf, &mut |_| {})
+}
 
-    let realm_key = attest_claims.realm_token_claims[4].data.get_bstr();
+/// Verifies the CCA token, reporting each `Milestone` reached to `progress`.
+///
+/// `progress` only observes the verification.
+/// It is handed copies of the milestones, never the token or the claims,
+/// so it can't change the result.
+pub fn verify_token_with_progress(
+    buf: &[u8],
+    progress: &mut dyn FnMut(Milestone),
+) -> Result<AttestationClaims, TokenError>
+{
+    let (attest_claims, realm_token) = parse_cca_token(buf, progress)?;
+
+    let realm_key = attest_claims
+        .realm_token_claims
+        .iter()
+        .find(|claim| claim.key == CCA_REALM_PUB_KEY as i64)
+        .ok_or(TokenError::InvalidTokenFormat("no realm public key"))
+        .and_then(|claim| claim_bstr(claim, "no realm public key"))?;
     cose::signing::verify(&realm_token, realm_key, b"").or(Err(TokenError::Signature))?;
+    progress(Milestone::SignatureVerified);
 
     //let platform_key = external_source();
     //cose::signing::verify(&platform_token, platform_key, b"").or(Err(TokenError::Signature))?;
 
     Ok(attest_claims)
 }
+
+// The deepest nesting of the CBOR items checked by `check_canonical`,
+// the same as the recursion limit of ciborium
+const CANONICAL_MAX_DEPTH: usize = 256;
+
+/// Checks that `buf` is a single CBOR data item in the canonical encoding
+/// (RFC 8949, Section 4.2.3), checking the encoded bytes rather than the decoded values:
+///   - the arguments (integers, lengths, counts and tags) are in the shortest form,
+///   - the lengths are definite,
+///   - the keys of every map are sorted by the length of their encodings and then bytewise,
+///     without duplicates.
+fn check_canonical(buf: &[u8]) -> Result<(), TokenError>
+{
+    match canonical_item(buf, 0, CANONICAL_MAX_DEPTH)? {
+        end if end == buf.len() => Ok(()),
+        _ => Err(TokenError::InvalidTokenFormat("trailing bytes after CBOR")),
+    }
+}
+
+/// Checks the data item at `pos` of `buf`, returning the position right after it.
+fn canonical_item(buf: &[u8], pos: usize, depth: usize) -> Result<usize, TokenError>
+{
+    let depth = depth
+        .checked_sub(1)
+        .ok_or(TokenError::InvalidTokenFormat("CBOR nested too deeply"))?;
+    let (major, arg, mut pos) = canonical_head(buf, pos)?;
+
+    match major {
+        // byte and text strings
+        2 | 3 => usize::try_from(arg)
+            .ok()
+            .and_then(|len| pos.checked_add(len))
+            .filter(|end| *end <= buf.len())
+            .ok_or(TokenError::InvalidTokenFormat("truncated CBOR")),
+        // arrays
+        4 => {
+            for _ in 0..arg {
+                pos = canonical_item(buf, pos, depth)?;
+            }
+            Ok(pos)
+        }
+        // maps
+        5 => {
+            let mut prev: Option<&[u8]> = None;
+            for _ in 0..arg {
+                let key_end = canonical_item(buf, pos, depth)?;
+                let key = &buf[pos..key_end];
+                if prev.is_some_and(|prev| (prev.len(), prev) >= (key.len(), key)) {
+                    return Err(TokenError::NonCanonicalCbor);
+                }
+                prev = Some(key);
+                pos = canonical_item(buf, key_end, depth)?;
+            }
+            Ok(pos)
+        }
+        // tags
+        6 => canonical_item(buf, pos, depth),
+        // integers, simple values and floats
+        _ => Ok(pos),
+    }
+}
+
+/// Reads the head of the data item at `pos` of `buf`,
+/// returning its major type, its argument and the position right after the head.
+fn canonical_head(buf: &[u8], pos: usize) -> Result<(u8, u64, usize), TokenError>
+{
+    let initial = *buf
+        .get(pos)
+        .ok_or(TokenError::InvalidTokenFormat("truncated CBOR"))?;
+    let (major, info) = (initial >> 5, initial & 0x1f);
+
+    let len: usize = match info {
+        0..=23 => return Ok((major, info as u64, pos + 1)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        // indefinite lengths (31) aren't canonical, and the others are reserved
+        _ => return Err(TokenError::NonCanonicalCbor),
+    };
+    let bytes = buf
+        .get(pos + 1..pos + 1 + len)
+        .ok_or(TokenError::InvalidTokenFormat("truncated CBOR"))?;
+    let arg = bytes
+        .iter()
+        .fold(0u64, |arg, byte| (arg << 8) | *byte as u64);
+
+    // The argument of a float is its value, which isn't shortened.
+    let is_float = major == 7 && len > 1;
+    let is_shortest = match len {
+        1 => arg >= 24,
+        _ => arg >> (len * 4) != 0,
+    };
+    if !is_float && !is_shortest {
+        return Err(TokenError::NonCanonicalCbor);
+    }
+
+    Ok((major, arg, pos + 1 + len))
+}
+
+/// Checks that the payload of the COSE_Sign1 token is canonical CBOR.
+fn check_canonical_payload(token: &[u8]) -> Result<(), TokenError>
+{
+    let sign1 = CoseSign1::from_tagged_slice(token)?;
+    let payload = sign1
+        .payload
+        .ok_or(TokenError::InvalidTokenFormat("no payload"))?;
+    check_canonical(&payload)
+}
+
+/// Verifies the CCA token as `verify_token` does,
+/// additionally requiring the token collection and the claims of both tokens
+/// to be encoded in canonical CBOR, which is rejected with `TokenError::NonCanonicalCbor`.
+pub fn verify_token_strict(buf: &[u8]) -> Result<AttestationClaims, TokenError>
+{
+    check_canonical(buf)?;
+    let (platform_token, realm_token) = verify_cca_token(buf)?;
+    check_canonical_payload(&platform_token)?;
+    check_canonical_payload(&realm_token)?;
+
+    verify_token(buf)
+}
+
+/// Parses the CCA token which carries only the platform token
+/// without verifying its signature, as the platform key comes from outside the token.
+/// The realm claims of the returned claims are not present.
+pub fn parse_platform_token_only(buf: &[u8]) -> Result<AttestationClaims, TokenError>
+{
+    let mut attest_claims = AttestationClaims::new();
+
+    let platform_token = verify_cca_platform_token(buf)?;
+
+    verify_token_sign1(
+        &platform_token,
+        &mut attest_claims.plat_cose_sign1,
+        &mut attest_claims.plat_cose_sign1_wrapper,
+    )?;
+
+    verify_platform_token(&mut attest_claims)?;
+
+    Ok(attest_claims)
+}
+
+/// Verifies the CCA token which carries only the platform token
+/// with the platform public key `platform_key` (SEC1-encoded).
+/// The realm claims of the returned claims are not present.
+pub fn verify_platform_token_only(
+    buf: &[u8],
+    platform_key: &[u8],
+) -> Result<AttestationClaims, TokenError>
+{
+    let platform_token = verify_cca_platform_token(buf)?;
+    cose::signing::verify(&platform_token, platform_key, b"").or(Err(TokenError::Signature))?;
+
+    parse_platform_token_only(buf)
+}
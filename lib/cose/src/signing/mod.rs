@@ -23,6 +23,114 @@ pub fn verify(object: &[u8], key: &[u8], aad: &[u8]) -> Result<(), &'static str>
     cose.verify_signature(aad, |sig, data| verifier.verify(sig, data))
 }
 
+/// Returns the material signed in the COSE_Sign1 `object` without verifying it.
+///
+/// The first element is the Sig_structure (RFC 8152, Section 4.4)
+/// serialized exactly as it was fed to the signer,
+/// followed by the signature and the COSE algorithm identifier.
+pub fn signed_material(object: &[u8], aad: &[u8]) -> Result<(Vec<u8>, Vec<u8>, i64), &'static str> {
+    let cose = parse(object)?;
+    let alg = match cose.protected.header.alg.as_ref() {
+        Some(coset::Algorithm::Assigned(alg)) => *alg as i64,
+        _ => return Err("Invalid algorithm"),
+    };
+    let sig_structure = coset::sig_structure_data(
+        coset::SignatureContext::CoseSign1,
+        cose.protected.clone(),
+        None,
+        aad,
+        cose.payload.as_ref().ok_or("Detached payload")?,
+    );
+
+    Ok((sig_structure, cose.signature, alg))
+}
+
+/// Reconstructs the Sig_structure of a detached COSE_Sign1 signature
+/// from the payload and the serialized protected header,
+/// i.e., the contents of the protected header bstr.
+///
+/// The protected header is kept as serialized, so the result is byte for byte
+/// what `signed_material` returns for the attached object.
+pub fn detached_sig_structure(
+    payload: &[u8],
+    protected: &[u8],
+) -> Result<(Vec<u8>, i64), &'static str> {
+    let (sig_structure, alg) = sig_structure_of(payload, protected)?;
+    match alg {
+        coset::Algorithm::Assigned(alg) => Ok((sig_structure, alg as i64)),
+        _ => Err("Invalid algorithm"),
+    }
+}
+
+/// Verifies a detached COSE_Sign1 signature over `payload` with `key`,
+/// where `protected` is the serialized protected header.
+pub fn verify_detached(
+    payload: &[u8],
+    signature: &[u8],
+    protected: &[u8],
+    key: &[u8],
+) -> Result<(), &'static str> {
+    let (sig_structure, alg) = sig_structure_of(payload, protected)?;
+    let verifier = Verifier::new(alg.try_into().or(Err("Failed to get algorithm."))?, key);
+    verifier.verify(signature, &sig_structure)
+}
+
+fn sig_structure_of(
+    payload: &[u8],
+    protected: &[u8],
+) -> Result<(Vec<u8>, coset::Algorithm), &'static str> {
+    let protected = coset::ProtectedHeader::from_cbor_bstr(Value::Bytes(protected.to_vec()))
+        .or(Err("Invalid protected header"))?;
+    let alg = protected.header.alg.clone().ok_or("Invalid algorithm")?;
+    let sig_structure = coset::sig_structure_data(
+        coset::SignatureContext::CoseSign1,
+        protected,
+        None,
+        b"",
+        payload,
+    );
+
+    Ok((sig_structure, alg))
+}
+
+/// The parts of a COSE_Sign1 object which `verify_detached` takes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DetachedParts {
+    pub payload: Vec<u8>,
+    pub signature: Vec<u8>,
+    /// The serialized protected header, i.e., the contents of the protected header bstr.
+    pub protected: Vec<u8>,
+}
+
+/// Splits the COSE_Sign1 `object` into the parts `verify_detached` takes.
+pub fn detached_parts(object: &[u8]) -> Result<DetachedParts, &'static str> {
+    let cose = parse(object)?;
+    let protected = cose
+        .protected
+        .original_data
+        .ok_or("Invalid protected header")?;
+    let payload = cose.payload.ok_or("Detached payload")?;
+
+    Ok(DetachedParts {
+        payload,
+        signature: cose.signature,
+        protected,
+    })
+}
+
+/// Returns the key identifier (`kid`) of the COSE_Sign1 `object`, or `None` if it has none.
+///
+/// The protected header takes precedence over the unprotected one.
+pub fn key_id(object: &[u8]) -> Result<Option<Vec<u8>>, &'static str> {
+    let cose = parse(object)?;
+    let kid = if cose.protected.header.key_id.is_empty() {
+        cose.unprotected.key_id
+    } else {
+        cose.protected.header.key_id
+    };
+    Ok((!kid.is_empty()).then_some(kid))
+}
+
 #[derive(Debug)]
 pub(crate) enum Algorithm {
     // sha256 + secp256r1/prime256v1/P-256
@@ -58,6 +58,10 @@ pub mod raw_ptr {
     //! - `SafetyAssured`: Provides guarantees that the instance
     //! pointed to by the raw pointer is properly initialized,
     //! adheres to Rust's ownership rules.
+    //!
+    //! - `FromBytes`: Marks the types which any initialized bytes
+    //! form a valid instance of, so that they can be read at an offset
+    //! into another instance.
 
     pub trait RawPtr: Sized {
         /// # Safety
@@ -82,6 +86,38 @@ pub mod raw_ptr {
             &mut *(addr as *mut T)
         }
 
+        /// Reads the instance of `T` at `addr` without creating a reference,
+        /// which the compiler is not allowed to elide or reorder.
+        ///
+        /// # Safety
+        ///
+        /// When calling this method, you have to ensure that all of the following is true:
+        ///
+        /// * The pointer must point to an initialized instance of `T`.
+        ///
+        /// * The pointer must be properly aligned for `T`.
+        ///
+        /// * The returned value is a bitwise copy, so you must not let both
+        ///   the copy and the original drop if `T` is not `Copy`.
+        unsafe fn read_volatile<T: RawPtr>(addr: usize) -> T {
+            core::ptr::read_volatile(addr as *const T)
+        }
+
+        /// Writes `val` to `addr` without creating a reference,
+        /// which the compiler is not allowed to elide or reorder.
+        /// The previous instance at `addr` is not dropped.
+        ///
+        /// # Safety
+        ///
+        /// When calling this method, you have to ensure that all of the following is true:
+        ///
+        /// * The pointer must be valid for writes and properly aligned for `T`.
+        ///
+        /// * You must enforce Rust's aliasing rules
+        unsafe fn write_volatile<T: RawPtr>(addr: usize, val: T) {
+            core::ptr::write_volatile(addr as *mut T, val)
+        }
+
         fn addr(&self) -> usize {
             let ptr: *const Self = self;
             ptr as usize
@@ -109,8 +145,36 @@ pub mod raw_ptr {
             !ptr.is_null()
         }
 
+        /// The alignment which the address of the instance must satisfy.
+        ///
+        /// It defaults to the alignment of the type and can be overridden
+        /// to require a stricter one (e.g., `GRANULE_SIZE` for a granule).
+        fn required_align(&self) -> usize {
+            core::mem::align_of::<Self>()
+        }
+
         fn is_aligned(&self) -> bool {
-            self.addr() % core::mem::align_of::<usize>() == 0
+            self.addr() % self.required_align() == 0
+        }
+
+        /// Checks whether the instance can be read through `SafetyAssumed`,
+        /// whose accessors (e.g., `with` or `Deref`) check it on every access.
+        ///
+        /// It returns `true` by default for the types without any access permission.
+        /// Types implementing `PermissionChecked` override it
+        /// with `has_read_permission_for_span`.
+        fn is_readable(&self) -> bool {
+            true
+        }
+
+        /// Checks whether the instance can be written through `SafetyAssumed`,
+        /// whose mutable accessors (e.g., `mut_with` or `DerefMut`) check it on every access.
+        ///
+        /// It returns `true` by default for the types without any access permission.
+        /// Types implementing `PermissionChecked` override it
+        /// with `has_write_permission_for_span`.
+        fn is_writable(&self) -> bool {
+            true
         }
     }
 
@@ -161,6 +225,177 @@ pub mod raw_ptr {
         /// practices to uphold Rust's safety guarantees, alerting developers to areas of concern
         /// that require attention.
         fn verify_ownership(&self) -> bool;
+
+        /// Checks whether the instance outlives the accesses through the raw pointer,
+        /// i.e., its memory is neither released nor repurposed while being accessed.
+        ///
+        /// It returns `true` by default for the implementors
+        /// which cover the lifetime in `verify_ownership`.
+        fn verify_lifetime(&self) -> bool {
+            true
+        }
+    }
+
+    /// `FromBytes` Trait
+    ///
+    /// This trait marks the types for which any initialized bytes of their size
+    /// form a valid instance (e.g., integers and arrays of them).
+    ///
+    /// Only these types can be read out of the memory of another instance
+    /// (e.g., by `SafetyAssumed::with_field`), as the bytes there may hold
+    /// any value, which is undefined behavior for types like `bool`,
+    /// enums or references.
+    ///
+    /// ```compile_fail
+    /// use safe_abstraction::raw_ptr::FromBytes;
+    ///
+    /// fn from_bytes<T: FromBytes>() {}
+    /// from_bytes::<bool>();
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// Implementors must be valid for every bit pattern of their size
+    /// and must not contain any padding.
+    pub unsafe trait FromBytes: Sized {}
+
+    macro_rules! impl_from_bytes {
+        ($($ty:ty),*) => {
+            $(
+                // Safety: Every bit pattern is a valid integer without padding.
+                unsafe impl FromBytes for $ty {}
+            )*
+        };
+    }
+
+    impl_from_bytes!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+    // Safety: An array has no padding between its elements,
+    //         each of which is valid for every bit pattern.
+    unsafe impl<T: FromBytes, const N: usize> FromBytes for [T; N] {}
+
+    /// `PermissionChecked` Trait
+    ///
+    /// This trait is used for instances located in memory
+    /// whose access permission is managed per granule
+    /// (e.g., by a granule state table).
+    ///
+    /// Implementors only need to tell whether a single granule can be read or written.
+    /// The default methods then validate every granule the instance spans,
+    /// which prevents a multi-granule structure or a slice from being accepted
+    /// when only its first granule has the right permission.
+    ///
+    /// Reading and writing are told apart, so that a granule which the RMM
+    /// only reads (e.g., in the `RD` or `REC` state) can't be written
+    /// through the wrong path.
+    pub trait PermissionChecked: RawPtr {
+        /// The size of the memory unit whose permission is managed.
+        /// It must be a power of two.
+        const GRANULE_SIZE: usize;
+
+        /// Checks whether the granule starting at `granule_addr` can be read.
+        fn has_granule_read_permission(granule_addr: usize) -> bool;
+
+        /// Checks whether the granule starting at `granule_addr` can be written.
+        fn has_granule_write_permission(granule_addr: usize) -> bool;
+
+        /// Checks whether the granule starting at `granule_addr` can be read and written.
+        fn has_granule_permission(granule_addr: usize) -> bool {
+            Self::has_granule_read_permission(granule_addr)
+                && Self::has_granule_write_permission(granule_addr)
+        }
+
+        /// Checks the read permission of the granule containing the instance.
+        fn has_read_permission(&self) -> bool {
+            Self::has_granule_read_permission(self.addr() & !(Self::GRANULE_SIZE - 1))
+        }
+
+        /// Checks the write permission of the granule containing the instance.
+        fn has_write_permission(&self) -> bool {
+            Self::has_granule_write_permission(self.addr() & !(Self::GRANULE_SIZE - 1))
+        }
+
+        /// Checks both the read and the write permission of the granule containing the instance.
+        fn has_permission(&self) -> bool {
+            self.has_read_permission() && self.has_write_permission()
+        }
+
+        /// Checks the permission of every granule in `[addr, addr + len)`.
+        ///
+        /// Returns `false` if the span overflows the address space
+        /// or any of the granules lacks the permission.
+        fn has_permission_for_span(&self, len: usize) -> bool {
+            span_has(
+                self.addr(),
+                len,
+                Self::GRANULE_SIZE,
+                Self::has_granule_permission,
+            )
+        }
+
+        /// Checks the read permission of every granule in `[addr, addr + len)`
+        /// as `has_permission_for_span` does.
+        fn has_read_permission_for_span(&self, len: usize) -> bool {
+            span_has(
+                self.addr(),
+                len,
+                Self::GRANULE_SIZE,
+                Self::has_granule_read_permission,
+            )
+        }
+
+        /// Checks the write permission of every granule in `[addr, addr + len)`
+        /// as `has_permission_for_span` does.
+        fn has_write_permission_for_span(&self, len: usize) -> bool {
+            span_has(
+                self.addr(),
+                len,
+                Self::GRANULE_SIZE,
+                Self::has_granule_write_permission,
+            )
+        }
+    }
+
+    /// Checks `permission` of every granule of `granule_size` in `[addr, addr + len)`.
+    fn span_has(
+        addr: usize,
+        len: usize,
+        granule_size: usize,
+        permission: impl FnMut(usize) -> bool,
+    ) -> bool {
+        let start = addr & !(granule_size - 1);
+        let end = match addr.checked_add(len) {
+            Some(end) => end,
+            None => return false,
+        };
+
+        (start..end).step_by(granule_size).all(permission)
+    }
+
+    /// The size of the granule which a slice from `SafetyAssumed::with_slice`
+    /// must stay within.
+    const SLICE_GRANULE_SIZE: usize = 4096;
+
+    /// Checks whether `[addr, addr + len)` lies entirely within `[base, base + size)`
+    /// (e.g., a granule or an IPA window).
+    ///
+    /// Neither range may wrap around the address space.
+    /// The window may reach the top of it, which is measured without any overflow.
+    pub const fn range_within(addr: usize, len: usize, base: usize, size: usize) -> bool {
+        if addr < base || addr.checked_add(len).is_none() {
+            return false;
+        }
+        let offset = addr - base;
+        offset <= size && len <= size - offset
+    }
+
+    /// Checks whether `[addr, addr + len)` crosses the boundary of the granule containing `addr`,
+    /// where `granule` is the size of a granule, which must be a power of two.
+    ///
+    /// An empty range crosses nothing, and a range wrapping around
+    /// the address space crosses the boundary at the top of it.
+    pub const fn is_granule_boundary_crossed(addr: usize, len: usize, granule: usize) -> bool {
+        !range_within(addr, len, addr & !(granule - 1), granule)
     }
 
     /// Enumerates the types of errors that can occur in the `assume_safe` function.
@@ -184,13 +419,62 @@ pub mod raw_ptr {
 
     impl core::error::Error for Error {}
 
+    /// Tells which check rejected the address in `assume_safe`,
+    /// so that callers can map it to their own error codes.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum AssumeError {
+        /// The address is null (`SafetyChecked::is_not_null`).
+        Null,
+        /// The address is not aligned (`SafetyChecked::is_aligned`).
+        Misaligned,
+        /// A granule the instance spans lacks the permission
+        /// (`PermissionChecked::has_permission_for_span`,
+        /// `SafetyChecked::is_readable` or `SafetyChecked::is_writable`).
+        NoPermission,
+        /// The instance is not initialized (`SafetyAssured::is_initialized`).
+        Uninitialized,
+        /// The instance may not outlive the access (`SafetyAssured::verify_lifetime`).
+        LifetimeViolation,
+        /// The ownership rules are not upheld (`SafetyAssured::verify_ownership`).
+        OwnershipViolation,
+    }
+
+    impl core::fmt::Display for AssumeError {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            match *self {
+                AssumeError::Null => write!(f, "Null address"),
+                AssumeError::Misaligned => write!(f, "Misaligned address"),
+                AssumeError::NoPermission => write!(f, "No permission"),
+                AssumeError::Uninitialized => write!(f, "Uninitialized instance"),
+                AssumeError::LifetimeViolation => write!(f, "Lifetime violation"),
+                AssumeError::OwnershipViolation => write!(f, "Ownership violation"),
+            }
+        }
+    }
+
+    impl core::error::Error for AssumeError {}
+
+    impl From<AssumeError> for Error {
+        fn from(error: AssumeError) -> Self {
+            match error {
+                AssumeError::Null | AssumeError::Misaligned | AssumeError::NoPermission => {
+                    Error::SafetyCheckFailed
+                }
+                AssumeError::Uninitialized
+                | AssumeError::LifetimeViolation
+                | AssumeError::OwnershipViolation => Error::AssuranceCheckFailed,
+            }
+        }
+    }
+
     /// Attempts to create a `SafetyAssumed` instance from a address.
     ///
     /// This function checks both `SafetyChecked` and `SafetyAssured` traits' conditions
     /// to ensure that the target at the given address adheres to safety guarantees.
     /// If all checks pass, it returns a `SafetyAssumed` instance encapsulating the address,
     /// signifying that interactions with the target can be safely performed.
-    /// Otherwise, it returns `None`, indicating that safety guarantees cannot be met.
+    /// Otherwise, it returns the `AssumeError` of the first check failed,
+    /// indicating that safety guarantees cannot be met.
     ///
     /// # Arguments
     ///
@@ -198,30 +482,59 @@ pub mod raw_ptr {
     ///
     /// # Returns
     ///
-    /// Returns `Ok(SafetyAssumed)` if all safety checks are satisfied, or `Error`
+    /// Returns `Ok(SafetyAssumed)` if all safety checks are satisfied, or `AssumeError`
     pub fn assume_safe<T: SafetyChecked + SafetyAssured>(
         addr: usize,
-    ) -> Result<SafetyAssumed<T>, Error> {
+    ) -> Result<SafetyAssumed<T>, AssumeError> {
         let ptr = addr as *const T;
+        if ptr.is_null() {
+            return Err(AssumeError::Null);
+        }
+        // A reference must be aligned for `T` even to check the required alignment.
+        if addr % core::mem::align_of::<T>() != 0 {
+            return Err(AssumeError::Misaligned);
+        }
         // Safety: This cast from a raw pointer to a reference is considered safe
         //         because it is used solely for the purpose of verifying alignment and range,
         //         without actually dereferencing the pointer.
         let ref_ = unsafe { &*(ptr) };
 
-        if !ref_.is_not_null() || !ref_.is_aligned() {
-            return Err(Error::SafetyCheckFailed);
+        if !ref_.is_not_null() {
+            return Err(AssumeError::Null);
         }
-
-        if !ref_.is_initialized() || !ref_.verify_ownership() {
-            return Err(Error::AssuranceCheckFailed);
+        if !ref_.is_aligned() {
+            return Err(AssumeError::Misaligned);
+        }
+        if !ref_.is_readable() {
+            return Err(AssumeError::NoPermission);
         }
 
+        assure(ref_)?;
+
         Ok(SafetyAssumed {
             addr,
             _phantom: core::marker::PhantomData,
         })
     }
 
+    /// Does what `assume_safe` does, additionally checking the permission
+    /// of every granule the instance of `T` spans.
+    pub fn assume_safe_permitted<T>(addr: usize) -> Result<SafetyAssumed<T>, AssumeError>
+    where
+        T: SafetyChecked + SafetyAssured + PermissionChecked,
+    {
+        let assumed = assume_safe::<T>(addr)?;
+        if !assumed.has_permission_for_span(core::mem::size_of::<T>()) {
+            return Err(AssumeError::NoPermission);
+        }
+        Ok(assumed)
+    }
+
+    /// Does what `assume_safe` does, discarding why the address was rejected.
+    pub fn assume_opt<T: SafetyChecked + SafetyAssured>(addr: usize) -> Option<SafetyAssumed<T>> {
+        assume_safe::<T>(addr).ok()
+    }
+
     /// Represents a target instance that has passed all necessary safety checks.
     ///
     /// An instance of `SafetyAssumed` signifies that it is safe to interact with the target
@@ -242,16 +555,302 @@ pub mod raw_ptr {
         _phantom: core::marker::PhantomData<T>,
     }
 
+    impl<T> SafetyAssumed<T>
+    where
+        T: SafetyChecked + SafetyAssured,
+    {
+        /// Returns the address of the instance of `T`, which passed the checks in `assume_safe`.
+        pub fn addr(&self) -> usize {
+            self.addr
+        }
+
+        /// Invokes `f` on the instance of `T` if it can be read (`SafetyChecked::is_readable`).
+        /// Otherwise, `AssumeError::NoPermission` is returned.
+        pub fn with<F, R>(&self, f: F) -> Result<R, AssumeError>
+        where
+            F: FnOnce(&T) -> R,
+        {
+            self.check_readable()?;
+            // Safety: The target has passed all the safety checks in `assume_safe`
+            //         and it can be read.
+            Ok(f(unsafe { T::as_ref(self.addr) }))
+        }
+
+        /// Invokes `f` on the mutable instance of `T`
+        /// if it can be written (`SafetyChecked::is_writable`).
+        /// Otherwise, `AssumeError::NoPermission` is returned.
+        ///
+        /// With the `overlap-check` feature, debug builds record the borrowed region
+        /// while `f` runs and assert that it doesn't overlap any other region
+        /// borrowed by `mut_with`, which catches two `SafetyAssumed` aliasing each other.
+        pub fn mut_with<F, R>(&mut self, f: F) -> Result<R, AssumeError>
+        where
+            F: FnOnce(&mut T) -> R,
+        {
+            self.check_writable()?;
+            #[cfg(all(feature = "overlap-check", debug_assertions))]
+            let _borrow = overlap::borrow(self.addr, core::mem::size_of::<T>());
+            // Safety: The target has passed all the safety checks in `assume_safe`,
+            //         it can be written and the exclusive borrow prevents
+            //         any other reference to it.
+            Ok(f(unsafe { T::as_mut(self.addr) }))
+        }
+
+        fn check_readable(&self) -> Result<(), AssumeError> {
+            // Safety: The reference is only used to check the permission
+            //         of the target which has passed the checks in `assume_safe`.
+            if !unsafe { T::as_ref::<T>(self.addr) }.is_readable() {
+                return Err(AssumeError::NoPermission);
+            }
+            Ok(())
+        }
+
+        fn check_writable(&self) -> Result<(), AssumeError> {
+            // Safety: The reference is only used to check the permission
+            //         of the target which has passed the checks in `assume_safe`.
+            if !unsafe { T::as_ref::<T>(self.addr) }.is_writable() {
+                return Err(AssumeError::NoPermission);
+            }
+            Ok(())
+        }
+
+        /// Overwrites the instance of `T` with zeros.
+        ///
+        /// The whole `size_of::<T>()` bytes of the target are cleared
+        /// through the address that passed the safety checks.
+        /// Note that the compiler is allowed to elide these writes
+        /// if the target is not read afterwards.
+        /// Use `zero_volatile` to scrub secrets instead.
+        /// `AssumeError::NoPermission` is returned unless the target can be written.
+        ///
+        /// `T` is bound by `FromBytes`, so that all-zero bytes are a valid instance of `T`.
+        pub fn zero(&mut self) -> Result<(), AssumeError>
+        where
+            T: FromBytes,
+        {
+            self.check_writable()?;
+            // Safety: The target has passed all the safety checks in `assume_safe`,
+            //         it can be written, the exclusive borrow prevents
+            //         any other reference to it and `T: FromBytes` is valid for zeros.
+            unsafe { core::ptr::write_bytes(self.addr as *mut u8, 0, core::mem::size_of::<T>()) };
+            Ok(())
+        }
+
+        /// Overwrites the instance of `T` with zeros using volatile writes.
+        ///
+        /// Unlike `zero`, the writes are guaranteed not to be elided
+        /// or reordered by the compiler,
+        /// which makes it suitable for scrubbing confidential data
+        /// (e.g., register files or scratch buffers) before releasing the memory.
+        /// `AssumeError::NoPermission` is returned unless the target can be written.
+        /// `T` is bound by `FromBytes` as `zero` is.
+        pub fn zero_volatile(&mut self) -> Result<(), AssumeError>
+        where
+            T: FromBytes,
+        {
+            self.check_writable()?;
+            let ptr = self.addr as *mut u8;
+            for i in 0..core::mem::size_of::<T>() {
+                // Safety: The target has passed all the safety checks in `assume_safe`
+                //         and `i` stays within the size of `T`.
+                unsafe { core::ptr::write_volatile(ptr.add(i), 0) };
+            }
+            core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+
+        /// Invokes `f` on the field of type `U` at `offset` in the instance of `T`
+        /// without materializing the whole instance.
+        ///
+        /// The field must lie within the `size_of::<T>()` bytes of the target
+        /// (e.g., the granule of a granule-sized structure like `Run`)
+        /// and its address must be aligned for `U`.
+        /// Otherwise, `Error::SafetyCheckFailed` is returned.
+        ///
+        /// The caller names the type of the field at `offset`,
+        /// which is initialized as part of the target that passed `is_initialized()`.
+        /// It is bound by `FromBytes`, as the bytes at `offset` may hold any value.
+        pub fn with_field<U, F, R>(&self, offset: usize, f: F) -> Result<R, Error>
+        where
+            U: FromBytes,
+            F: FnOnce(&U) -> R,
+        {
+            self.check_readable()?;
+            let end = offset
+                .checked_add(core::mem::size_of::<U>())
+                .ok_or(Error::SafetyCheckFailed)?;
+            if end > core::mem::size_of::<T>() {
+                return Err(Error::SafetyCheckFailed);
+            }
+
+            let field = self.addr + offset;
+            if field % core::mem::align_of::<U>() != 0 {
+                return Err(Error::SafetyCheckFailed);
+            }
+
+            // Safety: The field lies within the target which has passed
+            //         all the safety checks in `assume_safe`, it is properly aligned
+            //         and `U: FromBytes` is valid for whatever the bytes hold.
+            Ok(f(unsafe { &*(field as *const U) }))
+        }
+
+        /// Invokes `f` on the slice of `len` elements of type `U`
+        /// starting at the instance of `T` (e.g., to view a granule as `[u64]`).
+        ///
+        /// The slice must stay within the 4096-byte granule containing the instance
+        /// and its address must be aligned for `U`.
+        /// Otherwise, `Error::SafetyCheckFailed` is returned.
+        /// `U` is bound by `FromBytes`, as the bytes of the granule may hold any value.
+        pub fn with_slice<U, F, R>(&self, len: usize, f: F) -> Result<R, Error>
+        where
+            U: FromBytes,
+            F: FnOnce(&[U]) -> R,
+        {
+            self.check_readable()?;
+            self.check_slice::<U>(len)?;
+
+            // Safety: The slice lies within the granule of the target
+            //         which has passed all the safety checks in `assume_safe`,
+            //         it is properly aligned and `U: FromBytes` is valid
+            //         for whatever the bytes hold.
+            Ok(f(unsafe {
+                core::slice::from_raw_parts(self.addr as *const U, len)
+            }))
+        }
+
+        /// Invokes `f` on the mutable slice of `len` elements of type `U`
+        /// starting at the instance of `T`, checked as `with_slice` does.
+        ///
+        /// `T` is bound by `FromBytes` as well, so that whatever `f` writes
+        /// through the slice leaves a valid instance of `T` behind.
+        pub fn mut_with_slice<U, F, R>(&mut self, len: usize, f: F) -> Result<R, Error>
+        where
+            T: FromBytes,
+            U: FromBytes,
+            F: FnOnce(&mut [U]) -> R,
+        {
+            self.check_writable()?;
+            self.check_slice::<U>(len)?;
+
+            // Safety: The slice lies within the granule of the target
+            //         which has passed all the safety checks in `assume_safe`,
+            //         it is properly aligned, both `T` and `U` are valid for any bytes
+            //         and the exclusive borrow prevents any other reference to it.
+            Ok(f(unsafe {
+                core::slice::from_raw_parts_mut(self.addr as *mut U, len)
+            }))
+        }
+
+        fn check_slice<U>(&self, len: usize) -> Result<(), Error> {
+            let size = len
+                .checked_mul(core::mem::size_of::<U>())
+                .ok_or(Error::SafetyCheckFailed)?;
+            if is_granule_boundary_crossed(self.addr, size, SLICE_GRANULE_SIZE) {
+                return Err(Error::SafetyCheckFailed);
+            }
+
+            if self.addr % core::mem::align_of::<U>() != 0 {
+                return Err(Error::SafetyCheckFailed);
+            }
+            Ok(())
+        }
+
+        /// Invokes `f` on a copy of the instance of `T` read with a volatile read.
+        ///
+        /// Unlike `as_ref`, the read is guaranteed not to be elided or reordered
+        /// by the compiler, which makes it suitable for a target concurrently
+        /// visible to a Realm or the Normal World (e.g., a shared granule).
+        /// The address must be aligned for `T`,
+        /// otherwise `Error::SafetyCheckFailed` is returned.
+        pub fn with_volatile<F, R>(&self, f: F) -> Result<R, Error>
+        where
+            F: FnOnce(&T) -> R,
+        {
+            if self.addr % core::mem::align_of::<T>() != 0 {
+                return Err(Error::SafetyCheckFailed);
+            }
+            self.check_readable()?;
+
+            // Safety: The target has passed all the safety checks in `assume_safe`
+            //         and it is properly aligned. The copy is never dropped,
+            //         so it doesn't drop any resource owned by the target.
+            let copy = core::mem::ManuallyDrop::new(unsafe { T::read_volatile::<T>(self.addr) });
+            Ok(f(&copy))
+        }
+
+        /// Invokes `f` on a copy of the instance of `T` read with a volatile read,
+        /// and writes the copy back with a volatile write.
+        ///
+        /// The address must be aligned for `T`,
+        /// otherwise `Error::SafetyCheckFailed` is returned.
+        pub fn mut_with_volatile<F, R>(&mut self, f: F) -> Result<R, Error>
+        where
+            F: FnOnce(&mut T) -> R,
+        {
+            if self.addr % core::mem::align_of::<T>() != 0 {
+                return Err(Error::SafetyCheckFailed);
+            }
+            self.check_writable()?;
+
+            // Safety: The target has passed all the safety checks in `assume_safe`,
+            //         it is properly aligned and the exclusive borrow prevents
+            //         any other reference to it. The copy replaces the target
+            //         without dropping it, so nothing is dropped twice.
+            let mut copy =
+                core::mem::ManuallyDrop::new(unsafe { T::read_volatile::<T>(self.addr) });
+            let ret = f(&mut copy);
+            unsafe { T::write_volatile(self.addr, core::mem::ManuallyDrop::into_inner(copy)) };
+            Ok(ret)
+        }
+    }
+
+    impl<T> SafetyAssumed<T>
+    where
+        T: Copy + SafetyChecked + SafetyAssured,
+    {
+        /// Returns a bitwise copy of the instance of `T`, which passed the checks in `assume_safe`.
+        ///
+        /// The copy is owned by the caller, so it outlives the borrow of the target
+        /// and doesn't follow later changes to the underlying memory
+        /// (e.g., a host-shared structure rewritten after it is validated).
+        /// `AssumeError::NoPermission` is returned unless the target can be read.
+        pub fn read_copy(&self) -> Result<T, AssumeError> {
+            self.check_readable()?;
+            // Safety: The target has passed all the safety checks in `assume_safe`,
+            //         it can be read and `T: Copy` has no resource to be dropped twice.
+            Ok(unsafe { core::ptr::read(self.addr as *const T) })
+        }
+    }
+
+    impl<T> SafetyAssumed<T>
+    where
+        T: SafetyChecked + SafetyAssured + PermissionChecked,
+    {
+        /// Returns the instance of `T` at `bytes` past this one
+        /// (e.g., the next element of an array of fixed-size structures in a granule).
+        ///
+        /// The shifted instance must stay within the granule containing this one
+        /// and passes the checks of `assume_safe_permitted` again,
+        /// including the alignment and the permission. Otherwise, `None` is returned.
+        pub fn offset(&self, bytes: usize) -> Option<SafetyAssumed<T>> {
+            let addr = self.addr.checked_add(bytes)?;
+            let granule = self.addr & !(T::GRANULE_SIZE - 1);
+            if !range_within(addr, core::mem::size_of::<T>(), granule, T::GRANULE_SIZE) {
+                return None;
+            }
+            assume_safe_permitted::<T>(addr).ok()
+        }
+    }
+
     impl<T> AsRef<T> for SafetyAssumed<T>
     where
         T: SafetyChecked + SafetyAssured,
     {
-        /// Safely returns a mutable reference to the instance of `T`.
+        /// Safely returns a reference to the instance of `T`.
         ///
-        /// # Safety
-        /// Similar to `as_ref`, this function assumes that all required safety checks
-        /// are in place. Mutable access is granted under the presumption of exclusive ownership
-        /// and proper synchronization when accessed in multi-threaded contexts.
+        /// It doesn't check `SafetyChecked::is_readable` again,
+        /// which can change after `assume_safe` (e.g., a granule changing its state).
+        /// Access the types with such a permission through `with` instead.
         fn as_ref(&self) -> &T {
             unsafe { T::as_ref(self.addr) }
         }
@@ -263,10 +862,11 @@ pub mod raw_ptr {
     {
         /// Safely returns a mutable reference to the instance of `T`.
         ///
-        /// # Safety
-        /// Similar to `as_ref`, this function assumes that all required safety checks
-        /// are in place. Mutable access is granted under the presumption of exclusive ownership
+        /// Mutable access is granted under the presumption of exclusive ownership
         /// and proper synchronization when accessed in multi-threaded contexts.
+        ///
+        /// It doesn't check `SafetyChecked::is_writable`.
+        /// Access the types with such a permission through `mut_with` instead.
         fn as_mut(&mut self) -> &mut T {
             unsafe { T::as_mut(self.addr) }
         }
@@ -291,4 +891,800 @@ pub mod raw_ptr {
             self.as_mut()
         }
     }
+
+    /// An address of `T` aligned to `ALIGN`, which is checked once on construction.
+    ///
+    /// It is as large as a `usize` and its accessors skip the alignment check,
+    /// which suits a register block accessed on hot paths.
+    /// The accessors still check the permission of the granules `T` spans
+    /// and the conditions of `SafetyAssured` on every access.
+    /// `ALIGN` must be a power of two no smaller than the alignment of `T`,
+    /// which is checked at compile time.
+    ///
+    /// Like `SafetyAssumed`, it is neither `Clone` nor `Copy`, so the exclusive borrow
+    /// `mut_with` takes keeps the `&mut T` it hands out from being aliased through a copy.
+    ///
+    /// ```
+    /// use safe_abstraction::raw_ptr::AlignedPtr;
+    ///
+    /// assert!(AlignedPtr::<u64, 8>::try_new(4).is_none());
+    /// assert!(AlignedPtr::<u64, 8>::try_new(8).is_some());
+    /// ```
+    ///
+    /// ```compile_fail
+    /// use safe_abstraction::raw_ptr::AlignedPtr;
+    ///
+    /// let ptr = AlignedPtr::<u64, 8>::try_new(8).unwrap();
+    /// let copied = ptr;
+    /// assert_eq!(ptr.addr(), copied.addr());
+    /// ```
+    #[derive(Debug, PartialEq)]
+    pub struct AlignedPtr<T, const ALIGN: usize> {
+        addr: usize,
+        _phantom: core::marker::PhantomData<*mut T>,
+    }
+
+    impl<T, const ALIGN: usize> AlignedPtr<T, ALIGN> {
+        // `ALIGN` checked at compile time
+        const CHECKED_ALIGN: usize = {
+            assert!(
+                ALIGN.is_power_of_two() && ALIGN >= core::mem::align_of::<T>(),
+                "ALIGN must be a power of two no smaller than the alignment of T"
+            );
+            ALIGN
+        };
+
+        /// Returns `None` unless `addr` is a non-null address aligned to `ALIGN`.
+        pub fn try_new(addr: usize) -> Option<Self> {
+            if addr == 0 || addr % Self::CHECKED_ALIGN != 0 {
+                return None;
+            }
+            Some(Self {
+                addr,
+                _phantom: core::marker::PhantomData,
+            })
+        }
+
+        pub fn addr(&self) -> usize {
+            self.addr
+        }
+    }
+
+    impl<T, const ALIGN: usize> AlignedPtr<T, ALIGN>
+    where
+        T: SafetyChecked + SafetyAssured + PermissionChecked,
+    {
+        /// Invokes `f` on the instance of `T` if every granule it spans can be read
+        /// and it passes the checks of `SafetyAssured`.
+        ///
+        /// Unlike `assume_safe`, the address is not checked against the alignment again,
+        /// only `ALIGN` against the one `T` requires.
+        pub fn with<F, R>(&self, f: F) -> Result<R, AssumeError>
+        where
+            F: FnOnce(&T) -> R,
+        {
+            // Safety: The address is non-null and aligned for `T` as constructed.
+            //         It is not dereferenced until the checks below pass.
+            let ref_ = unsafe { &*(self.addr as *const T) };
+            if ref_.required_align() > ALIGN {
+                return Err(AssumeError::Misaligned);
+            }
+            if !ref_.has_read_permission_for_span(core::mem::size_of::<T>()) {
+                return Err(AssumeError::NoPermission);
+            }
+            assure(ref_)?;
+            Ok(f(ref_))
+        }
+
+        /// Invokes `f` on the mutable instance of `T`
+        /// if every granule it spans can be written, checked as `with` does otherwise.
+        pub fn mut_with<F, R>(&mut self, f: F) -> Result<R, AssumeError>
+        where
+            F: FnOnce(&mut T) -> R,
+        {
+            #[cfg(all(feature = "overlap-check", debug_assertions))]
+            let _borrow = overlap::borrow(self.addr, core::mem::size_of::<T>());
+            // Safety: The address is non-null and aligned for `T` as constructed,
+            //         and the exclusive borrow prevents any other reference through it.
+            //         It is not dereferenced until the checks below pass.
+            let ref_ = unsafe { &mut *(self.addr as *mut T) };
+            if ref_.required_align() > ALIGN {
+                return Err(AssumeError::Misaligned);
+            }
+            if !ref_.has_write_permission_for_span(core::mem::size_of::<T>()) {
+                return Err(AssumeError::NoPermission);
+            }
+            assure(ref_)?;
+            Ok(f(ref_))
+        }
+    }
+
+    fn assure<T: SafetyAssured>(ref_: &T) -> Result<(), AssumeError> {
+        if !ref_.is_initialized() {
+            return Err(AssumeError::Uninitialized);
+        }
+        if !ref_.verify_lifetime() {
+            return Err(AssumeError::LifetimeViolation);
+        }
+        if !ref_.verify_ownership() {
+            return Err(AssumeError::OwnershipViolation);
+        }
+        Ok(())
+    }
+
+    #[cfg(all(feature = "overlap-check", debug_assertions))]
+    mod overlap {
+        //! Registry of the regions borrowed by `mut_with`, which works in `no_std`.
+
+        use spin::Mutex;
+
+        const CAPACITY: usize = 16;
+
+        /// `(addr, size)` of the regions currently borrowed
+        static BORROWED: Mutex<[Option<(usize, usize)>; CAPACITY]> = Mutex::new([None; CAPACITY]);
+
+        /// A borrowed region, which is removed from the registry on drop.
+        /// Once the registry is full, further regions are checked but not recorded.
+        pub(super) struct Borrow {
+            slot: Option<usize>,
+        }
+
+        pub(super) fn borrow(addr: usize, size: usize) -> Borrow {
+            let mut borrowed = BORROWED.lock();
+            let end = addr + size;
+            debug_assert!(
+                borrowed
+                    .iter()
+                    .flatten()
+                    .all(|&(start, size)| end <= start || start + size <= addr),
+                "mut_with on {:#x}..{:#x} overlaps a borrowed region",
+                addr,
+                end
+            );
+
+            let slot = borrowed.iter().position(Option::is_none);
+            if let Some(slot) = slot {
+                borrowed[slot] = Some((addr, size));
+            }
+            Borrow { slot }
+        }
+
+        impl Drop for Borrow {
+            fn drop(&mut self) {
+                if let Some(slot) = self.slot {
+                    BORROWED.lock()[slot] = None;
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        /// Implements the traits of a mock which is always initialized and
+        /// owned.
+        ///
+        /// `align` overrides the required alignment. `read` and `write` give
+        /// the permission of every granule the mock spans, which is checked
+        /// on each access.
+        macro_rules! mock {
+            (@assured $ty:ty) => {
+                impl RawPtr for $ty {}
+
+                impl SafetyAssured for $ty {
+                    fn is_initialized(&self) -> bool {
+                        true
+                    }
+
+                    fn verify_ownership(&self) -> bool {
+                        true
+                    }
+                }
+            };
+            ($ty:ty $(, align = $align:expr)?) => {
+                mock!(@assured $ty);
+
+                impl SafetyChecked for $ty {
+                    $(
+                        fn required_align(&self) -> usize {
+                            $align
+                        }
+                    )?
+                }
+            };
+            ($ty:ty $(, align = $align:expr)?, read = $read:expr, write = $write:expr) => {
+                mock!(@assured $ty);
+
+                impl SafetyChecked for $ty {
+                    $(
+                        fn required_align(&self) -> usize {
+                            $align
+                        }
+                    )?
+
+                    fn is_readable(&self) -> bool {
+                        self.has_read_permission_for_span(core::mem::size_of::<Self>())
+                    }
+
+                    fn is_writable(&self) -> bool {
+                        self.has_write_permission_for_span(core::mem::size_of::<Self>())
+                    }
+                }
+
+                impl PermissionChecked for $ty {
+                    const GRANULE_SIZE: usize = MOCK_GRANULE_SIZE;
+
+                    fn has_granule_read_permission(_granule_addr: usize) -> bool {
+                        $read
+                    }
+
+                    fn has_granule_write_permission(_granule_addr: usize) -> bool {
+                        $write
+                    }
+                }
+            };
+        }
+
+        #[repr(C)]
+        struct Secret {
+            key: [u64; 4],
+            len: u32,
+            flags: u32,
+        }
+
+        mock!(Secret);
+
+        // Safety: It consists of integers only.
+        unsafe impl FromBytes for Secret {}
+
+        fn written() -> Secret {
+            Secret {
+                key: [0xdead_beef_cafe_babe; 4],
+                len: 32,
+                flags: 0xffff_ffff,
+            }
+        }
+
+        fn read_back(addr: usize) -> [u8; core::mem::size_of::<Secret>()] {
+            let mut bytes = [0xffu8; core::mem::size_of::<Secret>()];
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = unsafe { core::ptr::read_volatile((addr as *const u8).add(i)) };
+            }
+            bytes
+        }
+
+        #[test]
+        fn zero() {
+            let mut secret = written();
+            let addr = core::ptr::addr_of_mut!(secret) as usize;
+
+            let mut assumed = assume_safe::<Secret>(addr).unwrap();
+            assert_eq!(assumed.len, 32);
+            assumed.zero().unwrap();
+
+            assert!(read_back(addr).iter().all(|b| *b == 0));
+            assert_eq!(assumed.key, [0; 4]);
+            assert_eq!(assumed.len, 0);
+            assert_eq!(assumed.flags, 0);
+        }
+
+        #[test]
+        fn zero_volatile() {
+            let mut secret = written();
+            let addr = core::ptr::addr_of_mut!(secret) as usize;
+
+            let mut assumed = assume_safe::<Secret>(addr).unwrap();
+            assumed.zero_volatile().unwrap();
+
+            // The secret is not read through `assumed` after scrubbing,
+            // so non-volatile writes would be allowed to be elided here.
+            assert!(read_back(addr).iter().all(|b| *b == 0));
+        }
+
+        #[test]
+        fn with_field() {
+            let mut secret = written();
+            let addr = core::ptr::addr_of_mut!(secret) as usize;
+            let assumed = assume_safe::<Secret>(addr).unwrap();
+
+            // `len` follows `key` of 32 bytes
+            assert_eq!(assumed.with_field(32, |len: &u32| *len).unwrap(), 32);
+            let key = assumed.with_field(8, |key: &u64| *key).unwrap();
+            assert_eq!(key, 0xdead_beef_cafe_babe);
+            let key = assumed.with_field(0, |key: &[u64; 4]| *key).unwrap();
+            assert_eq!(key, [0xdead_beef_cafe_babe; 4]);
+        }
+
+        #[test]
+        fn with_field_out_of_bounds() {
+            let mut secret = written();
+            let addr = core::ptr::addr_of_mut!(secret) as usize;
+            let assumed = assume_safe::<Secret>(addr).unwrap();
+            let size = core::mem::size_of::<Secret>();
+
+            // Crosses the end of the target
+            assert!(assumed.with_field(size - 4, |_: &u64| ()).is_err());
+            assert!(assumed.with_field(size, |_: &u8| ()).is_err());
+            assert!(assumed.with_field(usize::MAX, |_: &u8| ()).is_err());
+            // Misaligned
+            assert!(assumed.with_field(4, |_: &u64| ()).is_err());
+            // The last byte is still accessible
+            assert!(assumed.with_field(size - 1, |_: &u8| ()).is_ok());
+        }
+
+        #[repr(C)]
+        struct Word(u32);
+
+        impl RawPtr for Word {}
+
+        impl SafetyChecked for Word {
+            fn required_align(&self) -> usize {
+                core::mem::align_of::<usize>()
+            }
+        }
+
+        static WORD_STATE: core::sync::atomic::AtomicU8 = core::sync::atomic::AtomicU8::new(0);
+        const WORD_UNINITIALIZED: u8 = 1;
+        const WORD_EXPIRED: u8 = 2;
+        const WORD_SHARED: u8 = 3;
+
+        impl SafetyAssured for Word {
+            fn is_initialized(&self) -> bool {
+                WORD_STATE.load(core::sync::atomic::Ordering::SeqCst) != WORD_UNINITIALIZED
+            }
+
+            fn verify_lifetime(&self) -> bool {
+                WORD_STATE.load(core::sync::atomic::Ordering::SeqCst) != WORD_EXPIRED
+            }
+
+            fn verify_ownership(&self) -> bool {
+                WORD_STATE.load(core::sync::atomic::Ordering::SeqCst) != WORD_SHARED
+            }
+        }
+
+        #[test]
+        fn assume_error() {
+            let mut words = [Word(0), Word(0), Word(0), Word(0)];
+            let addr = core::ptr::addr_of_mut!(words) as usize;
+            let addr =
+                addr - addr % core::mem::align_of::<usize>() + core::mem::align_of::<usize>();
+
+            assert!(matches!(assume_safe::<Word>(0), Err(AssumeError::Null)));
+            // Aligned for `Word` but not for `usize`
+            assert!(matches!(
+                assume_safe::<Word>(addr + 4),
+                Err(AssumeError::Misaligned)
+            ));
+            assert!(assume_opt::<Word>(addr + 4).is_none());
+
+            for (state, error) in [
+                (WORD_UNINITIALIZED, AssumeError::Uninitialized),
+                (WORD_EXPIRED, AssumeError::LifetimeViolation),
+                (WORD_SHARED, AssumeError::OwnershipViolation),
+            ] {
+                WORD_STATE.store(state, core::sync::atomic::Ordering::SeqCst);
+                assert_eq!(assume_safe::<Word>(addr).err(), Some(error));
+            }
+            WORD_STATE.store(0, core::sync::atomic::Ordering::SeqCst);
+            assert!(assume_safe::<Word>(addr).is_ok());
+            assert!(assume_opt::<Word>(addr).is_some());
+
+            // The coarse error is still available
+            assert!(matches!(
+                Error::from(AssumeError::Misaligned),
+                Error::SafetyCheckFailed
+            ));
+            assert!(matches!(
+                Error::from(AssumeError::OwnershipViolation),
+                Error::AssuranceCheckFailed
+            ));
+        }
+
+        #[test]
+        fn volatile_round_trip() {
+            let mut secret = written();
+            let addr = core::ptr::addr_of_mut!(secret) as usize;
+            let mut assumed = assume_safe::<Secret>(addr).unwrap();
+
+            let prev = assumed
+                .mut_with_volatile(|secret| {
+                    let prev = secret.len;
+                    secret.len = 16;
+                    secret.key[0] = 0x1234;
+                    prev
+                })
+                .unwrap();
+            assert_eq!(prev, 32);
+
+            let (len, key) = assumed.with_volatile(|s| (s.len, s.key[0])).unwrap();
+            assert_eq!((len, key), (16, 0x1234));
+            assert_eq!(read_back(addr)[32..36], 16u32.to_ne_bytes());
+
+            unsafe { Secret::write_volatile(addr, written()) };
+            let read = unsafe { Secret::read_volatile::<Secret>(addr) };
+            assert_eq!((read.key, read.len), ([0xdead_beef_cafe_babe; 4], 32));
+        }
+
+        #[repr(C, align(16))]
+        struct Wide {
+            lo: u64,
+            hi: u64,
+        }
+
+        mock!(Wide);
+
+        #[test]
+        fn volatile_alignment() {
+            let mut wides = [Wide { lo: 1, hi: 2 }, Wide { lo: 3, hi: 4 }];
+            let addr = core::ptr::addr_of_mut!(wides) as usize;
+
+            // Aligned for `usize` but not for `Wide`.
+            // It is built directly as `assume_safe` rejects it.
+            let mut assumed = SafetyAssumed::<Wide> {
+                addr: addr + 8,
+                _phantom: core::marker::PhantomData,
+            };
+            assert!(assumed.with_volatile(|_| ()).is_err());
+            assert!(assumed.mut_with_volatile(|w| w.lo = 0).is_err());
+            assert_eq!((wides[0].lo, wides[0].hi, wides[1].lo), (1, 2, 3));
+
+            let assumed = assume_safe::<Wide>(addr + 16).unwrap();
+            assert_eq!(assumed.with_volatile(|w| w.lo + w.hi).unwrap(), 7);
+        }
+
+        const MOCK_GRANULE_SIZE: usize = 4096;
+
+        #[repr(C, align(4096))]
+        struct TwoGranules([u8; 2 * MOCK_GRANULE_SIZE]);
+
+        static DENIED_GRANULE: core::sync::atomic::AtomicUsize =
+            core::sync::atomic::AtomicUsize::new(0);
+
+        // Safety: It is an array of bytes.
+        unsafe impl FromBytes for TwoGranules {}
+
+        impl PermissionChecked for TwoGranules {
+            const GRANULE_SIZE: usize = MOCK_GRANULE_SIZE;
+
+            fn has_granule_read_permission(granule_addr: usize) -> bool {
+                granule_addr != DENIED_GRANULE.load(core::sync::atomic::Ordering::SeqCst)
+            }
+
+            fn has_granule_write_permission(granule_addr: usize) -> bool {
+                Self::has_granule_read_permission(granule_addr)
+            }
+        }
+
+        mock!(TwoGranules);
+
+        #[test]
+        fn with_slice() {
+            let mut span = TwoGranules([0; 2 * MOCK_GRANULE_SIZE]);
+            let addr = core::ptr::addr_of_mut!(span) as usize;
+            let mut assumed = assume_safe::<TwoGranules>(addr).unwrap();
+
+            // Exact fit to the first granule
+            assumed
+                .mut_with_slice(MOCK_GRANULE_SIZE / 8, |words: &mut [u64]| {
+                    words.iter_mut().for_each(|w| *w = u64::MAX)
+                })
+                .unwrap();
+            let len = assumed
+                .with_slice(MOCK_GRANULE_SIZE, |bytes: &[u8]| {
+                    assert!(bytes.iter().all(|b| *b == 0xff));
+                    bytes.len()
+                })
+                .unwrap();
+            assert_eq!(len, MOCK_GRANULE_SIZE);
+            assert!(span.0[MOCK_GRANULE_SIZE..].iter().all(|b| *b == 0));
+        }
+
+        #[test]
+        fn with_slice_zero_length() {
+            let span = TwoGranules([0; 2 * MOCK_GRANULE_SIZE]);
+            let assumed = assume_safe::<TwoGranules>(span.addr()).unwrap();
+
+            assert_eq!(
+                assumed.with_slice(0, |words: &[u64]| words.len()).unwrap(),
+                0
+            );
+        }
+
+        #[test]
+        fn with_slice_beyond_granule() {
+            let mut span = TwoGranules([0; 2 * MOCK_GRANULE_SIZE]);
+            let addr = core::ptr::addr_of_mut!(span) as usize;
+            let mut assumed = assume_safe::<TwoGranules>(addr).unwrap();
+
+            // One element into the next granule
+            assert!(assumed
+                .with_slice(MOCK_GRANULE_SIZE / 8 + 1, |_: &[u64]| ())
+                .is_err());
+            assert!(assumed
+                .mut_with_slice(MOCK_GRANULE_SIZE + 1, |_: &mut [u8]| ())
+                .is_err());
+            // The size overflows
+            assert!(assumed.with_slice(usize::MAX, |_: &[u64]| ()).is_err());
+            assert!(assumed.with_slice(usize::MAX, |_: &[u8]| ()).is_err());
+        }
+
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        #[repr(C)]
+        struct Header {
+            imm: u16,
+            flags: u16,
+        }
+
+        mock!(Header);
+
+        #[test]
+        fn read_copy() {
+            let mut header = Header {
+                imm: 0x1234,
+                flags: 0x1,
+            };
+            let addr = core::ptr::addr_of_mut!(header) as usize;
+            let assumed = assume_safe::<Header>(addr).unwrap();
+
+            let copy = assumed.read_copy().unwrap();
+            // The memory changes after the copy is taken
+            unsafe { (*(addr as *mut Header)).imm = 0xffff };
+
+            assert_eq!(
+                copy,
+                Header {
+                    imm: 0x1234,
+                    flags: 0x1
+                }
+            );
+            assert_eq!(assumed.read_copy().unwrap().imm, 0xffff);
+        }
+
+        /// An element of an array filling a granule.
+        #[repr(C)]
+        struct Slot([u64; 2]);
+
+        mock!(Slot, read = true, write = true);
+
+        const SLOT_SIZE: usize = core::mem::size_of::<Slot>();
+
+        #[repr(C, align(4096))]
+        struct Slots([Slot; MOCK_GRANULE_SIZE / SLOT_SIZE]);
+
+        #[test]
+        fn offset() {
+            let mut slots = Slots(core::array::from_fn(|i| Slot([i as u64; 2])));
+            let addr = core::ptr::addr_of_mut!(slots) as usize;
+            let first = assume_safe_permitted::<Slot>(addr).unwrap();
+            assert_eq!(first.addr(), addr);
+
+            let second = first.offset(SLOT_SIZE).unwrap();
+            assert_eq!(second.addr(), addr + SLOT_SIZE);
+            assert_eq!(second.0, [1; 2]);
+            let last = first.offset(MOCK_GRANULE_SIZE - SLOT_SIZE).unwrap();
+            assert_eq!(last.0, [(slots.0.len() - 1) as u64; 2]);
+
+            // Leaves the granule
+            assert!(first.offset(MOCK_GRANULE_SIZE).is_none());
+            assert!(last.offset(SLOT_SIZE).is_none());
+            assert!(first.offset(usize::MAX).is_none());
+            // Misaligned
+            assert!(first.offset(4).is_none());
+        }
+
+        #[test]
+        fn range_within_window() {
+            const BASE: usize = 0x8000_0000;
+            const SIZE: usize = 0x1000;
+
+            assert!(range_within(BASE, SIZE, BASE, SIZE));
+            assert!(range_within(BASE + 0x10, 0x20, BASE, SIZE));
+            assert!(range_within(BASE + SIZE, 0, BASE, SIZE));
+            assert!(!range_within(BASE + SIZE, 1, BASE, SIZE));
+            assert!(!range_within(BASE - 1, 2, BASE, SIZE));
+            assert!(!range_within(BASE + 1, SIZE, BASE, SIZE));
+
+            // The end of the range overflows
+            assert!(!range_within(BASE, usize::MAX, BASE, SIZE));
+            assert!(!range_within(usize::MAX, 2, 0, usize::MAX));
+            // The window reaching the top of the address space
+            let top = usize::MAX - SIZE + 1;
+            assert!(range_within(top, SIZE - 1, top, SIZE));
+            assert!(range_within(usize::MAX, 0, top, SIZE));
+            assert!(range_within(0, usize::MAX, 0, usize::MAX));
+            assert!(!range_within(1, usize::MAX, 0, usize::MAX));
+        }
+
+        #[test]
+        fn granule_boundary_crossed() {
+            const GRANULE: usize = MOCK_GRANULE_SIZE;
+            const ADDR: usize = 0x8800_0000;
+
+            assert!(!is_granule_boundary_crossed(ADDR, GRANULE, GRANULE));
+            assert!(!is_granule_boundary_crossed(ADDR + GRANULE - 1, 1, GRANULE));
+            assert!(!is_granule_boundary_crossed(ADDR + 0x10, 0, GRANULE));
+            assert!(is_granule_boundary_crossed(ADDR + GRANULE - 1, 2, GRANULE));
+            assert!(is_granule_boundary_crossed(ADDR, GRANULE + 1, GRANULE));
+
+            // The last granule of the address space
+            assert!(!is_granule_boundary_crossed(usize::MAX, 0, GRANULE));
+            assert!(is_granule_boundary_crossed(usize::MAX, 1, GRANULE));
+            assert!(!is_granule_boundary_crossed(
+                usize::MAX - GRANULE + 1,
+                GRANULE - 1,
+                GRANULE
+            ));
+            assert!(is_granule_boundary_crossed(ADDR, usize::MAX, GRANULE));
+
+            // Usable in constants
+            const _: () = assert!(is_granule_boundary_crossed(0xfff, 2, 0x1000));
+        }
+
+        #[test]
+        fn permission_for_span() {
+            let span = TwoGranules([0; 2 * MOCK_GRANULE_SIZE]);
+            let first = span.addr();
+            let second = first + MOCK_GRANULE_SIZE;
+            let len = core::mem::size_of::<TwoGranules>();
+
+            assert!(span.has_permission());
+            assert!(span.has_permission_for_span(len));
+
+            // The second granule is in the wrong state
+            DENIED_GRANULE.store(second, core::sync::atomic::Ordering::SeqCst);
+            assert!(span.has_permission());
+            assert!(span.has_permission_for_span(MOCK_GRANULE_SIZE));
+            assert!(!span.has_permission_for_span(len));
+            assert!(!span.has_permission_for_span(MOCK_GRANULE_SIZE + 1));
+
+            assert!(!span.has_permission_for_span(usize::MAX));
+        }
+
+        #[repr(C, align(4096))]
+        struct Revoked([u8; MOCK_GRANULE_SIZE]);
+
+        mock!(Revoked, read = false, write = false);
+
+        #[test]
+        fn assume_permitted() {
+            let mut granule = Revoked([0; MOCK_GRANULE_SIZE]);
+            let addr = core::ptr::addr_of_mut!(granule) as usize;
+
+            assert!(matches!(
+                assume_safe_permitted::<Revoked>(addr),
+                Err(AssumeError::NoPermission)
+            ));
+            // Rejected by `is_readable` as well
+            assert!(matches!(
+                assume_safe::<Revoked>(addr),
+                Err(AssumeError::NoPermission)
+            ));
+        }
+
+        /// A granule which the RMM only reads (e.g., an RD).
+        #[repr(C, align(4096))]
+        struct ReadOnly([u8; MOCK_GRANULE_SIZE]);
+
+        mock!(ReadOnly, read = true, write = false);
+
+        // Safety: It is an array of bytes.
+        unsafe impl FromBytes for ReadOnly {}
+
+        #[test]
+        fn read_only_permission() {
+            let mut granule = ReadOnly([0xab; MOCK_GRANULE_SIZE]);
+            let addr = core::ptr::addr_of_mut!(granule) as usize;
+
+            assert!(granule.has_read_permission());
+            assert!(!granule.has_write_permission());
+            assert!(!granule.has_permission());
+            assert!(granule.has_read_permission_for_span(MOCK_GRANULE_SIZE));
+            assert!(!granule.has_write_permission_for_span(MOCK_GRANULE_SIZE));
+
+            let mut assumed = assume_safe::<ReadOnly>(addr).unwrap();
+            assert_eq!(assumed.with(|granule| granule.0[0]), Ok(0xab));
+            assert_eq!(assumed.0[1], 0xab);
+            assert_eq!(
+                assumed.mut_with(|granule| granule.0[0] = 0),
+                Err(AssumeError::NoPermission)
+            );
+            assert_eq!(assumed.zero(), Err(AssumeError::NoPermission));
+            assert_eq!(granule.0[0], 0xab);
+        }
+
+        /// A structure smaller than a granule, which has to occupy a granule.
+        #[repr(C)]
+        struct GranuleBound([u8; 64]);
+
+        mock!(
+            GranuleBound,
+            align = MOCK_GRANULE_SIZE,
+            read = true,
+            write = true
+        );
+
+        #[test]
+        fn required_align() {
+            let mut span = TwoGranules([0; 2 * MOCK_GRANULE_SIZE]);
+            let addr = core::ptr::addr_of_mut!(span) as usize;
+
+            assert!(assume_safe::<GranuleBound>(addr).is_ok());
+            assert!(assume_safe::<GranuleBound>(addr + MOCK_GRANULE_SIZE).is_ok());
+            for offset in [1, 8, 64, MOCK_GRANULE_SIZE / 2] {
+                assert!(matches!(
+                    assume_safe::<GranuleBound>(addr + offset),
+                    Err(AssumeError::Misaligned)
+                ));
+            }
+        }
+
+        #[test]
+        fn aligned_ptr() {
+            let mut slots = Slots(core::array::from_fn(|i| Slot([i as u64; 2])));
+            let addr = core::ptr::addr_of_mut!(slots) as usize;
+
+            assert!(AlignedPtr::<Slot, 8>::try_new(0).is_none());
+            assert!(AlignedPtr::<Slot, 8>::try_new(addr + 4).is_none());
+
+            let mut ptr = AlignedPtr::<Slot, 8>::try_new(addr + SLOT_SIZE).unwrap();
+            assert_eq!(core::mem::size_of_val(&ptr), core::mem::size_of::<usize>());
+            ptr.mut_with(|slot| slot.0[0] = 16).unwrap();
+            assert_eq!(ptr.with(|slot| slot.0).unwrap(), [16, 1]);
+
+            // `ALIGN` is looser than the alignment the type requires
+            let mut span = TwoGranules([0; 2 * MOCK_GRANULE_SIZE]);
+            let addr = core::ptr::addr_of_mut!(span) as usize;
+            let ptr = AlignedPtr::<GranuleBound, 64>::try_new(addr).unwrap();
+            assert_eq!(ptr.with(|_| ()), Err(AssumeError::Misaligned));
+        }
+
+        #[test]
+        fn aligned_ptr_permission() {
+            let mut granule = ReadOnly([0xab; MOCK_GRANULE_SIZE]);
+            let addr = core::ptr::addr_of_mut!(granule) as usize;
+            let mut ptr = AlignedPtr::<ReadOnly, MOCK_GRANULE_SIZE>::try_new(addr).unwrap();
+            assert_eq!(ptr.with(|granule| granule.0[0]), Ok(0xab));
+            assert_eq!(
+                ptr.mut_with(|granule| granule.0[0] = 0),
+                Err(AssumeError::NoPermission)
+            );
+            assert_eq!(granule.0[0], 0xab);
+
+            let mut granule = Revoked([0; MOCK_GRANULE_SIZE]);
+            let addr = core::ptr::addr_of_mut!(granule) as usize;
+            let ptr = AlignedPtr::<Revoked, MOCK_GRANULE_SIZE>::try_new(addr).unwrap();
+            assert_eq!(ptr.with(|_| ()), Err(AssumeError::NoPermission));
+        }
+
+        #[test]
+        fn mut_with() {
+            let mut secrets = [written(), written()];
+            let first = core::ptr::addr_of_mut!(secrets[0]) as usize;
+            let second = core::ptr::addr_of_mut!(secrets[1]) as usize;
+            let mut first = assume_safe::<Secret>(first).unwrap();
+            let mut second = assume_safe::<Secret>(second).unwrap();
+
+            // Adjacent regions don't overlap
+            first
+                .mut_with(|first| {
+                    first.len = 16;
+                    second.mut_with(|second| second.len = 8).unwrap();
+                })
+                .unwrap();
+            assert_eq!((first.len, second.len), (16, 8));
+        }
+
+        #[cfg(all(feature = "overlap-check", debug_assertions))]
+        #[test]
+        #[should_panic(expected = "overlaps a borrowed region")]
+        fn mut_with_overlapping() {
+            let mut secret = written();
+            let addr = core::ptr::addr_of_mut!(secret) as usize;
+            let mut first = assume_safe::<Secret>(addr).unwrap();
+            let mut second = assume_safe::<Secret>(addr).unwrap();
+
+            let _ = first.mut_with(|_| second.mut_with(|_| ()));
+        }
+    }
 }
@@ -0,0 +1,117 @@
+//! Checks the closure-based accesses of `SafetyAssumed` under Stacked Borrows.
+//!
+//! The targets are real heap allocations, so `cargo +nightly miri test` checks
+//! the references created from their addresses against their provenance.
+//! The tests also pass as plain unit tests.
+
+use safe_abstraction::raw_ptr::{assume_safe, RawPtr, SafetyAssured, SafetyChecked};
+
+struct Counter {
+    value: u64,
+    limit: u64,
+}
+
+impl RawPtr for Counter {}
+
+impl SafetyChecked for Counter {}
+
+impl SafetyAssured for Counter {
+    fn is_initialized(&self) -> bool {
+        self.value <= self.limit
+    }
+
+    fn verify_ownership(&self) -> bool {
+        true
+    }
+}
+
+fn counter() -> Box<Counter> {
+    Box::new(Counter {
+        value: 0,
+        limit: 16,
+    })
+}
+
+#[test]
+fn shared_access() {
+    let counter = counter();
+    let addr = &*counter as *const Counter as usize;
+
+    let first = assume_safe::<Counter>(addr).unwrap();
+    let second = assume_safe::<Counter>(addr).unwrap();
+    // shared references may alias
+    first
+        .with(|first| {
+            second
+                .with(|second| assert_eq!(first.limit, second.limit))
+                .unwrap()
+        })
+        .unwrap();
+    assert_eq!(counter.value, 0);
+}
+
+#[test]
+fn mutable_access() {
+    let mut counter = counter();
+    let addr = &mut *counter as *mut Counter as usize;
+
+    let mut assumed = assume_safe::<Counter>(addr).unwrap();
+    for _ in 0..4 {
+        assumed.mut_with(|counter| counter.value += 1).unwrap();
+    }
+    assert_eq!(assumed.with(|counter| counter.value), Ok(4));
+
+    // the owner takes over once the accesses are done
+    counter.value += 1;
+    assert_eq!(counter.value, 5);
+}
+
+#[test]
+fn disjoint_mutable_access() {
+    let mut counters = Box::new([
+        Counter {
+            value: 0,
+            limit: 16,
+        },
+        Counter {
+            value: 0,
+            limit: 16,
+        },
+    ]);
+    let first = &mut counters[0] as *mut Counter as usize;
+    let second = &mut counters[1] as *mut Counter as usize;
+
+    let mut first = assume_safe::<Counter>(first).unwrap();
+    let mut second = assume_safe::<Counter>(second).unwrap();
+    first
+        .mut_with(|first| {
+            first.value = 1;
+            second.mut_with(|second| second.value = 2).unwrap();
+            first.value += 1;
+        })
+        .unwrap();
+    assert_eq!((counters[0].value, counters[1].value), (2, 2));
+}
+
+/// Two `SafetyAssumed` over the same address hand out aliasing `&mut`,
+/// which nothing but the `overlap-check` feature catches at runtime.
+///
+/// Miri is expected to report Undefined Behavior here:
+/// creating the inner `&mut` invalidates the outer one,
+/// so the write through it afterwards uses a tag
+/// which no longer exists in the borrow stack.
+/// Run it explicitly with `cargo +nightly miri test -- --ignored`.
+#[cfg(miri)]
+#[test]
+#[ignore = "intentionally violates aliasing to show what Miri flags"]
+fn aliasing_mutable_access() {
+    let mut counter = counter();
+    let addr = &mut *counter as *mut Counter as usize;
+
+    let mut first = assume_safe::<Counter>(addr).unwrap();
+    let mut second = assume_safe::<Counter>(addr).unwrap();
+    let _ = first.mut_with(|first| {
+        let _ = second.mut_with(|second| second.value = 1);
+        first.value = 2;
+    });
+}
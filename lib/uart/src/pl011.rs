@@ -3,38 +3,38 @@ extern crate alloc;
 use alloc::boxed::Box;
 use spinning_top::Spinlock;
 
-use islet_rmm::io::{self, ConsoleWriter, Error, ErrorKind, Result, Write};
+use islet_rmm::io::{self, ConsoleWriter, Error, ErrorKind, Mmio, Result, Write};
 
 const CLK_IN_HZ: usize = 24000000;
 const BAUDRATE: usize = 115200;
 
-const REG_LEN: isize = core::mem::size_of::<u32>() as isize;
+const REG_LEN: usize = core::mem::size_of::<u32>();
 
-const UARTDR: isize = 0x000 / REG_LEN;
+const UARTDR: usize = 0x000 / REG_LEN;
 #[allow(dead_code)]
-const UARTRSR: isize = 0x004 / REG_LEN;
-const UARTECR: isize = 0x004 / REG_LEN;
+const UARTRSR: usize = 0x004 / REG_LEN;
+const UARTECR: usize = 0x004 / REG_LEN;
 #[allow(dead_code)]
-const UARTFR: isize = 0x018 / REG_LEN;
+const UARTFR: usize = 0x018 / REG_LEN;
 #[allow(dead_code)]
-const UARTIMSC: isize = 0x038 / REG_LEN;
+const UARTIMSC: usize = 0x038 / REG_LEN;
 #[allow(dead_code)]
-const UARTRIS: isize = 0x03C / REG_LEN;
+const UARTRIS: usize = 0x03C / REG_LEN;
 #[allow(dead_code)]
-const UARTICR: isize = 0x044 / REG_LEN;
+const UARTICR: usize = 0x044 / REG_LEN;
 
 #[allow(dead_code)]
-const UARTILPR: isize = 0x020 / REG_LEN;
-const UARTIBRD: isize = 0x024 / REG_LEN;
-const UARTFBRD: isize = 0x028 / REG_LEN;
-const UARTLCR_H: isize = 0x02C / REG_LEN;
-const UARTCR: isize = 0x030 / REG_LEN;
+const UARTILPR: usize = 0x020 / REG_LEN;
+const UARTIBRD: usize = 0x024 / REG_LEN;
+const UARTFBRD: usize = 0x028 / REG_LEN;
+const UARTLCR_H: usize = 0x02C / REG_LEN;
+const UARTCR: usize = 0x030 / REG_LEN;
 #[allow(dead_code)]
-const UARTIFLS: isize = 0x034 / REG_LEN;
+const UARTIFLS: usize = 0x034 / REG_LEN;
 #[allow(dead_code)]
-const UARTMIS: isize = 0x040 / REG_LEN;
+const UARTMIS: usize = 0x040 / REG_LEN;
 #[allow(dead_code)]
-const UARTDMACR: isize = 0x048 / REG_LEN;
+const UARTDMACR: usize = 0x048 / REG_LEN;
 
 const UARTFR_TXFF_BIT: u32 = 5;
 
@@ -68,27 +68,27 @@ enum UARTLCR_H {
 const LINE_CONTROL: u32 = UARTLCR_H::FEN as u32 | UARTLCR_H::WLEN_8 as u32;
 
 struct DeviceInner {
-    register: *mut u32,
+    mmio: Mmio,
     ready: bool,
 }
 
 impl DeviceInner {
     pub const fn new() -> Self {
         Self {
-            register: 0 as *mut u32,
+            mmio: Mmio::empty(),
             ready: false,
         }
     }
 
-    pub fn set_base(&mut self, base: usize) {
-        self.register = base as *mut u32;
+    pub fn set_mmio(&mut self, mmio: Mmio) {
+        self.mmio = mmio;
     }
 
     pub fn putc(&mut self, byte: u8) -> Result<()> {
         if self.ready {
             unsafe {
-                while self.register.offset(UARTFR).read_volatile() & UARTFR_TXFF_BIT == 0 {}
-                self.register.offset(UARTDR).write_volatile(byte as u32);
+                while self.mmio.read(UARTFR) & UARTFR_TXFF_BIT == 0 {}
+                self.mmio.write(UARTDR, byte as u32);
             }
             Ok(())
         } else {
@@ -106,28 +106,28 @@ impl io::Device for DeviceInner {
         if !self.ready {
             unsafe {
                 //Disable uart before programming
-                self.register.offset(UARTCR).write_volatile(
-                    self.register.offset(UARTCR).read_volatile() & !(UARTCR::EN as u32),
-                );
+                self.mmio
+                    .write(UARTCR, self.mmio.read(UARTCR) & !(UARTCR::EN as u32));
 
                 //Program the baudrate
                 let divisor = (CLK_IN_HZ << 2) / BAUDRATE;
                 let ibrd = (divisor >> 6) as u32;
-                self.register.offset(UARTIBRD).write_volatile(ibrd);
+                self.mmio.write(UARTIBRD, ibrd);
 
                 //Write the FBRD
                 let fbrd = (ibrd & 0x3f) as u32;
-                self.register.offset(UARTFBRD).write_volatile(fbrd);
+                self.mmio.write(UARTFBRD, fbrd);
 
-                self.register.offset(UARTLCR_H).write_volatile(LINE_CONTROL);
+                self.mmio.write(UARTLCR_H, LINE_CONTROL);
 
                 //Clear any pending errors
-                self.register.offset(UARTECR).write_volatile(0);
+                self.mmio.write(UARTECR, 0);
 
                 //Enable tx, rx, and uart overall */
-                self.register
-                    .offset(UARTCR)
-                    .write_volatile(UARTCR::RXE as u32 | UARTCR::TXE as u32 | UARTCR::EN as u32);
+                self.mmio.write(
+                    UARTCR,
+                    UARTCR::RXE as u32 | UARTCR::TXE as u32 | UARTCR::EN as u32,
+                );
             }
 
             self.ready = true;
@@ -156,7 +156,7 @@ unsafe impl Send for DeviceInner {}
 
 static DEVICE_INNER: Spinlock<DeviceInner> = Spinlock::new(DeviceInner::new());
 
-pub struct Device(usize);
+pub struct Device(Mmio);
 
 impl io::Device for Device {
     fn initialized(&self) -> bool {
@@ -164,7 +164,7 @@ impl io::Device for Device {
     }
 
     fn initialize(&mut self) -> Result<()> {
-        DEVICE_INNER.lock().set_base(self.0);
+        DEVICE_INNER.lock().set_mmio(self.0);
         DEVICE_INNER.lock().initialize()
     }
 }
@@ -177,6 +177,8 @@ impl Write for Device {
 
 impl ConsoleWriter for Device {}
 
-pub fn device(base: usize) -> Box<Device> {
-    Box::new(Device(base))
+/// Creates a PL011 device whose registers are located at `base`
+/// and spaced `stride` bytes apart.
+pub fn device(base: usize, stride: usize) -> Result<Box<Device>> {
+    Ok(Box::new(Device(Mmio::new(base, stride)?)))
 }
@@ -3,7 +3,7 @@ use crate::log::LevelFilter;
 
 use armv9a::regs::*;
 use core::ptr::{addr_of, addr_of_mut};
-use islet_rmm::config::{NUM_OF_CPU, RMM_STACK_SIZE};
+use islet_rmm::config::{NUM_OF_CPU, RMM_STACK_SIZE, UART_BASE, UART_REG_STRIDE};
 use islet_rmm::io::stdout;
 use islet_rmm::logger;
 
@@ -49,8 +49,9 @@ unsafe fn clear_bss() {
 }
 
 fn init_console() {
-    const UART3_BASE: usize = 0x1c0c_0000usize;
-    let _ = stdout().attach(uart::pl011::device(UART3_BASE));
+    if let Ok(device) = uart::pl011::device(UART_BASE, UART_REG_STRIDE) {
+        let _ = stdout().attach(device);
+    }
     logger::register_global_logger(LevelFilter::Trace); // Control log level
     info!("Initialized the console!");
 }
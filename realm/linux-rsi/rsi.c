@@ -36,20 +36,38 @@ static struct class *cls;
 static DEFINE_MUTEX(attestation_call);
 
 
+/* RSI_VERSION takes the requested revision and returns the status
+ * with the lower and the higher revisions the RMM supports.
+ */
+static unsigned long rsi_version(unsigned long *lower, unsigned long *higher)
+{
+	struct arm_smccc_1_2_regs input = {0}, output = {0};
+
+	input.a0 = SMC_RSI_ABI_VERSION;
+	input.a1 = RSI_ABI_VERSION_REQUEST;
+
+	arm_smccc_1_2_smc(&input, &output);
+
+	*lower = output.a1;
+	*higher = output.a2;
+	return output.a0;
+}
+
 static void rsi_playground(void)
 {
 	unsigned long ret = 0;
 	bool realm = false;
-	unsigned long ver = 0;
+	unsigned long lower = 0, higher = 0;
 
 	// creative use of an API
 	realm = cc_platform_has(CC_ATTR_MEM_ENCRYPT);
 	printk(RSI_INFO "Is realm: %s\n", realm ? "true" : "false");
 
 	// version
-	ver = rsi_get_version();
-	printk(RSI_INFO "RSI version: %lu.%lu\n",
-	       RSI_ABI_VERSION_GET_MAJOR(ver), RSI_ABI_VERSION_GET_MINOR(ver));
+	ret = rsi_version(&lower, &higher);
+	printk(RSI_INFO "RSI version ret: %lu, lower: %lu.%lu, higher: %lu.%lu\n", ret,
+	       RSI_ABI_VERSION_GET_MAJOR(lower), RSI_ABI_VERSION_GET_MINOR(lower),
+	       RSI_ABI_VERSION_GET_MAJOR(higher), RSI_ABI_VERSION_GET_MINOR(higher));
 
 	// get config
 	ret = rsi_get_realm_config(&config);
@@ -228,6 +246,7 @@ static long device_ioctl(struct file *f, unsigned int cmd, unsigned long arg)
 {
 	int ret = 0;
 
+	unsigned long lower = 0, higher = 0;
 	uint32_t version = 0;
 	struct rsi_measurement *measur = NULL;
 	struct rsi_attestation *attest = NULL;
@@ -236,7 +255,14 @@ static long device_ioctl(struct file *f, unsigned int cmd, unsigned long arg)
 	case RSIIO_ABI_VERSION:
 		printk(RSI_INFO "ioctl: abi_version\n");
 
-		version = (uint32_t)rsi_get_version();
+		ret = rsi_version(&lower, &higher);
+		if (ret != RSI_SUCCESS) {
+			printk(RSI_ALERT "ioctl: unsupported RSI version: %lu.%lu\n",
+			       RSI_ABI_VERSION_GET_MAJOR(higher), RSI_ABI_VERSION_GET_MINOR(higher));
+			return -rsi_ret_to_errno(ret);
+		}
+
+		version = (uint32_t)lower;
 		ret = copy_to_user((uint32_t*)arg, &version, sizeof(uint32_t));
 		if (ret != 0) {
 			printk(RSI_ALERT "ioctl: copy_to_user failed: %d\n", ret);
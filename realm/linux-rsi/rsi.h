@@ -9,6 +9,9 @@
 #define RSI_ABI_VERSION_GET_MINOR(_version) ((_version) & 0xFFFF)
 #endif
 
+/* The revision of RSI this module is written for */
+#define RSI_ABI_VERSION_REQUEST    ((1UL << 16) | 0)
+
 #define MAX_MEASUR_LEN  0x40
 #define CHALLENGE_LEN   0x40
 #define MAX_TOKEN_LEN   0x1000
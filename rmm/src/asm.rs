@@ -1,5 +1,13 @@
 use core::arch::asm;
 
+#[cfg(not(test))]
+use crate::realm::mm::stage2_translation::{tlbi_ns, TLBI_OP};
+#[cfg(not(test))]
+use armv9a::bits_in_reg;
+use armv9a::regs::CNTPCT_EL0;
+#[cfg(not(test))]
+use armv9a::regs::{PMCCNTR_EL0, VTTBR_EL2};
+
 pub const SMC_SUCCESS: usize = 0;
 #[cfg(kani)]
 pub const SMC_ERROR: usize = 1;
@@ -81,3 +89,112 @@ pub fn dcache_flush(addr: usize, len: usize) {
         }
     }
 }
+
+/// Reads a random number from RNDR (FEAT_RNG),
+/// which fails if no random number is available in a reasonable time.
+/// The caller has to check that FEAT_RNG is implemented.
+#[inline(always)]
+pub fn rndr() -> Option<u64> {
+    let random: u64;
+    let valid: u64;
+    unsafe {
+        asm!(
+            "mrs {random}, s3_3_c2_c4_0",
+            "cset {valid}, ne",
+            random = out(reg) random,
+            valid = out(reg) valid,
+        );
+    }
+    (valid != 0).then_some(random)
+}
+
+/// Reads the physical count of the system counter (CNTPCT_EL0).
+#[inline(always)]
+pub fn system_counter() -> u64 {
+    unsafe { CNTPCT_EL0.get() }
+}
+
+/// Reads the cycle counter (PMCCNTR_EL0),
+/// which counts only if it is enabled by PMCR_EL0.E and PMCNTENSET_EL0.C.
+#[cfg(not(test))]
+#[inline(always)]
+pub fn cycle_counter() -> u64 {
+    unsafe { PMCCNTR_EL0.get() }
+}
+
+/// Invalidates the stage 2 TLB entries of the realm for `ipa` mapped at `level`.
+///
+/// VTTBR_EL2 is switched to `vttbr` of the realm (i.e., its VMID and RTT base) for the TLBI,
+/// which applies to the current VMID. As the stage 1 entries may combine the invalidated
+/// ones, `TLBI IPAS2E1IS` is followed by `DSB ISH` and `TLBI VMALLE1IS` (DDI0487 D8.13.5).
+#[cfg(not(test))]
+pub fn tlbi_ipa(vttbr: u64, ipa: usize, level: usize) {
+    // TTL: the translation granule is 4KB (0b01) and the level of the entry
+    let operand = bits_in_reg(TLBI_OP::NS, tlbi_ns::IPAS_S)
+        | bits_in_reg(TLBI_OP::TTL, 0b0100 | level as u64)
+        | bits_in_reg(TLBI_OP::IPA, (ipa >> 12) as u64);
+    unsafe {
+        let saved = VTTBR_EL2.get();
+        VTTBR_EL2.set(vttbr);
+        asm!(
+            "isb",
+            "dsb ishst",
+            "tlbi ipas2e1is, {}",
+            "dsb ish",
+            "tlbi vmalle1is",
+            "dsb ish",
+            "isb",
+            in(reg) operand,
+        );
+        VTTBR_EL2.set(saved);
+        asm!("isb");
+    }
+}
+
+/// Invalidates all the stage 1 and stage 2 TLB entries of the realm of `vttbr`,
+/// e.g., when a table entry is replaced and any entry below it may be cached.
+#[cfg(not(test))]
+pub fn tlbi_vmid(vttbr: u64) {
+    unsafe {
+        let saved = VTTBR_EL2.get();
+        VTTBR_EL2.set(vttbr);
+        asm!("isb", "dsb ishst", "tlbi vmalls12e1is", "dsb ish", "isb",);
+        VTTBR_EL2.set(saved);
+        asm!("isb");
+    }
+}
+
+/// The calls of the stubbed `tlbi_ipa` as `(vttbr, ipa, level)`, in order.
+#[cfg(test)]
+pub static TLBI_IPA_CALLS: spin::mutex::Mutex<alloc::vec::Vec<(u64, usize, usize)>> =
+    spin::mutex::Mutex::new(alloc::vec::Vec::new());
+
+/// Stub of `tlbi_ipa` for tests running on the host, recording the calls in `TLBI_IPA_CALLS`.
+#[cfg(test)]
+pub fn tlbi_ipa(vttbr: u64, ipa: usize, level: usize) {
+    TLBI_IPA_CALLS.lock().push((vttbr, ipa, level));
+}
+
+/// The VTTBRs of the calls of the stubbed `tlbi_vmid`, in order.
+#[cfg(test)]
+pub static TLBI_VMID_CALLS: spin::mutex::Mutex<alloc::vec::Vec<u64>> =
+    spin::mutex::Mutex::new(alloc::vec::Vec::new());
+
+/// Stub of `tlbi_vmid` for tests running on the host, recording the calls in `TLBI_VMID_CALLS`.
+#[cfg(test)]
+pub fn tlbi_vmid(vttbr: u64) {
+    TLBI_VMID_CALLS.lock().push(vttbr);
+}
+
+/// The number of cycles the stubbed counter advances on every read.
+#[cfg(test)]
+pub const CYCLE_COUNTER_STEP: u64 = 100;
+
+/// Stub of the cycle counter for tests running on the host,
+/// advancing by `CYCLE_COUNTER_STEP` on every read.
+#[cfg(test)]
+pub fn cycle_counter() -> u64 {
+    use core::sync::atomic::{AtomicU64, Ordering};
+    static CYCLES: AtomicU64 = AtomicU64::new(0);
+    CYCLES.fetch_add(CYCLE_COUNTER_STEP, Ordering::Relaxed)
+}
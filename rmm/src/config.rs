@@ -1,3 +1,5 @@
+use core::ops::RangeInclusive;
+
 pub const NUM_OF_CPU: usize = 8;
 pub const NUM_OF_CLUSTER: usize = 2;
 pub const NUM_OF_CPU_PER_CLUSTER: usize = NUM_OF_CPU / NUM_OF_CLUSTER;
@@ -10,8 +12,90 @@ pub const HUGE_PAGE_SIZE: usize = 1024 * 1024 * 1024; // 1GiB
 pub const RMM_STACK_SIZE: usize = 1024 * 1024;
 pub const RMM_HEAP_SIZE: usize = 16 * 1024 * 1024;
 
+// PL011 UART3 of FVP
+pub const UART_BASE: usize = 0x1c0c_0000;
+pub const UART_REG_STRIDE: usize = 4;
+
+// The maximum number of realms existing at the same time.
+// RMI_REALM_CREATE fails beyond it, and RMI_REALM_COUNT_GET reports it to the host.
+pub const MAX_REALMS: usize = 64;
+
+// The minimum IPA width (in bits) of a realm.
+// RMI_REALM_CREATE rejects a realm configured with a narrower IPA space.
+// The default follows the smallest width allowed by the RMM spec;
+// raise it to require more address-space isolation (e.g., 40).
+pub const MIN_REALM_IPA_BITS: usize = 32;
+
+// The maximum number of the regions whose RIPAS is unaccounted for, which
+// RMI_RTT_UNACCOUNTED_GET finds at once. It bounds the walk under the lock of
+// the stage 2 table, and the host continues from the IPA where the walk stopped.
+pub const MAX_UNACCOUNTED_REGIONS: usize = 16;
+
+// The rate limit of the host exits which a realm triggers (i.e., RIPAS changes and host calls)
+// per REC. Beyond HOST_EXIT_BURST exits within HOST_EXIT_WINDOW ticks of the system counter,
+// an exit is penalized as HOST_EXIT_PENALTY so that the realm can't overwhelm the host.
+pub const HOST_EXIT_BURST: u32 = 256;
+pub const HOST_EXIT_WINDOW: u64 = 1_000_000; // 10ms at 100MHz
+pub const HOST_EXIT_PENALTY: Penalty = Penalty::Flag;
+
+/// How a host exit beyond the rate limit of a REC is penalized.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Penalty {
+    /// Exits to the host, flagging the exit as throttled in `Run`.
+    Flag,
+    /// Returns RSI_INCOMPLETE to the realm without progress where the realm can retry
+    /// (i.e., RIPAS changes). Other exits are flagged as `Flag`.
+    Retry,
+}
+
 pub const VM_STACK_SIZE: usize = 1 << 15;
 pub const STACK_ALIGN: usize = 16;
 
+// Zero the realm's data granules as soon as their RIPAS is changed from RAM to EMPTY,
+// instead of leaving the contents until the granules are reclaimed.
+// This costs a granule-sized write per page on every RIPAS change.
+pub const ZERO_ON_RIPAS_EMPTY: bool = cfg!(feature = "zero_ripas_empty");
+
+// Include the number of granules whose RIPAS is RAM in the realm token,
+// so that a verifier can tell the memory footprint of the realm at attestation time.
+pub const CLAIM_COMMITTED_RAM: bool = cfg!(feature = "committed_ram_claim");
+
+// Apply the realm's RIPAS changes in the RMM as if the host accepted them,
+// instead of exiting to the host with RMI_EXIT_RIPAS_CHANGE.
+// The host can neither see nor reject the changes,
+// so enable it only with a trusted, cooperative host (e.g., test setups).
+pub const RIPAS_AUTO_ACCEPT: bool = cfg!(feature = "ripas_auto_accept");
+
+// What happens when the RSI handlers fail to write a result register of the realm.
+// High-assurance builds may choose Halt, as such a failure implies a corrupted state.
+pub const REG_WRITE_FAILURE: RegWriteFailure = RegWriteFailure::Error;
+
+/// What happens when writing a result register of a realm fails.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RegWriteFailure {
+    /// Logs the failure and continues as if the register were written.
+    Warn,
+    /// Returns the error to the dispatcher.
+    Error,
+    /// Halts the RMM, as the failure implies a corrupted state.
+    Halt,
+}
+
+// The immediates of host calls which the host recognizes. The RMM spec allows any 16-bit value,
+// so narrow it down to what the host handles to reject the others instead of forwarding them.
+// The commands of the hosts in this tree (e.g., CMD_GET_SHARED_BUF of realm/rsi-test) are
+// below 0x100. A platform whose host handles other commands widens it.
+pub const HOST_CALL_IMMS: RangeInclusive<u16> = 0..=0xff;
+
+// The largest attestation token the RMM hands out to a realm, over as many
+// RSI_ATTEST_TOKEN_CONTINUE calls as it takes. A larger token fails the attestation
+// rather than being copied beyond what the realm expects.
+pub const MAX_ATTEST_TOKEN_SIZE: usize = 0x2000;
+
+// The most events the event log of a realm keeps, each of which takes EVENT_LOG_ENTRY_SIZE
+// bytes of the RMM heap for as long as the realm lives. It's bounded so that the logs of
+// MAX_REALMS realms fit in the heap. Once it's full, the events are measured but not logged.
+pub const EVENT_LOG_MAX_ENTRIES: usize = 0x100;
+
 // TODO: Acquire this address properly.
 pub const RMM_SHARED_BUFFER_START: usize = 0xFFBFF000;
@@ -3,6 +3,7 @@ pub enum ErrorKind {
     NotConnected,
     AlreadyExists,
     Unsupported,
+    InvalidInput,
     Other,
 }
 
@@ -27,6 +28,7 @@ impl From<Error> for &'static str {
             ErrorKind::NotConnected => "Communication error: NotConnected",
             ErrorKind::AlreadyExists => "Communication error: AlreadyExists",
             ErrorKind::Unsupported => "Communication error: Unsupported",
+            ErrorKind::InvalidInput => "Communication error: InvalidInput",
             ErrorKind::Other => "Communication error: Other",
         }
     }
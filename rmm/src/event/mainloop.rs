@@ -1,39 +1,57 @@
 extern crate alloc;
 
-use super::Context;
+use super::{slots, Command, Context, Handlers};
 use crate::asm::smc;
 use crate::rmi;
 use crate::rmi::error::Error;
 use crate::Monitor;
 
 use alloc::boxed::Box;
-use alloc::collections::btree_map::BTreeMap;
 use alloc::collections::vec_deque::VecDeque;
+use core::ops::Range;
 use spin::mutex::Mutex;
 
 pub type Handler = Box<dyn Fn(&[usize], &mut [usize], &Monitor) -> Result<(), Error>>;
 
+// 0xc400_0150 ~ 0xc400_018f are reserved for RMI by the specification.
+const RMI_COMMANDS: Range<Command> = 0xc400_0150..0xc400_0190;
+#[cfg(not(feature = "diagnostics"))]
+const COMMANDS: &[Range<Command>] = &[RMI_COMMANDS];
+#[cfg(feature = "diagnostics")]
+const COMMANDS: &[Range<Command>] = &[RMI_COMMANDS, rmi::DIAG_BASE..rmi::DIAG_BASE + 0x10];
+const SLOTS: usize = slots(COMMANDS);
+
 pub struct Mainloop {
     pub queue: Mutex<VecDeque<Context>>, // TODO: we need a more realistic queue considering multi-core environments if needed
-    pub on_event: BTreeMap<usize, Handler>,
+    pub on_event: Handlers<Handler, SLOTS>,
 }
 
 impl Mainloop {
     pub fn new() -> Self {
         Self {
             queue: Mutex::new(VecDeque::new()),
-            on_event: BTreeMap::new(),
+            on_event: Handlers::new(COMMANDS),
         }
     }
 
     #[cfg(not(kani))]
-    fn add_event_handlers(&mut self) {
+    pub fn add_event_handlers(&mut self) {
         rmi::features::set_event_handler(self);
         rmi::gpt::set_event_handler(self);
         rmi::realm::set_event_handler(self);
         rmi::rec::set_event_handler(self);
         rmi::rtt::set_event_handler(self);
         rmi::version::set_event_handler(self);
+        #[cfg(feature = "profiling")]
+        crate::rsi::profile::set_event_handler(self);
+        #[cfg(feature = "diagnostics")]
+        {
+            rmi::realm::set_diagnostics_handler(self);
+            rmi::rec::set_diagnostics_handler(self);
+            rmi::rtt::set_diagnostics_handler(self);
+        }
+        #[cfg(feature = "log_buffer")]
+        crate::log_buffer::set_event_handler(self);
     }
     #[cfg(kani)]
     fn add_event_handlers(&mut self) {
@@ -47,11 +65,10 @@ impl Mainloop {
     }
 
     #[cfg(not(kani))]
-    pub fn boot_complete(&mut self) {
+    pub fn boot_complete(&self) {
         let mut ctx = Context::new(rmi::BOOT_COMPLETE);
         ctx.init_arg(&[rmi::BOOT_SUCCESS]);
 
-        self.add_event_handlers();
         self.dispatch(ctx);
     }
     #[cfg(kani)]
@@ -113,15 +130,7 @@ impl Mainloop {
                 panic!("There is no registered event handler.");
             }
 
-            match self.on_event.get(&ctx.cmd) {
-                Some(handler) => {
-                    ctx.do_rmi(|arg, ret| handler(arg, ret, monitor));
-                }
-                None => {
-                    error!("Not registered event: {:X}", ctx.cmd);
-                    ctx.init_arg(&[rmi::RET_FAIL]);
-                }
-            };
+            self.invoke(&mut ctx, monitor);
 
             ctx.cmd = rmi::REQ_COMPLETE;
             self.dispatch(ctx);
@@ -137,12 +146,12 @@ impl Mainloop {
             panic!("There is no registered event handler.");
         }
 
-        match self.on_event.get(&ctx.cmd()) {
+        match self.on_event.get(ctx.cmd()) {
             Some(handler) => ctx.do_rmi(|arg, ret| handler(arg, ret, monitor)),
             None => {
                 assert!(false);
                 error!("Not registered event: {:X}", ctx.cmd());
-                ctx.init_arg(&[rmi::RET_FAIL]);
+                ctx.init_arg(&[rmi::NOT_SUPPORTED]);
 
                 return [0; 5]; // this is a bogus statement to meet the return type
             }
@@ -150,6 +159,72 @@ impl Mainloop {
     }
 
     pub fn add_event_handler(&mut self, code: usize, handler: Handler) {
-        self.on_event.insert(code, handler);
+        if self.on_event.insert(code, handler).is_err() {
+            panic!("Not an RMI command: {:X}", code);
+        }
+    }
+
+    /// Returns the handler registered for `cmd`, if any.
+    pub fn handler(&self, cmd: usize) -> Option<&Handler> {
+        self.on_event.get(cmd)
+    }
+
+    /// Runs the handler registered for the RMI call in `ctx`.
+    /// Returns `false`, leaving `NOT_SUPPORTED` for the host, if there is none.
+    #[cfg(not(kani))]
+    fn invoke(&self, ctx: &mut Context, monitor: &Monitor) -> bool {
+        match self.handler(ctx.cmd) {
+            Some(handler) => {
+                ctx.do_rmi(|arg, ret| handler(arg, ret, monitor));
+                true
+            }
+            None => {
+                error!("Not registered event: {:X}", ctx.cmd);
+                ctx.init_arg(&[rmi::NOT_SUPPORTED]);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn registration() {
+        let mut monitor = Monitor::new();
+        // not assigned by the specification
+        let cmd = 0xc400_0180;
+
+        // not registered
+        let mut ctx = Context::new(cmd);
+        ctx.resize_ret(2);
+        assert!(!monitor.rmi.invoke(&mut ctx, &monitor));
+        assert_eq!(ctx.arg_slice(), [rmi::NOT_SUPPORTED]);
+
+        let handler = |_: &[usize], ret: &mut [usize], _: &Monitor| {
+            ret[1] = 0x1234;
+            Ok(())
+        };
+        assert!(monitor.register_rmi(cmd, Box::new(handler)).is_ok());
+        let mut ctx = Context::new(cmd);
+        ctx.resize_ret(2);
+        assert!(monitor.rmi.invoke(&mut ctx, &monitor));
+        assert_eq!(ctx.arg_slice(), [rmi::SUCCESS, 0x1234]);
+
+        // neither a built-in handler nor a registered one is replaced
+        let res = monitor.register_rmi(cmd, Box::new(|_, _, _| Ok(())));
+        assert!(matches!(res, Err(Error::RmiErrorInUse)));
+        let res = monitor.register_rmi(rmi::VERSION, Box::new(|_, _, _| Ok(())));
+        assert!(matches!(res, Err(Error::RmiErrorInUse)));
+
+        // out of the RMI commands
+        let res = monitor.register_rmi(rmi::VERSION + 0x100, Box::new(|_, _, _| Ok(())));
+        assert!(matches!(res, Err(Error::RmiErrorInput)));
+        let mut ctx = Context::new(rmi::VERSION + 0x100);
+        ctx.resize_ret(2);
+        assert!(!monitor.rmi.invoke(&mut ctx, &monitor));
+        assert_eq!(ctx.arg_slice(), [rmi::NOT_SUPPORTED]);
     }
 }
@@ -9,6 +9,7 @@ pub use rsihandle::RsiHandle;
 
 extern crate alloc;
 use alloc::vec::Vec;
+use core::ops::Range;
 
 #[macro_export]
 macro_rules! listen {
@@ -155,3 +156,59 @@ impl Default for Context {
         Context::new(0)
     }
 }
+
+/// Event handlers of the commands in `ranges`, kept in a fixed-size array.
+///
+/// The handler of `cmd` is at `cmd - base` of its range,
+/// after the slots of the ranges ahead of it.
+pub struct Handlers<H, const N: usize> {
+    ranges: &'static [Range<Command>],
+    slots: [Option<H>; N],
+}
+
+impl<H, const N: usize> Handlers<H, N> {
+    pub fn new(ranges: &'static [Range<Command>]) -> Self {
+        assert_eq!(slots(ranges), N);
+        Self {
+            ranges,
+            slots: core::array::from_fn(|_| None),
+        }
+    }
+
+    fn index(&self, cmd: Command) -> Option<usize> {
+        let mut offset = 0;
+        for range in self.ranges {
+            if range.contains(&cmd) {
+                return Some(offset + cmd - range.start);
+            }
+            offset += range.len();
+        }
+        None
+    }
+
+    /// Fails with `RmiErrorInput` if `cmd` is not in any of the ranges.
+    pub fn insert(&mut self, cmd: Command, handler: H) -> Result<(), Error> {
+        let index = self.index(cmd).ok_or(Error::RmiErrorInput)?;
+        self.slots[index] = Some(handler);
+        Ok(())
+    }
+
+    pub fn get(&self, cmd: Command) -> Option<&H> {
+        self.slots[self.index(cmd)?].as_ref()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(Option::is_none)
+    }
+}
+
+/// Returns the number of the commands in `ranges`.
+pub const fn slots(ranges: &[Range<Command>]) -> usize {
+    let mut count = 0;
+    let mut i = 0;
+    while i < ranges.len() {
+        count += ranges[i].end - ranges[i].start;
+        i += 1;
+    }
+    count
+}
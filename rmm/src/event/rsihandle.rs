@@ -1,6 +1,6 @@
 extern crate alloc;
 
-use super::Context;
+use super::{slots, Command, Context, Handlers};
 
 use crate::get_granule;
 use crate::get_granule_if;
@@ -16,13 +16,24 @@ use crate::realm::context::set_reg;
 use crate::rmi::error::Error;
 
 use alloc::boxed::Box;
-use alloc::collections::btree_map::BTreeMap;
+use core::ops::Range;
 
 pub type Handler =
     Box<dyn Fn(&[usize], &mut [usize], &Monitor, &mut Rec<'_>, &mut Run) -> Result<(), Error>>;
 
+// SMCCC and PSCI (SMC32 and SMC64), 0xc400_0190 ~ 0xc400_01af which the specification
+// reserves for RSI, and our own extensions
+const COMMANDS: &[Range<Command>] = &[
+    psci::SMCCC_VERSION..psci::SMCCC_ARCH_FEATURES + 1,
+    psci::PSCI_VERSION..psci::PSCI_VERSION + 0x20,
+    0xc400_0000..0xc400_0020,
+    0xc400_0190..0xc400_01b0,
+    rsi::VENDOR_BASE..rsi::VENDOR_BASE + 0x10,
+];
+const SLOTS: usize = slots(COMMANDS);
+
 pub struct RsiHandle {
-    pub on_event: BTreeMap<usize, Handler>,
+    pub on_event: Handlers<Handler, SLOTS>,
 }
 
 impl RsiHandle {
@@ -32,22 +43,83 @@ impl RsiHandle {
 
     pub fn new() -> Self {
         let mut rsi = Self {
-            on_event: BTreeMap::new(),
+            on_event: Handlers::new(COMMANDS),
         };
         rsi.set_event_handlers();
         rsi
     }
 
+    /// Dispatches the RSI call of `rec` to its handler.
+    ///
+    /// `realmid` is the realm of the Rd which REC_ENTER locked to enter the REC.
+    /// The call is rejected with `RmiErrorRec` before any handler runs
+    /// if the REC ran under the stage 2 of another realm.
     pub fn dispatch(
         &self,
         ctx: &mut Context,
         monitor: &Monitor,
         rec: &mut Rec<'_>,
         run: &mut Run,
+        realmid: usize,
     ) -> usize {
-        match self.on_event.get(&ctx.cmd) {
+        let res = verify_realm(rec.stage2_vmid(), realmid, || {
+            self.invoke(ctx, monitor, rec, run)
+        });
+        let handled = match res {
+            Ok(handled) => handled,
+            Err(e) => {
+                error!(
+                    "RSI {:X} from a REC not owned by realm {}",
+                    ctx.cmd, realmid
+                );
+                ctx.init_ret(&[e.into()]);
+                return RsiHandle::RET_SUCCESS;
+            }
+        };
+
+        if !handled {
+            let res = rec.owner();
+            let rec_owner = if let Ok(owner) = res {
+                owner
+            } else {
+                error!("Failed to get realm owner");
+                return RsiHandle::RET_FAIL;
+            };
+            let res = get_granule_if!(rec_owner, GranuleState::RD);
+            let rd_granule = if let Ok(x) = res {
+                x
+            } else {
+                error!("Failed to get rd granule");
+                return RsiHandle::RET_FAIL;
+            };
+            let rd = rd_granule.content::<Rd>();
+
+            // TODO: handle the error properly
+            let _ = set_reg(rd, rec.vcpuid(), 0, RsiHandle::NOT_SUPPORTED);
+
+            return RsiHandle::RET_FAIL;
+        }
+        RsiHandle::RET_SUCCESS
+    }
+
+    /// Runs the handler registered for the RSI call in `ctx`.
+    /// Returns `false`, leaving `NOT_SUPPORTED` as the result, if there is none.
+    fn invoke(
+        &self,
+        ctx: &mut Context,
+        monitor: &Monitor,
+        rec: &mut Rec<'_>,
+        run: &mut Run,
+    ) -> bool {
+        match self.handler(ctx.cmd) {
             Some(handler) => {
+                #[cfg(feature = "profiling")]
+                rsi::profile::measure(ctx.cmd, || {
+                    ctx.do_rsi(|arg, ret| handler(arg, ret, monitor, rec, run))
+                });
+                #[cfg(not(feature = "profiling"))]
                 ctx.do_rsi(|arg, ret| handler(arg, ret, monitor, rec, run));
+                true
             }
             None => {
                 ctx.init_ret(&[RsiHandle::NOT_SUPPORTED]);
@@ -56,30 +128,9 @@ impl RsiHandle {
                     ctx.cmd,
                     RsiHandle::NOT_SUPPORTED
                 );
-
-                let res = rec.owner();
-                let rec_owner = if let Ok(owner) = res {
-                    owner
-                } else {
-                    error!("Failed to get realm owner");
-                    return RsiHandle::RET_FAIL;
-                };
-                let res = get_granule_if!(rec_owner, GranuleState::RD);
-                let rd_granule = if let Ok(x) = res {
-                    x
-                } else {
-                    error!("Failed to get rd granule");
-                    return RsiHandle::RET_FAIL;
-                };
-                let rd = rd_granule.content::<Rd>();
-
-                // TODO: handle the error properly
-                let _ = set_reg(rd, rec.vcpuid(), 0, RsiHandle::NOT_SUPPORTED);
-
-                return RsiHandle::RET_FAIL;
+                false
             }
         }
-        RsiHandle::RET_SUCCESS
     }
 
     fn set_event_handlers(&mut self) {
@@ -88,9 +139,136 @@ impl RsiHandle {
     }
 
     pub fn add_event_handler(&mut self, code: usize, handler: Handler) {
-        self.on_event.insert(code, handler);
+        if self.on_event.insert(code, handler).is_err() {
+            panic!("Not an RSI command: {:X}", code);
+        }
+    }
+
+    /// Returns the handler registered for `cmd`, if any.
+    pub fn handler(&self, cmd: usize) -> Option<&Handler> {
+        self.on_event.get(cmd)
+    }
+}
+
+/// Runs `f` (i.e., the RSI handler) only if the REC belongs to `realmid`,
+/// as a defense in depth against a corrupted association between the REC and its realm.
+fn verify_realm<T>(
+    rec_realmid: Result<usize, Error>,
+    realmid: usize,
+    f: impl FnOnce() -> T,
+) -> Result<T, Error> {
+    match rec_realmid {
+        Ok(id) if id == realmid => Ok(f()),
+        _ => Err(Error::RmiErrorRec),
     }
 }
 
 unsafe impl Send for RsiHandle {}
 unsafe impl Sync for RsiHandle {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mismatched_realm() {
+        let mut called = false;
+        let res = verify_realm(Ok(1), 2, || called = true);
+        assert!(matches!(res, Err(Error::RmiErrorRec)));
+        assert!(!called);
+
+        // a REC without its owner
+        let res = verify_realm(Err(Error::RmiErrorRec), 2, || called = true);
+        assert!(matches!(res, Err(Error::RmiErrorRec)));
+        assert!(!called);
+
+        assert!(verify_realm(Ok(2), 2, || called = true).is_ok());
+        assert!(called);
+    }
+
+    #[test]
+    fn registration() {
+        let mut monitor = Monitor::new();
+        let cmd = rsi::VENDOR_BASE + 0x8;
+        let mut rec = crate::rmi::rec::test::rec();
+        let mut run = Run::default();
+
+        // not registered
+        let mut ctx = Context::new(cmd);
+        ctx.resize_ret(2);
+        assert!(!monitor.rsi.invoke(&mut ctx, &monitor, &mut rec, &mut run));
+        assert_eq!(ctx.ret_slice(), [RsiHandle::NOT_SUPPORTED]);
+
+        let handler =
+            |_: &[usize], ret: &mut [usize], _: &Monitor, _: &mut Rec<'_>, _: &mut Run| {
+                ret[1] = 0x1234;
+                Ok(())
+            };
+        assert!(monitor.register_rsi(cmd, Box::new(handler)).is_ok());
+        let mut ctx = Context::new(cmd);
+        ctx.resize_ret(2);
+        assert!(monitor.rsi.invoke(&mut ctx, &monitor, &mut rec, &mut run));
+        assert_eq!(ctx.ret_slice(), [rsi::SUCCESS, 0x1234]);
+
+        // neither a built-in handler nor a registered one is replaced
+        let res = monitor.register_rsi(cmd, Box::new(|_, _, _, _, _| Ok(())));
+        assert!(matches!(res, Err(Error::RmiErrorInUse)));
+        let res = monitor.register_rsi(rsi::IPA_STATE_GET, Box::new(|_, _, _, _, _| Ok(())));
+        assert!(matches!(res, Err(Error::RmiErrorInUse)));
+
+        // out of the RSI commands
+        let res =
+            monitor.register_rsi(rsi::IPA_STATE_GET + 0x100, Box::new(|_, _, _, _, _| Ok(())));
+        assert!(matches!(res, Err(Error::RmiErrorInput)));
+    }
+
+    fn dispatch(
+        monitor: &Monitor,
+        cmd: usize,
+        rec: &mut Rec<'_>,
+        realmid: usize,
+    ) -> alloc::vec::Vec<usize> {
+        let mut ctx = Context::new(cmd);
+        ctx.resize_ret(2);
+        let mut run = Run::default();
+        let res = monitor
+            .rsi
+            .dispatch(&mut ctx, monitor, rec, &mut run, realmid);
+        assert_eq!(res, RsiHandle::RET_SUCCESS);
+        ctx.ret_slice().to_vec()
+    }
+
+    #[test]
+    fn dispatch_under_another_realm() {
+        use crate::realm::mm::vttbr;
+        use crate::test_support::OneGranuleTable;
+
+        let mut monitor = Monitor::new();
+        let cmd = rsi::VENDOR_BASE + 0x9;
+        let handler =
+            |_: &[usize], ret: &mut [usize], _: &Monitor, _: &mut Rec<'_>, _: &mut Run| {
+                ret[1] = 0x1234;
+                Ok(())
+            };
+        assert!(monitor.register_rsi(cmd, Box::new(handler)).is_ok());
+
+        // a REC of the realm 1, which REC_ENTER locked
+        let rd = Box::leak(crate::rmi::realm::rd::test::rd(
+            1,
+            33,
+            OneGranuleTable::new(),
+        ));
+        rd.vcpus[0].lock().context.sys_regs.vttbr = vttbr(1, 0);
+        let mut rec = crate::rmi::rec::test::rec();
+        rec.init(rd as *const Rd as usize, 0, 0).unwrap();
+        assert_eq!(dispatch(&monitor, cmd, &mut rec, 1), [rsi::SUCCESS, 0x1234]);
+
+        // entered under the realm 2
+        let rejected: usize = Error::RmiErrorRec.into();
+        assert_eq!(dispatch(&monitor, cmd, &mut rec, 2), [rejected]);
+
+        // run under the stage 2 of the realm 2
+        rd.vcpus[0].lock().context.sys_regs.vttbr = vttbr(2, 0);
+        assert_eq!(dispatch(&monitor, cmd, &mut rec, 1), [rejected]);
+    }
+}
@@ -16,12 +16,16 @@ use crate::granule::{FVP_DRAM0_REGION, FVP_DRAM1_IDX, FVP_DRAM1_REGION};
 pub struct Granule {
     /// granule state
     state: u8,
+    /// VMID of the realm which the granule is assigned to (see `assign_granule`)
+    owner: Option<u16>,
 }
 #[cfg(kani)]
 // DIFF: `gpt` ghost field is added to track GPT entry's status
 pub struct Granule {
     /// granule state
     state: u8,
+    /// VMID of the realm which the granule is assigned to (see `assign_granule`)
+    owner: Option<u16>,
     /// granule protection table (ghost field)
     pub gpt: GranuleGpt,
 }
@@ -38,7 +42,7 @@ impl Granule {
     #[cfg(not(kani))]
     fn new() -> Self {
         let state = GranuleState::Undelegated;
-        Granule { state }
+        Granule { state, owner: None }
     }
     #[cfg(kani)]
     // DIFF: `state` and `gpt` are filled with non-deterministic values
@@ -54,7 +58,11 @@ impl Granule {
                 gpt
             }
         };
-        Granule { state, gpt }
+        Granule {
+            state,
+            owner: None,
+            gpt,
+        }
     }
 
     #[cfg(kani)]
@@ -89,6 +97,35 @@ impl Granule {
         Ok(())
     }
 
+    /// Sets the state without zeroizing the granule and clears its owner,
+    /// which is used to restore the table to a snapshot in tests.
+    #[cfg(test)]
+    pub(crate) fn restore_state(&mut self, state: u8) {
+        self.state = state;
+        self.owner = None;
+    }
+
+    /// Returns the VMID of the realm which the granule is assigned to, if any.
+    pub fn owner(&self) -> Option<usize> {
+        self.owner.map(|vmid| vmid as usize)
+    }
+
+    /// Records `realm` as the owner of the granule, which fails if another realm owns it.
+    pub fn set_owner(&mut self, realm: usize) -> Result<(), Error> {
+        let realm = u16::try_from(realm).or(Err(Error::RmiErrorInput))?;
+        match self.owner {
+            Some(owner) if owner != realm => Err(Error::RmiErrorInput),
+            _ => {
+                self.owner = Some(realm);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn clear_owner(&mut self) {
+        self.owner = None;
+    }
+
     pub fn content_mut<T: Content>(&mut self) -> &mut T {
         let addr = self.index_to_addr();
         unsafe { &mut *(addr as *mut T) }
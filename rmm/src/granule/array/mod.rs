@@ -112,6 +112,33 @@ pub fn set_granule(granule: &mut Granule, state: u8) -> Result<(), Error> {
     granule.set_state(state)
 }
 
+/// Assigns the delegated granule to the realm `realm` (i.e., `Rd::id()`) in `state`
+/// until `release_granule`.
+///
+/// It is rejected if another realm owns the granule,
+/// so that a granule never belongs to two realms at once.
+pub fn assign_granule(granule: &mut Granule, state: u8, realm: usize) -> Result<(), Error> {
+    if granule.owner().is_some_and(|owner| owner != realm) {
+        return Err(Error::RmiErrorInput);
+    }
+    if granule.state() != GranuleState::Delegated {
+        return Err(Error::RmiErrorInput);
+    }
+    granule.set_owner(realm)?;
+    granule.set_state(state)
+}
+
+/// Releases the granule assigned by `assign_granule` on its teardown, delegating it again.
+pub fn release_granule(granule: &mut Granule) -> Result<(), Error> {
+    let state = granule.state();
+    if state == GranuleState::Delegated || state == GranuleState::Undelegated {
+        return Err(Error::RmiErrorInput);
+    }
+    granule.set_state(GranuleState::Delegated)?;
+    granule.clear_owner();
+    Ok(())
+}
+
 lazy_static! {
     pub static ref GRANULE_STATUS_TABLE: GranuleStatusTable = GranuleStatusTable::new();
 }
@@ -177,9 +204,97 @@ macro_rules! get_granule_if {
     }};
 }
 
+/// Returns the address of the first granule in `[base, limit)` which is in `state`.
+///
+/// Each granule is checked while holding its lock,
+/// so the caller must not hold the lock of any granule in the range.
+pub fn find_next(base: usize, limit: usize, state: u8) -> Option<usize> {
+    if !is_granule_aligned(base) {
+        return None;
+    }
+    (base..limit).step_by(GRANULE_SIZE).find(|&addr| {
+        let idx = granule_addr_to_index(addr);
+        if idx >= GRANULE_STATUS_TABLE_SIZE {
+            return false;
+        }
+        match GRANULE_STATUS_TABLE.entries[idx].lock() {
+            Ok(granule) => granule.state() == state,
+            Err(_) => false,
+        }
+    })
+}
+
 pub fn is_not_in_realm(addr: usize) -> bool {
     match get_granule_if!(addr, GranuleState::Undelegated) {
         Ok(_) => true,
         _ => false,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TEST_ADDR: usize = 0x880c_0000;
+
+    #[test]
+    fn find_next_granule() {
+        let base = TEST_ADDR;
+        let limit = TEST_ADDR + 4 * GRANULE_SIZE;
+        let delegated = TEST_ADDR + 2 * GRANULE_SIZE;
+        {
+            let mut granule = get_granule!(delegated).unwrap();
+            set_granule(&mut granule, GranuleState::Delegated).unwrap();
+        }
+
+        // skips the undelegated granules before it
+        assert_eq!(
+            find_next(base, limit, GranuleState::Delegated),
+            Some(delegated)
+        );
+        assert_eq!(
+            find_next(base, limit, GranuleState::Undelegated),
+            Some(base)
+        );
+        // none in range
+        assert_eq!(find_next(base, delegated, GranuleState::Delegated), None);
+        assert_eq!(find_next(base, limit, GranuleState::RD), None);
+        // out of the managed memory
+        assert_eq!(
+            find_next(0x0, 4 * GRANULE_SIZE, GranuleState::Undelegated),
+            None
+        );
+
+        let mut granule = get_granule!(delegated).unwrap();
+        set_granule(&mut granule, GranuleState::Undelegated).unwrap();
+    }
+
+    #[test]
+    fn reject_aliased_granule() {
+        let addr = TEST_ADDR + 8 * GRANULE_SIZE;
+        let mut granule = get_granule!(addr).unwrap();
+        // not delegated yet
+        assert!(assign_granule(&mut granule, GranuleState::Data, 1).is_err());
+        assert_eq!(granule.owner(), None);
+        set_granule(&mut granule, GranuleState::Delegated).unwrap();
+
+        assign_granule(&mut granule, GranuleState::Data, 1).unwrap();
+        assert_eq!(granule.owner(), Some(1));
+        // already owned by realm 1
+        assert!(assign_granule(&mut granule, GranuleState::RTT, 2).is_err());
+        assert_eq!(
+            (granule.state(), granule.owner()),
+            (GranuleState::Data, Some(1))
+        );
+
+        // released on its teardown, then free to be assigned to another realm
+        release_granule(&mut granule).unwrap();
+        assert_eq!(granule.owner(), None);
+        assert!(release_granule(&mut granule).is_err());
+        assign_granule(&mut granule, GranuleState::RTT, 2).unwrap();
+        assert_eq!(granule.owner(), Some(2));
+
+        release_granule(&mut granule).unwrap();
+        set_granule(&mut granule, GranuleState::Undelegated).unwrap();
+    }
+}
@@ -114,6 +114,8 @@ pub struct Inner {
     granule: Rc<Granule>,
     table: bool,
     valid: bool,
+    /// VMID of the realm which the granule is assigned to (see `assign_granule`)
+    owner: Option<u16>,
 }
 
 impl Inner {
@@ -126,6 +128,7 @@ impl Inner {
             }),
             table: false,
             valid: false,
+            owner: None,
         }
     }
 
@@ -160,6 +163,26 @@ impl Inner {
         Ok(())
     }
 
+    pub fn owner(&self) -> Option<usize> {
+        self.owner.map(|vmid| vmid as usize)
+    }
+
+    pub fn set_owner(&mut self, realm: usize) -> Result<(), Error> {
+        // a granule belongs to at most one realm
+        let realm = u16::try_from(realm).or(Err(Error::MmStateError))?;
+        match self.owner {
+            Some(owner) if owner != realm => Err(Error::MmStateError),
+            _ => {
+                self.owner = Some(realm);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn clear_owner(&mut self) {
+        self.owner = None;
+    }
+
     pub fn set_parent(&mut self, parent: Inner) -> Result<(), Error> {
         Rc::get_mut(&mut self.granule)
             .map_or_else(|| Err(Error::MmRefcountError), |g| g.set_parent(parent))
@@ -209,6 +232,7 @@ impl Clone for Inner {
             granule: self.granule.clone(),
             table: self.table,
             valid: self.valid,
+            owner: self.owner,
         }
     }
 }
@@ -191,6 +191,26 @@ pub fn set_granule(granule: &mut Inner, state: u64) -> Result<(), RmiError> {
     Ok(())
 }
 
+/// Assigns the delegated granule to the realm `realm` (i.e., `Rd::id()`) in `state`
+/// until `release_granule`.
+///
+/// It is rejected if another realm owns the granule,
+/// so that a granule never belongs to two realms at once.
+pub fn assign_granule(granule: &mut Inner, state: u64, realm: usize) -> Result<(), RmiError> {
+    if granule.owner().is_some_and(|owner| owner != realm) {
+        return Err(RmiError::RmiErrorInput);
+    }
+    set_granule(granule, state)?;
+    to_rmi_result(granule.set_owner(realm))
+}
+
+/// Releases the granule assigned by `assign_granule` on its teardown, delegating it again.
+pub fn release_granule(granule: &mut Inner) -> Result<(), RmiError> {
+    set_granule(granule, GranuleState::Delegated)?;
+    granule.clear_owner();
+    Ok(())
+}
+
 pub fn check_granule_parent(parent: &Inner, child: &Inner) -> Result<(), RmiError> {
     to_rmi_result(child.check_parent(parent))
 }
@@ -256,10 +276,33 @@ pub fn is_granule_aligned(addr: usize) -> bool {
     addr % GRANULE_SIZE == 0
 }
 
+/// Returns the address of the first granule in `[base, limit)` which is in `state`.
+///
+/// Each granule is checked while holding its lock,
+/// so the caller must not hold the lock of any granule in the range.
+/// A granule without an entry in the table is regarded as undelegated.
+pub fn find_next(base: usize, limit: usize, state: u64) -> Option<usize> {
+    if !is_granule_aligned(base) {
+        return None;
+    }
+    (base..limit).step_by(GRANULE_SIZE).find(|&addr| {
+        if !(FVP_DRAM0_REGION.contains(&addr) || FVP_DRAM1_REGION.contains(&addr)) {
+            return false;
+        }
+        match get_granule!(addr) {
+            Ok(granule) => granule.state() == state,
+            Err(Error::MmNoEntry) => state == GranuleState::Undelegated,
+            Err(_) => false,
+        }
+    })
+}
+
 #[cfg(test)]
 mod test {
     use crate::granule::translation::{GranuleStatusTable, GRANULE_STATUS_TABLE};
-    use crate::granule::{set_granule, GranuleState};
+    use crate::granule::{
+        assign_granule, find_next, release_granule, set_granule, GranuleState, GRANULE_SIZE,
+    };
     use crate::set_state_and_get_granule;
     use vmsa::error::Error;
 
@@ -327,6 +370,28 @@ mod test {
         assert!(test_fn(TEST_ADDR).is_ok());
     }
 
+    #[test]
+    fn test_reject_aliased_granule() {
+        recreate_granule_status_table();
+
+        let test_fn = |addr: usize| -> Result<(), Error> {
+            let mut granule = set_state_and_get_granule!(addr, GranuleState::Delegated)?;
+            assert!(assign_granule(&mut granule, GranuleState::Data, 1).is_ok());
+            assert_eq!(granule.owner(), Some(1));
+            // already owned by realm 1
+            assert!(assign_granule(&mut granule, GranuleState::RTT, 2).is_err());
+            assert_eq!(granule.state(), GranuleState::Data);
+
+            // released on its teardown, then free to be assigned to another realm
+            assert!(release_granule(&mut granule).is_ok());
+            assert_eq!(granule.owner(), None);
+            assert!(assign_granule(&mut granule, GranuleState::RTT, 2).is_ok());
+            assert_eq!(granule.owner(), Some(2));
+            Ok(())
+        };
+        assert!(test_fn(TEST_ADDR).is_ok());
+    }
+
     #[test]
     fn test_get_granule_state() {
         recreate_granule_status_table();
@@ -338,4 +403,38 @@ mod test {
         };
         assert!(test_fn(TEST_ADDR).is_ok());
     }
+
+    #[test]
+    fn test_find_next_granule() {
+        recreate_granule_status_table();
+
+        let base = TEST_ADDR;
+        let limit = TEST_ADDR + 4 * GRANULE_SIZE;
+        let delegated = TEST_ADDR + 2 * GRANULE_SIZE;
+        {
+            let _ = set_state_and_get_granule!(delegated, GranuleState::Delegated).unwrap();
+        }
+
+        // skips the undelegated granules before it
+        assert_eq!(
+            find_next(base, limit, GranuleState::Delegated),
+            Some(delegated)
+        );
+        assert_eq!(
+            find_next(base, limit, GranuleState::Undelegated),
+            Some(base)
+        );
+        // none in range
+        assert_eq!(find_next(base, delegated, GranuleState::Delegated), None);
+        assert_eq!(find_next(base, limit, GranuleState::RD), None);
+        // out of the managed memory
+        assert_eq!(
+            find_next(
+                TEST_WRONG_ADDR,
+                TEST_WRONG_ADDR + GRANULE_SIZE,
+                GranuleState::Undelegated
+            ),
+            None
+        );
+    }
 }
@@ -4,6 +4,7 @@ use crate::mm::translation::PageTable;
 use crate::{get_granule, get_granule_if};
 
 use safe_abstraction::raw_ptr::{assume_safe, SafetyAssured, SafetyChecked};
+use spinning_top::{Spinlock, SpinlockGuard};
 use vmsa::guard::Content;
 
 pub fn copy_from<T: SafetyChecked + SafetyAssured + Copy>(addr: usize) -> Option<T> {
@@ -42,6 +43,57 @@ pub fn copy_to<T: SafetyChecked + SafetyAssured + Copy>(src: &T, dst: usize) ->
     }
 }
 
+/// An RMM-private granule which the data from the host is staged in
+/// on its way to a realm granule, so that the NS granule and the realm granule
+/// are never mapped at the same time.
+static STAGING_BUFFER: Spinlock<DataPage> = Spinlock::new(DataPage([0; GRANULE_SIZE]));
+
+/// The data from the host staged in the staging buffer.
+///
+/// It holds the lock of the staging buffer and zeroes the buffer on drop,
+/// so nothing is carried over to the next copy.
+pub struct Staged<'a> {
+    buffer: SpinlockGuard<'a, DataPage>,
+}
+
+impl Staged<'_> {
+    pub fn page(&self) -> &DataPage {
+        &self.buffer
+    }
+
+    pub fn copy_to(&self, dst: &mut DataPage) {
+        *dst = *self.buffer;
+    }
+}
+
+impl Drop for Staged<'_> {
+    fn drop(&mut self) {
+        self.buffer.0.fill(0);
+    }
+}
+
+fn stage<F>(buffer: &Spinlock<DataPage>, read: F) -> Option<Staged<'_>>
+where
+    F: FnOnce(&mut DataPage) -> Option<()>,
+{
+    let mut staged = Staged {
+        buffer: buffer.lock(),
+    };
+    read(&mut staged.buffer)?;
+    Some(staged)
+}
+
+/// Copies the granule at `src` of the host to the staging buffer.
+///
+/// The granule at `src` is unmapped before returning, so the caller
+/// maps the realm granule only after that to copy the staged data to.
+pub fn stage_from(src: usize) -> Option<Staged<'static>> {
+    stage(&STAGING_BUFFER, |page| {
+        *page = copy_from::<DataPage>(src)?;
+        Some(())
+    })
+}
+
 /// DataPage is used to convey realm data from host to realm.
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -51,6 +103,10 @@ impl DataPage {
     pub fn as_slice(&self) -> &[u8] {
         self.0.as_slice()
     }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.0.as_mut_slice()
+    }
 }
 
 impl Default for DataPage {
@@ -63,7 +119,11 @@ impl Content for DataPage {}
 
 impl safe_abstraction::raw_ptr::RawPtr for DataPage {}
 
-impl safe_abstraction::raw_ptr::SafetyChecked for DataPage {}
+impl safe_abstraction::raw_ptr::SafetyChecked for DataPage {
+    fn required_align(&self) -> usize {
+        GRANULE_SIZE
+    }
+}
 
 impl safe_abstraction::raw_ptr::SafetyAssured for DataPage {
     fn is_initialized(&self) -> bool {
@@ -85,3 +145,37 @@ impl safe_abstraction::raw_ptr::SafetyAssured for DataPage {
         true
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn staged_copy() {
+        let buffer = Spinlock::new(DataPage::default());
+        let mut src = DataPage::default();
+        src.0.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+
+        let mut dst = DataPage::default();
+        let staged = stage(&buffer, |page| {
+            *page = src;
+            Some(())
+        })
+        .unwrap();
+        staged.copy_to(&mut dst);
+        assert_eq!(staged.page().as_slice(), src.as_slice());
+        assert_eq!(dst.as_slice(), src.as_slice());
+
+        // zeroed between copies
+        core::mem::drop(staged);
+        assert!(buffer.lock().as_slice().iter().all(|b| *b == 0));
+
+        // including when reading from the host fails halfway
+        let staged = stage(&buffer, |page| {
+            page.0[..GRANULE_SIZE / 2].fill(0xff);
+            None
+        });
+        assert!(staged.is_none());
+        assert!(buffer.lock().as_slice().iter().all(|b| *b == 0));
+    }
+}
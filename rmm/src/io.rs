@@ -3,6 +3,7 @@ extern crate alloc;
 use alloc::boxed::Box;
 use spinning_top::{Spinlock, SpinlockGuard};
 
+use crate::config::PAGE_SIZE;
 pub use crate::error::{Error, ErrorKind};
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -49,10 +50,64 @@ pub fn stdout() -> SpinlockGuard<'static, Stdout> {
     STDOUT.lock()
 }
 
+/// Memory-mapped register block of a device.
+///
+/// Registers are addressed by index, which is scaled by `stride`
+/// to get the byte offset from `base`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mmio {
+    base: usize,
+    stride: usize,
+}
+
+impl Mmio {
+    pub const fn empty() -> Self {
+        Self { base: 0, stride: 0 }
+    }
+
+    /// Creates a register block at `base`.
+    ///
+    /// The base must be page aligned as the RMM maps devices by pages
+    /// and the stride must be a power of two that fits a 32-bit register.
+    pub fn new(base: usize, stride: usize) -> Result<Self> {
+        if base == 0
+            || base % PAGE_SIZE != 0
+            || !stride.is_power_of_two()
+            || stride < core::mem::size_of::<u32>()
+        {
+            return Err(Error::new(ErrorKind::InvalidInput));
+        }
+        Ok(Self { base, stride })
+    }
+
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    pub fn addr(&self, index: usize) -> usize {
+        self.base + index * self.stride
+    }
+
+    /// # Safety
+    ///
+    /// The register block must be mapped as device memory.
+    pub unsafe fn read(&self, index: usize) -> u32 {
+        (self.addr(index) as *const u32).read_volatile()
+    }
+
+    /// # Safety
+    ///
+    /// The register block must be mapped as device memory.
+    pub unsafe fn write(&self, index: usize, val: u32) {
+        (self.addr(index) as *mut u32).write_volatile(val)
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     extern crate alloc;
-    use crate::io::{ConsoleWriter, Device, Result, Stdout, Write};
+    use crate::config::{UART_BASE, UART_REG_STRIDE};
+    use crate::io::{ConsoleWriter, Device, Mmio, Result, Stdout, Write};
     use alloc::boxed::Box;
     use alloc::string::String;
     use alloc::vec::Vec;
@@ -125,4 +180,29 @@ pub mod test {
         stdout.write_all("World!".as_bytes()).ok().unwrap();
         assert_eq!(unsafe { (*mock_ptr).output() }, "Hello World!");
     }
+
+    #[test]
+    fn mmio_at_configured_base() {
+        const UART0_BASE: usize = 0x1c09_0000;
+        const UARTFR: usize = 0x018 / 4;
+
+        let uart0 = Mmio::new(UART0_BASE, UART_REG_STRIDE).unwrap();
+        assert_eq!(uart0.base(), UART0_BASE);
+        assert_eq!(uart0.addr(UARTFR), UART0_BASE + 0x018);
+
+        let uart = Mmio::new(UART_BASE, UART_REG_STRIDE).unwrap();
+        assert_eq!(uart.base(), UART_BASE);
+        assert_eq!(uart.addr(UARTFR), UART_BASE + 0x018);
+
+        let wide = Mmio::new(UART_BASE, 2 * UART_REG_STRIDE).unwrap();
+        assert_eq!(wide.addr(UARTFR), UART_BASE + 0x030);
+    }
+
+    #[test]
+    fn mmio_at_misaligned_base() {
+        assert!(Mmio::new(UART_BASE + 0x4, UART_REG_STRIDE).is_err());
+        assert!(Mmio::new(0, UART_REG_STRIDE).is_err());
+        assert!(Mmio::new(UART_BASE, 2).is_err());
+        assert!(Mmio::new(UART_BASE, 3).is_err());
+    }
 }
@@ -20,6 +20,8 @@ pub mod granule;
 #[macro_use]
 pub mod host;
 pub mod io;
+#[cfg(feature = "log_buffer")]
+pub mod log_buffer;
 pub mod logger;
 pub mod mm;
 pub mod mmio;
@@ -31,6 +33,8 @@ pub mod rsi;
 pub mod rtt;
 #[cfg(feature = "stat")]
 pub mod stat;
+#[cfg(test)]
+pub mod test_support;
 #[macro_use]
 pub mod r#macro;
 mod measurement;
@@ -42,6 +46,9 @@ pub mod monitor;
 mod monitor;
 mod rmm_el3;
 
+// The platform selects the hash backend of the measurements with `crypto::set_backend()`.
+pub use crate::measurement::crypto;
+
 extern crate alloc;
 
 #[macro_use]
@@ -56,6 +63,7 @@ use crate::granule::create_granule_status_table as setup_gst;
 use crate::mm::translation::get_page_table;
 use crate::monitor::Monitor;
 use crate::rmm_el3::setup_el3_ifc;
+use crate::rsi::random::setup_rndr;
 
 use armv9a::{bits_in_reg, regs::*};
 use core::ptr::addr_of;
@@ -72,6 +80,7 @@ pub unsafe fn start(cpu_id: usize) {
     // TODO: call once or with every start?
     if cpu_id == 0 {
         setup_el3_ifc();
+        setup_rndr();
     }
 
     Monitor::new().run();
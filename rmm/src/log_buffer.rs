@@ -0,0 +1,270 @@
+use crate::asm::cycle_counter;
+use crate::event::Mainloop;
+use crate::granule::GRANULE_SIZE;
+use crate::host;
+use crate::listen;
+use crate::rmi;
+use crate::rmi::error::Error;
+
+use core::fmt::Write;
+use log::{Level, Record};
+use spin::mutex::Mutex;
+
+/// The length of the message of a log record, which is truncated beyond it.
+pub const MSG_LEN: usize = 100;
+/// The number of the records kept, i.e., as many as a granule holds.
+pub const RECORD_NR: usize = GRANULE_SIZE / core::mem::size_of::<LogRecord>();
+
+/// A log record as the host reads it, in the little-endian layout below:
+///   0x00: sequence number, counting all records written since boot
+///   0x08: timestamp in cycles
+///   0x10: id of the CPU which wrote the record
+///   0x14: severity, 1 (ERROR) to 5 (TRACE)
+///   0x18: length of the message
+///   0x1c: message in UTF-8, truncated to `MSG_LEN` bytes
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct LogRecord {
+    pub seq: u64,
+    pub timestamp: u64,
+    pub cpu: u32,
+    pub level: u32,
+    pub len: u32,
+    pub msg: [u8; MSG_LEN],
+}
+
+const _: () = assert!(core::mem::size_of::<LogRecord>() == 128);
+
+impl LogRecord {
+    const fn empty() -> Self {
+        Self {
+            seq: 0,
+            timestamp: 0,
+            cpu: 0,
+            level: 0,
+            len: 0,
+            msg: [0; MSG_LEN],
+        }
+    }
+
+    /// Builds a record, formatting `args` into its message.
+    pub fn new(level: Level, timestamp: u64, cpu: usize, args: core::fmt::Arguments<'_>) -> Self {
+        let mut record = Self {
+            timestamp,
+            cpu: cpu as u32,
+            level: level as u32,
+            ..Self::empty()
+        };
+        let _ = MsgWriter(&mut record).write_fmt(args);
+        record
+    }
+
+    pub fn level(&self) -> Option<Level> {
+        match self.level {
+            1 => Some(Level::Error),
+            2 => Some(Level::Warn),
+            3 => Some(Level::Info),
+            4 => Some(Level::Debug),
+            5 => Some(Level::Trace),
+            _ => None,
+        }
+    }
+
+    pub fn msg(&self) -> &str {
+        let len = core::cmp::min(self.len as usize, MSG_LEN);
+        core::str::from_utf8(&self.msg[..len]).unwrap_or("")
+    }
+}
+
+/// Writes a message into a record, truncating it at a character boundary.
+struct MsgWriter<'a>(&'a mut LogRecord);
+
+impl Write for MsgWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let len = self.0.len as usize;
+        let mut end = core::cmp::min(s.len(), MSG_LEN - len);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.0.msg[len..len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.0.len += end as u32;
+        Ok(())
+    }
+}
+
+/// The ring of the recent log records, which drops the oldest on overflow.
+pub struct LogRing {
+    records: [LogRecord; RECORD_NR],
+    // the sequence number of the next record
+    next: u64,
+}
+
+impl LogRing {
+    pub const fn new() -> Self {
+        Self {
+            records: [LogRecord::empty(); RECORD_NR],
+            next: 0,
+        }
+    }
+
+    /// Keeps the record, numbering it with the next sequence number.
+    pub fn push(&mut self, record: LogRecord) {
+        self.records[self.next as usize % RECORD_NR] = LogRecord {
+            seq: self.next,
+            ..record
+        };
+        self.next += 1;
+    }
+
+    /// Returns the records kept, the oldest first.
+    pub fn records(&self) -> impl Iterator<Item = &LogRecord> {
+        let start = self.next.saturating_sub(RECORD_NR as u64);
+        (start..self.next).map(|seq| &self.records[seq as usize % RECORD_NR])
+    }
+
+    /// Returns the sequence number of the next record,
+    /// from which the host can tell how many records were dropped.
+    pub fn next(&self) -> u64 {
+        self.next
+    }
+
+    fn to_page(&self) -> LogPage {
+        let mut page = LogPage([LogRecord::empty(); RECORD_NR]);
+        for (dst, src) in page.0.iter_mut().zip(self.records()) {
+            *dst = *src;
+        }
+        page
+    }
+}
+
+static LOG_RING: Mutex<LogRing> = Mutex::new(LogRing::new());
+
+/// The least severe level kept in the ring.
+///
+/// The ring is read by the untrusted host, while the records below this level
+/// trace the state of realms (e.g., their registers and IPAs).
+pub const MAX_LEVEL: Level = Level::Warn;
+
+fn is_exported(level: Level) -> bool {
+    level <= MAX_LEVEL
+}
+
+/// Keeps a log record up to `MAX_LEVEL` in the ring,
+/// stamped with the cycles and the CPU writing it.
+pub fn record(record: &Record<'_>) {
+    if !is_exported(record.level()) {
+        return;
+    }
+    // The message is formatted before taking the ring,
+    // as formatting the arguments may log again.
+    let record = LogRecord::new(
+        record.level(),
+        cycle_counter(),
+        crate::cpu::get_cpu_id(),
+        *record.args(),
+    );
+    LOG_RING.lock().push(record);
+}
+
+/// The records copied to the granule of the host, the oldest first.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LogPage([LogRecord; RECORD_NR]);
+
+impl safe_abstraction::raw_ptr::RawPtr for LogPage {}
+
+impl safe_abstraction::raw_ptr::SafetyChecked for LogPage {}
+
+impl safe_abstraction::raw_ptr::SafetyAssured for LogPage {
+    fn is_initialized(&self) -> bool {
+        // The RMM only writes to this memory and never reads the values of the host.
+        true
+    }
+
+    fn verify_ownership(&self) -> bool {
+        // This memory is owned by the Host, which is the only reader of the records.
+        // As the RMM never reads it back, whatever the Host does to it concurrently
+        // has no effect on RMM's memory safety.
+        true
+    }
+}
+
+pub fn set_event_handler(mainloop: &mut Mainloop) {
+    // Copies the recent log records to the NS granule at x0:
+    //   x1: the number of the records copied, x2: the sequence number of the next record
+    listen!(mainloop, rmi::RMM_LOG_GET, |arg, ret, _| {
+        // The ring is released before copying, as the copy may log
+        let (page, count, next) = {
+            let ring = LOG_RING.lock();
+            (ring.to_page(), ring.records().count(), ring.next())
+        };
+        host::copy_to::<LogPage>(&page, arg[0]).ok_or(Error::RmiErrorInput)?;
+        ret[1] = count;
+        ret[2] = next as usize;
+        Ok(())
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn records_in_order() {
+        let mut ring = LogRing::new();
+        ring.push(LogRecord::new(Level::Error, 1, 0, format_args!("first")));
+        ring.push(LogRecord::new(
+            Level::Debug,
+            2,
+            3,
+            format_args!("second {}", 2),
+        ));
+
+        let records: alloc::vec::Vec<_> = ring.records().collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].level(), Some(Level::Error));
+        assert_eq!(records[0].msg(), "first");
+        assert_eq!(records[1].level(), Some(Level::Debug));
+        assert_eq!(records[1].msg(), "second 2");
+        assert_eq!((records[1].timestamp, records[1].cpu), (2, 3));
+    }
+
+    #[test]
+    fn drops_oldest_on_overflow() {
+        let mut ring = LogRing::new();
+        let levels = [Level::Error, Level::Warn, Level::Info];
+        for i in 0..RECORD_NR + 5 {
+            let level = levels[i % levels.len()];
+            ring.push(LogRecord::new(level, i as u64, 0, format_args!("{}", i)));
+        }
+
+        assert_eq!(ring.next(), (RECORD_NR + 5) as u64);
+        let page = ring.to_page();
+        for (i, record) in page.0.iter().enumerate() {
+            let seq = i + 5;
+            assert_eq!(record.seq, seq as u64);
+            assert_eq!(record.level(), Some(levels[seq % levels.len()]));
+            assert_eq!(record.msg(), alloc::format!("{}", seq));
+        }
+    }
+
+    #[test]
+    fn truncated_message() {
+        let mut ring = LogRing::new();
+        let long = "가".repeat(MSG_LEN);
+        ring.push(LogRecord::new(Level::Warn, 0, 0, format_args!("{}", long)));
+
+        let record = ring.records().next().unwrap();
+        assert_eq!(record.len as usize, MSG_LEN / 3 * 3);
+        assert!(record.msg().chars().all(|c| c == '가'));
+    }
+
+    #[test]
+    fn exports_warnings_only() {
+        assert!(is_exported(Level::Error));
+        assert!(is_exported(Level::Warn));
+        assert!(!is_exported(Level::Info));
+        assert!(!is_exported(Level::Debug));
+        assert!(!is_exported(Level::Trace));
+    }
+}
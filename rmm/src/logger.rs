@@ -10,6 +10,21 @@ impl log::Log for SimpleLogger {
     }
 
     fn log(&self, record: &Record<'_>) {
+        #[cfg(feature = "log_buffer")]
+        if self.enabled(record.metadata()) {
+            crate::log_buffer::record(record);
+        }
+
+        #[cfg(feature = "structured_log")]
+        if self.enabled(record.metadata()) {
+            let msg = alloc::format!("{}", record.args());
+            let mut frame = alloc::string::String::new();
+            if write_frame(&mut frame, record.level(), record.target(), &msg).is_ok() {
+                crate::print!("{}", frame);
+            }
+            return;
+        }
+
         if self.enabled(record.metadata()) {
             if record.metadata().level() <= Level::Warn {
                 crate::eprintln!(
@@ -32,9 +47,50 @@ impl log::Log for SimpleLogger {
     fn flush(&self) {}
 }
 
+/// Prefix of a structured log frame, which doesn't appear in human-readable text.
+pub const FRAME_PREFIX: char = '\x1e';
+
+/// Writes a log record as a structured frame for test harnesses:
+///   \x1elevel=<level> target=<len>:<target> msg=<len>:<msg>\n
+/// Each length is the byte length of the value following it,
+/// so the values are written as they are, without escaping.
+pub fn write_frame(
+    w: &mut impl core::fmt::Write,
+    level: Level,
+    target: &str,
+    msg: &str,
+) -> core::fmt::Result {
+    writeln!(
+        w,
+        "{}level={} target={}:{} msg={}:{}",
+        FRAME_PREFIX,
+        level,
+        target.len(),
+        target,
+        msg.len(),
+        msg
+    )
+}
+
 static LOGGER: SimpleLogger = SimpleLogger;
 
 pub fn register_global_logger(maxlevel: LevelFilter) {
     log::set_logger(&LOGGER).unwrap();
     log::set_max_level(maxlevel);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::String;
+
+    #[test]
+    fn structured_frame() {
+        let mut frame = String::new();
+        write_frame(&mut frame, Level::Warn, "islet_rmm::rmi", "a=1 b=\"2\"").unwrap();
+        assert_eq!(
+            frame,
+            "\x1elevel=WARN target=14:islet_rmm::rmi msg=9:a=1 b=\"2\"\n"
+        );
+    }
+}
@@ -1,11 +1,11 @@
 // TODO: Expands to cover args, ret
 #[macro_export]
 macro_rules! define_interface {
-    (command {$($variant:ident = $val:expr),*,}) => {
-        $(pub const $variant: usize = $val;)*
+    (command {$($(#[$attr:meta])* $variant:ident = $val:expr),*,}) => {
+        $($(#[$attr])* pub const $variant: usize = $val;)*
         pub fn to_str(code: usize) -> &'static str {
             match code {
-                $($variant => stringify!($variant)),*,
+                $($(#[$attr])* $variant => stringify!($variant)),*,
                 _ => "Undefined",
             }
         }
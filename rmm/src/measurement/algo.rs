@@ -0,0 +1,62 @@
+use super::MeasurementError;
+use crate::rmi::{HASH_ALGO_SHA256, HASH_ALGO_SHA512};
+
+/// The hash algorithm of a realm's measurements, selected at realm creation.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgo {
+    Sha256 = HASH_ALGO_SHA256,
+    Sha512 = HASH_ALGO_SHA512,
+}
+
+impl HashAlgo {
+    /// Parses the `hash_algo` field of the realm parameters
+    /// (i.e., RMI_HASH_SHA_256 or RMI_HASH_SHA_512).
+    pub fn parse(hash_algo: u8) -> Result<Self, MeasurementError> {
+        match hash_algo {
+            HASH_ALGO_SHA256 => Ok(Self::Sha256),
+            HASH_ALGO_SHA512 => Ok(Self::Sha512),
+            _ => Err(MeasurementError::InvalidHashAlgorithmValue(hash_algo)),
+        }
+    }
+
+    /// Returns the size of the digest in bytes.
+    pub fn digest_len(&self) -> usize {
+        match self {
+            Self::Sha256 => 32,
+            Self::Sha512 => 64,
+        }
+    }
+
+    /// Returns the id of the algorithm in the attestation token.
+    pub fn claim_id(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha-256",
+            Self::Sha512 => "sha-512",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::measurement::MEASUREMENTS_SLOT_MAX_SIZE;
+
+    #[test]
+    fn digest_len() {
+        assert_eq!(HashAlgo::Sha256.digest_len(), 32);
+        assert_eq!(HashAlgo::Sha512.digest_len(), 64);
+        assert_eq!(HashAlgo::Sha512.digest_len(), MEASUREMENTS_SLOT_MAX_SIZE);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(HashAlgo::parse(HASH_ALGO_SHA256).unwrap(), HashAlgo::Sha256);
+        assert_eq!(HashAlgo::parse(HASH_ALGO_SHA512).unwrap(), HashAlgo::Sha512);
+        assert!(matches!(
+            HashAlgo::parse(2),
+            Err(MeasurementError::InvalidHashAlgorithmValue(2))
+        ));
+        assert!(HashAlgo::parse(0xff).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use super::{HashAlgo, MeasurementError};
+
+use alloc::boxed::Box;
+use sha2::digest::DynDigest;
+use sha2::{Digest, Sha256, Sha512};
+use spinning_top::Spinlock;
+
+/// A hash function computing the realm measurements.
+pub trait Hasher {
+    fn update(&mut self, data: &[u8]);
+    /// Writes the digest into the front of `out` and resets the state for reuse.
+    fn finalize(&mut self, out: &mut [u8]) -> Result<(), MeasurementError>;
+    fn output_size(&self) -> usize;
+}
+
+/// An implementation of the hash functions (e.g., a crypto accelerator)
+/// which the measurement logic is built on.
+pub trait Backend: Sync {
+    /// Returns a hasher of `hash_algo`, or `None` if the backend doesn't support it.
+    fn hasher(&self, hash_algo: HashAlgo) -> Option<Box<dyn Hasher>>;
+}
+
+/// The default backend computing the hashes in software.
+pub struct Software;
+
+struct SoftwareHasher(Box<dyn DynDigest>);
+
+impl Hasher for SoftwareHasher {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(&mut self, out: &mut [u8]) -> Result<(), MeasurementError> {
+        let size = self.0.output_size();
+        let out = out
+            .get_mut(0..size)
+            .ok_or(MeasurementError::OutputBufferTooSmall)?;
+        self.0
+            .finalize_into_reset(out)
+            .map_err(|_| MeasurementError::OutputBufferTooSmall)
+    }
+
+    fn output_size(&self) -> usize {
+        self.0.output_size()
+    }
+}
+
+impl Backend for Software {
+    fn hasher(&self, hash_algo: HashAlgo) -> Option<Box<dyn Hasher>> {
+        let digest: Box<dyn DynDigest> = match hash_algo {
+            HashAlgo::Sha256 => Box::new(Sha256::new()),
+            HashAlgo::Sha512 => Box::new(Sha512::new()),
+        };
+        Some(Box::new(SoftwareHasher(digest)))
+    }
+}
+
+static BACKEND: Spinlock<&'static dyn Backend> = Spinlock::new(&Software);
+
+/// Selects the backend of the measurements taken from now on.
+/// The platform calls it once at boot, before any realm gets created.
+pub fn set_backend(backend: &'static dyn Backend) {
+    *BACKEND.lock() = backend;
+}
+
+pub fn backend() -> &'static dyn Backend {
+    *BACKEND.lock()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::measurement::event_log::EVENT_TYPE_EXTEND;
+    use crate::measurement::{
+        EventLogEntry, Hasher as MeasurementHasher, Measurement, MEASUREMENTS_SLOT_RIM,
+        MEASURE_DESC_TYPE_DATA, RMI_MEASURE_CONTENT,
+    };
+    use alloc::vec::Vec;
+
+    /// A stub standing for an accelerator which supports no algorithms.
+    struct Unsupported;
+
+    impl Backend for Unsupported {
+        fn hasher(&self, _hash_algo: HashAlgo) -> Option<Box<dyn Hasher>> {
+            None
+        }
+    }
+
+    /// A stub standing for an accelerator which hashes the whole input at once.
+    struct Buffered;
+
+    struct BufferedHasher {
+        hash_algo: HashAlgo,
+        data: Vec<u8>,
+    }
+
+    impl Hasher for BufferedHasher {
+        fn update(&mut self, data: &[u8]) {
+            self.data.extend_from_slice(data);
+        }
+
+        fn finalize(&mut self, out: &mut [u8]) -> Result<(), MeasurementError> {
+            let size = self.output_size();
+            let out = out
+                .get_mut(0..size)
+                .ok_or(MeasurementError::OutputBufferTooSmall)?;
+            match self.hash_algo {
+                HashAlgo::Sha256 => out.copy_from_slice(&Sha256::digest(&self.data)),
+                HashAlgo::Sha512 => out.copy_from_slice(&Sha512::digest(&self.data)),
+            }
+            self.data.clear();
+            Ok(())
+        }
+
+        fn output_size(&self) -> usize {
+            self.hash_algo.digest_len()
+        }
+    }
+
+    impl Backend for Buffered {
+        fn hasher(&self, hash_algo: HashAlgo) -> Option<Box<dyn Hasher>> {
+            Some(Box::new(BufferedHasher {
+                hash_algo,
+                data: Vec::new(),
+            }))
+        }
+    }
+
+    fn extend(backend: &'static dyn Backend, hash_algo: HashAlgo) -> Measurement {
+        let hasher = MeasurementHasher::with_backend(backend, hash_algo).unwrap();
+        let mut data_hash = Measurement::empty();
+        hasher
+            .hash_fields_into(&mut data_hash, |h| h.hash([0x22u8; 0x1000]))
+            .unwrap();
+        let mut extended = Measurement::empty();
+        extended.as_mut_slice()[0..32].fill(0x33);
+
+        let mut rim = Measurement::empty();
+        let events = [
+            EventLogEntry::new(
+                MEASURE_DESC_TYPE_DATA,
+                MEASUREMENTS_SLOT_RIM,
+                0x8000_0000,
+                RMI_MEASURE_CONTENT,
+                data_hash,
+            ),
+            EventLogEntry::new(EVENT_TYPE_EXTEND, MEASUREMENTS_SLOT_RIM, 0, 32, extended),
+        ];
+        for event in events {
+            event.extend_into(&hasher, &mut rim).unwrap();
+        }
+        rim
+    }
+
+    #[test]
+    fn identical_across_backends() {
+        for hash_algo in [HashAlgo::Sha256, HashAlgo::Sha512] {
+            let software = extend(&Software, hash_algo);
+            let buffered = extend(&Buffered, hash_algo);
+            assert_eq!(software.as_slice(), buffered.as_slice());
+            assert_ne!(software.as_slice(), Measurement::empty().as_slice());
+        }
+    }
+
+    #[test]
+    fn unsupported_algorithm() {
+        assert!(MeasurementHasher::with_backend(&Unsupported, HashAlgo::Sha256).is_err());
+    }
+}
@@ -1,6 +1,7 @@
+use super::event_log::{EVENT_TYPE_EXTEND, EVENT_TYPE_REALM_CREATE};
 use super::{
-    Hasher, Measurement, MeasurementError, MEASUREMENTS_SLOT_RIM, MEASURE_DESC_TYPE_DATA,
-    MEASURE_DESC_TYPE_REC, MEASURE_DESC_TYPE_RIPAS, RMI_MEASURE_CONTENT,
+    EventLogEntry, Hasher, Measurement, MeasurementError, MEASUREMENTS_SLOT_RIM,
+    MEASURE_DESC_TYPE_DATA, MEASURE_DESC_TYPE_REC, MEASURE_DESC_TYPE_RIPAS, RMI_MEASURE_CONTENT,
 };
 use crate::rmi::rec::params::Params as RecParams;
 use crate::{
@@ -23,9 +24,17 @@ impl<'a> HashContext<'a> {
     }
 
     pub fn measure_realm_create(&mut self, params: &RealmParams) -> Result<(), rsi::error::Error> {
-        crate::rsi::measurement::extend(self.rd, MEASUREMENTS_SLOT_RIM, |rim| {
-            self.hasher.hash_object_into(params, rim)
-        })
+        let mut params_measurement = Measurement::empty();
+        self.hasher
+            .hash_object_into(params, &mut params_measurement)?;
+
+        self.record(EventLogEntry::new(
+            EVENT_TYPE_REALM_CREATE,
+            MEASUREMENTS_SLOT_RIM,
+            0,
+            0,
+            params_measurement,
+        ))
     }
 
     pub fn extend_measurement(
@@ -33,14 +42,20 @@ impl<'a> HashContext<'a> {
         buffer: &[u8],
         index: usize,
     ) -> Result<(), rsi::error::Error> {
-        crate::rsi::measurement::extend(self.rd, index, |current| {
-            let old_value = *current;
+        let mut value = Measurement::empty();
+        value
+            .as_mut_slice()
+            .get_mut(0..buffer.len())
+            .ok_or(MeasurementError::OutputBufferTooSmall)?
+            .copy_from_slice(buffer);
 
-            self.hasher.hash_fields_into(current, |h| {
-                h.hash(&old_value.as_ref()[0..self.hasher.output_size()]);
-                h.hash(buffer);
-            })
-        })
+        self.record(EventLogEntry::new(
+            EVENT_TYPE_EXTEND,
+            index,
+            0,
+            buffer.len(),
+            value,
+        ))
     }
 
     pub fn measure_data_granule(
@@ -57,20 +72,13 @@ impl<'a> HashContext<'a> {
             })?;
         }
 
-        crate::rsi::measurement::extend(self.rd, MEASUREMENTS_SLOT_RIM, |current| {
-            let oldrim = *current;
-
-            self.hasher.hash_fields_into(current, |h| {
-                h.hash_u8(MEASURE_DESC_TYPE_DATA); // desc type
-                h.hash([0u8; 7]); // padding
-                h.hash_u64(0x100); // desc struct size
-                h.hash(oldrim); // old RIM value
-                h.hash_usize(ipa); // ipa
-                h.hash_usize(flags); // flags
-                h.hash(data_measurement); // data granule hash
-                h.hash([0u8; 0x100 - 0xa0]); // padding
-            })
-        })
+        self.record(EventLogEntry::new(
+            MEASURE_DESC_TYPE_DATA,
+            MEASUREMENTS_SLOT_RIM,
+            ipa,
+            flags,
+            data_measurement,
+        ))
     }
 
     pub fn measure_rec_params(&mut self, params: &RecParams) -> Result<(), rsi::error::Error> {
@@ -78,18 +86,13 @@ impl<'a> HashContext<'a> {
         self.hasher
             .hash_object_into(params, &mut params_measurement)?;
 
-        crate::rsi::measurement::extend(self.rd, MEASUREMENTS_SLOT_RIM, |current| {
-            let oldrim = *current;
-
-            self.hasher.hash_fields_into(current, |h| {
-                h.hash_u8(MEASURE_DESC_TYPE_REC); // desc type
-                h.hash([0u8; 7]); // padding
-                h.hash_u64(0x100); // desc struct size
-                h.hash(oldrim); // old RIM value
-                h.hash(params_measurement); // REC params hash
-                h.hash([0u8; 0x100 - 0x90]); // padding
-            })
-        })
+        self.record(EventLogEntry::new(
+            MEASURE_DESC_TYPE_REC,
+            MEASUREMENTS_SLOT_RIM,
+            0,
+            0,
+            params_measurement,
+        ))
     }
 
     pub fn measure_ripas_granule(
@@ -97,19 +100,23 @@ impl<'a> HashContext<'a> {
         ipa: usize,
         level: u8,
     ) -> Result<(), rsi::error::Error> {
-        crate::rsi::measurement::extend(self.rd, MEASUREMENTS_SLOT_RIM, |current| {
-            let oldrim = *current;
+        self.record(EventLogEntry::new(
+            MEASURE_DESC_TYPE_RIPAS,
+            MEASUREMENTS_SLOT_RIM,
+            ipa,
+            level as usize,
+            Measurement::empty(),
+        ))
+    }
 
-            self.hasher.hash_fields_into(current, |h| {
-                h.hash_u8(MEASURE_DESC_TYPE_RIPAS); // desc type
-                h.hash([0u8; 7]); // padding
-                h.hash_u64(0x100); // desc struct size
-                h.hash(oldrim); // old RIM value
-                h.hash_usize(ipa); // ipa
-                h.hash_u8(level); // level
-                h.hash([0u8; 7]); // level's padding
-                h.hash([0u8; 0xa0]); // padding to 0x100 size
-            })
-        })
+    /// Extends the measurement slot of `event` and appends it to the realm's event log.
+    /// Once the log is full, the event is measured but not logged.
+    fn record(&mut self, event: EventLogEntry) -> Result<(), rsi::error::Error> {
+        let hasher = &self.hasher;
+        crate::rsi::measurement::extend(self.rd, event.slot as usize, |current| {
+            event.extend_into(hasher, current)
+        })?;
+        self.rd.event_log.push(event);
+        Ok(())
     }
 }
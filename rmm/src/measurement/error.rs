@@ -2,4 +2,6 @@
 pub enum MeasurementError {
     InvalidHashAlgorithmValue(u8),
     OutputBufferTooSmall,
+    InvalidEventType(u8),
+    EventLogFull,
 }
@@ -0,0 +1,306 @@
+use super::{
+    Hasher, Measurement, MeasurementError, MEASUREMENTS_SLOT_MAX_SIZE, MEASURE_DESC_TYPE_DATA,
+    MEASURE_DESC_TYPE_REC, MEASURE_DESC_TYPE_RIPAS,
+};
+use crate::config::EVENT_LOG_MAX_ENTRIES;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+// Event types which are not measurement descriptors of the RMM spec.
+// The descriptor types (MEASURE_DESC_TYPE_*) are used as they are.
+pub const EVENT_TYPE_REALM_CREATE: u8 = 0x10;
+pub const EVENT_TYPE_EXTEND: u8 = 0x11;
+
+/// Size of a serialized event log entry.
+///
+/// Layout (little endian):
+///   0x00 event type (u8)
+///   0x01 measurement slot (u8)
+///   0x02 padding (6 bytes)
+///   0x08 ipa (u64)
+///   0x10 flags (u64)
+///   0x18 digest (64 bytes)
+pub const EVENT_LOG_ENTRY_SIZE: usize = 0x18 + MEASUREMENTS_SLOT_MAX_SIZE;
+
+/// A single measured event.
+///
+/// `flags` holds the data creation flags for data events,
+/// the RTT level for RIPAS events and the buffer size for extend events.
+/// `digest` holds the hash of the measured content
+/// (or the extended value itself for extend events).
+#[derive(Copy, Clone, Debug)]
+pub struct EventLogEntry {
+    pub event_type: u8,
+    pub slot: u8,
+    pub ipa: usize,
+    pub flags: usize,
+    pub digest: Measurement,
+}
+
+impl EventLogEntry {
+    pub fn new(event_type: u8, slot: usize, ipa: usize, flags: usize, digest: Measurement) -> Self {
+        Self {
+            event_type,
+            slot: slot as u8,
+            ipa,
+            flags,
+            digest,
+        }
+    }
+
+    fn empty() -> Self {
+        Self::new(0, 0, 0, 0, Measurement::empty())
+    }
+
+    /// Applies this event to the `current` value of its measurement slot.
+    pub fn extend_into(
+        &self,
+        hasher: &Hasher,
+        current: &mut Measurement,
+    ) -> Result<(), MeasurementError> {
+        let old = *current;
+
+        match self.event_type {
+            EVENT_TYPE_REALM_CREATE => {
+                current
+                    .as_mut_slice()
+                    .copy_from_slice(self.digest.as_slice());
+                Ok(())
+            }
+            MEASURE_DESC_TYPE_DATA => hasher.hash_fields_into(current, |h| {
+                h.hash_u8(MEASURE_DESC_TYPE_DATA); // desc type
+                h.hash([0u8; 7]); // padding
+                h.hash_u64(0x100); // desc struct size
+                h.hash(old); // old RIM value
+                h.hash_usize(self.ipa); // ipa
+                h.hash_usize(self.flags); // flags
+                h.hash(self.digest); // data granule hash
+                h.hash([0u8; 0x100 - 0xa0]); // padding
+            }),
+            MEASURE_DESC_TYPE_REC => hasher.hash_fields_into(current, |h| {
+                h.hash_u8(MEASURE_DESC_TYPE_REC); // desc type
+                h.hash([0u8; 7]); // padding
+                h.hash_u64(0x100); // desc struct size
+                h.hash(old); // old RIM value
+                h.hash(self.digest); // REC params hash
+                h.hash([0u8; 0x100 - 0x90]); // padding
+            }),
+            MEASURE_DESC_TYPE_RIPAS => hasher.hash_fields_into(current, |h| {
+                h.hash_u8(MEASURE_DESC_TYPE_RIPAS); // desc type
+                h.hash([0u8; 7]); // padding
+                h.hash_u64(0x100); // desc struct size
+                h.hash(old); // old RIM value
+                h.hash_usize(self.ipa); // ipa
+                h.hash_u8(self.flags as u8); // level
+                h.hash([0u8; 7]); // level's padding
+                h.hash([0u8; 0xa0]); // padding to 0x100 size
+            }),
+            EVENT_TYPE_EXTEND => hasher.hash_fields_into(current, |h| {
+                h.hash(&old.as_ref()[0..hasher.output_size()]);
+                h.hash(&self.digest.as_ref()[0..self.flags]);
+            }),
+            _ => Err(MeasurementError::InvalidEventType(self.event_type)),
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; EVENT_LOG_ENTRY_SIZE] {
+        let mut bytes = [0u8; EVENT_LOG_ENTRY_SIZE];
+        bytes[0x0] = self.event_type;
+        bytes[0x1] = self.slot;
+        bytes[0x8..0x10].copy_from_slice(&(self.ipa as u64).to_le_bytes());
+        bytes[0x10..0x18].copy_from_slice(&(self.flags as u64).to_le_bytes());
+        bytes[0x18..].copy_from_slice(self.digest.as_slice());
+        bytes
+    }
+}
+
+/// Ordered log of the events measured during the realm's lifetime.
+///
+/// A verifier can replay the log to recompute the measurement slots
+/// (e.g. the RIM) independently from the values reported in the token.
+/// It keeps the first `EVENT_LOG_MAX_ENTRIES` events in a buffer which is
+/// allocated on the first event and freed by `release()`. The events beyond
+/// are still measured, but only counted as dropped.
+#[derive(Debug, Default)]
+pub struct EventLog {
+    entries: Option<Box<[EventLogEntry]>>,
+    len: usize,
+    dropped: usize,
+}
+
+impl EventLog {
+    pub const fn new() -> Self {
+        Self {
+            entries: None,
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Appends `entry`, or counts it as dropped if the log is full
+    /// or its buffer can't be allocated.
+    pub fn push(&mut self, entry: EventLogEntry) {
+        let len = self.len;
+        match self.buffer().and_then(|entries| entries.get_mut(len)) {
+            Some(slot) => {
+                *slot = entry;
+                self.len += 1;
+            }
+            None => self.dropped += 1,
+        }
+    }
+
+    fn buffer(&mut self) -> Option<&mut [EventLogEntry]> {
+        if self.entries.is_none() {
+            let mut entries = Vec::new();
+            entries.try_reserve_exact(EVENT_LOG_MAX_ENTRIES).ok()?;
+            entries.resize(EVENT_LOG_MAX_ENTRIES, EventLogEntry::empty());
+            self.entries = Some(entries.into_boxed_slice());
+        }
+        self.entries.as_deref_mut()
+    }
+
+    /// Frees the buffer of the log, which the realm's granule doesn't own.
+    pub fn release(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len >= EVENT_LOG_MAX_ENTRIES
+    }
+
+    /// Returns the number of the events which were measured but not logged.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    pub fn entries(&self) -> &[EventLogEntry] {
+        match &self.entries {
+            Some(entries) => &entries[..self.len],
+            None => &[],
+        }
+    }
+
+    /// Returns the size of the serialized log in bytes.
+    pub fn size(&self) -> usize {
+        self.len * EVENT_LOG_ENTRY_SIZE
+    }
+
+    /// Copies the serialized log starting at `offset` into `out`
+    /// and returns the number of bytes copied.
+    pub fn read(&self, offset: usize, out: &mut [u8]) -> usize {
+        let mut written = 0;
+        let mut pos = offset;
+
+        while written < out.len() {
+            let Some(entry) = self.entries().get(pos / EVENT_LOG_ENTRY_SIZE) else {
+                break;
+            };
+            let start = pos % EVENT_LOG_ENTRY_SIZE;
+            let len = core::cmp::min(EVENT_LOG_ENTRY_SIZE - start, out.len() - written);
+            out[written..written + len].copy_from_slice(&entry.to_bytes()[start..start + len]);
+            written += len;
+            pos += len;
+        }
+        written
+    }
+
+    /// Recomputes the value of the measurement `slot` from the logged events,
+    /// which fails if any event was dropped.
+    pub fn replay(&self, hasher: &Hasher, slot: usize) -> Result<Measurement, MeasurementError> {
+        if self.dropped > 0 {
+            return Err(MeasurementError::EventLogFull);
+        }
+        let mut measurement = Measurement::empty();
+        for entry in self.entries().iter().filter(|e| e.slot as usize == slot) {
+            entry.extend_into(hasher, &mut measurement)?;
+        }
+        Ok(measurement)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::measurement::HashAlgo;
+    use crate::measurement::{MEASUREMENTS_SLOT_RIM, RMI_MEASURE_CONTENT};
+
+    #[test]
+    fn read_serialized_log() {
+        let mut log = EventLog::new();
+        let mut digest = Measurement::empty();
+        digest.as_mut_slice().fill(0xab);
+        log.push(EventLogEntry::new(
+            MEASURE_DESC_TYPE_DATA,
+            MEASUREMENTS_SLOT_RIM,
+            0x1000,
+            RMI_MEASURE_CONTENT,
+            digest,
+        ));
+        log.push(EventLogEntry::new(
+            MEASURE_DESC_TYPE_RIPAS,
+            MEASUREMENTS_SLOT_RIM,
+            0x2000,
+            3,
+            Measurement::empty(),
+        ));
+        assert_eq!(log.size(), 2 * EVENT_LOG_ENTRY_SIZE);
+
+        // Read across the entry boundary in small chunks
+        let mut out = [0u8; 2 * EVENT_LOG_ENTRY_SIZE];
+        let mut offset = 0;
+        while offset < log.size() {
+            let end = core::cmp::min(offset + 0x20, out.len());
+            offset += log.read(offset, &mut out[offset..end]);
+        }
+        assert_eq!(log.read(offset, &mut [0u8; 8]), 0);
+
+        assert_eq!(out[0x0], MEASURE_DESC_TYPE_DATA);
+        assert_eq!(&out[0x8..0x10], &0x1000u64.to_le_bytes());
+        assert_eq!(
+            &out[0x10..0x18],
+            &(RMI_MEASURE_CONTENT as u64).to_le_bytes()
+        );
+        assert_eq!(&out[0x18..EVENT_LOG_ENTRY_SIZE], digest.as_slice());
+        assert_eq!(out[EVENT_LOG_ENTRY_SIZE], MEASURE_DESC_TYPE_RIPAS);
+        assert_eq!(
+            &out[EVENT_LOG_ENTRY_SIZE + 0x10..EVENT_LOG_ENTRY_SIZE + 0x18],
+            &3u64.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn full_log() {
+        let entry = || {
+            EventLogEntry::new(
+                MEASURE_DESC_TYPE_RIPAS,
+                MEASUREMENTS_SLOT_RIM,
+                0x2000,
+                3,
+                Measurement::empty(),
+            )
+        };
+        let hasher = Hasher::from_hash_algo(HashAlgo::Sha256).unwrap();
+        let mut log = EventLog::new();
+        for _ in 0..EVENT_LOG_MAX_ENTRIES {
+            log.push(entry());
+        }
+        assert!(log.is_full());
+        assert!(log.replay(&hasher, MEASUREMENTS_SLOT_RIM).is_ok());
+
+        // dropped, not failed
+        log.push(entry());
+        assert_eq!(log.dropped(), 1);
+        assert_eq!(log.entries().len(), EVENT_LOG_MAX_ENTRIES);
+        assert_eq!(log.size(), EVENT_LOG_MAX_ENTRIES * EVENT_LOG_ENTRY_SIZE);
+        assert!(matches!(
+            log.replay(&hasher, MEASUREMENTS_SLOT_RIM),
+            Err(MeasurementError::EventLogFull)
+        ));
+
+        log.release();
+        assert!(log.entries().is_empty());
+        assert_eq!(log.dropped(), 0);
+    }
+}
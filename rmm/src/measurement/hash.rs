@@ -1,14 +1,10 @@
 use alloc::boxed::Box;
-use sha2::Digest;
-use sha2::{digest::DynDigest, Sha256, Sha512};
 
-use crate::{
-    measurement::MeasurementError,
-    rmi::{HASH_ALGO_SHA256, HASH_ALGO_SHA512},
-};
+use super::crypto::{self, Backend};
+use crate::measurement::{HashAlgo, MeasurementError};
 
 pub struct HashWrapper {
-    pub hash_func: Box<dyn DynDigest>,
+    pub hash_func: Box<dyn crypto::Hasher>,
 }
 
 impl HashWrapper {
@@ -43,33 +39,34 @@ impl HashWrapper {
     }
 
     fn finish(&mut self, mut out: impl AsMut<[u8]>) -> Result<(), MeasurementError> {
-        self.hash_func
-            .finalize_into_reset(&mut out.as_mut()[0..self.hash_func.output_size()])
-            .map_err(|_| MeasurementError::OutputBufferTooSmall)
+        self.hash_func.finalize(out.as_mut())
     }
 }
 
 pub struct Hasher {
-    factory: Box<dyn Fn() -> Box<dyn DynDigest>>,
+    backend: &'static dyn Backend,
+    hash_algo: HashAlgo,
     block_size: usize,
 }
 
 impl Hasher {
-    pub fn from_hash_algo(hash_algo: u8) -> Result<Self, MeasurementError> {
-        let factory: Box<dyn Fn() -> Box<dyn DynDigest>> = match hash_algo {
-            HASH_ALGO_SHA256 => Box::new(|| Box::new(Sha256::new())),
-            HASH_ALGO_SHA512 => Box::new(|| Box::new(Sha512::new())),
-            _ => return Err(MeasurementError::InvalidHashAlgorithmValue(hash_algo)),
-        };
+    /// Returns a hasher of `hash_algo` on the backend selected by `crypto::set_backend()`.
+    pub fn from_hash_algo(hash_algo: HashAlgo) -> Result<Self, MeasurementError> {
+        Self::with_backend(crypto::backend(), hash_algo)
+    }
 
-        let block_size = match hash_algo {
-            HASH_ALGO_SHA256 => <Sha256 as Digest>::output_size(),
-            HASH_ALGO_SHA512 => <Sha512 as Digest>::output_size(),
-            _ => return Err(MeasurementError::InvalidHashAlgorithmValue(hash_algo)),
-        };
+    pub fn with_backend(
+        backend: &'static dyn Backend,
+        hash_algo: HashAlgo,
+    ) -> Result<Self, MeasurementError> {
+        let block_size = backend
+            .hasher(hash_algo)
+            .ok_or(MeasurementError::InvalidHashAlgorithmValue(hash_algo as u8))?
+            .output_size();
 
         Ok(Self {
-            factory,
+            backend,
+            hash_algo,
             block_size,
         })
     }
@@ -80,7 +77,9 @@ impl Hasher {
         f: impl Fn(&mut HashWrapper),
     ) -> Result<(), MeasurementError> {
         let mut wrapper = HashWrapper {
-            hash_func: (self.factory)(),
+            hash_func: self.backend.hasher(self.hash_algo).ok_or(
+                MeasurementError::InvalidHashAlgorithmValue(self.hash_algo as u8),
+            )?,
         };
         f(&mut wrapper);
         wrapper.finish(out)
@@ -1,9 +1,14 @@
+mod algo;
+pub mod crypto;
 mod ctx;
 mod error;
+pub mod event_log;
 mod hash;
 
+pub use algo::HashAlgo;
 pub use ctx::HashContext;
 pub use error::MeasurementError;
+pub use event_log::{EventLog, EventLogEntry};
 pub use hash::Hashable;
 pub use hash::Hasher;
 
@@ -1,3 +1,41 @@
 pub mod page;
 pub mod page_table;
 pub mod translation;
+
+/// Returns the first unprotected IPA of a realm whose IPAs are `ipa_bits` wide,
+/// as the protected IPA space is the lower half of the IPA space.
+///
+/// A width out of the range the platform supports (e.g., zero) gets no protected IPAs,
+/// though such a realm is rejected at creation.
+pub fn protected_ipa_boundary(ipa_bits: usize) -> usize {
+    ipa_bits
+        .checked_sub(1)
+        .and_then(|shift| 1usize.checked_shl(shift as u32))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rmi::rtt::{is_protected_ipa, validate_ipa};
+
+    #[test]
+    fn boundary() {
+        assert_eq!(protected_ipa_boundary(40), 0x80_0000_0000);
+        assert_eq!(protected_ipa_boundary(44), 0x800_0000_0000);
+        assert_eq!(protected_ipa_boundary(48), 0x8000_0000_0000);
+        assert_eq!(protected_ipa_boundary(52), 0x8_0000_0000_0000);
+        assert_eq!(protected_ipa_boundary(0), 0);
+    }
+
+    #[test]
+    fn agrees_with_validators() {
+        for ipa_bits in [40, 44, 48, 52] {
+            let boundary = protected_ipa_boundary(ipa_bits);
+            assert!(is_protected_ipa(boundary - 1, ipa_bits));
+            assert!(!is_protected_ipa(boundary, ipa_bits));
+            assert!(validate_ipa(boundary - 0x1000, ipa_bits).is_ok());
+            assert!(validate_ipa(boundary, ipa_bits).is_err());
+        }
+    }
+}
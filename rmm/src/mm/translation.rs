@@ -1,6 +1,6 @@
 use super::page_table::entry::Entry;
 use super::page_table::{attr, L1Table};
-use crate::config::{PAGE_SIZE, RMM_SHARED_BUFFER_START};
+use crate::config::{PAGE_SIZE, RMM_SHARED_BUFFER_START, UART_BASE};
 use crate::mm::page::BasePageSize;
 use crate::mm::page_table::entry::PTDesc;
 
@@ -94,7 +94,7 @@ impl<'a> Inner<'a> {
             let rw_start = &__RW_START__ as *const u64 as u64;
             let ro_size = rw_start - base_address;
             let rw_size = &__RW_END__ as *const u64 as u64 - rw_start;
-            let uart_phys: u64 = 0x1c0c_0000;
+            let uart_phys: u64 = UART_BASE as u64;
             let shared_start = RMM_SHARED_BUFFER_START;
             self.set_pages(
                 VirtAddr::from(base_address),
@@ -1,9 +1,14 @@
+#[cfg(not(kani))]
+use crate::event::{mainloop::Handler as RmiHandler, rsihandle::Handler as RsiHandler, Command};
 use crate::event::{Mainloop, RsiHandle};
 use crate::mm::translation::PageTable;
+#[cfg(not(kani))]
+use crate::rmi::error::Error;
 
 #[cfg(not(kani))]
 pub struct Monitor {
     pub rsi: RsiHandle,
+    pub rmi: Mainloop,
     pub page_table: PageTable,
 }
 
@@ -15,8 +20,11 @@ pub struct Monitor {}
 impl Monitor {
     #[cfg(not(kani))]
     pub fn new() -> Self {
+        let mut rmi = Mainloop::new();
+        rmi.add_event_handlers();
         Self {
             rsi: RsiHandle::new(),
+            rmi,
             page_table: PageTable::get_ref(),
         }
     }
@@ -25,11 +33,34 @@ impl Monitor {
         Self {}
     }
 
+    /// Registers `handler` for the RSI command `cmd` on top of the built-in ones.
+    /// Fails with `RmiErrorInUse` if `cmd` already has a handler,
+    /// or with `RmiErrorInput` if `cmd` is out of the PSCI, RSI and vendor commands.
+    /// An RSI command without a handler returns `RsiHandle::NOT_SUPPORTED` to the realm.
+    #[cfg(not(kani))]
+    pub fn register_rsi(&mut self, cmd: Command, handler: RsiHandler) -> Result<(), Error> {
+        if self.rsi.handler(cmd).is_some() {
+            return Err(Error::RmiErrorInUse);
+        }
+        self.rsi.on_event.insert(cmd, handler)
+    }
+
+    /// Registers `handler` for the RMI command `cmd` on top of the built-in ones.
+    /// Fails with `RmiErrorInUse` if `cmd` already has a handler,
+    /// or with `RmiErrorInput` if `cmd` is out of the RMI (and diagnostics) commands.
+    /// An RMI command without a handler returns `rmi::NOT_SUPPORTED` to the host.
+    #[cfg(not(kani))]
+    pub fn register_rmi(&mut self, cmd: Command, handler: RmiHandler) -> Result<(), Error> {
+        if self.rmi.handler(cmd).is_some() {
+            return Err(Error::RmiErrorInUse);
+        }
+        self.rmi.on_event.insert(cmd, handler)
+    }
+
     #[cfg(not(kani))]
     pub fn run(&self) {
-        let mut mainloop = Mainloop::new();
-        mainloop.boot_complete();
-        mainloop.run(self)
+        self.rmi.boot_complete();
+        self.rmi.run(self)
     }
     #[cfg(kani)]
     // DIFF: `symbolic` parameter is added to pass symbolic input
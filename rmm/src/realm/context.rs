@@ -1,4 +1,5 @@
 use super::timer;
+use crate::config::{RegWriteFailure, REG_WRITE_FAILURE};
 use crate::cpu::get_cpu_id;
 use crate::gic;
 use crate::realm::vcpu::VCPU;
@@ -20,6 +21,19 @@ pub struct Context {
     pub fp_regs: [u128; 32],
 }
 
+/// A register of a REC as `set_reg` indexes it: x0-x30, then ELR (31) and SPSR (32).
+pub type GprIndex = usize;
+
+/// Writes all of `regs` to the VCPU under a single lock, or none of them if any index is invalid.
+pub fn write_regs(rd: &Rd, vcpu: usize, regs: &[(GprIndex, u64)]) -> Result<(), Error> {
+    rd.vcpus
+        .get(vcpu)
+        .ok_or(Error::RmiErrorOthers(NotExistVCPU))?
+        .lock()
+        .context
+        .write_regs(regs)
+}
+
 pub fn set_reg(rd: &Rd, vcpu: usize, register: usize, value: usize) -> Result<(), Error> {
     match register {
         0..=30 => {
@@ -80,6 +94,46 @@ pub fn get_reg(rd: &Rd, vcpu: usize, register: usize) -> Result<usize, Error> {
     }
 }
 
+/// Handles the result of writing `register` of the realm (`realmid`, `vcpu`) as `policy`.
+pub fn on_reg_write(
+    policy: RegWriteFailure,
+    res: Result<(), Error>,
+    realmid: usize,
+    vcpu: usize,
+    register: usize,
+) -> Result<(), Error> {
+    let Err(e) = res else {
+        return Ok(());
+    };
+    match policy {
+        RegWriteFailure::Warn => {
+            warn!(
+                "Unable to set register {}. realmid: {:?} vcpuid: {:?}",
+                register, realmid, vcpu
+            );
+            Ok(())
+        }
+        RegWriteFailure::Error => Err(e),
+        RegWriteFailure::Halt => panic!(
+            "Unable to set register {}. realmid: {:?} vcpuid: {:?}",
+            register, realmid, vcpu
+        ),
+    }
+}
+
+/// Writes a result register of the realm, handling a failure as `REG_WRITE_FAILURE`.
+pub fn set_result_reg(rd: &Rd, vcpu: usize, register: usize, value: usize) -> Result<(), Error> {
+    let res = set_reg(rd, vcpu, register, value);
+    on_reg_write(REG_WRITE_FAILURE, res, rd.id(), vcpu, register)
+}
+
+/// Writes result registers of the realm at once, handling a failure as `REG_WRITE_FAILURE`.
+pub fn write_result_regs(rd: &Rd, vcpu: usize, regs: &[(GprIndex, u64)]) -> Result<(), Error> {
+    let res = write_regs(rd, vcpu, regs);
+    let register = regs.first().map_or(0, |&(register, _)| register);
+    on_reg_write(REG_WRITE_FAILURE, res, rd.id(), vcpu, register)
+}
+
 impl Context {
     pub fn new() -> Self {
         // Set appropriate sys registers
@@ -91,6 +145,32 @@ impl Context {
         }
     }
 
+    fn reg_mut(&mut self, register: GprIndex) -> Option<&mut u64> {
+        match register {
+            0..=30 => Some(&mut self.gp_regs[register]),
+            31 => Some(&mut self.elr),
+            32 => Some(&mut self.spsr),
+            _ => None,
+        }
+    }
+
+    /// Validates every index of `regs` before applying any write,
+    /// so a batch either applies as a whole or not at all.
+    pub fn write_regs(&mut self, regs: &[(GprIndex, u64)]) -> Result<(), Error> {
+        if regs
+            .iter()
+            .any(|&(register, _)| self.reg_mut(register).is_none())
+        {
+            return Err(Error::RmiErrorInput);
+        }
+        for &(register, value) in regs {
+            if let Some(reg) = self.reg_mut(register) {
+                *reg = value;
+            }
+        }
+        Ok(())
+    }
+
     pub unsafe fn into_current(vcpu: &mut VCPU) {
         vcpu.pcpu = Some(get_cpu_id());
         vcpu.context.sys_regs.vmpidr = vcpu.pcpu.unwrap() as u64;
@@ -168,3 +248,47 @@ pub struct TimerRegister {
     pub cntp_ctl_el0: u64,
     pub cnthctl_el2: u64,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // a REC whose register writes fail
+    fn failing_write() -> Result<(), Error> {
+        Err(Error::RmiErrorOthers(NotExistVCPU))
+    }
+
+    #[test]
+    fn reg_write_warn() {
+        assert!(on_reg_write(RegWriteFailure::Warn, failing_write(), 1, 0, 1).is_ok());
+    }
+
+    #[test]
+    fn reg_write_error() {
+        assert!(on_reg_write(RegWriteFailure::Error, failing_write(), 1, 0, 1).is_err());
+        assert!(on_reg_write(RegWriteFailure::Error, Ok(()), 1, 0, 1).is_ok());
+    }
+
+    #[test]
+    #[should_panic]
+    fn reg_write_halt() {
+        let _ = on_reg_write(RegWriteFailure::Halt, failing_write(), 1, 0, 1);
+    }
+
+    #[test]
+    fn write_regs_batch() {
+        let mut context = Context::new();
+        let spsr = context.spsr;
+        assert!(matches!(
+            context.write_regs(&[(0, 1), (33, 2), (32, 3)]),
+            Err(Error::RmiErrorInput)
+        ));
+        assert_eq!(context.gp_regs[0], 0);
+        assert_eq!(context.spsr, spsr);
+
+        assert!(context.write_regs(&[(0, 1), (31, 2), (32, 3)]).is_ok());
+        assert_eq!(context.gp_regs[0], 1);
+        assert_eq!(context.elr, 2);
+        assert_eq!(context.spsr, 3);
+    }
+}
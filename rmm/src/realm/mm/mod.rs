@@ -3,14 +3,27 @@ pub mod page;
 pub mod page_table;
 pub mod stage2_translation;
 pub mod stage2_tte;
+pub mod translate;
 pub mod translation_granule_4k;
 
 use crate::rmi::error::Error;
+use armv9a::bits_in_reg;
+use armv9a::regs::VTTBR_EL2;
 use core::ffi::c_void;
 use core::fmt::Debug;
 
 use address::{GuestPhysAddr, PhysAddr};
 
+/// Returns the VTTBR_EL2 of the realm (`vmid`) whose RTT starts at `rtt_base`.
+pub fn vttbr(vmid: usize, rtt_base: usize) -> u64 {
+    bits_in_reg(VTTBR_EL2::VMID, vmid as u64) | bits_in_reg(VTTBR_EL2::BADDR, rtt_base as u64)
+}
+
+/// Returns the VMID of `vttbr`, i.e., the realm whose stage 2 it selects.
+pub fn vttbr_vmid(vttbr: u64) -> usize {
+    ((vttbr & VTTBR_EL2::VMID) >> VTTBR_EL2::VMID.trailing_zeros()) as usize
+}
+
 pub trait IPATranslation: Debug + Send + Sync {
     fn get_base_address(&self) -> *const c_void;
     // TODO: remove mut
@@ -2,12 +2,14 @@ use core::mem::size_of;
 use vmsa::address::PhysAddr;
 
 use super::address::GuestPhysAddr;
+use super::{vttbr, IPATranslation};
+use crate::asm::{tlbi_ipa, tlbi_vmid};
 use crate::granule::GRANULE_SIZE;
-use crate::realm::mm::page_table::pte::{attribute, shareable};
+use crate::realm::mm::page_table::pte::{attribute, permission, shareable};
 use crate::rmi::error::Error;
 use crate::rmi::realm::Rd;
-use crate::rmi::rtt::{RTT_MIN_BLOCK_LEVEL, RTT_PAGE_LEVEL};
-use armv9a::{define_bitfield, define_bits, define_mask};
+use crate::rmi::rtt::{level_to_size, RTT_MIN_BLOCK_LEVEL, RTT_PAGE_LEVEL};
+use armv9a::{bits_in_reg, define_bitfield, define_bits, define_mask};
 use vmsa::guard::Content;
 
 pub const INVALID_UNPROTECTED: u64 = 0x0;
@@ -28,6 +30,15 @@ pub mod desc_type {
     pub const L012_BLOCK: u64 = 0x1;
     pub const L3_PAGE: u64 = 0x3;
     pub const LX_INVALID: u64 = 0x0;
+
+    /// The type of a valid entry at `level`, a page at the last level and a block otherwise.
+    pub fn valid(level: usize) -> u64 {
+        if level == crate::rmi::rtt::RTT_PAGE_LEVEL {
+            L3_PAGE
+        } else {
+            L012_BLOCK
+        }
+    }
 }
 
 #[repr(C)]
@@ -91,6 +102,43 @@ impl S2TTE {
         Ok((S2TTE::from(s2tte as usize), last_level))
     }
 
+    /// Writes `new` over the entry at `level` mapping `ipa` in `s2_table` of the realm (`vmid`).
+    /// If the old entry was valid (e.g., on unmap), its stale TLB entries are invalidated
+    /// so that the realm can't keep accessing the page.
+    ///
+    /// A table entry (e.g., on fold), or a valid one replaced by a table (e.g., a block
+    /// unfolded on create), is broken before the new one is made. As the TLB may cache
+    /// any entry of the table below it, all the TLB entries of the realm are invalidated
+    /// in between.
+    pub fn update(
+        s2_table: &mut dyn IPATranslation,
+        vmid: usize,
+        ipa: usize,
+        level: usize,
+        new: u64,
+    ) -> Result<(), Error> {
+        let guest = GuestPhysAddr::from(ipa);
+        let (old, last_level) = s2_table
+            .ipa_to_pte(guest, level)
+            .ok_or(Error::RmiErrorRtt(0))?;
+        let old = S2TTE::from(old as usize);
+        // the TLBI applies to the VMID and the RTT of the realm
+        let vttbr = vttbr(vmid, s2_table.get_base_address() as usize);
+        let at_level = last_level == level;
+        let valid = at_level && (old.is_valid(level, false) || old.is_valid(level, true));
+        if at_level && (old.is_table(level) || (valid && S2TTE::new(new).is_table(level))) {
+            s2_table.ipa_to_pte_set(guest, level, 0)?;
+            tlbi_vmid(vttbr);
+            return s2_table.ipa_to_pte_set(guest, level, new);
+        }
+        s2_table.ipa_to_pte_set(guest, level, new)?;
+
+        if valid {
+            tlbi_ipa(vttbr, ipa, level);
+        }
+        Ok(())
+    }
+
     pub fn is_valid(&self, level: usize, is_ns: bool) -> bool {
         let ns = self.get_masked_value(S2TTE::NS);
         let ns_valid = if is_ns { ns == 1 } else { ns == 0 };
@@ -167,4 +215,341 @@ impl S2TTE {
     pub fn get_ripas(&self) -> u64 {
         self.get_masked_value(S2TTE::INVALID_RIPAS)
     }
+
+    /// Returns the entry at `level` mapping the data granule (or the block of them) at `pa`,
+    /// whose RIPAS is `ripas`: valid for RAM, assigned for EMPTY.
+    pub fn data(pa: usize, level: usize, ripas: u64) -> u64 {
+        let mut s2tte = pa as u64;
+        if ripas == invalid_ripas::EMPTY {
+            s2tte |= bits_in_reg(S2TTE::INVALID_HIPAS, invalid_hipas::ASSIGNED);
+            s2tte |= bits_in_reg(S2TTE::INVALID_RIPAS, invalid_ripas::EMPTY);
+        } else if ripas == invalid_ripas::RAM {
+            // S2TTE_PAGE  : S2TTE_ATTRS | S2TTE_L3_PAGE
+            // S2TTE_BLOCK : S2TTE_ATTRS | S2TTE_L012_BLOCK
+            s2tte |= bits_in_reg(S2TTE::DESC_TYPE, desc_type::valid(level));
+            // S2TTE_ATTRS : S2TTE_MEMATTR_FWB_NORMAL_WB | S2TTE_AP_RW | S2TTE_SH_IS | S2TTE_AF
+            s2tte |= bits_in_reg(S2TTE::MEMATTR, attribute::NORMAL_FWB);
+            s2tte |= bits_in_reg(S2TTE::AP, permission::RW);
+            s2tte |= bits_in_reg(S2TTE::SH, shareable::INNER);
+            s2tte |= bits_in_reg(S2TTE::AF, 1);
+        } else {
+            panic!("Unexpected ripas: {}", ripas);
+        }
+        s2tte
+    }
+
+    /// Unfolds this entry at `level` into `table`, the RTT of the next level
+    /// covering the same range, e.g., a 2MB block into 512 pages.
+    ///
+    /// An unassigned or destroyed entry is copied to all entries of `table`.
+    /// An assigned or valid entry is split into entries mapping the consecutive parts
+    /// of its output range with the same attributes.
+    pub fn unfold(&self, level: usize, table: &mut RttPage) -> Result<(), Error> {
+        if self.is_unassigned() || self.is_destroyed() {
+            table.0.fill(self.get());
+            return Ok(());
+        }
+
+        let desc = if self.is_assigned() {
+            desc_type::LX_INVALID
+        } else if self.is_valid(level, false) || self.is_valid(level, true) {
+            desc_type::valid(level + 1)
+        } else {
+            return Err(Error::RmiErrorRtt(level));
+        };
+        let pa: usize = self.address(level).ok_or(Error::RmiErrorRtt(level))?.into();
+        let attrs = self.attrs() & !Self::desc_mask();
+        let size = level_to_size(level + 1) as usize;
+        for (i, entry) in table.0.iter_mut().enumerate() {
+            *entry = (pa + i * size) as u64 | attrs | bits_in_reg(S2TTE::DESC_TYPE, desc);
+        }
+        Ok(())
+    }
+
+    /// Folds `table`, an RTT at `level`, into the entry of the previous level
+    /// covering the same range, the reverse of `unfold`.
+    ///
+    /// Returns `None` unless all entries are the same unassigned or destroyed entry,
+    /// or all are assigned (or valid) mapping the consecutive parts of an output range
+    /// aligned to a block of the previous level with the same attributes.
+    pub fn fold(table: &RttPage, level: usize) -> Option<u64> {
+        let first = S2TTE::new(*table.get(0)?);
+        if first.is_unassigned() || first.is_destroyed() {
+            return table
+                .0
+                .iter()
+                .all(|e| *e == first.get())
+                .then_some(first.get());
+        }
+
+        let parent = level.checked_sub(1).filter(|l| *l >= RTT_MIN_BLOCK_LEVEL)?;
+        let desc = if first.is_assigned() {
+            desc_type::LX_INVALID
+        } else if first.is_valid(level, false) || first.is_valid(level, true) {
+            desc_type::valid(parent)
+        } else {
+            return None;
+        };
+        let pa: usize = first.address(level)?.into();
+        if pa % level_to_size(parent) as usize != 0 {
+            return None;
+        }
+        let size = level_to_size(level);
+        let consecutive = table
+            .0
+            .iter()
+            .enumerate()
+            .all(|(i, e)| *e == first.get() + i as u64 * size);
+        consecutive
+            .then_some((first.get() & !Self::desc_mask()) | bits_in_reg(S2TTE::DESC_TYPE, desc))
+    }
+
+    /// Returns the bits of the entry other than the output address.
+    pub fn attrs(&self) -> u64 {
+        self.get() & !Self::addr_mask()
+    }
+
+    fn addr_mask() -> u64 {
+        S2TTE::new(!0).get_masked(S2TTE::ADDR_L3_PAGE)
+    }
+
+    fn desc_mask() -> u64 {
+        S2TTE::new(!0).get_masked(S2TTE::DESC_TYPE)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BLOCK_PA: usize = 0x8860_0000;
+    // the RTT base of every test table
+    const RTT_BASE: usize = 0x8890_0000;
+
+    // A stage 2 table with a single entry at `level` for any IPA
+    #[derive(Debug)]
+    struct OneEntry {
+        pte: u64,
+        level: usize,
+        // the values written to the entry, in order
+        writes: alloc::vec::Vec<u64>,
+    }
+
+    impl IPATranslation for OneEntry {
+        fn get_base_address(&self) -> *const core::ffi::c_void {
+            RTT_BASE as *const core::ffi::c_void
+        }
+        fn ipa_to_pa(&mut self, _guest: GuestPhysAddr, _level: usize) -> Option<PhysAddr> {
+            None
+        }
+        fn ipa_to_pte(&mut self, _guest: GuestPhysAddr, _level: usize) -> Option<(u64, usize)> {
+            Some((self.pte, self.level))
+        }
+        fn ipa_to_pte_set(
+            &mut self,
+            _guest: GuestPhysAddr,
+            _level: usize,
+            val: u64,
+        ) -> Result<(), Error> {
+            self.pte = val;
+            self.writes.push(val);
+            Ok(())
+        }
+        fn clean(&mut self) {}
+    }
+
+    fn tlbi_calls(vmid: usize) -> alloc::vec::Vec<(usize, usize)> {
+        crate::asm::TLBI_IPA_CALLS
+            .lock()
+            .iter()
+            .filter(|call| call.0 == vttbr(vmid, RTT_BASE))
+            .map(|call| (call.1, call.2))
+            .collect()
+    }
+
+    #[test]
+    fn unmap_invalidates_tlb() {
+        const VMID: usize = 0x7a1;
+        let ipa = 0x8000_0000_1000;
+        let ns_page = S2TTE::data(0x8800_0000, RTT_PAGE_LEVEL, invalid_ripas::RAM)
+            | bits_in_reg(S2TTE::NS, 1);
+        let mut s2_table = OneEntry {
+            pte: ns_page,
+            level: RTT_PAGE_LEVEL,
+            writes: alloc::vec::Vec::new(),
+        };
+
+        S2TTE::update(
+            &mut s2_table,
+            VMID,
+            ipa,
+            RTT_PAGE_LEVEL,
+            INVALID_UNPROTECTED,
+        )
+        .unwrap();
+        assert_eq!(s2_table.pte, INVALID_UNPROTECTED);
+        assert_eq!(tlbi_calls(VMID), [(ipa, RTT_PAGE_LEVEL)]);
+
+        // nothing is cached for an invalid entry
+        S2TTE::update(
+            &mut s2_table,
+            VMID,
+            ipa,
+            RTT_PAGE_LEVEL,
+            INVALID_UNPROTECTED,
+        )
+        .unwrap();
+        assert_eq!(tlbi_calls(VMID).len(), 1);
+    }
+
+    #[test]
+    fn fold_invalidates_vmid() {
+        const VMID: usize = 0x7a3;
+        let ipa = 0x40_0000;
+        let table = BLOCK_PA as u64 | bits_in_reg(S2TTE::DESC_TYPE, desc_type::L012_TABLE);
+        let mut s2_table = OneEntry {
+            pte: table,
+            level: RTT_MIN_BLOCK_LEVEL,
+            writes: alloc::vec::Vec::new(),
+        };
+        let block = S2TTE::data(BLOCK_PA, RTT_MIN_BLOCK_LEVEL, invalid_ripas::RAM);
+
+        S2TTE::update(&mut s2_table, VMID, ipa, RTT_MIN_BLOCK_LEVEL, block).unwrap();
+        assert_eq!(s2_table.pte, block);
+        assert!(crate::asm::TLBI_VMID_CALLS
+            .lock()
+            .contains(&vttbr(VMID, RTT_BASE)));
+        // nothing is cached for the table entry itself
+        assert!(tlbi_calls(VMID).is_empty());
+    }
+
+    #[test]
+    fn destroy_invalidates_vmid() {
+        const VMID: usize = 0x7a5;
+        let ipa = 0x80_0000;
+        let table = 0x8880_0000 | bits_in_reg(S2TTE::DESC_TYPE, desc_type::L012_TABLE);
+        let mut s2_table = OneEntry {
+            pte: table,
+            level: RTT_MIN_BLOCK_LEVEL,
+            writes: alloc::vec::Vec::new(),
+        };
+        let destroyed = bits_in_reg(S2TTE::INVALID_HIPAS, invalid_hipas::DESTROYED);
+
+        S2TTE::update(&mut s2_table, VMID, ipa, RTT_MIN_BLOCK_LEVEL, destroyed).unwrap();
+        assert_eq!(s2_table.writes, [0, destroyed]);
+        assert!(crate::asm::TLBI_VMID_CALLS
+            .lock()
+            .contains(&vttbr(VMID, RTT_BASE)));
+    }
+
+    #[test]
+    fn unfold_breaks_before_make() {
+        const VMID: usize = 0x7a4;
+        let ipa = 0x60_0000;
+        let block = S2TTE::data(BLOCK_PA, RTT_MIN_BLOCK_LEVEL, invalid_ripas::RAM);
+        let mut s2_table = OneEntry {
+            pte: block,
+            level: RTT_MIN_BLOCK_LEVEL,
+            writes: alloc::vec::Vec::new(),
+        };
+        // the RTT holding the pages of the block, as on create
+        let mut rtt = RttPage([0; GRANULE_SIZE / size_of::<u64>()]);
+        S2TTE::new(block)
+            .unfold(RTT_MIN_BLOCK_LEVEL, &mut rtt)
+            .unwrap();
+        let table = 0x8880_0000 | bits_in_reg(S2TTE::DESC_TYPE, desc_type::L012_TABLE);
+
+        S2TTE::update(&mut s2_table, VMID, ipa, RTT_MIN_BLOCK_LEVEL, table).unwrap();
+        // the block is invalidated before the table is installed
+        assert_eq!(s2_table.writes, [0, table]);
+        assert!(crate::asm::TLBI_VMID_CALLS
+            .lock()
+            .contains(&vttbr(VMID, RTT_BASE)));
+        assert!(tlbi_calls(VMID).is_empty());
+    }
+
+    #[test]
+    fn ripas_empty_invalidates_tlb() {
+        const VMID: usize = 0x7a2;
+        let ipa = 0x20_0000;
+        let mut s2_table = OneEntry {
+            pte: S2TTE::data(BLOCK_PA, RTT_MIN_BLOCK_LEVEL, invalid_ripas::RAM),
+            level: RTT_MIN_BLOCK_LEVEL,
+            writes: alloc::vec::Vec::new(),
+        };
+        let assigned = S2TTE::data(BLOCK_PA, RTT_MIN_BLOCK_LEVEL, invalid_ripas::EMPTY);
+
+        S2TTE::update(&mut s2_table, VMID, ipa, RTT_MIN_BLOCK_LEVEL, assigned).unwrap();
+        assert_eq!(tlbi_calls(VMID), [(ipa, RTT_MIN_BLOCK_LEVEL)]);
+    }
+
+    #[test]
+    fn block_mapping() {
+        let level = RTT_MIN_BLOCK_LEVEL;
+        let block = S2TTE::new(S2TTE::data(BLOCK_PA, level, invalid_ripas::RAM));
+        assert!(block.is_valid(level, false));
+        assert!(!block.is_table(level));
+        assert_eq!(block.address(level), Some(PhysAddr::from(BLOCK_PA)));
+
+        let mut table = RttPage([0; GRANULE_SIZE / size_of::<u64>()]);
+        block.unfold(level, &mut table).unwrap();
+        for i in [0, 1, table.len() - 1] {
+            let page = S2TTE::new(*table.get(i).unwrap());
+            assert!(page.is_valid(RTT_PAGE_LEVEL, false));
+            let pa = BLOCK_PA + i * GRANULE_SIZE;
+            assert_eq!(page.address(RTT_PAGE_LEVEL), Some(PhysAddr::from(pa)));
+        }
+        assert_eq!(S2TTE::fold(&table, RTT_PAGE_LEVEL), Some(block.get()));
+    }
+
+    #[test]
+    fn ripas_change_splits_block() {
+        let level = RTT_MIN_BLOCK_LEVEL;
+        let block = S2TTE::new(S2TTE::data(BLOCK_PA, level, invalid_ripas::RAM));
+        let mut table = RttPage([0; GRANULE_SIZE / size_of::<u64>()]);
+        block.unfold(level, &mut table).unwrap();
+
+        // a single page of the block becomes EMPTY, as make_shared does
+        let pa = BLOCK_PA + GRANULE_SIZE;
+        *table.get_mut(1).unwrap() = S2TTE::data(pa, RTT_PAGE_LEVEL, invalid_ripas::EMPTY);
+
+        assert!(S2TTE::new(*table.get(0).unwrap()).is_valid(RTT_PAGE_LEVEL, false));
+        assert_eq!(
+            S2TTE::new(*table.get(1).unwrap()).get_ripas(),
+            invalid_ripas::EMPTY
+        );
+        assert_eq!(S2TTE::fold(&table, RTT_PAGE_LEVEL), None);
+    }
+
+    #[test]
+    fn fold_unassigned() {
+        let unassigned = bits_in_reg(S2TTE::INVALID_RIPAS, invalid_ripas::RAM);
+        let mut table = RttPage([unassigned; GRANULE_SIZE / size_of::<u64>()]);
+        assert_eq!(S2TTE::fold(&table, RTT_PAGE_LEVEL), Some(unassigned));
+
+        *table.get_mut(3).unwrap() = bits_in_reg(S2TTE::INVALID_HIPAS, invalid_hipas::DESTROYED);
+        assert_eq!(S2TTE::fold(&table, RTT_PAGE_LEVEL), None);
+    }
+
+    #[test]
+    fn fold_mixed_ripas() {
+        let ram = bits_in_reg(S2TTE::INVALID_RIPAS, invalid_ripas::RAM);
+        let mut table = RttPage([ram; GRANULE_SIZE / size_of::<u64>()]);
+        *table.get_mut(7).unwrap() = bits_in_reg(S2TTE::INVALID_RIPAS, invalid_ripas::EMPTY);
+        assert_eq!(S2TTE::fold(&table, RTT_PAGE_LEVEL), None);
+    }
+
+    #[test]
+    fn fold_assigned() {
+        let level = RTT_MIN_BLOCK_LEVEL;
+        let block = S2TTE::new(S2TTE::data(BLOCK_PA, level, invalid_ripas::EMPTY));
+        let mut table = RttPage([0; GRANULE_SIZE / size_of::<u64>()]);
+        block.unfold(level, &mut table).unwrap();
+        assert!(S2TTE::new(*table.get(5).unwrap()).is_assigned());
+        assert_eq!(S2TTE::fold(&table, RTT_PAGE_LEVEL), Some(block.get()));
+
+        // a page out of the consecutive range
+        *table.get_mut(5).unwrap() = S2TTE::data(0x9000_0000, RTT_PAGE_LEVEL, invalid_ripas::EMPTY);
+        assert_eq!(S2TTE::fold(&table, RTT_PAGE_LEVEL), None);
+    }
 }
@@ -0,0 +1,136 @@
+use crate::realm::mm::stage2_tte::invalid_ripas;
+use crate::realm::mm::stage2_tte::S2TTE;
+use crate::rmi::error::Error;
+use crate::rmi::realm::Rd;
+use crate::rmi::rtt::level_to_size;
+use crate::rtt::{read_rtt, walk_entry};
+
+/// The result of translating an IPA of a realm through its RTT.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TranslationResult {
+    /// The PA backing the IPA, including its offset in the page or block.
+    pub pa: usize,
+    /// The level of the entry mapping the IPA, `RTT_MIN_BLOCK_LEVEL` for a 2MB block.
+    pub level: usize,
+    pub ripas: u64,
+    /// The bits of the entry other than the output address.
+    pub attrs: u64,
+}
+
+impl TranslationResult {
+    /// Returns the PA and the level of the mapping if it is valid (i.e., RAM),
+    /// or `RmiErrorRtt` with its level otherwise.
+    pub fn valid(&self) -> Result<(usize, usize), Error> {
+        if !S2TTE::new(self.attrs).is_valid(self.level, false) {
+            return Err(Error::RmiErrorRtt(self.level));
+        }
+        Ok((self.pa, self.level))
+    }
+}
+
+/// Translates `ipa` with the RTT from the table at `root` of `start_level`,
+/// reading the entry at `index` of the table at `table` with `read(table, index)`.
+///
+/// The walk stops early at a block or an invalid entry. Only an entry with an output
+/// address, i.e., a valid one (RAM) or an assigned one (EMPTY), translates `ipa`.
+/// Any other fails with `RmiErrorRtt` at the level where the walk ends.
+pub fn walk_ipa(
+    root: usize,
+    start_level: usize,
+    ipa: usize,
+    ipa_bits: usize,
+    read: impl Fn(usize, usize) -> u64,
+) -> Result<TranslationResult, Error> {
+    if ipa_bits >= usize::BITS as usize || ipa >> ipa_bits != 0 {
+        return Err(Error::RmiErrorInput);
+    }
+
+    let (s2tte, level) = walk_entry(root, start_level, ipa, read)?;
+    let ripas = if s2tte.is_valid(level, false) {
+        invalid_ripas::RAM
+    } else if s2tte.is_assigned() {
+        s2tte.get_ripas()
+    } else {
+        return Err(Error::RmiErrorRtt(level));
+    };
+    let pa: usize = s2tte
+        .address(level)
+        .ok_or(Error::RmiErrorRtt(level))?
+        .into();
+    let offset = ipa & (level_to_size(level) as usize - 1);
+    Ok(TranslationResult {
+        pa: pa + offset,
+        level,
+        ripas,
+        attrs: s2tte.attrs(),
+    })
+}
+
+/// Translates `ipa` of the realm as `walk_ipa` does.
+pub fn translate_ipa(rd: &Rd, ipa: usize) -> Result<TranslationResult, Error> {
+    let start_level = usize::try_from(rd.s2_starting_level()).or(Err(Error::RmiErrorInput))?;
+
+    // The RTT doesn't change during the walk while holding the lock of the stage 2 table.
+    let _s2_table = rd.s2_table().lock();
+    walk_ipa(rd.rtt_base(), start_level, ipa, rd.ipa_bits(), read_rtt)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::realm::mm::stage2_tte::desc_type;
+    use crate::rmi::rtt::{RTT_MIN_BLOCK_LEVEL, RTT_PAGE_LEVEL};
+    use crate::rtt::walk_test::{read, IPA_BITS, L2_TABLE, L3_TABLE};
+    use armv9a::bits_in_reg;
+
+    fn walk(ipa: usize) -> Result<TranslationResult, Error> {
+        walk_ipa(L2_TABLE, RTT_MIN_BLOCK_LEVEL, ipa, IPA_BITS, read)
+    }
+
+    #[test]
+    fn page_hit() {
+        let res = walk(0x1abc).unwrap();
+        assert_eq!(res.pa, 0x8800_0abc);
+        assert_eq!(res.level, RTT_PAGE_LEVEL);
+        assert_eq!(res.ripas, invalid_ripas::RAM);
+        assert_eq!(res.attrs, S2TTE::new(read(L3_TABLE, 1)).attrs());
+    }
+
+    #[test]
+    fn block_hit() {
+        let res = walk(0x0030_0010).unwrap();
+        assert_eq!(res.pa, 0x8870_0010);
+        assert_eq!(res.level, RTT_MIN_BLOCK_LEVEL);
+        assert_eq!(res.ripas, invalid_ripas::RAM);
+        assert_eq!(
+            res.attrs & bits_in_reg(S2TTE::DESC_TYPE, !0),
+            desc_type::L012_BLOCK
+        );
+
+        let res = walk(0x0040_2000).unwrap();
+        assert_eq!(res.pa, 0x8880_2000);
+        assert_eq!(res.ripas, invalid_ripas::EMPTY);
+    }
+
+    #[test]
+    fn only_valid() {
+        assert_eq!(
+            walk(0x1000).unwrap().valid().unwrap(),
+            (0x8800_0000, RTT_PAGE_LEVEL)
+        );
+        assert_eq!(
+            walk(0x0030_0010).unwrap().valid().unwrap(),
+            (0x8870_0010, RTT_MIN_BLOCK_LEVEL)
+        );
+        // assigned, but not valid
+        let res = walk(0x0040_0000).unwrap().valid();
+        assert!(matches!(res, Err(Error::RmiErrorRtt(2))));
+    }
+
+    #[test]
+    fn unmapped() {
+        assert!(matches!(walk(0x2000), Err(Error::RmiErrorRtt(3))));
+        assert!(matches!(walk(0x0060_0000), Err(Error::RmiErrorRtt(2))));
+        assert!(matches!(walk(1 << IPA_BITS), Err(Error::RmiErrorInput)));
+    }
+}
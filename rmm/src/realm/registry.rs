@@ -1,4 +1,109 @@
+use crate::config::MAX_REALMS;
+use crate::rmi::error::Error;
+
 use alloc::collections::BTreeSet;
 use spinning_top::Spinlock;
 
 pub static VMID_SET: Spinlock<BTreeSet<usize>> = Spinlock::new(BTreeSet::new());
+
+/// Registers the VMID of a new realm, up to `MAX_REALMS` realms at a time.
+pub fn insert_vmid(vmid_set: &mut BTreeSet<usize>, vmid: usize) -> Result<(), Error> {
+    if vmid_set.contains(&vmid) || vmid_set.len() >= MAX_REALMS {
+        return Err(Error::RmiErrorInput);
+    }
+    vmid_set.insert(vmid);
+    Ok(())
+}
+
+/// The result of looking up a realm without blocking.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TryLookup {
+    Found,
+    Missing,
+    /// The registry is locked by another CPU (e.g., creating a realm),
+    /// which doesn't tell whether the realm exists.
+    Contended,
+}
+
+/// Looks up the realm of `vmid` without blocking behind a writer of `vmid_set`.
+pub fn try_lookup(vmid_set: &Spinlock<BTreeSet<usize>>, vmid: usize) -> TryLookup {
+    match vmid_set.try_lock() {
+        Some(set) if set.contains(&vmid) => TryLookup::Found,
+        Some(_) => TryLookup::Missing,
+        None => TryLookup::Contended,
+    }
+}
+
+/// Returns whether the realm of `vmid` is registered in `vmid_set`.
+///
+/// The lookup doesn't block on the fast path and waits for the registry
+/// only while another CPU holds it.
+pub fn realm_exists(vmid_set: &Spinlock<BTreeSet<usize>>, vmid: usize) -> bool {
+    match try_lookup(vmid_set, vmid) {
+        TryLookup::Found => true,
+        TryLookup::Missing => false,
+        TryLookup::Contended => vmid_set.lock().contains(&vmid),
+    }
+}
+
+/// Encodes the realm count into the output registers of RMI_REALM_COUNT_GET:
+///   x1: the maximum number of realms
+///   x2: the number of realms currently existing
+pub fn realm_count(vmid_set: &BTreeSet<usize>) -> [usize; 2] {
+    [MAX_REALMS, vmid_set.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reported_max_is_creation_cap() {
+        let mut vmid_set = BTreeSet::new();
+        let [max, _] = realm_count(&vmid_set);
+
+        for vmid in 0..max {
+            insert_vmid(&mut vmid_set, vmid).unwrap();
+        }
+        assert_eq!(realm_count(&vmid_set), [max, max]);
+        assert!(insert_vmid(&mut vmid_set, max).is_err());
+
+        vmid_set.remove(&0);
+        assert_eq!(realm_count(&vmid_set), [max, max - 1]);
+        insert_vmid(&mut vmid_set, max).unwrap();
+    }
+
+    #[test]
+    fn contended_lookup() {
+        let vmid_set = Spinlock::new(BTreeSet::new());
+        insert_vmid(&mut vmid_set.lock(), 1).unwrap();
+
+        assert_eq!(try_lookup(&vmid_set, 1), TryLookup::Found);
+        assert_eq!(try_lookup(&vmid_set, 2), TryLookup::Missing);
+
+        // a realm being created on another CPU
+        let mut creating = vmid_set.lock();
+        assert_eq!(try_lookup(&vmid_set, 1), TryLookup::Contended);
+        assert_eq!(try_lookup(&vmid_set, 2), TryLookup::Contended);
+        insert_vmid(&mut creating, 2).unwrap();
+        core::mem::drop(creating);
+
+        assert_eq!(try_lookup(&vmid_set, 2), TryLookup::Found);
+    }
+
+    #[test]
+    fn exists() {
+        let vmid_set = Spinlock::new(BTreeSet::new());
+        insert_vmid(&mut vmid_set.lock(), 1).unwrap();
+
+        assert!(realm_exists(&vmid_set, 1));
+        assert!(!realm_exists(&vmid_set, 2));
+    }
+
+    #[test]
+    fn duplicate_vmid() {
+        let mut vmid_set = BTreeSet::new();
+        insert_vmid(&mut vmid_set, 1).unwrap();
+        assert!(insert_vmid(&mut vmid_set, 1).is_err());
+    }
+}
@@ -1,11 +1,11 @@
 use crate::gic;
 use crate::realm::context::Context;
+use crate::realm::mm::vttbr;
 use crate::realm::registry::VMID_SET;
 use crate::realm::timer;
 use crate::rmi::error::Error;
 use crate::rmi::realm::Rd;
 use alloc::sync::{Arc, Weak};
-use armv9a::bits_in_reg;
 use armv9a::regs::*;
 use spin::Mutex;
 
@@ -80,8 +80,7 @@ pub unsafe fn current() -> Option<&'static mut VCPU> {
 
 pub fn create_vcpu(rd: &mut Rd) -> Result<usize, Error> {
     let page_table = rd.s2_table().lock().get_base_address();
-    let vttbr = bits_in_reg(VTTBR_EL2::VMID, rd.id() as u64)
-        | bits_in_reg(VTTBR_EL2::BADDR, page_table as u64);
+    let vttbr = vttbr(rd.id(), page_table as usize);
 
     let vcpu = VCPU::new();
     vcpu.lock().context.sys_regs.vttbr = vttbr;
@@ -45,8 +45,26 @@ fn pick(cmd: Command) -> Option<Constraint> {
         rmi::REC_AUX_COUNT => Constraint::new(rmi::REC_AUX_COUNT, 2, 2),
         rmi::RTT_CREATE => Constraint::new(rmi::RTT_CREATE, 5, 1),
         rmi::RTT_DESTROY => Constraint::new(rmi::RTT_DESTROY, 5, 1),
+        rmi::RTT_FOLD => Constraint::new(rmi::RTT_FOLD, 4, 2),
         rmi::RTT_INIT_RIPAS => Constraint::new(rmi::RTT_INIT_RIPAS, 4, 2),
         rmi::RTT_SET_RIPAS => Constraint::new(rmi::RTT_SET_RIPAS, 6, 2),
+        // the diagnostics of our own (see `rmi::DIAG_BASE`)
+        #[cfg(feature = "diagnostics")]
+        rmi::REC_PENDING_GET => Constraint::new(rmi::REC_PENDING_GET, 2, 5),
+        #[cfg(feature = "diagnostics")]
+        rmi::REALM_ATTEST_METRICS_GET => Constraint::new(rmi::REALM_ATTEST_METRICS_GET, 2, 6),
+        #[cfg(feature = "diagnostics")]
+        rmi::REALM_COUNT_GET => Constraint::new(rmi::REALM_COUNT_GET, 1, 3),
+        #[cfg(feature = "diagnostics")]
+        rmi::RSI_PROFILE_GET => Constraint::new(rmi::RSI_PROFILE_GET, 2, 3),
+        #[cfg(feature = "diagnostics")]
+        rmi::RTT_TRANSLATE_GET => Constraint::new(rmi::RTT_TRANSLATE_GET, 3, 3),
+        #[cfg(feature = "diagnostics")]
+        rmi::REALM_DEBUG_GET => Constraint::new(rmi::REALM_DEBUG_GET, 2, 2),
+        #[cfg(feature = "diagnostics")]
+        rmi::RTT_UNACCOUNTED_GET => Constraint::new(rmi::RTT_UNACCOUNTED_GET, 3, 5),
+        #[cfg(feature = "diagnostics")]
+        rmi::RMM_LOG_GET => Constraint::new(rmi::RMM_LOG_GET, 2, 3),
         rmi::REQ_COMPLETE => Constraint::new(rmi::REQ_COMPLETE, 4, 2),
         _ => return None,
     };
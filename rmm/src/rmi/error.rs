@@ -22,6 +22,8 @@ pub enum InternalError {
     NotExistVCPU,
     MeasurementError,
     InvalidMeasurementIndex,
+    NoEntropy,
+    NoRng,
 }
 
 impl From<Error> for usize {
@@ -62,6 +64,22 @@ impl From<rsi::error::Error> for Error {
     }
 }
 
+impl From<raw_ptr::AssumeError> for Error {
+    fn from(error: raw_ptr::AssumeError) -> Self {
+        error!("Failed to convert a raw pointer to the struct. {:?}", error);
+        match error {
+            raw_ptr::AssumeError::Null
+            | raw_ptr::AssumeError::Misaligned
+            | raw_ptr::AssumeError::NoPermission
+            | raw_ptr::AssumeError::Uninitialized => Error::RmiErrorInput,
+            // the instance is being used by another party
+            raw_ptr::AssumeError::LifetimeViolation | raw_ptr::AssumeError::OwnershipViolation => {
+                Error::RmiErrorInUse
+            }
+        }
+    }
+}
+
 impl From<raw_ptr::Error> for Error {
     fn from(error: raw_ptr::Error) -> Self {
         error!("Failed to convert a raw pointer to the struct. {:?}", error);
@@ -9,9 +9,9 @@ const S2SZ_WIDTH: usize = 8;
 const S2SZ_VALUE: usize = 48;
 
 const LPA2_SHIFT: usize = 8;
-#[allow(unused)]
 const LPA2_WIDTH: usize = 1;
-const LPA2_VALUE: usize = 0;
+// The RTT walk only uses the 4KB granule format without LPA2 (i.e., up to 48-bit IPAs)
+const LPA2_VALUE: usize = NOT_SUPPORTED;
 
 const PMU_EN_SHIFT: usize = 22;
 const PMU_EN_WIDTH: usize = 1;
@@ -34,7 +34,7 @@ const FEATURE_REGISTER_0_INDEX: usize = 0;
 
 fn extract(reg: usize, shift: usize, width: usize) -> usize {
     let mask = mask(shift, width);
-    (reg << (mask.trailing_zeros())) & mask
+    (reg & mask) >> shift
 }
 
 fn mask(shift: usize, width: usize) -> usize {
@@ -66,6 +66,10 @@ pub fn ipa_bits(feat_reg0: usize) -> usize {
     extract(feat_reg0, S2SZ_SHIFT, S2SZ_WIDTH)
 }
 
+pub fn lpa2(feat_reg0: usize) -> bool {
+    extract(feat_reg0, LPA2_SHIFT, LPA2_WIDTH) == SUPPORTED
+}
+
 //TODO: locate validate() in armv9a to check against AA64MMFR_EL1 register
 pub fn validate(feat_reg0: usize) -> bool {
     const MIN_IPA_SIZE: usize = 32;
@@ -79,6 +83,9 @@ pub fn validate(feat_reg0: usize) -> bool {
     }
 
     // TODO: Add a check for LPA2 flag with AA64MMFR_EL1 reigster after refactoring
+    if lpa2(feat_reg0) && LPA2_VALUE == NOT_SUPPORTED {
+        return false;
+    }
 
     if extract(feat_reg0, PMU_EN_SHIFT, PMU_EN_WIDTH) == SUPPORTED
         && extract(feat_reg0, PMU_NUM_CTRS_SHIFT, PMU_NUM_CTRS_WIDTH) != PMU_NUM_CTRS_VALUE
@@ -88,3 +95,26 @@ pub fn validate(feat_reg0: usize) -> bool {
 
     true
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn extract_fields() {
+        // S2SZ of 40, with the PMU enabled with 31 counters
+        let reg = 40 | (1 << PMU_EN_SHIFT) | (31 << PMU_NUM_CTRS_SHIFT);
+        assert_eq!(extract(reg, S2SZ_SHIFT, S2SZ_WIDTH), 40);
+        assert_eq!(extract(reg, LPA2_SHIFT, LPA2_WIDTH), 0);
+        assert_eq!(extract(reg, PMU_EN_SHIFT, PMU_EN_WIDTH), 1);
+        assert_eq!(extract(reg, PMU_NUM_CTRS_SHIFT, PMU_NUM_CTRS_WIDTH), 31);
+
+        // a field above bit 0 is read from its own bits
+        assert!(!lpa2(33));
+        assert!(lpa2(33 | (1 << LPA2_SHIFT)));
+        assert!(validate(33));
+        assert!(!validate(
+            33 | (1 << PMU_EN_SHIFT) | (31 << PMU_NUM_CTRS_SHIFT)
+        ));
+    }
+}
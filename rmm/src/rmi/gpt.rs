@@ -1,13 +1,13 @@
 use crate::asm::{smc, SMC_SUCCESS};
 use crate::event::Mainloop;
-use crate::granule::{set_granule, GranuleState};
+use crate::get_granule;
+use crate::granule::{is_granule_aligned, set_granule, GranuleState};
 use crate::listen;
 use crate::rmi;
 use crate::rmi::error::Error;
-#[cfg(not(feature = "gst_page_table"))]
-use crate::{get_granule, get_granule_if};
 #[cfg(feature = "gst_page_table")]
-use crate::{get_granule, get_granule_if, set_state_and_get_granule};
+use crate::set_state_and_get_granule;
+use crate::Monitor;
 
 #[cfg(feature = "gst_page_table")]
 use vmsa::error::Error as MmError;
@@ -18,60 +18,108 @@ extern crate alloc;
 pub const MARK_REALM: usize = 0xc400_01b0;
 pub const MARK_NONSECURE: usize = 0xc400_01b1;
 
-pub fn set_event_handler(mainloop: &mut Mainloop) {
-    #[cfg(any(not(kani), feature = "mc_rmi_granule_delegate"))]
-    listen!(mainloop, rmi::GRANULE_DELEGATE, |arg, _, rmm| {
-        let addr = arg[0];
-        #[cfg(feature = "gst_page_table")]
-        let mut granule = match get_granule_if!(addr, GranuleState::Undelegated) {
-            Err(MmError::MmNoEntry) => set_state_and_get_granule!(addr, GranuleState::Undelegated),
-            other => other,
-        }?;
-        #[cfg(not(feature = "gst_page_table"))]
-        let mut granule = get_granule_if!(addr, GranuleState::Undelegated)?;
+/// Checks that GRANULE_DELEGATE or GRANULE_UNDELEGATE can move the granule at `addr`,
+/// in `state`, out of `from`.
+///
+/// The granule must be aligned and in `from`, so one already in the target state
+/// (or still bound into a realm) is rejected.
+fn check_transition<S: PartialEq>(addr: usize, state: S, from: S) -> Result<(), Error> {
+    if !is_granule_aligned(addr) || state != from {
+        return Err(Error::RmiErrorInput);
+    }
+    Ok(())
+}
 
-        if smc(MARK_REALM, &[addr])[0] != SMC_SUCCESS {
-            return Err(Error::RmiErrorInput);
-        }
-        #[cfg(not(kani))]
-        // `page_table` is currently not reachable in model checking harnesses
-        rmm.page_table.map(addr, true);
-        set_granule(&mut granule, GranuleState::Delegated).map_err(|e| {
-            #[cfg(not(kani))]
-            // `page_table` is currently not reachable in model checking harnesses
-            rmm.page_table.unmap(addr);
-            e
-        })?;
+/// Delegates the granule at x0 to the realm world, wiping its contents.
+#[cfg(any(not(kani), feature = "mc_rmi_granule_delegate"))]
+pub fn delegate(arg: &[usize], _ret: &mut [usize], rmm: &Monitor) -> Result<(), Error> {
+    let addr = arg[0];
+    #[cfg(feature = "gst_page_table")]
+    let mut granule = match get_granule!(addr) {
+        Err(MmError::MmNoEntry) => set_state_and_get_granule!(addr, GranuleState::Undelegated),
+        other => other,
+    }?;
+    #[cfg(not(feature = "gst_page_table"))]
+    let mut granule = get_granule!(addr)?;
+    check_transition(addr, granule.state(), GranuleState::Undelegated)?;
+
+    if smc(MARK_REALM, &[addr])[0] != SMC_SUCCESS {
+        return Err(Error::RmiErrorInput);
+    }
+    #[cfg(not(kani))]
+    // `page_table` is currently not reachable in model checking harnesses
+    rmm.page_table.map(addr, true);
+    // the contents are wiped on the transition
+    set_granule(&mut granule, GranuleState::Delegated).map_err(|e| {
         #[cfg(not(kani))]
         // `page_table` is currently not reachable in model checking harnesses
         rmm.page_table.unmap(addr);
-        Ok(())
-    });
+        e
+    })?;
+    #[cfg(not(kani))]
+    // `page_table` is currently not reachable in model checking harnesses
+    rmm.page_table.unmap(addr);
+    Ok(())
+}
 
-    #[cfg(any(not(kani), feature = "mc_rmi_granule_undelegate"))]
-    listen!(mainloop, rmi::GRANULE_UNDELEGATE, |arg, _, rmm| {
-        let addr = arg[0];
-        let mut granule = get_granule_if!(addr, GranuleState::Delegated)?;
+/// Undelegates the granule at x0 back to the non-secure world, wiping its contents.
+#[cfg(any(not(kani), feature = "mc_rmi_granule_undelegate"))]
+pub fn undelegate(arg: &[usize], _ret: &mut [usize], rmm: &Monitor) -> Result<(), Error> {
+    let addr = arg[0];
+    let mut granule = get_granule!(addr)?;
+    check_transition(addr, granule.state(), GranuleState::Delegated)?;
 
-        if smc(MARK_NONSECURE, &[addr])[0] != SMC_SUCCESS {
-            panic!(
-                "A delegated granule should only be undelegated on request from RMM. {:X}",
-                addr
-            );
-        }
+    if smc(MARK_NONSECURE, &[addr])[0] != SMC_SUCCESS {
+        panic!(
+            "A delegated granule should only be undelegated on request from RMM. {:X}",
+            addr
+        );
+    }
 
-        #[cfg(not(kani))]
-        // `page_table` is currently not reachable in model checking harnesses
-        rmm.page_table.map(addr, false);
-        set_granule(&mut granule, GranuleState::Undelegated).map_err(|e| {
-            #[cfg(not(kani))]
-            // `page_table` is currently not reachable in model checking harnesses
-            rmm.page_table.unmap(addr);
-            e
-        })?;
+    #[cfg(not(kani))]
+    // `page_table` is currently not reachable in model checking harnesses
+    rmm.page_table.map(addr, false);
+    // the contents are wiped on the transition
+    set_granule(&mut granule, GranuleState::Undelegated).map_err(|e| {
         #[cfg(not(kani))]
         // `page_table` is currently not reachable in model checking harnesses
         rmm.page_table.unmap(addr);
-        Ok(())
-    });
+        e
+    })?;
+    #[cfg(not(kani))]
+    // `page_table` is currently not reachable in model checking harnesses
+    rmm.page_table.unmap(addr);
+    Ok(())
+}
+
+pub fn set_event_handler(mainloop: &mut Mainloop) {
+    #[cfg(any(not(kani), feature = "mc_rmi_granule_delegate"))]
+    listen!(mainloop, rmi::GRANULE_DELEGATE, delegate);
+
+    #[cfg(any(not(kani), feature = "mc_rmi_granule_undelegate"))]
+    listen!(mainloop, rmi::GRANULE_UNDELEGATE, undelegate);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ADDR: usize = 0x880c_0000;
+
+    #[test]
+    fn delegate_twice() {
+        let (from, to) = (GranuleState::Undelegated, GranuleState::Delegated);
+        assert!(check_transition(ADDR, from, from).is_ok());
+        // already delegated
+        assert!(check_transition(ADDR, to, from).is_err());
+        assert!(check_transition(ADDR + 0x800, from, from).is_err());
+    }
+
+    #[test]
+    fn undelegate_data() {
+        let from = GranuleState::Delegated;
+        assert!(check_transition(ADDR, from, from).is_ok());
+        // still used by a realm
+        assert!(check_transition(ADDR, GranuleState::Data, from).is_err());
+    }
 }
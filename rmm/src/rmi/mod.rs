@@ -10,29 +10,55 @@ pub mod version;
 use crate::define_interface;
 use crate::rmi::realm::Rd;
 
+/// The base of the diagnostics commands, which are our own, not part of the specification.
+///
+/// They are only available with the `diagnostics` feature. They are in the range of
+/// the vendor-specific hypervisor service calls of SMCCC (OEN 6) after the RSI commands
+/// of our own (see `rsi::VENDOR_BASE`), apart from 0xc400_0150 ~ 0xc400_018f,
+/// which the specification reserves for RMI.
+#[cfg(feature = "diagnostics")]
+pub const DIAG_BASE: usize = 0xc600_0100;
+
 define_interface! {
     command {
-         VERSION                = 0xc400_0150,
-         GRANULE_DELEGATE       = 0xc400_0151,
-         GRANULE_UNDELEGATE     = 0xc400_0152,
-         DATA_CREATE            = 0xc400_0153,
-         DATA_CREATE_UNKNOWN    = 0xc400_0154,
-         DATA_DESTROY           = 0xc400_0155,
-         REALM_ACTIVATE         = 0xc400_0157,
-         REALM_CREATE           = 0xc400_0158,
-         REALM_DESTROY          = 0xc400_0159,
-         REC_CREATE             = 0xc400_015a,
-         REC_DESTROY            = 0xc400_015b,
-         REC_ENTER              = 0xc400_015c,
-         RTT_CREATE             = 0xc400_015d,
-         RTT_DESTROY            = 0xc400_015e,
-         RTT_MAP_UNPROTECTED    = 0xc400_015f,
-         RTT_UNMAP_UNPROTECTED  = 0xc400_0162,
-         RTT_READ_ENTRY         = 0xc400_0161,
-         FEATURES               = 0xc400_0165,
-         REC_AUX_COUNT          = 0xc400_0167,
-         RTT_INIT_RIPAS         = 0xc400_0168,
-         RTT_SET_RIPAS          = 0xc400_0169,
+         VERSION                  = 0xc400_0150,
+         GRANULE_DELEGATE         = 0xc400_0151,
+         GRANULE_UNDELEGATE       = 0xc400_0152,
+         DATA_CREATE              = 0xc400_0153,
+         DATA_CREATE_UNKNOWN      = 0xc400_0154,
+         DATA_DESTROY             = 0xc400_0155,
+         REALM_ACTIVATE           = 0xc400_0157,
+         REALM_CREATE             = 0xc400_0158,
+         REALM_DESTROY            = 0xc400_0159,
+         REC_CREATE               = 0xc400_015a,
+         REC_DESTROY              = 0xc400_015b,
+         REC_ENTER                = 0xc400_015c,
+         RTT_CREATE               = 0xc400_015d,
+         RTT_DESTROY              = 0xc400_015e,
+         RTT_MAP_UNPROTECTED      = 0xc400_015f,
+         RTT_UNMAP_UNPROTECTED    = 0xc400_0162,
+         RTT_READ_ENTRY           = 0xc400_0161,
+         FEATURES                 = 0xc400_0165,
+         RTT_FOLD                 = 0xc400_0166,
+         REC_AUX_COUNT            = 0xc400_0167,
+         RTT_INIT_RIPAS           = 0xc400_0168,
+         RTT_SET_RIPAS            = 0xc400_0169,
+         #[cfg(feature = "diagnostics")]
+         REC_PENDING_GET          = DIAG_BASE,
+         #[cfg(feature = "diagnostics")]
+         REALM_ATTEST_METRICS_GET = DIAG_BASE + 0x1,
+         #[cfg(feature = "diagnostics")]
+         REALM_COUNT_GET          = DIAG_BASE + 0x2,
+         #[cfg(feature = "diagnostics")]
+         RSI_PROFILE_GET          = DIAG_BASE + 0x3,
+         #[cfg(feature = "diagnostics")]
+         RTT_TRANSLATE_GET        = DIAG_BASE + 0x4,
+         #[cfg(feature = "diagnostics")]
+         REALM_DEBUG_GET          = DIAG_BASE + 0x5,
+         #[cfg(feature = "diagnostics")]
+         RTT_UNACCOUNTED_GET      = DIAG_BASE + 0x6,
+         #[cfg(feature = "diagnostics")]
+         RMM_LOG_GET              = DIAG_BASE + 0x7,
     }
 }
 
@@ -46,6 +72,9 @@ pub const BOOT_SUCCESS: usize = 0x0;
 
 pub const NOT_SUPPORTED_YET: usize = 0xFFFF_EEEE;
 
+/// Returned for a command without its handler (i.e., `SMCCC_NOT_SUPPORTED`).
+pub const NOT_SUPPORTED: usize = !0;
+
 pub const ABI_MAJOR_VERSION: usize = 1;
 pub const ABI_MINOR_VERSION: usize = 0;
 
@@ -0,0 +1,52 @@
+use crate::granule::GRANULE_SIZE;
+
+/// Counts the granules of a realm whose RIPAS is RAM,
+/// which the realm token may carry as the committed-RAM claim.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RamCommitment {
+    granules: usize,
+}
+
+impl RamCommitment {
+    pub const fn new() -> Self {
+        Self { granules: 0 }
+    }
+
+    /// Accounts a RIPAS change of `size` bytes, from RAM or not (`was_ram`) to RAM or not (`is_ram`).
+    pub fn account(&mut self, size: usize, was_ram: bool, is_ram: bool) {
+        let granules = size / GRANULE_SIZE;
+        match (was_ram, is_ram) {
+            (false, true) => self.granules = self.granules.saturating_add(granules),
+            (true, false) => self.granules = self.granules.saturating_sub(granules),
+            _ => {}
+        }
+    }
+
+    pub fn granules(&self) -> usize {
+        self.granules
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rmi::rtt::level_to_size;
+
+    #[test]
+    fn ram_assignment() {
+        let mut committed = RamCommitment::new();
+
+        // RTT_INIT_RIPAS of a 2MB block
+        committed.account(level_to_size(2) as usize, false, true);
+        assert_eq!(committed.granules(), 512);
+
+        // the realm turns a page into RAM, then one already RAM again
+        committed.account(GRANULE_SIZE, false, true);
+        committed.account(GRANULE_SIZE, true, true);
+        assert_eq!(committed.granules(), 513);
+
+        // the realm shares a page with the host
+        committed.account(GRANULE_SIZE, true, false);
+        assert_eq!(committed.granules(), 512);
+    }
+}
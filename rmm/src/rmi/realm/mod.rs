@@ -1,21 +1,27 @@
+pub(crate) mod commitment;
 pub(crate) mod params;
+pub(crate) mod quiesce;
 pub(crate) mod rd;
+pub(crate) mod recs;
 
 pub use self::rd::Rd;
 
 use self::params::Params;
 use self::rd::State;
 use super::error::Error;
+use crate::config::MIN_REALM_IPA_BITS;
 use crate::event::Mainloop;
 use crate::granule::GRANULE_SIZE;
-use crate::granule::{set_granule, GranuleState};
+use crate::granule::{assign_granule, release_granule, GranuleState};
 use crate::host;
 use crate::listen;
 use crate::measurement::HashContext;
 use crate::mm::translation::PageTable;
 use crate::realm::mm::stage2_translation::Stage2Translation;
 use crate::realm::mm::IPATranslation;
-use crate::realm::registry::VMID_SET;
+#[cfg(feature = "diagnostics")]
+use crate::realm::registry::realm_count;
+use crate::realm::registry::{insert_vmid, VMID_SET};
 use crate::realm::vcpu::remove;
 use crate::rmi;
 use crate::{get_granule, get_granule_if};
@@ -56,7 +62,7 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
         rmm.page_table.map(rd, true);
 
         let params = host::copy_from::<Params>(params_ptr).ok_or(Error::RmiErrorInput)?;
-        params.verify_compliance(rd)?;
+        params.verify_compliance(rd, MIN_REALM_IPA_BITS)?;
 
         let rtt_granule = get_granule_if!(params.rtt_base as usize, GranuleState::Delegated)?;
         // This is required to prevent from the deadlock in the below epilog
@@ -85,7 +91,10 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
             PageTable::get_ref().map(rtt, true);
         }
 
-        rd_obj.set_hash_algo(params.hash_algo);
+        rd_obj.set_hash_algo(params.measurement_algo()?);
+        rd_obj.set_measured_data_only(params.measured_data_only());
+        rd_obj.set_debug(params.debug());
+        rd_obj.set_num_recs(params.num_recs());
 
         #[cfg(not(kani))]
         // `rsi` is currently not reachable in model checking harnesses
@@ -93,8 +102,8 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
 
         let mut eplilog = move || {
             let mut rtt_granule = get_granule_if!(rtt_base, GranuleState::Delegated)?;
-            set_granule(&mut rtt_granule, GranuleState::RTT)?;
-            set_granule(&mut rd_granule, GranuleState::RD)
+            assign_granule(&mut rtt_granule, GranuleState::RTT, params.vmid as usize)?;
+            assign_granule(&mut rd_granule, GranuleState::RD, params.vmid as usize)
         };
 
         eplilog().map_err(|e| {
@@ -113,15 +122,38 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
 
     listen!(mainloop, rmi::REALM_DESTROY, |arg, _ret, rmm| {
         // get the lock for Rd
-        let mut rd_granule = get_granule_if!(arg[0], GranuleState::RD)?;
-        let rd = rd_granule.content::<Rd>();
-        remove(rd.id())?;
-
-        let mut rtt_granule = get_granule_if!(rd.rtt_base(), GranuleState::RTT)?;
-        set_granule(&mut rtt_granule, GranuleState::Delegated)?;
-
-        // change state when everything goes fine.
-        set_granule(&mut rd_granule, GranuleState::Delegated)?;
+        let rd_granule = get_granule_if!(arg[0], GranuleState::RD)?;
+        // refuse new REC entries from now on
+        rd_granule.content::<Rd>().quiesce().begin()?;
+        core::mem::drop(rd_granule);
+
+        // wait for the in-flight REC executions to exit.
+        // Rd's lock is released while waiting, as the exiting RECs acquire it.
+        let mut rd_granule = loop {
+            let rd_granule = get_granule_if!(arg[0], GranuleState::RD)?;
+            if rd_granule.content::<Rd>().quiesce().is_quiescent() {
+                break rd_granule;
+            }
+            core::mem::drop(rd_granule);
+            core::hint::spin_loop();
+        };
+        let mut teardown = || -> Result<(), Error> {
+            let rd = rd_granule.content::<Rd>();
+            check_recs_destroyed(rd)?;
+            let mut rtt_granule = get_granule_if!(rd.rtt_base(), GranuleState::RTT)?;
+            remove(rd.id())?;
+            release_granule(&mut rtt_granule)?;
+            // the event log is on the heap, which zeroizing the granule doesn't free
+            rd_granule.content_mut::<Rd>().event_log.release();
+
+            // change state when everything goes fine.
+            release_granule(&mut rd_granule)
+        };
+        if let Err(e) = teardown() {
+            // the realm is still there, let its RECs enter again
+            rd_granule.content::<Rd>().quiesce().abort();
+            return Err(e);
+        }
         #[cfg(not(kani))]
         // `page_table` is currently not reachable in model checking harnesses
         rmm.page_table.unmap(arg[0]);
@@ -130,13 +162,62 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
     });
 }
 
+/// Registers the diagnostics which expose the state of realms to the host,
+/// so they are left out of production builds.
+#[cfg(feature = "diagnostics")]
+pub fn set_diagnostics_handler(mainloop: &mut Mainloop) {
+    listen!(mainloop, rmi::REALM_COUNT_GET, |_, ret, _| {
+        ret[1..3].copy_from_slice(&realm_count(&VMID_SET.lock()));
+        Ok(())
+    });
+
+    listen!(mainloop, rmi::REALM_DEBUG_GET, |arg, ret, _| {
+        let rd_granule = get_granule_if!(arg[0], GranuleState::RD)?;
+        ret[1] = rd_granule.content::<Rd>().debug() as usize;
+        Ok(())
+    });
+
+    listen!(mainloop, rmi::REALM_ATTEST_METRICS_GET, |arg, ret, _| {
+        let rd_granule = get_granule_if!(arg[0], GranuleState::RD)?;
+        let rd = rd_granule.content::<Rd>();
+        ret[1..6].copy_from_slice(&rd.attest_metrics.to_regs());
+        Ok(())
+    });
+}
+
 fn create_realm(vmid: usize) -> Result<(), Error> {
-    let mut vmid_set = VMID_SET.lock();
-    if vmid_set.contains(&vmid) {
-        return Err(Error::RmiErrorInput);
-    } else {
-        vmid_set.insert(vmid);
-    };
+    insert_vmid(&mut VMID_SET.lock(), vmid)
+}
 
+/// Fails with `RmiErrorRealm` while any REC of the realm has not been destroyed,
+/// as its granule still refers to the Rd.
+fn check_recs_destroyed(rd: &Rd) -> Result<(), Error> {
+    if rd.recs().next().is_some() {
+        return Err(Error::RmiErrorRealm(0));
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::OneGranuleTable;
+
+    #[test]
+    fn destroy_with_live_rec() {
+        const REC: usize = 0x8800_1000;
+        let mut rd = rd::test::rd(1, 33, OneGranuleTable::new());
+        assert!(check_recs_destroyed(&rd).is_ok());
+
+        // REC_CREATE
+        rd.add_rec(REC, 0);
+        assert!(matches!(
+            check_recs_destroyed(&rd),
+            Err(Error::RmiErrorRealm(0))
+        ));
+
+        // REC_DESTROY
+        assert!(rd.remove_rec(REC));
+        assert!(check_recs_destroyed(&rd).is_ok());
+    }
+}
@@ -1,13 +1,29 @@
 use crate::const_assert_eq;
 use crate::granule::{GRANULE_SHIFT, GRANULE_SIZE};
-use crate::measurement::Hashable;
+use crate::measurement::{HashAlgo, Hashable};
 use crate::rmi::error::Error;
 use crate::rmi::features;
 use crate::rmi::rtt::{RTT_PAGE_LEVEL, S2TTE_STRIDE};
-use crate::rmi::{HASH_ALGO_SHA256, HASH_ALGO_SHA512};
 
 use autopadding::*;
 
+// Vendor extensions (not in the RMM spec)
+//
+// The RMM spec reserves RealmParams from 0x820 to the end of the granule.
+// Islet takes the start of that space for the fields below:
+//   - flags at 0x820: the REALM_FLAG_* bits
+//   - num_recs at 0x828: the number of RECs, zero for no limit
+// A spec-conformant host leaves them zero, which keeps the spec behaviour.
+// Any other bit in that space is rejected by verify_compliance(),
+// so garbage left by the host fails REALM_CREATE instead of
+// silently changing the realm.
+
+// Rejects RMI_DATA_CREATE_UNKNOWN so that the RIM covers all initial memory
+pub const REALM_FLAG_MEASURED_DATA_ONLY: u64 = 1 << 0;
+// Allows debug access for development, which the realm token tells verifiers
+pub const REALM_FLAG_DEBUG: u64 = 1 << 1;
+const REALM_FLAGS_MASK: u64 = REALM_FLAG_MEASURED_DATA_ONLY | REALM_FLAG_DEBUG;
+
 pad_struct_and_impl_default!(
 pub struct Params {
     0x0    pub features_0: u64,
@@ -17,6 +33,8 @@ pub struct Params {
     0x808  pub rtt_base: u64,
     0x810  pub rtt_level_start: i64,
     0x818  pub rtt_num_start: u32,
+    0x820  pub flags: u64,
+    0x828  pub num_recs: u32,
     0x1000 => @END,
 }
 );
@@ -33,6 +51,8 @@ impl core::fmt::Debug for Params {
             .field("rtt_base", &format_args!("{:#X}", &self.rtt_base))
             .field("rtt_level_start", &self.rtt_level_start)
             .field("rtt_num_start", &self.rtt_num_start)
+            .field("flags", &format_args!("{:#X}", &self.flags))
+            .field("num_recs", &self.num_recs)
             .finish()
     }
 }
@@ -56,6 +76,10 @@ impl Hashable for Params {
             alg.hash_u64(0); // rtt_level_start is not used
             alg.hash_u32(0); // rtt_num_start is not used
             alg.hash(self._padrtt_num_start);
+            alg.hash_u64(self.flags);
+            alg.hash(self._padflags);
+            alg.hash_u32(self.num_recs);
+            alg.hash(self._padnum_recs);
         })
     }
 }
@@ -65,7 +89,29 @@ impl Params {
         features::ipa_bits(self.features_0 as usize)
     }
 
-    pub fn verify_compliance(&self, rd: usize) -> Result<(), Error> {
+    /// Returns the hash algorithm of the realm's measurements,
+    /// rejecting an unknown one.
+    pub fn measurement_algo(&self) -> Result<HashAlgo, Error> {
+        HashAlgo::parse(self.hash_algo).map_err(|_| Error::RmiErrorInput)
+    }
+
+    pub fn measured_data_only(&self) -> bool {
+        self.flags & REALM_FLAG_MEASURED_DATA_ONLY != 0
+    }
+
+    pub fn debug(&self) -> bool {
+        self.flags & REALM_FLAG_DEBUG != 0
+    }
+
+    /// Returns the number of RECs (VCPUs) of the realm.
+    /// Zero means that the host doesn't limit the number of RECs.
+    pub fn num_recs(&self) -> usize {
+        self.num_recs as usize
+    }
+
+    /// Checks the parameters given by the host,
+    /// rejecting a realm whose IPA width is below `min_ipa_bits` as well.
+    pub fn verify_compliance(&self, rd: usize, min_ipa_bits: usize) -> Result<(), Error> {
         if self.rtt_base as usize == rd {
             return Err(Error::RmiErrorInput);
         }
@@ -78,8 +124,22 @@ impl Params {
             return Err(Error::RmiErrorInput);
         }
 
-        // Check misconfigurations between IPA size and SL
+        if self.flags & !REALM_FLAGS_MASK != 0 {
+            return Err(Error::RmiErrorInput);
+        }
+
+        // The rest of the vendor extension space is unknown
+        if self._padnum_recs.iter().any(|b| *b != 0) {
+            return Err(Error::RmiErrorInput);
+        }
+
+        // Check the IPA size against the policy of the platform
         let ipa_bits = self.ipa_bits();
+        if ipa_bits < min_ipa_bits {
+            return Err(Error::RmiErrorInput);
+        }
+
+        // Check misconfigurations between IPA size and SL
         let rtt_slvl = self.rtt_level_start as usize;
 
         let level = RTT_PAGE_LEVEL - rtt_slvl;
@@ -90,10 +150,7 @@ impl Params {
             return Err(Error::RmiErrorInput);
         }
 
-        match self.hash_algo {
-            HASH_ALGO_SHA256 | HASH_ALGO_SHA512 => Ok(()),
-            _ => Err(Error::RmiErrorInput),
-        }
+        self.measurement_algo().map(|_| ())
     }
 }
 
@@ -126,6 +183,7 @@ impl safe_abstraction::raw_ptr::SafetyAssured for Params {
 pub mod test {
     use super::*;
     use crate::offset_of;
+    use crate::rmi::{HASH_ALGO_SHA256, HASH_ALGO_SHA512};
 
     #[test]
     fn spec_params() {
@@ -138,5 +196,115 @@ pub mod test {
         assert_eq!(offset_of!(Params, rtt_base), 0x808);
         assert_eq!(offset_of!(Params, rtt_level_start), 0x810);
         assert_eq!(offset_of!(Params, rtt_num_start), 0x818);
+        assert_eq!(offset_of!(Params, flags), 0x820);
+        assert_eq!(offset_of!(Params, num_recs), 0x828);
+    }
+
+    fn compliant_params() -> Params {
+        Params {
+            features_0: 48, // S2SZ
+            hash_algo: HASH_ALGO_SHA256,
+            rtt_base: 0x8800_0000,
+            rtt_level_start: 0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn measured_data_only_flag() {
+        let rd = 0x8800_1000;
+
+        let permissive = compliant_params();
+        assert!(permissive.verify_compliance(rd, 32).is_ok());
+        assert!(!permissive.measured_data_only());
+
+        let mut strict = compliant_params();
+        strict.flags = REALM_FLAG_MEASURED_DATA_ONLY;
+        assert!(strict.verify_compliance(rd, 32).is_ok());
+        assert!(strict.measured_data_only());
+
+        let mut unknown = compliant_params();
+        unknown.flags = 1 << 2;
+        assert!(unknown.verify_compliance(rd, 32).is_err());
+    }
+
+    #[test]
+    fn debug_flag() {
+        let rd = 0x8800_1000;
+
+        assert!(!compliant_params().debug());
+
+        let mut debug = compliant_params();
+        debug.flags = REALM_FLAG_DEBUG;
+        assert!(debug.verify_compliance(rd, 32).is_ok());
+        assert!(debug.debug());
+        assert!(!debug.measured_data_only());
+    }
+
+    #[test]
+    fn lpa2_not_supported() {
+        let rd = 0x8800_1000;
+
+        let params = compliant_params();
+        assert!(params.verify_compliance(rd, 32).is_ok());
+        assert!(!features::lpa2(params.features_0 as usize));
+
+        let mut lpa2 = compliant_params();
+        lpa2.features_0 |= 1 << 8;
+        assert!(features::lpa2(lpa2.features_0 as usize));
+        assert!(lpa2.verify_compliance(rd, 32).is_err());
+    }
+
+    #[test]
+    fn num_recs() {
+        let rd = 0x8800_1000;
+
+        let mut params = compliant_params();
+        params.num_recs = 4;
+        assert!(params.verify_compliance(rd, 32).is_ok());
+        assert_eq!(params.num_recs(), 4);
+    }
+
+    #[test]
+    fn reserved_after_vendor_extensions() {
+        let rd = 0x8800_1000;
+
+        let mut params = compliant_params();
+        params._padnum_recs[0] = 1;
+        assert!(params.verify_compliance(rd, 32).is_err());
+
+        let mut params = compliant_params();
+        let last = params._padnum_recs.len() - 1;
+        params._padnum_recs[last] = 0xff;
+        assert!(params.verify_compliance(rd, 32).is_err());
+    }
+
+    #[test]
+    fn hash_algo() {
+        let rd = 0x8800_1000;
+
+        let mut params = compliant_params();
+        assert_eq!(params.measurement_algo().unwrap(), HashAlgo::Sha256);
+
+        params.hash_algo = HASH_ALGO_SHA512;
+        assert!(params.verify_compliance(rd, 32).is_ok());
+        assert_eq!(params.measurement_algo().unwrap(), HashAlgo::Sha512);
+
+        params.hash_algo = 2;
+        assert!(params.verify_compliance(rd, 32).is_err());
+    }
+
+    #[test]
+    fn min_ipa_bits() {
+        let rd = 0x8800_1000;
+
+        let mut params = compliant_params();
+        params.features_0 = 40; // S2SZ
+        params.rtt_level_start = 1;
+        assert!(params.verify_compliance(rd, 40).is_ok());
+
+        params.features_0 = 39; // S2SZ
+        assert!(params.verify_compliance(rd, 40).is_err());
+        assert!(params.verify_compliance(rd, 32).is_ok());
     }
 }
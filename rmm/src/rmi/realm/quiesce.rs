@@ -0,0 +1,135 @@
+use crate::granule::GranuleState;
+use crate::rmi::error::Error;
+use crate::rmi::realm::Rd;
+use crate::{get_granule, get_granule_if};
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Tracks the in-flight REC executions of a realm,
+/// so that the realm is torn down only after all of them have exited.
+///
+/// Once `begin()` marks the realm as destroying, REC entries are refused
+/// and `is_quiescent()` tells whether the RECs already running have exited.
+#[derive(Debug, Default)]
+pub struct Quiesce {
+    destroying: AtomicBool,
+    running: AtomicUsize,
+}
+
+impl Quiesce {
+    pub const fn new() -> Self {
+        Self {
+            destroying: AtomicBool::new(false),
+            running: AtomicUsize::new(0),
+        }
+    }
+
+    /// Registers a REC entry, which fails once the realm is being destroyed.
+    pub fn enter(&self) -> Result<(), Error> {
+        // Count first, then check, to pair with `begin()` setting the flag
+        // before checking the count. Either of them sees the other.
+        self.running.fetch_add(1, Ordering::SeqCst);
+        if self.destroying.load(Ordering::SeqCst) {
+            self.exit();
+            return Err(Error::RmiErrorRealm(0));
+        }
+        Ok(())
+    }
+
+    pub fn exit(&self) {
+        self.running.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Marks the realm as destroying. It fails if it is already marked.
+    pub fn begin(&self) -> Result<(), Error> {
+        if self.destroying.swap(true, Ordering::SeqCst) {
+            return Err(Error::RmiErrorRealm(0));
+        }
+        Ok(())
+    }
+
+    /// Unmarks the realm when its destruction fails, so that its RECs can enter again.
+    pub fn abort(&self) {
+        self.destroying.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_destroying(&self) -> bool {
+        self.destroying.load(Ordering::SeqCst)
+    }
+
+    pub fn is_quiescent(&self) -> bool {
+        self.running.load(Ordering::SeqCst) == 0
+    }
+}
+
+/// An in-flight REC execution of the realm `rd`, registered by `Quiesce::enter()`.
+/// It is unregistered on drop, whichever way REC_ENTER returns.
+pub struct InFlight {
+    rd: usize,
+}
+
+impl InFlight {
+    pub fn enter(rd_addr: usize, rd: &Rd) -> Result<Self, Error> {
+        rd.quiesce().enter()?;
+        Ok(Self { rd: rd_addr })
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        // The realm isn't torn down until every REC has exited,
+        // so the Rd is still there.
+        if let Ok(rd_granule) = get_granule_if!(self.rd, GranuleState::RD) {
+            rd_granule.content::<Rd>().quiesce().exit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn enter_during_quiesce() {
+        let quiesce = Quiesce::new();
+        quiesce.enter().unwrap();
+
+        quiesce.begin().unwrap();
+        assert!(quiesce.is_destroying());
+        assert!(quiesce.enter().is_err());
+        // destroying twice
+        assert!(quiesce.begin().is_err());
+
+        // the rejected entry isn't counted as in-flight
+        quiesce.exit();
+        assert!(quiesce.is_quiescent());
+    }
+
+    #[test]
+    fn quiescent_after_exit() {
+        let quiesce = Quiesce::new();
+        quiesce.enter().unwrap();
+        quiesce.enter().unwrap();
+
+        quiesce.begin().unwrap();
+        assert!(!quiesce.is_quiescent());
+        quiesce.exit();
+        assert!(!quiesce.is_quiescent());
+        quiesce.exit();
+        assert!(quiesce.is_quiescent());
+    }
+
+    #[test]
+    fn enter_after_abort() {
+        let quiesce = Quiesce::new();
+        quiesce.begin().unwrap();
+        assert!(quiesce.enter().is_err());
+
+        quiesce.abort();
+        assert!(!quiesce.is_destroying());
+        quiesce.enter().unwrap();
+        assert!(!quiesce.is_quiescent());
+        // destroying again
+        quiesce.begin().unwrap();
+    }
+}
@@ -1,10 +1,14 @@
-use crate::rmi::rtt::realm_par_size;
+use crate::mm::protected_ipa_boundary;
+use crate::rmi::realm::commitment::RamCommitment;
+use crate::rmi::realm::quiesce::Quiesce;
+use crate::rmi::realm::recs::{LiveRec, Recs};
 
 use vmsa::guard::Content;
 
-use crate::measurement::{Measurement, MEASUREMENTS_SLOT_NR};
+use crate::measurement::{EventLog, HashAlgo, Measurement, MEASUREMENTS_SLOT_NR};
 use crate::realm::mm::IPATranslation;
 use crate::realm::vcpu::VCPU;
+use crate::rsi::attestation::metrics::AttestMetrics;
 use alloc::boxed::Box;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -17,10 +21,18 @@ pub struct Rd {
     rtt_base: usize,
     ipa_bits: usize,
     rec_index: usize,
+    num_recs: usize,
     s2_starting_level: isize,
     s2_table: Arc<Mutex<Box<dyn IPATranslation>>>,
-    hash_algo: u8,
+    hash_algo: HashAlgo,
+    measured_data_only: bool,
+    debug: bool,
     pub measurements: [Measurement; MEASUREMENTS_SLOT_NR],
+    pub event_log: EventLog,
+    pub attest_metrics: AttestMetrics,
+    pub committed_ram: RamCommitment,
+    quiesce: Quiesce,
+    recs: Recs,
     pub vcpus: Vec<Arc<Mutex<VCPU>>>,
 }
 
@@ -38,10 +50,17 @@ impl Rd {
         self.rtt_base = rtt_base;
         self.ipa_bits = ipa_bits;
         self.rec_index = 0;
+        self.num_recs = 0;
+        self.debug = false;
         self.s2_starting_level = s2_starting_level;
         // XXX: without `clone()`, the below assignment would cause a data abort exception
         self.s2_table = s2_table.clone();
         self.measurements = [Measurement::empty(); MEASUREMENTS_SLOT_NR];
+        self.event_log = EventLog::new();
+        self.attest_metrics = AttestMetrics::new();
+        self.committed_ram = RamCommitment::new();
+        self.quiesce = Quiesce::new();
+        self.recs = Recs::new();
         self.vcpus = Vec::new();
     }
 
@@ -65,6 +84,10 @@ impl Rd {
         self.state == compared
     }
 
+    pub fn quiesce(&self) -> &Quiesce {
+        &self.quiesce
+    }
+
     pub fn rtt_base(&self) -> usize {
         self.rtt_base
     }
@@ -77,6 +100,20 @@ impl Rd {
         self.rec_index
     }
 
+    /// Iterates the live RECs of the realm.
+    /// The caller holds the Rd's lock throughout, so the RECs don't change meanwhile.
+    pub fn recs(&self) -> impl Iterator<Item = LiveRec> + '_ {
+        self.recs.iter()
+    }
+
+    pub fn add_rec(&mut self, addr: usize, vcpuid: usize) {
+        self.recs.insert(addr, vcpuid);
+    }
+
+    pub fn remove_rec(&mut self, addr: usize) -> bool {
+        self.recs.remove(addr)
+    }
+
     pub fn s2_starting_level(&self) -> isize {
         self.s2_starting_level
     }
@@ -85,18 +122,59 @@ impl Rd {
         self.rec_index += 1;
     }
 
+    pub fn set_num_recs(&mut self, num_recs: usize) {
+        self.num_recs = num_recs;
+    }
+
+    /// Checks whether another REC can be created under the REC count
+    /// given at realm creation. Zero means there is no limit.
+    pub fn can_create_rec(&self) -> bool {
+        self.num_recs == 0 || self.rec_index < self.num_recs
+    }
+
+    /// Returns the number of VCPUs (RECs) of the realm.
+    ///
+    /// It is the REC count given at realm creation if any.
+    /// Otherwise, it is the number of RECs created
+    /// as all RECs are created before the realm gets activated.
+    pub fn vcpu_count(&self) -> usize {
+        match self.num_recs {
+            0 => self.rec_index,
+            num_recs => num_recs,
+        }
+    }
+
     pub fn addr_in_par(&self, addr: usize) -> bool {
         let ipa_bits = self.ipa_bits();
-        addr < realm_par_size(ipa_bits)
+        addr < protected_ipa_boundary(ipa_bits)
     }
 
-    pub fn hash_algo(&self) -> u8 {
+    /// Returns the hash algorithm of the measurements and the attestation token.
+    pub fn hash_algo(&self) -> HashAlgo {
         self.hash_algo
     }
 
-    pub fn set_hash_algo(&mut self, alg: u8) {
+    pub fn set_hash_algo(&mut self, alg: HashAlgo) {
         self.hash_algo = alg;
     }
+
+    pub fn measured_data_only(&self) -> bool {
+        self.measured_data_only
+    }
+
+    pub fn set_measured_data_only(&mut self, val: bool) {
+        self.measured_data_only = val;
+    }
+
+    /// Returns whether the realm was created with debug access,
+    /// which its attestation token carries as the realm debug claim.
+    pub fn debug(&self) -> bool {
+        self.debug
+    }
+
+    pub fn set_debug(&mut self, val: bool) {
+        self.debug = val;
+    }
 }
 
 impl Content for Rd {}
@@ -108,3 +186,33 @@ pub enum State {
     Active,
     SystemOff,
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    /// Returns a realm of `ipa_bits` with one VCPU over `s2_table`,
+    /// which isn't backed by an RD granule.
+    pub fn rd(vmid: u16, ipa_bits: usize, s2_table: Box<dyn IPATranslation>) -> Box<Rd> {
+        Box::new(Rd {
+            vmid,
+            state: State::New,
+            rtt_base: s2_table.get_base_address() as usize,
+            ipa_bits,
+            rec_index: 1,
+            num_recs: 0,
+            s2_starting_level: 0,
+            s2_table: Arc::new(Mutex::new(s2_table)),
+            hash_algo: HashAlgo::Sha256,
+            measured_data_only: false,
+            debug: false,
+            measurements: [Measurement::empty(); MEASUREMENTS_SLOT_NR],
+            event_log: EventLog::new(),
+            attest_metrics: AttestMetrics::new(),
+            committed_ram: RamCommitment::new(),
+            quiesce: Quiesce::new(),
+            recs: Recs::new(),
+            vcpus: alloc::vec![VCPU::new()],
+        })
+    }
+}
@@ -0,0 +1,78 @@
+use alloc::collections::BTreeMap;
+
+/// A live REC of a realm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LiveRec {
+    /// The address of the REC granule
+    pub addr: usize,
+    pub vcpuid: usize,
+}
+
+/// Tracks the live RECs of a realm, from REC_CREATE to REC_DESTROY.
+///
+/// It is kept in the Rd, so iterating it requires holding the Rd's lock,
+/// which gives a consistent snapshot against REC creation and destruction.
+/// The REC granules themselves aren't locked by the iteration. As REC_ENTER
+/// acquires the Rd's lock while holding a REC's lock, the Rd's lock must be
+/// released before acquiring the lock of any REC visited.
+#[derive(Debug, Default)]
+pub struct Recs {
+    recs: BTreeMap<usize, usize>,
+}
+
+impl Recs {
+    pub const fn new() -> Self {
+        Self {
+            recs: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, addr: usize, vcpuid: usize) {
+        self.recs.insert(addr, vcpuid);
+    }
+
+    /// Returns whether the REC was live.
+    pub fn remove(&mut self, addr: usize) -> bool {
+        self.recs.remove(&addr).is_some()
+    }
+
+    /// Iterates the live RECs in the order of their addresses.
+    pub fn iter(&self) -> impl Iterator<Item = LiveRec> + '_ {
+        self.recs
+            .iter()
+            .map(|(&addr, &vcpuid)| LiveRec { addr, vcpuid })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn skip_destroyed() {
+        let mut recs = Recs::new();
+        recs.insert(0x8800_3000, 0);
+        recs.insert(0x8800_1000, 1);
+        recs.insert(0x8800_2000, 2);
+
+        assert!(recs.remove(0x8800_1000));
+        // destroyed twice
+        assert!(!recs.remove(0x8800_1000));
+
+        let live: Vec<LiveRec> = recs.iter().collect();
+        assert_eq!(
+            live,
+            [
+                LiveRec {
+                    addr: 0x8800_2000,
+                    vcpuid: 2
+                },
+                LiveRec {
+                    addr: 0x8800_3000,
+                    vcpuid: 0
+                },
+            ]
+        );
+    }
+}
@@ -21,6 +21,7 @@ pub fn handle_realm_exit(
     rmm: &Monitor,
     rec: &mut Rec<'_>,
     run: &mut Run,
+    realmid: usize,
 ) -> Result<(bool, usize), Error> {
     let mut return_to_ns = true;
     let ret = match RecExitReason::from(realm_exit_res[0]) {
@@ -37,7 +38,7 @@ pub fn handle_realm_exit(
                 rsi_ctx.resize_ret(ret_num);
 
                 // set default value
-                if rsi.dispatch(&mut rsi_ctx, rmm, rec, run) == RsiHandle::RET_SUCCESS {
+                if rsi.dispatch(&mut rsi_ctx, rmm, rec, run, realmid) == RsiHandle::RET_SUCCESS {
                     if rsi_ctx.ret_slice()[0] == rmi::SUCCESS_REC_ENTER {
                         return_to_ns = false;
                     }
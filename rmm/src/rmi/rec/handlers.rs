@@ -1,28 +1,57 @@
-use super::mpidr::MPIDR;
-use super::params::Params;
-use super::run::{Run, REC_ENTRY_FLAG_TRAP_WFE, REC_ENTRY_FLAG_TRAP_WFI};
+use super::params::{Params, RecParams};
+use super::run::{DiagCheck, Run, RunHint, REC_ENTRY_FLAG_TRAP_WFE, REC_ENTRY_FLAG_TRAP_WFI};
 use super::vtcr::{activate_stage2_mmu, prepare_vtcr};
 use super::Rec;
 use crate::event::Mainloop;
-#[cfg(feature = "gst_page_table")]
-use crate::granule::{set_granule, set_granule_with_parent, GranuleState};
 #[cfg(not(feature = "gst_page_table"))]
-use crate::granule::{set_granule, GranuleState};
+use crate::granule::assign_granule;
+#[cfg(feature = "gst_page_table")]
+use crate::granule::set_granule_with_parent;
+use crate::granule::{release_granule, GranuleState};
 use crate::host;
 use crate::listen;
 use crate::measurement::HashContext;
-use crate::realm::context::set_reg;
+use crate::realm::context::{set_reg, set_result_reg};
+use crate::realm::registry::{realm_exists, VMID_SET};
 use crate::realm::vcpu::create_vcpu;
 use crate::rmi;
 use crate::rmi::error::Error;
+use crate::rmi::realm::quiesce::InFlight;
 use crate::rmi::realm::{rd::State, Rd};
 use crate::rmi::rec::exit::handle_realm_exit;
 use crate::rmi::rec::RecState;
-use crate::rsi::do_host_call;
+use crate::rsi::hostcall::host_call;
+use crate::rsi::ripas::{ripas_progress, ripas_response, set_ripas_exit, RipasProgress};
 use crate::{get_granule, get_granule_if};
 
 extern crate alloc;
 
+/// Completes the RIPAS change requested by the realm with the host's response.
+///
+/// If the host has accepted only a part of the range, it exits to the host again
+/// for the rest instead, keeping the change pending, and returns `true`.
+fn complete_ripas(rd: &Rd, rec: &mut Rec<'_>, run: &mut Run) -> Result<bool, Error> {
+    let addr = rec.ripas_addr();
+    if addr == 0 {
+        return Ok(false);
+    }
+    let response = ripas_response(run.entry_flags());
+    match ripas_progress(rec.ripas_exit_base(), addr, rec.ripas_end(), response) {
+        RipasProgress::Resume { base, size } => {
+            set_ripas_exit(run, base, size, rec.ripas_state())?;
+            rec.resume_ripas();
+            Ok(true)
+        }
+        RipasProgress::Complete { next, response } => {
+            set_result_reg(rd, rec.vcpuid(), 0, 0)?;
+            set_result_reg(rd, rec.vcpuid(), 1, next as usize)?;
+            set_result_reg(rd, rec.vcpuid(), 2, response)?;
+            rec.clear_ripas();
+            Ok(false)
+        }
+    }
+}
+
 pub fn set_event_handler(mainloop: &mut Mainloop) {
     listen!(mainloop, rmi::REC_CREATE, |arg, ret, rmm| {
         let rec = arg[0];
@@ -36,17 +65,16 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
 
         let params = host::copy_from::<Params>(params_ptr).ok_or(Error::RmiErrorInput)?;
         params.verify_compliance(rec, rd, params_ptr)?;
+        let rec_params = RecParams::from(&params);
 
-        let rec_index = MPIDR::from(params.mpidr).index();
         let mut rd_granule = get_granule_if!(rd, GranuleState::RD)?;
         let rd = rd_granule.content_mut::<Rd>();
         if !rd.at_state(State::New) {
             return Err(Error::RmiErrorRealm(0));
         }
 
-        if rec_index != rd.rec_index() {
-            return Err(Error::RmiErrorInput);
-        }
+        rec_params.validate(rd)?;
+
         // set Rec_state and grab the lock for Rec granule
         let mut rec_granule = get_granule_if!(rec, GranuleState::Delegated)?;
         #[cfg(not(kani))]
@@ -57,22 +85,24 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
         match create_vcpu(rd) {
             Ok(vcpuid) => {
                 ret[1] = vcpuid;
-                rec.init(owner, vcpuid, params.flags)?;
+                rec.init(owner, vcpuid, rec_params.flags)?;
             }
             Err(_) => return Err(Error::RmiErrorInput),
         }
 
-        for (idx, gpr) in params.gprs.iter().enumerate() {
+        for (idx, gpr) in rec_params.gprs.iter().enumerate() {
             if set_reg(rd, rec.vcpuid(), idx, *gpr as usize).is_err() {
                 return Err(Error::RmiErrorInput);
             }
         }
-        if set_reg(rd, rec.vcpuid(), 31, params.pc as usize).is_err() {
+        if set_reg(rd, rec.vcpuid(), 31, rec_params.pc).is_err() {
             return Err(Error::RmiErrorInput);
         }
+        rec.set_initial_state(rec_params.initial_state());
         rec.set_vtcr(prepare_vtcr(rd)?);
 
         rd.inc_rec_index();
+        rd.add_rec(arg[0], rec.vcpuid());
         #[cfg(not(kani))]
         // `rsi` is currently not reachable in model checking harnesses
         HashContext::new(rd)?.measure_rec_params(&params)?;
@@ -80,18 +110,27 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
         #[cfg(feature = "gst_page_table")]
         return set_granule_with_parent(rd_granule.clone(), &mut rec_granule, GranuleState::Rec);
         #[cfg(not(feature = "gst_page_table"))]
-        return set_granule(&mut rec_granule, GranuleState::Rec);
+        return assign_granule(&mut rec_granule, GranuleState::Rec, rd.id());
     });
 
     listen!(mainloop, rmi::REC_DESTROY, |arg, _ret, rmm| {
         let mut rec_granule = get_granule_if!(arg[0], GranuleState::Rec)?;
+        let owner = rec_granule.content::<Rec<'_>>().owner()?;
 
-        set_granule(&mut rec_granule, GranuleState::Delegated).map_err(|e| {
+        release_granule(&mut rec_granule).map_err(|e| {
             #[cfg(not(kani))]
             // `page_table` is currently not reachable in model checking harnesses
             rmm.page_table.unmap(arg[0]);
             e
         })?;
+        // The attestation token in progress is on the heap
+        rec_granule
+            .content_mut::<Rec<'_>>()
+            .set_attest_continuation(None);
+        // Rd's lock is acquired while holding the REC's lock as REC_ENTER does
+        if let Ok(mut rd_granule) = get_granule_if!(owner, GranuleState::RD) {
+            rd_granule.content_mut::<Rd>().remove_rec(arg[0]);
+        }
         #[cfg(not(kani))]
         // `page_table` is currently not reachable in model checking harnesses
         rmm.page_table.unmap(arg[0]);
@@ -116,6 +155,8 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
 
         let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
         let rd = rd_granule.content::<Rd>();
+        // the realm which the RSI calls during this entry are dispatched under
+        let realmid = rd.id();
         match rd.state() {
             State::Active => {}
             State::New => {
@@ -128,6 +169,14 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
                 panic!("Unexpected realm state");
             }
         }
+        // a live realm stays registered, which is checked without waiting
+        // behind the creation of another realm unless the registry is held
+        if !realm_exists(&VMID_SET, realmid) {
+            error!("Realm {} isn't registered", realmid);
+            return Err(Error::RmiErrorRealm(0));
+        }
+        // refused while the realm is being destroyed
+        let _in_flight = InFlight::enter(rec.owner()?, rd)?;
         // XXX: we explicitly release Rd's lock here to avoid a deadlock
         core::mem::drop(rd_granule);
 
@@ -138,7 +187,7 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
 
         if rec.host_call_pending() {
             // The below should be called without holding rd's lock
-            do_host_call(arg, ret, rmm, rec, &mut run)?;
+            host_call(arg, ret, rmm, rec, &mut run)?;
         }
 
         let mut rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
@@ -146,12 +195,7 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
         crate::gic::receive_state_from_host(rd, rec.vcpuid(), &run)?;
         crate::mmio::emulate_mmio(rd, rec.vcpuid(), &run)?;
 
-        let ripas = rec.ripas_addr() as usize;
-        if ripas > 0 {
-            set_reg(rd, rec.vcpuid(), 0, 0)?;
-            set_reg(rd, rec.vcpuid(), 1, ripas)?;
-            rec.set_ripas(0, 0, 0, 0);
-        }
+        let resumed = complete_ripas(rd, rec, &mut run)?;
         // XXX: we explicitly release Rd's lock here to avoid a deadlock
         core::mem::drop(rd_granule);
 
@@ -163,10 +207,18 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
 
         activate_stage2_mmu(rec);
 
-        let mut ret_ns;
-        loop {
+        run.set_diag(DiagCheck::None, 0);
+
+        // the rest of a RIPAS change goes back to the host without running the realm
+        let mut ret_ns = resumed;
+        if resumed {
+            ret[0] = rmi::SUCCESS;
+        }
+        while !ret_ns {
             ret_ns = true;
             run.set_imm(0);
+            run.set_run_hint(RunHint::Runnable);
+            run.set_throttled(false);
 
             let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
             let rd = rd_granule.content::<Rd>();
@@ -177,15 +229,12 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
             core::mem::drop(rd_granule);
             match crate::rmi::rec::run() {
                 Ok(realm_exit_res) => {
-                    (ret_ns, ret[0]) = handle_realm_exit(realm_exit_res, rmm, rec, &mut run)?
+                    (ret_ns, ret[0]) =
+                        handle_realm_exit(realm_exit_res, rmm, rec, &mut run, realmid)?
                 }
                 Err(_) => ret[0] = rmi::ERROR_REC,
             }
             rec.set_state(RecState::Ready);
-
-            if ret_ns {
-                break;
-            }
         }
         let mut rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
         let rd = rd_granule.content_mut::<Rd>();
@@ -196,3 +245,45 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
         host::copy_to::<Run>(&run, run_pa).ok_or(Error::RmiErrorInput)
     });
 }
+
+/// Registers the diagnostics which expose the state of RECs to the host,
+/// so they are left out of production builds.
+#[cfg(feature = "diagnostics")]
+pub fn set_diagnostics_handler(mainloop: &mut Mainloop) {
+    listen!(mainloop, rmi::REC_PENDING_GET, |arg, ret, _rmm| {
+        // The snapshot is taken at once while holding the REC's lock
+        // and nothing in the REC is modified.
+        let rec_granule = get_granule_if!(arg[0], GranuleState::Rec)?;
+        let pending = rec_granule.content::<Rec<'_>>().pending();
+        debug!("RMI_REC_PENDING_GET: {:X} {:X?}", arg[0], pending);
+
+        ret[1..5].copy_from_slice(&pending.to_regs());
+        Ok(())
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(not(feature = "gst_page_table"))]
+    fn host_call_as_run() {
+        use crate::granule::GRANULE_SIZE;
+        use crate::test_support::{restore_granules, snapshot_granules};
+
+        // a granule which no other test uses
+        const ADDR: usize = 0x8813_0000;
+        let snapshot = snapshot_granules(ADDR..ADDR + GRANULE_SIZE);
+
+        // the host passes a data granule of a realm, where its `HostCall` is,
+        // as run_pa of REC_ENTER, which reads `Run` only from a granule of the host
+        get_granule!(ADDR)
+            .unwrap()
+            .restore_state(GranuleState::Data);
+        assert!(host::copy_from::<Run>(ADDR).is_none());
+        assert!(host::copy_from::<Run>(ADDR + 0x100).is_none());
+
+        restore_granules(snapshot);
+    }
+}
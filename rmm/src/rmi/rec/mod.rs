@@ -4,15 +4,24 @@ pub mod mpidr;
 pub mod params;
 pub mod run;
 pub mod vtcr;
+use crate::config::{HOST_EXIT_BURST, HOST_EXIT_WINDOW};
 use crate::realm;
+use crate::realm::context::Context;
+use crate::realm::mm::vttbr_vmid;
 use crate::realm::vcpu::State as RecState;
 use crate::realm::vcpu::VCPU;
 use crate::rmi::error::Error;
 use crate::rmi::error::InternalError::*;
+use crate::rmi::rec::params::NR_GPRS;
 use crate::rmi::Rd;
 use crate::rmm_exit;
+use crate::rsi::attestation::Continuation;
+use crate::rsi::ripas::RipasChangeFlags;
+use crate::rsi::throttle::Throttle;
 use core::cell::OnceCell;
 
+#[cfg(feature = "diagnostics")]
+pub use self::handlers::set_diagnostics_handler;
 pub use self::handlers::set_event_handler;
 
 use vmsa::guard::Content;
@@ -23,18 +32,87 @@ pub enum RmmRecAttestState {
     NoAttestInProgress,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 struct Ripas {
     start: u64,
     end: u64,
     addr: u64,
+    // the start of the range of the last EXIT_RIPAS_CHANGE
+    exit_base: u64,
     state: u8,
+    flags: RipasChangeFlags,
+}
+
+/// Completion the REC waits for from the host before it can make progress.
+///
+/// At most one completion is pending at a time, so setting one replaces any other.
+#[derive(Clone, Copy, Debug, Default)]
+enum Completion {
+    #[default]
+    None,
+    Ripas(Ripas),
+    HostCall,
+}
+
+/// Register state given by the host at REC creation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InitialState {
+    pub pc: u64,
+    pub gprs: [u64; NR_GPRS],
+}
+
+/// RIPAS change requested by the realm which the host has not completed yet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PendingRipas {
+    pub start: u64,
+    pub end: u64,
+    /// Next IPA to be applied by the host
+    pub addr: u64,
+    pub state: u8,
+}
+
+/// Snapshot of the completions a REC is waiting for before it can make progress.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PendingCompletion {
+    pub ripas: Option<PendingRipas>,
+    pub host_call: bool,
+    pub attest: bool,
+}
+
+impl PendingCompletion {
+    pub const FLAG_RIPAS: usize = 1 << 0;
+    pub const FLAG_HOST_CALL: usize = 1 << 1;
+    pub const FLAG_ATTEST: usize = 1 << 2;
+    const RIPAS_STATE_SHIFT: usize = 8;
+
+    /// Encodes the snapshot into the output registers of RMI_REC_PENDING_GET:
+    ///   x1: flags (bits[2:0]) and the RIPAS state (bits[15:8])
+    ///   x2: start of the pending RIPAS range
+    ///   x3: next IPA to be applied in the pending RIPAS range
+    ///   x4: end of the pending RIPAS range
+    pub fn to_regs(&self) -> [usize; 4] {
+        let mut regs = [0; 4];
+        if let Some(ripas) = self.ripas {
+            regs[0] |= Self::FLAG_RIPAS | (ripas.state as usize) << Self::RIPAS_STATE_SHIFT;
+            regs[1] = ripas.start as usize;
+            regs[2] = ripas.addr as usize;
+            regs[3] = ripas.end as usize;
+        }
+        if self.host_call {
+            regs[0] |= Self::FLAG_HOST_CALL;
+        }
+        if self.attest {
+            regs[0] |= Self::FLAG_ATTEST;
+        }
+        regs
+    }
 }
 
 #[derive(Debug)]
 pub struct Rec<'a> {
     attest_state: RmmRecAttestState,
     attest_challenge: [u8; 64],
+    attest_continuation: Option<Continuation>,
     /// PA of RD of Realm which owns this REC
     ///
     /// Safety:
@@ -44,9 +122,10 @@ pub struct Rec<'a> {
     vcpuid: usize,
     runnable: bool,
     state: RecState,
-    ripas: Ripas,
+    completion: Completion,
     vtcr: u64,
-    host_call_pending: bool,
+    initial: InitialState,
+    exit_throttle: Throttle,
 }
 
 impl Rec<'_> {
@@ -66,7 +145,10 @@ impl Rec<'_> {
         }
 
         self.vcpuid = vcpuid;
-        self.set_ripas(0, 0, 0, 0);
+        // The granule doesn't hold a valid continuation to drop yet.
+        unsafe { core::ptr::write(core::ptr::addr_of_mut!(self.attest_continuation), None) };
+        self.completion = Completion::None;
+        self.exit_throttle = Throttle::default();
         self.set_runnable(flags);
         self.set_state(RecState::Ready);
 
@@ -81,6 +163,11 @@ impl Rec<'_> {
         &self.attest_challenge
     }
 
+    /// Takes the token being copied to the realm, since the first RSI_ATTEST_TOKEN_CONTINUE.
+    pub fn take_attest_continuation(&mut self) -> Option<Continuation> {
+        self.attest_continuation.take()
+    }
+
     pub fn runnable(&self) -> bool {
         self.runnable
     }
@@ -102,7 +189,29 @@ impl Rec<'_> {
     }
 
     pub fn host_call_pending(&self) -> bool {
-        self.host_call_pending
+        matches!(self.completion, Completion::HostCall)
+    }
+
+    fn pending_ripas(&self) -> Option<&Ripas> {
+        match &self.completion {
+            Completion::Ripas(ripas) => Some(ripas),
+            _ => None,
+        }
+    }
+
+    /// Returns every completion this REC is currently blocked on.
+    pub fn pending(&self) -> PendingCompletion {
+        let ripas = self.pending_ripas().map(|ripas| PendingRipas {
+            start: ripas.start,
+            end: ripas.end,
+            addr: ripas.addr,
+            state: ripas.state,
+        });
+        PendingCompletion {
+            ripas,
+            host_call: self.host_call_pending(),
+            attest: self.attest_state == RmmRecAttestState::AttestInProgress,
+        }
     }
 
     pub fn set_attest_state(&mut self, state: RmmRecAttestState) {
@@ -113,15 +222,79 @@ impl Rec<'_> {
         self.attest_challenge.copy_from_slice(challenge);
     }
 
+    pub fn set_attest_continuation(&mut self, continuation: Option<Continuation>) {
+        self.attest_continuation = continuation;
+    }
+
     pub fn set_host_call_pending(&mut self, val: bool) {
-        self.host_call_pending = val;
+        if val {
+            self.completion = Completion::HostCall;
+        } else if self.host_call_pending() {
+            self.completion = Completion::None;
+        }
+    }
+
+    pub fn set_ripas(
+        &mut self,
+        start: u64,
+        end: u64,
+        addr: u64,
+        state: u8,
+        flags: RipasChangeFlags,
+    ) {
+        self.completion = Completion::Ripas(Ripas {
+            start,
+            end,
+            addr,
+            exit_base: addr,
+            state,
+            flags,
+        });
+    }
+
+    /// Returns the start of the range of the last RIPAS change exit to the host.
+    pub fn ripas_exit_base(&self) -> u64 {
+        self.pending_ripas().map_or(0, |ripas| ripas.exit_base)
+    }
+
+    /// Keeps the RIPAS change pending while exiting to the host again
+    /// for the rest of the range, from the next IPA to be applied.
+    pub fn resume_ripas(&mut self) {
+        if let Completion::Ripas(ripas) = &mut self.completion {
+            ripas.exit_base = ripas.addr;
+        }
+    }
+
+    /// Drops the pending RIPAS change, if any, once the host has completed it.
+    pub fn clear_ripas(&mut self) {
+        if self.pending_ripas().is_some() {
+            self.completion = Completion::None;
+        }
+    }
+
+    /// Records a host exit triggered by the realm at `now` (ticks of the system counter)
+    /// and returns whether it is beyond the rate limit.
+    pub fn throttle_host_exit(&mut self, now: u64) -> bool {
+        self.exit_throttle
+            .hit(now, HOST_EXIT_BURST, HOST_EXIT_WINDOW)
+    }
+
+    pub fn set_initial_state(&mut self, initial: InitialState) {
+        self.initial = initial;
     }
 
-    pub fn set_ripas(&mut self, start: u64, end: u64, addr: u64, state: u8) {
-        self.ripas.start = start;
-        self.ripas.end = end;
-        self.ripas.addr = addr;
-        self.ripas.state = state;
+    pub fn initial_state(&self) -> &InitialState {
+        &self.initial
+    }
+
+    /// Clears the completions this REC is waiting for
+    /// (i.e., RIPAS change, host call and attestation) and makes it runnable.
+    fn clear_transient(&mut self) {
+        self.completion = Completion::None;
+        self.attest_state = RmmRecAttestState::NoAttestInProgress;
+        self.attest_continuation = None;
+        self.runnable = true;
+        self.state = RecState::Ready;
     }
 
     pub fn set_vtcr(&mut self, vtcr: u64) {
@@ -145,19 +318,26 @@ impl Rec<'_> {
     }
 
     pub fn inc_ripas_addr(&mut self, size: u64) {
-        self.ripas.addr += size;
+        if let Completion::Ripas(ripas) = &mut self.completion {
+            ripas.addr += size;
+        }
     }
 
     pub fn ripas_addr(&mut self) -> u64 {
-        self.ripas.addr
+        self.pending_ripas().map_or(0, |ripas| ripas.addr)
     }
 
     pub fn ripas_state(&self) -> u8 {
-        self.ripas.state
+        self.pending_ripas().map_or(0, |ripas| ripas.state)
     }
 
     pub fn ripas_end(&self) -> u64 {
-        self.ripas.end
+        self.pending_ripas().map_or(0, |ripas| ripas.end)
+    }
+
+    pub fn ripas_flags(&self) -> RipasChangeFlags {
+        self.pending_ripas()
+            .map_or(RipasChangeFlags::default(), |ripas| ripas.flags)
     }
 
     pub fn vtcr(&self) -> u64 {
@@ -173,10 +353,58 @@ impl Rec<'_> {
         let owner = self.get_owner()?;
         Ok(owner.ipa_bits())
     }
+
+    /// Returns the VMID of the stage 2 translation which the REC runs under,
+    /// as programmed to VTTBR_EL2 on its entry.
+    pub fn stage2_vmid(&self) -> Result<usize, Error> {
+        let owner = self.get_owner()?;
+        let vcpu = owner.vcpus.get(self.vcpuid).ok_or(Error::RmiErrorRec)?;
+        let vttbr = vcpu.lock().context.sys_regs.vttbr;
+        Ok(vttbr_vmid(vttbr))
+    }
 }
 
 impl Content for Rec<'_> {}
 
+/// Resets the REC to its state at creation without destroying it.
+///
+/// The GPRs, PC, SP and PSTATE are restored to the initial state captured at creation,
+/// and any pending completion is cleared. The rest of the context (e.g., the stage 2
+/// translation, the timer and the GIC state) is kept as the REC is still live.
+/// The effects of the REC on the realm (e.g., memory contents or RIPAS already changed)
+/// are not reverted.
+pub fn reset(rec: &mut Rec<'_>) -> Result<(), Error> {
+    if let RecState::Running = rec.get_state() {
+        return Err(Error::RmiErrorRec);
+    }
+
+    let vcpuid = rec.vcpuid();
+    let initial = *rec.initial_state();
+    reset_context(
+        &mut rec
+            .get_owner()?
+            .vcpus
+            .get(vcpuid)
+            .ok_or(Error::RmiErrorOthers(NotExistVCPU))?
+            .lock()
+            .context,
+        &initial,
+    );
+    rec.clear_transient();
+    Ok(())
+}
+
+fn reset_context(context: &mut Context, initial: &InitialState) {
+    // the registers not given by the host are as a new VCPU has them
+    let created = Context::new();
+    context.gp_regs = [0; 31];
+    context.gp_regs[..NR_GPRS].copy_from_slice(&initial.gprs);
+    context.elr = initial.pc;
+    context.spsr = created.spsr;
+    context.sys_regs.sp = created.sys_regs.sp;
+    context.sys_regs.sp_el0 = created.sys_regs.sp_el0;
+}
+
 fn enter() -> [usize; 4] {
     unsafe {
         if let Some(vcpu) = realm::vcpu::current() {
@@ -231,3 +459,263 @@ pub fn run() -> Result<[usize; 4], Error> {
     exit();
     Ok(ret)
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    /// Returns a REC which hasn't been bound to a realm.
+    pub fn rec() -> Rec<'static> {
+        Rec {
+            attest_state: RmmRecAttestState::NoAttestInProgress,
+            attest_challenge: [0; 64],
+            attest_continuation: None,
+            owner: OnceCell::new(),
+            vcpuid: 0,
+            runnable: true,
+            state: RecState::Ready,
+            completion: Completion::None,
+            vtcr: 0,
+            initial: InitialState::default(),
+            exit_throttle: Throttle::default(),
+        }
+    }
+
+    #[test]
+    fn nothing_pending() {
+        let rec = rec();
+        let pending = rec.pending();
+        assert_eq!(pending.ripas, None);
+        assert_eq!(pending.to_regs(), [0; 4]);
+    }
+
+    #[test]
+    fn resume_ripas_change() {
+        let mut rec = rec();
+        rec.set_ripas(
+            0x8000_0000,
+            0x8000_4000,
+            0x8000_0000,
+            1,
+            RipasChangeFlags::default(),
+        );
+        rec.inc_ripas_addr(0x1000);
+        rec.inc_ripas_addr(0x1000);
+        assert_eq!(rec.ripas_exit_base(), 0x8000_0000);
+
+        rec.resume_ripas();
+        assert_eq!(rec.ripas_exit_base(), 0x8000_2000);
+        assert_eq!(rec.ripas_addr(), 0x8000_2000);
+        assert_eq!(
+            rec.pending().ripas.map(|ripas| ripas.start),
+            Some(0x8000_0000)
+        );
+    }
+
+    #[test]
+    fn pending_ripas_change() {
+        let mut rec = rec();
+        rec.set_ripas(
+            0x8000_0000,
+            0x8000_4000,
+            0x8000_0000,
+            1,
+            RipasChangeFlags::default(),
+        );
+        rec.inc_ripas_addr(0x1000);
+
+        let pending = rec.pending();
+        assert_eq!(
+            pending.ripas,
+            Some(PendingRipas {
+                start: 0x8000_0000,
+                end: 0x8000_4000,
+                addr: 0x8000_1000,
+                state: 1,
+            })
+        );
+        assert!(!pending.host_call && !pending.attest);
+        assert_eq!(
+            pending.to_regs(),
+            [
+                PendingCompletion::FLAG_RIPAS | 1 << 8,
+                0x8000_0000,
+                0x8000_1000,
+                0x8000_4000
+            ]
+        );
+
+        // Completed by the next REC_ENTER
+        rec.clear_ripas();
+        assert_eq!(rec.pending().ripas, None);
+    }
+
+    #[test]
+    fn host_call_replaces_ripas() {
+        let mut rec = rec();
+        rec.set_ripas(
+            0x8000_0000,
+            0x8000_4000,
+            0x8000_0000,
+            1,
+            RipasChangeFlags::default(),
+        );
+        rec.set_host_call_pending(true);
+
+        let pending = rec.pending();
+        assert_eq!(pending.ripas, None);
+        assert!(pending.host_call);
+        assert_eq!(rec.ripas_addr(), 0);
+        assert_eq!(rec.ripas_end(), 0);
+
+        // Clearing the wrong kind of completion leaves the pending one intact
+        rec.clear_ripas();
+        assert!(rec.host_call_pending());
+    }
+
+    #[test]
+    fn ripas_replaces_host_call() {
+        let mut rec = rec();
+        rec.set_host_call_pending(true);
+        rec.set_ripas(
+            0x8000_0000,
+            0x8000_4000,
+            0x8000_0000,
+            1,
+            RipasChangeFlags::default(),
+        );
+
+        let pending = rec.pending();
+        assert!(!pending.host_call);
+        assert!(pending.ripas.is_some());
+
+        rec.set_host_call_pending(false);
+        assert_eq!(rec.ripas_addr(), 0x8000_0000);
+        assert_eq!(rec.ripas_end(), 0x8000_4000);
+    }
+
+    #[test]
+    fn reset_to_initial_state() {
+        let initial = InitialState {
+            pc: 0x8000_0000,
+            gprs: [1, 2, 3, 4, 5, 6, 7, 8],
+        };
+        let mut rec = rec();
+        rec.set_initial_state(initial);
+
+        // Running for a while
+        let mut context = Context::new();
+        reset_context(&mut context, &initial);
+        context.gp_regs[0] = 0xdead;
+        context.gp_regs[20] = 0xbeef;
+        context.elr = 0x8000_1000;
+        context.spsr = 0;
+        context.sys_regs.sp = 0x8000_f000;
+        context.sys_regs.vttbr = 0x1_0000_8000;
+        context.timer.cntv_ctl_el0 = 1;
+        rec.set_ripas(
+            0x8000_0000,
+            0x8000_4000,
+            0x8000_0000,
+            1,
+            RipasChangeFlags::default(),
+        );
+        rec.set_host_call_pending(true);
+        rec.set_attest_state(RmmRecAttestState::AttestInProgress);
+
+        // what `reset()` does on the REC's VCPU without the owner realm
+        reset_context(&mut context, rec.initial_state());
+        rec.clear_transient();
+
+        assert_eq!(&context.gp_regs[..NR_GPRS], &initial.gprs);
+        assert!(context.gp_regs[NR_GPRS..].iter().all(|&r| r == 0));
+        assert_eq!(context.elr, initial.pc);
+        assert_eq!(context.spsr, Context::new().spsr);
+        assert_eq!(context.sys_regs.sp, Context::new().sys_regs.sp);
+        // the rest of the live context is kept
+        assert_eq!(context.sys_regs.vttbr, 0x1_0000_8000);
+        assert_eq!(context.timer.cntv_ctl_el0, 1);
+        let pending = rec.pending();
+        assert_eq!(pending.ripas, None);
+        assert!(!pending.host_call && !pending.attest);
+        assert!(rec.runnable());
+    }
+
+    #[test]
+    fn reset_bound_rec() {
+        use crate::realm::context::{get_reg, set_reg};
+        use crate::test_support::OneGranuleTable;
+        use alloc::boxed::Box;
+
+        let rd = Box::leak(crate::rmi::realm::rd::test::rd(
+            1,
+            33,
+            OneGranuleTable::new(),
+        ));
+        let mut rec = rec();
+        rec.init(rd as *const Rd as usize, 0, 0).unwrap();
+        // what REC_CREATE does
+        let initial = InitialState {
+            pc: 0x8000_0000,
+            gprs: [1, 2, 3, 4, 5, 6, 7, 8],
+        };
+        for (idx, gpr) in initial.gprs.iter().enumerate() {
+            set_reg(rd, 0, idx, *gpr as usize).unwrap();
+        }
+        set_reg(rd, 0, 31, initial.pc as usize).unwrap();
+        rec.set_initial_state(initial);
+        let sp = rd.vcpus[0].lock().context.sys_regs.sp;
+
+        let run = |rec: &mut Rec<'_>| {
+            set_reg(rd, 0, 0, 0xdead).unwrap();
+            set_reg(rd, 0, 20, 0xbeef).unwrap();
+            set_reg(rd, 0, 31, 0x8000_1000).unwrap();
+            rd.vcpus[0].lock().context.sys_regs.sp = 0x8000_f000;
+            rec.set_state(RecState::Ready);
+        };
+        let assert_reset = |rec: &Rec<'_>| {
+            for (idx, gpr) in initial.gprs.iter().enumerate() {
+                assert_eq!(get_reg(rd, 0, idx).unwrap(), *gpr as usize);
+            }
+            for idx in NR_GPRS..31 {
+                assert_eq!(get_reg(rd, 0, idx).unwrap(), 0);
+            }
+            assert_eq!(get_reg(rd, 0, 31).unwrap(), initial.pc as usize);
+            assert_eq!(rd.vcpus[0].lock().context.sys_regs.sp, sp);
+            let pending = rec.pending();
+            assert_eq!(pending.ripas, None);
+            assert!(!pending.host_call && !pending.attest);
+            assert!(rec.runnable());
+        };
+
+        // waiting for a RIPAS change in the middle of an attestation
+        run(&mut rec);
+        rec.set_ripas(
+            0x8000_0000,
+            0x8000_4000,
+            0x8000_0000,
+            1,
+            RipasChangeFlags::default(),
+        );
+        rec.set_attest_state(RmmRecAttestState::AttestInProgress);
+        assert!(rec.pending().ripas.is_some() && rec.pending().attest);
+        reset(&mut rec).unwrap();
+        assert_reset(&rec);
+
+        // waiting for a host call
+        run(&mut rec);
+        rec.set_host_call_pending(true);
+        reset(&mut rec).unwrap();
+        assert_reset(&rec);
+
+        // not while running
+        rec.set_state(RecState::Running);
+        assert!(matches!(reset(&mut rec), Err(Error::RmiErrorRec)));
+    }
+
+    #[test]
+    fn reset_without_owner() {
+        let mut rec = rec();
+        assert!(reset(&mut rec).is_err());
+    }
+}
@@ -1,14 +1,17 @@
-use super::mpidr;
+use super::mpidr::{self, MPIDR};
+use super::InitialState;
 use crate::const_assert_eq;
 use crate::granule::{GranuleState, GRANULE_SIZE};
 use crate::measurement::Hashable;
+use crate::mm::protected_ipa_boundary;
 use crate::rmi::error::Error;
+use crate::rmi::realm::Rd;
 use crate::{get_granule, get_granule_if};
 
 use autopadding::*;
 
 const NR_AUX: usize = 16;
-const NR_GPRS: usize = 8;
+pub const NR_GPRS: usize = 8;
 
 pad_struct_and_impl_default!(
 pub struct Params {
@@ -48,6 +51,60 @@ impl Params {
     }
 }
 
+/// The REC parameters given by the host in their typed form,
+/// which are validated against the realm at once before creating the REC.
+#[derive(Clone, Copy, Debug)]
+pub struct RecParams {
+    pub flags: u64,
+    pub index: usize,
+    pub pc: usize,
+    pub gprs: [u64; NR_GPRS],
+}
+
+impl From<&Params> for RecParams {
+    fn from(params: &Params) -> Self {
+        Self {
+            flags: params.flags,
+            index: MPIDR::from(params.mpidr).index(),
+            pc: params.pc as usize,
+            gprs: params.gprs,
+        }
+    }
+}
+
+impl RecParams {
+    pub fn validate(&self, realm: &Rd) -> Result<(), Error> {
+        self.validate_with(realm.ipa_bits(), realm.rec_index(), realm.can_create_rec())
+    }
+
+    /// Checks that the PC is within the protected address range of the realm
+    /// whose IPA width is `ipa_bits`, and that the REC is the next one
+    /// (`rec_index`) so that no two RECs share an index.
+    fn validate_with(
+        &self,
+        ipa_bits: usize,
+        rec_index: usize,
+        can_create: bool,
+    ) -> Result<(), Error> {
+        if self.pc >= protected_ipa_boundary(ipa_bits) || self.pc % 4 != 0 {
+            return Err(Error::RmiErrorInput);
+        }
+
+        if self.index != rec_index || !can_create {
+            return Err(Error::RmiErrorInput);
+        }
+
+        Ok(())
+    }
+
+    pub fn initial_state(&self) -> InitialState {
+        InitialState {
+            pc: self.pc as u64,
+            gprs: self.gprs,
+        }
+    }
+}
+
 impl core::fmt::Debug for Params {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("Params")
@@ -124,4 +181,41 @@ pub mod test {
         assert_eq!(offset_of!(Params, num_aux), 0x800);
         assert_eq!(offset_of!(Params, aux), 0x808);
     }
+
+    fn rec_params(mpidr: u64, pc: u64) -> RecParams {
+        RecParams::from(&Params {
+            mpidr,
+            pc,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn validate_rec_params() {
+        let ipa_bits = 40;
+        let par_size = protected_ipa_boundary(ipa_bits) as u64;
+
+        // the second REC (AFF0 = 1) with its PC in the protected range
+        assert!(rec_params(1, 0x8000_0000)
+            .validate_with(ipa_bits, 1, true)
+            .is_ok());
+        assert!(rec_params(1, par_size - 4)
+            .validate_with(ipa_bits, 1, true)
+            .is_ok());
+
+        // out-of-range PC
+        assert!(rec_params(1, par_size)
+            .validate_with(ipa_bits, 1, true)
+            .is_err());
+
+        // duplicate VCPU index with the first REC
+        assert!(rec_params(0, 0x8000_0000)
+            .validate_with(ipa_bits, 1, true)
+            .is_err());
+
+        // beyond the REC count of the realm
+        assert!(rec_params(1, 0x8000_0000)
+            .validate_with(ipa_bits, 1, false)
+            .is_err());
+    }
 }
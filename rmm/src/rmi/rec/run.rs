@@ -1,6 +1,8 @@
 use crate::const_assert_eq;
 use crate::granule::GRANULE_SIZE;
+use crate::rmi;
 use crate::rmi::error::Error;
+use crate::rsi::hostcall::HOST_CALL_NR_GPRS;
 
 use autopadding::*;
 
@@ -46,6 +48,10 @@ struct Exit {
     0x700 pmu_ovf: u64,
     0x708 pmu_intr_en: u64,
     0x710 pmu_cntr_en: u64,
+    0x780 run_hint: u8,
+    0x781 throttled: u8,
+    0x790 diag_check: u8,
+    0x798 diag_addr: u64,
     0x800 => @END,
 }
 );
@@ -55,6 +61,22 @@ impl Run {
         self.entry.flags
     }
 
+    /// Sets the entry flags as the host does. Only tests act as the host.
+    #[cfg(test)]
+    pub fn set_entry_flags(&mut self, flags: u64) {
+        self.entry.flags = flags;
+    }
+
+    /// Sets an entry GPR as the host does. Only tests act as the host.
+    #[cfg(test)]
+    pub fn set_entry_gpr(&mut self, idx: usize, val: u64) -> Result<(), Error> {
+        if idx >= NR_GPRS {
+            return Err(Error::RmiErrorInput);
+        }
+        self.entry.gprs[idx] = val;
+        Ok(())
+    }
+
     pub fn entry_gpr(&self, idx: usize) -> Result<u64, Error> {
         if idx >= NR_GPRS {
             error!("out of index: {}", idx);
@@ -79,10 +101,59 @@ impl Run {
         self.exit.imm = imm;
     }
 
+    /// Returns the immediate of the host call on exit, as the host reads it.
+    #[cfg(test)]
+    pub fn imm(&self) -> u16 {
+        self.exit.imm
+    }
+
+    /// Returns an exit GPR, as the host reads it.
+    #[cfg(test)]
+    pub fn exit_gpr(&self, idx: usize) -> Result<u64, Error> {
+        self.exit.gprs.get(idx).copied().ok_or(Error::RmiErrorInput)
+    }
+
+    pub fn exit_reason(&self) -> u8 {
+        self.exit.exit_reason
+    }
+
     pub fn set_exit_reason(&mut self, exit_reason: u8) {
         self.exit.exit_reason = exit_reason;
     }
 
+    pub fn run_hint(&self) -> u8 {
+        self.exit.run_hint
+    }
+
+    pub fn set_run_hint(&mut self, hint: RunHint) {
+        self.exit.run_hint = hint as u8;
+    }
+
+    /// Whether the exit is beyond the rate limit of the host exits of the REC.
+    pub fn throttled(&self) -> bool {
+        self.exit.throttled != 0
+    }
+
+    pub fn set_throttled(&mut self, throttled: bool) {
+        self.exit.throttled = throttled as u8;
+    }
+
+    /// Records the check which a realm request failed and the address it failed at,
+    /// for the host to tell why the request was rejected.
+    /// Nothing is recorded without the `diagnostics` feature.
+    pub fn set_diag(&mut self, check: DiagCheck, addr: u64) {
+        if cfg!(feature = "diagnostics") {
+            self.exit.diag_check = check as u8;
+            self.exit.diag_addr = addr;
+        }
+    }
+
+    /// Returns the check and the address recorded by `set_diag`, as the host reads them.
+    #[cfg(test)]
+    pub fn diag(&self) -> (u8, u64) {
+        (self.exit.diag_check, self.exit.diag_addr)
+    }
+
     pub fn set_esr(&mut self, esr: u64) {
         self.exit.esr = esr;
     }
@@ -104,6 +175,15 @@ impl Run {
         Ok(())
     }
 
+    /// Returns the RIPAS change requested on exit as (base, size, state).
+    pub fn ripas(&self) -> (u64, u64, u8) {
+        (
+            self.exit.ripas_base,
+            self.exit.ripas_size,
+            self.exit.ripas_value,
+        )
+    }
+
     pub fn set_ripas(&mut self, base: u64, size: u64, state: u8) {
         self.exit.ripas_base = base;
         self.exit.ripas_size = size;
@@ -155,6 +235,13 @@ impl core::fmt::Debug for Run {
             .field("entry::gicv3_lrs", &self.entry.gicv3_lrs)
             .field("exit::exit_reason", &self.exit.exit_reason)
             .field("exit::imm", &self.exit.imm)
+            .field("exit::run_hint", &self.exit.run_hint)
+            .field("exit::throttled", &self.exit.throttled)
+            .field("exit::diag_check", &self.exit.diag_check)
+            .field(
+                "exit::diag_addr",
+                &format_args!("{:#X}", &self.exit.diag_addr),
+            )
             .field("exit::cntp_ctl", &self.exit.cntp_ctl)
             .field("exit::cntp_cval", &self.exit.cntp_cval)
             .field("exit::cntv_ctl", &self.exit.cntv_ctl)
@@ -182,6 +269,120 @@ pub const REC_ENTRY_FLAG_TRAP_WFI: u64 = 1 << 2;
 ///  val 1: Trap is enabled.
 #[allow(dead_code)]
 pub const REC_ENTRY_FLAG_TRAP_WFE: u64 = 1 << 3;
+/// Host response to RIPAS change request.
+///  val 0: Host accepted the RIPAS change request.
+///  val 1: Host rejected the RIPAS change request.
+pub const REC_ENTRY_FLAG_RIPAS_RESPONSE: u64 = 1 << 4;
+
+/// Hint on the run state of the VCPU on REC exit for the host's scheduler.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunHint {
+    /// The VCPU can be entered again at any time.
+    Runnable = 0,
+    /// The VCPU is blocked until the host completes the RIPAS change.
+    BlockedRipas = 1,
+    /// The VCPU is blocked until the host completes the host call.
+    BlockedHostCall = 2,
+}
+
+/// The check which a realm request failed, recorded on REC exit with the `diagnostics`
+/// feature so that the host can tell more than the error code the realm got.
+///
+/// The payload is in the exit part of `Run`, in the little-endian layout below:
+///   0x790: the failed check, 0 (`None`) if no request was rejected during the REC entry
+///   0x798: the address the check failed at (e.g., the offending IPA)
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiagCheck {
+    None = 0,
+    /// The address or the size isn't aligned to the granule.
+    Alignment = 1,
+    /// The range wraps around the address space.
+    Overflow = 2,
+    /// The address is out of the protected IPA space.
+    ProtectedRange = 3,
+    /// The value requested (e.g., the target RIPAS) is unknown.
+    InvalidValue = 4,
+    /// The current RIPAS of the page doesn't allow the change.
+    RipasTransition = 5,
+}
+
+/// The reason of a REC exit, which tells the host the payload to read from `Run`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExitReason {
+    Sync,
+    Irq,
+    Fiq,
+    Psci,
+    RipasChange,
+    HostCall,
+    SError,
+}
+
+impl From<ExitReason> for u8 {
+    fn from(reason: ExitReason) -> Self {
+        match reason {
+            ExitReason::Sync => rmi::EXIT_SYNC,
+            ExitReason::Irq => rmi::EXIT_IRQ,
+            ExitReason::Fiq => rmi::EXIT_FIQ,
+            ExitReason::Psci => rmi::EXIT_PSCI,
+            ExitReason::RipasChange => rmi::EXIT_RIPAS_CHANGE,
+            ExitReason::HostCall => rmi::EXIT_HOST_CALL,
+            ExitReason::SError => rmi::EXIT_SERROR,
+        }
+    }
+}
+
+/// The payload of a REC exit, written to the fields of `Run` of its exit reason.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExitPayload {
+    None,
+    RipasChange {
+        base: u64,
+        size: u64,
+        state: u8,
+    },
+    HostCall {
+        imm: u16,
+        gprs: [u64; HOST_CALL_NR_GPRS],
+    },
+}
+
+/// Writes the exit reason, its payload and the run hint it implies
+/// to the host-visible fields of `run`.
+///
+/// The payload must be the one of the exit reason, if it has any.
+pub fn marshal_exit(
+    run: &mut Run,
+    reason: &ExitReason,
+    payload: &ExitPayload,
+) -> Result<(), Error> {
+    let hint = match (reason, payload) {
+        (ExitReason::RipasChange, ExitPayload::RipasChange { base, size, state }) => {
+            run.set_ripas(*base, *size, *state);
+            RunHint::BlockedRipas
+        }
+        (ExitReason::HostCall, ExitPayload::HostCall { imm, gprs }) => {
+            run.set_imm(*imm);
+            for (idx, val) in gprs.iter().enumerate() {
+                run.set_gpr(idx, *val)?;
+            }
+            RunHint::BlockedHostCall
+        }
+        (ExitReason::RipasChange | ExitReason::HostCall, _)
+        | (_, ExitPayload::RipasChange { .. })
+        | (_, ExitPayload::HostCall { .. }) => {
+            error!("Mismatched exit payload {:?} for {:?}", payload, reason);
+            return Err(Error::RmiErrorInput);
+        }
+        (_, ExitPayload::None) => RunHint::Runnable,
+    };
+    run.set_exit_reason((*reason).into());
+    run.set_run_hint(hint);
+    Ok(())
+}
+
 pub const NR_GPRS: usize = 31;
 const NR_GIC_LRS: usize = 16;
 
@@ -217,3 +418,56 @@ impl safe_abstraction::raw_ptr::SafetyAssured for Run {
         true
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn field<const N: usize>(run: &Run, offset: usize) -> [u8; N] {
+        let bytes = unsafe {
+            core::slice::from_raw_parts(run as *const Run as *const u8, core::mem::size_of::<Run>())
+        };
+        bytes[offset..offset + N].try_into().unwrap()
+    }
+
+    #[test]
+    fn ripas_change_layout() {
+        const EXIT: usize = 0x800;
+        let mut run = Run::default();
+        let payload = ExitPayload::RipasChange {
+            base: 0x8000_0000,
+            size: 0x20_0000,
+            state: 1,
+        };
+        marshal_exit(&mut run, &ExitReason::RipasChange, &payload).unwrap();
+
+        assert_eq!(field::<1>(&run, EXIT), [rmi::EXIT_RIPAS_CHANGE]);
+        assert_eq!(field::<8>(&run, EXIT + 0x500), 0x8000_0000u64.to_le_bytes());
+        assert_eq!(field::<8>(&run, EXIT + 0x508), 0x20_0000u64.to_le_bytes());
+        assert_eq!(field::<1>(&run, EXIT + 0x510), [1]);
+        assert_eq!(
+            field::<1>(&run, EXIT + 0x780),
+            [RunHint::BlockedRipas as u8]
+        );
+        // nothing of the other exit reasons
+        assert_eq!(field::<2>(&run, EXIT + 0x600), [0; 2]);
+    }
+
+    #[test]
+    fn mismatched_payload() {
+        let mut run = Run::default();
+        let ripas = ExitPayload::RipasChange {
+            base: 0,
+            size: 0x1000,
+            state: 0,
+        };
+        assert!(marshal_exit(&mut run, &ExitReason::HostCall, &ripas).is_err());
+        assert!(marshal_exit(&mut run, &ExitReason::RipasChange, &ExitPayload::None).is_err());
+        assert!(marshal_exit(&mut run, &ExitReason::Sync, &ripas).is_err());
+        assert_eq!(run.exit_reason(), 0);
+
+        marshal_exit(&mut run, &ExitReason::Irq, &ExitPayload::None).unwrap();
+        assert_eq!(run.exit_reason(), rmi::EXIT_IRQ);
+        assert_eq!(run.run_hint(), RunHint::Runnable as u8);
+    }
+}
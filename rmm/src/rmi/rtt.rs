@@ -4,19 +4,19 @@ use super::realm::{rd::State, Rd};
 use super::rec::Rec;
 use crate::event::Mainloop;
 use crate::granule::{
-    is_granule_aligned, is_not_in_realm, set_granule, GranuleState, GRANULE_SHIFT, GRANULE_SIZE,
+    assign_granule, is_granule_aligned, is_not_in_realm, release_granule, GranuleState,
+    GRANULE_SHIFT, GRANULE_SIZE,
 };
 use crate::host;
 use crate::host::DataPage;
 use crate::listen;
 use crate::measurement::HashContext;
+use crate::mm::protected_ipa_boundary;
 use crate::realm::mm::stage2_tte::S2TTE;
 use crate::rmi;
 use crate::rmi::error::Error;
-#[cfg(not(feature = "gst_page_table"))]
+use crate::rsi::ripas::RIPAS_DESTROYED;
 use crate::{get_granule, get_granule_if};
-#[cfg(feature = "gst_page_table")]
-use crate::{get_granule, get_granule_if, set_state_and_get_granule};
 
 pub const RTT_MIN_BLOCK_LEVEL: usize = 2;
 pub const RTT_PAGE_LEVEL: usize = 3;
@@ -25,7 +25,7 @@ pub const S2TTE_STRIDE: usize = GRANULE_SHIFT - 3;
 const RIPAS_EMPTY: u64 = 0;
 const RIPAS_RAM: u64 = 1;
 
-fn level_to_size(level: usize) -> u64 {
+pub fn level_to_size(level: usize) -> u64 {
     // TODO: get the translation granule from src/armv9
     match level {
         0 => 512 << 30, // 512GB
@@ -36,6 +36,44 @@ fn level_to_size(level: usize) -> u64 {
     }
 }
 
+/// Registers the diagnostics which expose the mappings of realms to the host,
+/// so they are left out of production builds.
+#[cfg(feature = "diagnostics")]
+pub fn set_diagnostics_handler(mainloop: &mut Mainloop) {
+    // Translates a realm IPA to the PA backing it:
+    //   x1: the PA, x2: the level of the mapping
+    listen!(mainloop, rmi::RTT_TRANSLATE_GET, |arg, ret, _rmm| {
+        let rd_granule = get_granule_if!(arg[0], GranuleState::RD)?;
+        let rd = rd_granule.content::<Rd>();
+
+        let (pa, level) = crate::rtt::translate(rd, arg[1])?;
+        ret[1] = pa;
+        ret[2] = level;
+        Ok(())
+    });
+
+    // Finds the regions of the protected IPA space from the IPA at x1
+    // whose RIPAS isn't defined by the RTT, up to MAX_UNACCOUNTED_REGIONS regions:
+    //   x1: the number of the regions, x2 and x3: the base and the size of the first one
+    //   x4: the IPA to continue from, which is the protected IPA boundary at the end
+    listen!(mainloop, rmi::RTT_UNACCOUNTED_GET, |arg, ret, _rmm| {
+        let rd_granule = get_granule_if!(arg[0], GranuleState::RD)?;
+        let rd = rd_granule.content::<Rd>();
+
+        let (gaps, next) = crate::rtt::unaccounted(rd, arg[1])?;
+        for gap in &gaps {
+            warn!("RIPAS unaccounted for: {:X} ~ {:X}", gap.start, gap.end);
+        }
+        ret[1] = gaps.len();
+        if let Some(gap) = gaps.first() {
+            ret[2] = gap.start;
+            ret[3] = gap.len();
+        }
+        ret[4] = next;
+        Ok(())
+    });
+}
+
 fn is_valid_rtt_cmd(ipa: usize, level: usize) -> bool {
     if level > RTT_PAGE_LEVEL {
         return false;
@@ -53,6 +91,69 @@ fn is_valid_rtt_cmd(ipa: usize, level: usize) -> bool {
     true
 }
 
+/// Checks that a table at `level` can be created to translate `ipa`.
+///
+/// The tables of the starting level are created along with the realm,
+/// so the new table must be below `start_level` and not below the page level.
+/// Level 0 tables are created as well when the realm starts at level -1 (LPA2).
+/// This bounds the depth of the RTT walk to the levels required by `ipa_bits`.
+fn is_valid_rtt_create(ipa: usize, level: usize, ipa_bits: usize, start_level: isize) -> bool {
+    if level as isize <= start_level || level > RTT_PAGE_LEVEL {
+        return false;
+    }
+    if ipa_bits >= usize::BITS as usize || ipa >> ipa_bits != 0 {
+        return false;
+    }
+    is_valid_rtt_cmd(ipa, level)
+}
+
+/// Checks that the entry at `ipa` and `level` can take the RIPAS requested up to `end`.
+///
+/// The entry must start at `ipa` and lie within the range, so that a RIPAS change
+/// never applies to a part of a block, e.g., the range straddling a 2MB boundary
+/// is set page by page rather than by the block either side of it.
+fn is_valid_ripas_range(ipa: usize, level: usize, end: u64) -> bool {
+    if !is_valid_rtt_cmd(ipa, level) {
+        return false;
+    }
+    match (ipa as u64).checked_add(level_to_size(level)) {
+        Some(top) => top <= end,
+        None => false,
+    }
+}
+
+/// Runs `change` on the RIPAS of the entry at `ipa`, which leaves its RIPAS RAM or not (`to_ram`),
+/// accounting the RAM committed to the realm by the change.
+///
+/// The entry is read before `change` writes to it, so that nothing can fail once it is changed.
+fn account_ram<T>(
+    rd: &mut Rd,
+    ipa: usize,
+    level: usize,
+    to_ram: bool,
+    change: impl FnOnce(&Rd) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let was_ram = crate::rtt::is_ram(rd, ipa, level)?;
+    let res = change(rd)?;
+    rd.committed_ram
+        .account(level_to_size(level) as usize, was_ram, to_ram);
+    Ok(res)
+}
+
+/// Changes the RIPAS of the RTT entry of `ipa` at `level` to `ripas`,
+/// accounting the committed RAM of the realm.
+pub fn set_ripas(rd: &mut Rd, ipa: usize, level: usize, ripas: u64) -> Result<(), Error> {
+    let to_ram = match ripas {
+        RIPAS_EMPTY => false,
+        RIPAS_RAM => true,
+        _ => return Err(Error::RmiErrorInput),
+    };
+    account_ram(rd, ipa, level, to_ram, |rd| match to_ram {
+        false => crate::rtt::make_shared(rd, ipa, level),
+        true => crate::rtt::make_exclusive(rd, ipa, level),
+    })
+}
+
 pub fn set_event_handler(mainloop: &mut Mainloop) {
     listen!(mainloop, rmi::RTT_CREATE, |arg, _ret, _rmm| {
         let rtt_addr = arg[0];
@@ -61,7 +162,7 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
         let ipa = arg[2];
         let level = arg[3];
 
-        if !is_valid_rtt_cmd(ipa, level) {
+        if !is_valid_rtt_create(ipa, level, rd.ipa_bits(), rd.s2_starting_level()) {
             return Err(Error::RmiErrorInput);
         }
         if rtt_addr == arg[1] {
@@ -85,6 +186,22 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
         Ok(())
     });
 
+    // Folds the RTT at `level` covering `ipa` into a single entry of its parent:
+    //   x1: the address of the RTT granule, which is delegated again
+    listen!(mainloop, rmi::RTT_FOLD, |arg, ret, _rmm| {
+        let rd_granule = get_granule_if!(arg[0], GranuleState::RD)?;
+        let rd = rd_granule.content::<Rd>();
+        let ipa = arg[1];
+        let level = arg[2];
+
+        // only a table which RTT_CREATE could have created can be folded
+        if !is_valid_rtt_create(ipa, level, rd.ipa_bits(), rd.s2_starting_level()) {
+            return Err(Error::RmiErrorInput);
+        }
+        ret[1] = crate::rtt::fold(rd, ipa, level)?;
+        Ok(())
+    });
+
     listen!(mainloop, rmi::RTT_INIT_RIPAS, |arg, _ret, _rmm| {
         let mut rd_granule = get_granule_if!(arg[0], GranuleState::RD)?;
         let rd = rd_granule.content_mut::<Rd>();
@@ -97,7 +214,9 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
         if !is_valid_rtt_cmd(ipa, level) {
             return Err(Error::RmiErrorInput);
         }
-        crate::rtt::init_ripas(rd, ipa, level)?;
+        account_ram(rd, ipa, level, true, |rd| {
+            crate::rtt::init_ripas(rd, ipa, level)
+        })?;
 
         #[cfg(not(kani))]
         // `rsi` is currently not reachable in model checking harnesses
@@ -111,8 +230,8 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
         let level = arg[3];
         let ripas = arg[4];
 
-        let rd_granule = get_granule_if!(arg[0], GranuleState::RD)?;
-        let rd = rd_granule.content::<Rd>();
+        let mut rd_granule = get_granule_if!(arg[0], GranuleState::RD)?;
+        let rd = rd_granule.content_mut::<Rd>();
         let mut rec_granule = get_granule_if!(arg[1], GranuleState::Rec)?;
         let rec = rec_granule.content_mut::<Rec<'_>>();
 
@@ -134,18 +253,17 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
             return Err(Error::RmiErrorInput);
         }
 
-        let map_size = level_to_size(level);
-        if ipa as u64 + map_size > rec.ripas_end() {
+        if !is_valid_ripas_range(ipa, level, rec.ripas_end()) {
             return Err(Error::RmiErrorInput);
         }
-
-        if ripas as u64 == RIPAS_EMPTY {
-            crate::rtt::make_shared(rd, ipa, level)?;
-        } else if ripas as u64 == RIPAS_RAM {
-            crate::rtt::make_exclusive(rd, ipa, level)?;
-        } else {
-            unreachable!();
+        // a page destroyed since the request is kept so unless the realm allowed it
+        let destroyed = matches!(crate::rtt::ripas_at(rd, ipa), Ok((RIPAS_DESTROYED, _)));
+        if destroyed && !rec.ripas_flags().change_destroyed() {
+            return Err(Error::RmiErrorInput);
         }
+        let map_size = level_to_size(level);
+
+        set_ripas(rd, ipa, level, ripas as u64)?;
         rec.inc_ripas_addr(map_size);
         Ok(())
     });
@@ -195,25 +313,29 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
 
         // data granule lock for the target page
         let mut target_page_granule = get_granule_if!(target_pa, GranuleState::Delegated)?;
-        let target_page = target_page_granule.content_mut::<DataPage>();
-        #[cfg(not(kani))]
-        // `page_table` is currently not reachable in model checking harnesses
-        rmm.page_table.map(target_pa, true);
 
-        // read src page
-        let src_page = host::copy_from::<DataPage>(src_pa).ok_or(Error::RmiErrorInput)?;
+        // read src page into the staging buffer, which unmaps src
+        // before the target page is mapped
+        let staged = host::stage_from(src_pa).ok_or(Error::RmiErrorInput)?;
 
         #[cfg(not(kani))]
         // `rsi` is currently not reachable in model checking harnesses
-        HashContext::new(rd)?.measure_data_granule(&src_page, ipa, flags)?;
+        HashContext::new(rd)?.measure_data_granule(staged.page(), ipa, flags)?;
+
+        #[cfg(not(kani))]
+        // `page_table` is currently not reachable in model checking harnesses
+        rmm.page_table.map(target_pa, true);
+        let target_page = target_page_granule.content_mut::<DataPage>();
 
         // 3. copy src to _data
-        *target_page = src_page;
+        staged.copy_to(target_page);
+        // zeroes and releases the staging buffer
+        core::mem::drop(staged);
 
         // 4. map ipa to taget_pa in S2 table
         crate::rtt::data_create(rd, ipa, target_pa)?;
 
-        set_granule(&mut target_page_granule, GranuleState::Data)?;
+        assign_granule(&mut target_page_granule, GranuleState::Data, rd.id())?;
         Ok(())
     });
 
@@ -229,6 +351,12 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
         let rd_granule = get_granule_if!(arg[1], GranuleState::RD)?;
         let rd = rd_granule.content::<Rd>();
 
+        // Unmeasured data is not allowed for the realm
+        // which requires the RIM to cover all initial memory.
+        if rd.measured_data_only() {
+            return Err(Error::RmiErrorRealm(0));
+        }
+
         validate_ipa(ipa, rd.ipa_bits())?;
 
         // 0. Make sure granule state can make a transition to DATA
@@ -243,26 +371,24 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
 
         // TODO: 2. perform measure
         // L0czek - not needed here see: tf-rmm/runtime/rmi/rtt.c:883
-        set_granule(&mut target_page_granule, GranuleState::Data)?;
+        assign_granule(&mut target_page_granule, GranuleState::Data, rd.id())?;
         Ok(())
     });
 
     listen!(mainloop, rmi::DATA_DESTROY, |arg, _ret, _rmm| {
         // rd granule lock
-        let rd_granule = get_granule_if!(arg[0], GranuleState::RD)?;
-        let rd = rd_granule.content::<Rd>();
+        let mut rd_granule = get_granule_if!(arg[0], GranuleState::RD)?;
+        let rd = rd_granule.content_mut::<Rd>();
         let ipa = arg[1];
 
-        let pa = crate::rtt::data_destroy(rd, ipa)?;
+        // the RIPAS of a destroyed entry is not RAM
+        let pa = account_ram(rd, ipa, RTT_PAGE_LEVEL, false, |rd| {
+            crate::rtt::data_destroy(rd, ipa)
+        })?;
 
         // data granule lock and change state
-        #[cfg(feature = "gst_page_table")]
-        set_state_and_get_granule!(pa, GranuleState::Delegated)?;
-        #[cfg(not(feature = "gst_page_table"))]
-        {
-            let mut granule = get_granule!(pa)?;
-            set_granule(&mut granule, GranuleState::Delegated)?;
-        }
+        let mut granule = get_granule!(pa)?;
+        release_granule(&mut granule)?;
         Ok(())
     });
 
@@ -303,32 +429,178 @@ pub fn set_event_handler(mainloop: &mut Mainloop) {
     });
 }
 
-fn realm_ipa_size(ipa_bits: usize) -> usize {
-    1 << ipa_bits
+/// Where an IPA lies in the IPA space of a realm.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IpaSpace {
+    /// The lower half of the IPA space, below `protected_ipa_boundary`.
+    Protected,
+    /// The upper half of the IPA space.
+    Unprotected,
+    /// Beyond the IPA space, i.e., any bit at or above `ipa_bits` is set.
+    Invalid,
 }
 
-pub fn realm_par_size(ipa_bits: usize) -> usize {
-    realm_ipa_size(ipa_bits) / 2
+/// Classifies `ipa` of a realm whose IPAs are `ipa_bits` wide.
+pub fn ipa_space(ipa: usize, ipa_bits: usize) -> IpaSpace {
+    let in_space = match 1usize.checked_shl(ipa_bits as u32) {
+        Some(size) => ipa < size,
+        None => ipa_bits >= usize::BITS as usize,
+    };
+    if !in_space {
+        IpaSpace::Invalid
+    } else if ipa < protected_ipa_boundary(ipa_bits) {
+        IpaSpace::Protected
+    } else {
+        IpaSpace::Unprotected
+    }
 }
 
 pub fn is_protected_ipa(ipa: usize, ipa_bits: usize) -> bool {
-    ipa < realm_par_size(ipa_bits)
+    ipa_space(ipa, ipa_bits) == IpaSpace::Protected
 }
 
+/// Checks that the whole range `[start, end)` lies within the protected IPA space,
+/// not just its endpoints. An empty or wrapped-around range is not protected.
+pub fn is_protected_range(start: usize, end: usize, ipa_bits: usize) -> bool {
+    start < end && end <= protected_ipa_boundary(ipa_bits)
+}
+
+/// Checks that `ipa` is the page-aligned address of a page in the protected IPA space
+/// of a realm whose IPAs are `ipa_bits` wide, which the RIPAS applies to.
+///
+/// It rejects an unaligned IPA, an unprotected one and any IPA with a bit set
+/// at or above `ipa_bits`. Use `ipa_space` to tell an unprotected IPA from an invalid one.
 pub fn validate_ipa(ipa: usize, ipa_bits: usize) -> Result<(), Error> {
     if !is_granule_aligned(ipa) {
         error!("ipa: {:x} is not aligned with {:x}", ipa, GRANULE_SIZE);
         return Err(Error::RmiErrorInput);
     }
 
-    if !is_protected_ipa(ipa, ipa_bits) {
-        error!(
-            "ipa: {:x} is not in protected ipa range {:x}",
-            ipa,
-            realm_par_size(ipa_bits)
+    match ipa_space(ipa, ipa_bits) {
+        IpaSpace::Protected => Ok(()),
+        IpaSpace::Unprotected => {
+            error!(
+                "ipa: {:x} is not in protected ipa range {:x}",
+                ipa,
+                protected_ipa_boundary(ipa_bits)
+            );
+            Err(Error::RmiErrorInput)
+        }
+        IpaSpace::Invalid => {
+            error!("ipa: {:x} is beyond the {}-bit ipa space", ipa, ipa_bits);
+            Err(Error::RmiErrorInput)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const IPA_BITS: usize = 40;
+    const START_LEVEL: isize = 1;
+
+    #[test]
+    fn protected_range() {
+        let par_size = protected_ipa_boundary(IPA_BITS);
+        assert!(is_protected_range(0x0, 0x1000, IPA_BITS));
+        assert!(is_protected_range(par_size - 0x1000, par_size, IPA_BITS));
+
+        // crossing into the unprotected IPA space
+        assert!(!is_protected_range(
+            par_size - 0x1000,
+            par_size + 0x1000,
+            IPA_BITS
+        ));
+        // both endpoints are protected but the range wraps around through the unprotected space
+        assert!(is_protected_ipa(par_size - 0x1000, IPA_BITS));
+        assert!(is_protected_ipa(0x1000 - 1, IPA_BITS));
+        assert!(!is_protected_range(par_size - 0x1000, 0x1000, IPA_BITS));
+        // empty
+        assert!(!is_protected_range(0x1000, 0x1000, IPA_BITS));
+    }
+
+    #[test]
+    fn ipa_bits_boundary() {
+        let top = 1usize << IPA_BITS;
+        let par_size = protected_ipa_boundary(IPA_BITS);
+        assert_eq!(ipa_space(par_size - 0x1000, IPA_BITS), IpaSpace::Protected);
+        assert_eq!(ipa_space(par_size, IPA_BITS), IpaSpace::Unprotected);
+        assert_eq!(ipa_space(top - 1, IPA_BITS), IpaSpace::Unprotected);
+        // exactly at and just above the width of the IPA space
+        assert_eq!(ipa_space(top, IPA_BITS), IpaSpace::Invalid);
+        assert_eq!(ipa_space(top + 0x1000, IPA_BITS), IpaSpace::Invalid);
+        assert_eq!(ipa_space(usize::MAX, IPA_BITS), IpaSpace::Invalid);
+        assert_eq!(
+            ipa_space(usize::MAX, usize::BITS as usize),
+            IpaSpace::Unprotected
         );
-        return Err(Error::RmiErrorInput);
+
+        assert!(validate_ipa(par_size - 0x1000, IPA_BITS).is_ok());
+        assert!(validate_ipa(par_size - 0x800, IPA_BITS).is_err());
+        assert!(validate_ipa(top, IPA_BITS).is_err());
+        assert!(validate_ipa(top + 0x1000, IPA_BITS).is_err());
     }
 
-    Ok(())
+    #[test]
+    fn ripas_range_across_block() {
+        const BLOCK: usize = 2 << 20;
+        const PAGE: usize = 1 << 12;
+        let (start, end) = (BLOCK - PAGE, (2 * BLOCK + PAGE) as u64);
+
+        // the block entries aren't applied across the boundary
+        assert!(!is_valid_ripas_range(start, 2, end));
+        assert!(!is_valid_ripas_range(BLOCK, 2, (BLOCK + PAGE) as u64));
+        // but the range is split into the page before and the block after it
+        assert!(is_valid_ripas_range(start, RTT_PAGE_LEVEL, end));
+        assert!(is_valid_ripas_range(BLOCK, 2, end));
+        assert!(is_valid_ripas_range(2 * BLOCK, RTT_PAGE_LEVEL, end));
+        assert!(!is_valid_ripas_range(2 * BLOCK + PAGE, RTT_PAGE_LEVEL, end));
+    }
+
+    #[test]
+    fn rtt_create_legal_depth() {
+        assert!(is_valid_rtt_create(0x4000_0000, 2, IPA_BITS, START_LEVEL));
+        assert!(is_valid_rtt_create(
+            0x4020_0000,
+            RTT_PAGE_LEVEL,
+            IPA_BITS,
+            START_LEVEL
+        ));
+    }
+
+    #[test]
+    fn rtt_create_beyond_depth() {
+        // below the page level
+        assert!(!is_valid_rtt_create(
+            0x4020_1000,
+            RTT_PAGE_LEVEL + 1,
+            IPA_BITS,
+            START_LEVEL
+        ));
+        assert!(!is_valid_rtt_create(0x0, usize::MAX, IPA_BITS, START_LEVEL));
+        // the tables of the starting level are created along with the realm
+        assert!(!is_valid_rtt_create(
+            0x0,
+            START_LEVEL as usize,
+            IPA_BITS,
+            START_LEVEL
+        ));
+        assert!(!is_valid_rtt_create(0x0, 0, IPA_BITS, 0));
+        // level 0 is below the starting level of an LPA2 realm
+        assert!(is_valid_rtt_create(0x0, 0, 52, -1));
+        // outside of the IPA space
+        assert!(!is_valid_rtt_create(
+            1 << IPA_BITS,
+            2,
+            IPA_BITS,
+            START_LEVEL
+        ));
+        assert!(!is_valid_rtt_create(
+            0x0,
+            2,
+            usize::BITS as usize,
+            START_LEVEL
+        ));
+    }
 }
@@ -53,8 +53,6 @@ pub(super) fn get_realm_attest_key() {
 
     let v = utils::va_to_vec(*guard, buflen);
     utils::set_vector(v, &REALM_ATTEST_KEY);
-
-    debug!("{:x?}", super::realm_attest_key());
 }
 
 pub(super) fn get_plat_token() {
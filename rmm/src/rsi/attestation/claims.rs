@@ -13,6 +13,9 @@ pub const EXTENSIBLE_MEASUREMENTS_LABEL: u64 = 44239;
 pub const HASH_ALGO_ID_LABEL: u64 = 44236;
 pub const PUBLIC_KEY_LABEL: u64 = 44237;
 pub const PUBLIC_KEY_HASH_ALOG_ID_LABEL: u64 = 44240;
+// NOTE: the labels below are not part of the CCA token but our own claims
+pub const COMMITTED_RAM_LABEL: u64 = 44300;
+pub const REALM_DEBUG_LABEL: u64 = 44301;
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct MeasurementEntry(Measurement, usize);
@@ -28,7 +31,9 @@ impl<T: Copy + Default, const N: usize> Data<T, N> {
 
 pub const REM_SLOT_NR: usize = MEASUREMENTS_SLOT_NR - 1;
 
-pub type Challenge = Data<u8, 64>;
+pub const CHALLENGE_SIZE: usize = 64;
+
+pub type Challenge = Data<u8, CHALLENGE_SIZE>;
 pub type PersonalizationValue = Data<u8, 64>;
 pub type REMs = Data<MeasurementEntry, REM_SLOT_NR>;
 pub type RIM = MeasurementEntry;
@@ -72,6 +77,22 @@ where
     }
 }
 
+/// The number of granules whose RIPAS is RAM at attestation time.
+pub fn committed_ram(granules: usize) -> Claim<u64> {
+    Claim {
+        label: COMMITTED_RAM_LABEL,
+        value: granules as u64,
+    }
+}
+
+/// Tells that the realm was created with debug access.
+pub fn realm_debug() -> Claim<bool> {
+    Claim {
+        label: REALM_DEBUG_LABEL,
+        value: true,
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct RealmClaims {
     pub challenge: Claim<Challenge>,
@@ -0,0 +1,105 @@
+use crate::rmi::rec::RmmRecAttestState;
+use crate::rmi::rtt::validate_ipa;
+use crate::rsi::{ERROR_INPUT, ERROR_STATE};
+
+/// Reasons an attestation request of a realm fails.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttestFailure {
+    /// The token buffer passed by the realm is not a valid IPA or is not mapped.
+    BadInput,
+    /// The token is requested without initializing the attestation.
+    BadState,
+    /// The token can't be signed, e.g., with a broken RAK.
+    Signing,
+    /// The token is larger than the RMM hands out to the realm.
+    TokenTooLarge,
+}
+
+impl AttestFailure {
+    pub fn rsi_error(&self) -> usize {
+        match self {
+            AttestFailure::BadInput => ERROR_INPUT,
+            AttestFailure::BadState | AttestFailure::Signing | AttestFailure::TokenTooLarge => {
+                ERROR_STATE
+            }
+        }
+    }
+}
+
+/// Counts the attestation requests of a realm by their outcome,
+/// so that a realm repeatedly failing attestation can be spotted.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AttestMetrics {
+    successes: u64,
+    bad_input: u64,
+    bad_state: u64,
+    signing: u64,
+    token_too_large: u64,
+}
+
+impl AttestMetrics {
+    pub const fn new() -> Self {
+        Self {
+            successes: 0,
+            bad_input: 0,
+            bad_state: 0,
+            signing: 0,
+            token_too_large: 0,
+        }
+    }
+
+    pub fn success(&mut self) {
+        self.successes = self.successes.saturating_add(1);
+    }
+
+    pub fn failure(&mut self, failure: AttestFailure) {
+        let counter = match failure {
+            AttestFailure::BadInput => &mut self.bad_input,
+            AttestFailure::BadState => &mut self.bad_state,
+            AttestFailure::Signing => &mut self.signing,
+            AttestFailure::TokenTooLarge => &mut self.token_too_large,
+        };
+        *counter = counter.saturating_add(1);
+    }
+
+    pub fn successes(&self) -> u64 {
+        self.successes
+    }
+
+    pub fn failures(&self, failure: AttestFailure) -> u64 {
+        match failure {
+            AttestFailure::BadInput => self.bad_input,
+            AttestFailure::BadState => self.bad_state,
+            AttestFailure::Signing => self.signing,
+            AttestFailure::TokenTooLarge => self.token_too_large,
+        }
+    }
+
+    /// Encodes the counters into the output registers of RMI_REALM_ATTEST_METRICS_GET:
+    ///   x1: the number of tokens returned
+    ///   x2: the number of requests failed with a bad input
+    ///   x3: the number of requests failed in a bad state
+    ///   x4: the number of requests failed to sign the token
+    ///   x5: the number of requests failed with a token too large to return
+    pub fn to_regs(&self) -> [usize; 5] {
+        [
+            self.successes as usize,
+            self.bad_input as usize,
+            self.bad_state as usize,
+            self.signing as usize,
+            self.token_too_large as usize,
+        ]
+    }
+}
+
+/// Checks a request to continue the attestation with the token buffer at `ipa`.
+pub fn check_continue(
+    state: RmmRecAttestState,
+    ipa: usize,
+    ipa_bits: usize,
+) -> Result<(), AttestFailure> {
+    if state != RmmRecAttestState::AttestInProgress {
+        return Err(AttestFailure::BadState);
+    }
+    validate_ipa(ipa, ipa_bits).or(Err(AttestFailure::BadInput))
+}
@@ -1,4 +1,5 @@
 pub mod claims;
+pub mod metrics;
 
 use alloc::{boxed::Box, string::String, vec, vec::Vec};
 use ciborium::{ser, Value};
@@ -7,12 +8,26 @@ use ecdsa::signature::Signer;
 use tinyvec::ArrayVec;
 
 use crate::{
-    measurement::Measurement,
-    rmi::{HASH_ALGO_SHA256, HASH_ALGO_SHA512},
+    granule::GRANULE_SIZE,
+    measurement::{HashAlgo, Measurement},
 };
 
 use self::claims::RealmClaims;
+use self::metrics::{check_continue, AttestFailure};
+use crate::config::{CLAIM_COMMITTED_RAM, MAX_ATTEST_TOKEN_SIZE};
+use crate::granule::GranuleState;
+use crate::realm::context::{get_reg, set_reg};
+use crate::realm::mm::address::GuestPhysAddr;
+use crate::rmi;
+use crate::rmi::error::Error;
+use crate::rmi::realm::Rd;
+use crate::rmi::rec::run::Run;
+use crate::rmi::rec::{Rec, RmmRecAttestState};
+use crate::rmi::rtt::RTT_PAGE_LEVEL;
 use crate::rmm_el3::{plat_token, realm_attest_key};
+use crate::rsi::{INCOMPLETE, SUCCESS};
+use crate::Monitor;
+use crate::{get_granule, get_granule_if};
 
 const DUMMY_PERSONALIZATION_VALUE: [u8; 64] = [0; 64];
 
@@ -46,54 +61,73 @@ impl Attestation {
         self.rak_priv = key_priv.iter().cloned().collect();
     }
 
-    // TODO: Consider returning errors.
-    // Though all errors in here are programmer errors
-    // or a result of incorrect data passed from HES.
     pub fn create_attestation_token(
         &self,
         challenge: &[u8],
         measurements: &[Measurement],
-        hash_algo: u8,
-    ) -> Vec<u8> {
+        hash_algo: HashAlgo,
+        committed_ram: Option<usize>,
+        debug: bool,
+    ) -> Result<Vec<u8>, SigningError> {
         let mut cca_token = Vec::new();
 
-        let realm_token = self.create_realm_token(challenge, measurements, hash_algo);
+        let realm_token =
+            self.create_realm_token(challenge, measurements, hash_algo, committed_ram, debug)?;
 
         let realm_token_entry = (
             Value::Integer(CCA_REALM_DELEGATED_TOKEN.into()),
             Value::Bytes(realm_token),
         );
 
-        let platform_token_entry = (
-            Value::Integer(CCA_PLATFORM_TOKEN.into()),
-            Value::Bytes(self.platform_token.to_vec()),
-        );
+        let token_map: Vec<(Value, Value)> = vec![self.platform_token_entry(), realm_token_entry];
+
+        ser::into_writer(
+            &Value::Tag(CCA_TOKEN_COLLECTION, Box::new(canonical_map(token_map))),
+            &mut cca_token,
+        )
+        .or(Err(SigningError::Encoding))?;
+
+        Ok(cca_token)
+    }
+
+    /// Creates a CCA token collection which carries only the platform token.
+    pub fn create_platform_token(&self) -> Result<Vec<u8>, SigningError> {
+        let mut cca_token = Vec::new();
 
-        let token_map: Vec<(Value, Value)> = vec![platform_token_entry, realm_token_entry];
+        let token_map: Vec<(Value, Value)> = vec![self.platform_token_entry()];
 
         ser::into_writer(
-            &Value::Tag(CCA_TOKEN_COLLECTION, Box::new(Value::Map(token_map))),
+            &Value::Tag(CCA_TOKEN_COLLECTION, Box::new(canonical_map(token_map))),
             &mut cca_token,
         )
-        .expect("Failed to serialize CCA token");
+        .or(Err(SigningError::Encoding))?;
+
+        Ok(cca_token)
+    }
 
-        cca_token
+    fn platform_token_entry(&self) -> (Value, Value) {
+        (
+            Value::Integer(CCA_PLATFORM_TOKEN.into()),
+            Value::Bytes(self.platform_token.to_vec()),
+        )
     }
 
     fn create_realm_token(
         &self,
         challenge: &[u8],
         measurements: &[Measurement],
-        hash_algo: u8,
-    ) -> Vec<u8> {
-        let hash_algo_id = match hash_algo {
-            HASH_ALGO_SHA256 => String::from("sha-256"),
-            HASH_ALGO_SHA512 => String::from("sha-512"),
-            _ => panic!("Unrecognized hash algorithm {}", hash_algo),
-        };
+        hash_algo: HashAlgo,
+        committed_ram: Option<usize>,
+        debug: bool,
+    ) -> Result<Vec<u8>, SigningError> {
+        if challenge.len() > claims::CHALLENGE_SIZE {
+            return Err(SigningError::BadChallenge);
+        }
+
+        let hash_algo_id = String::from(hash_algo.claim_id());
 
         let secret_key =
-            p384::SecretKey::from_slice(&self.rak_priv).expect("Failed to import private RAK.");
+            p384::SecretKey::from_slice(&self.rak_priv).or(Err(SigningError::BadKey))?;
 
         let public_key = secret_key.public_key().to_sec1_bytes().to_vec();
 
@@ -107,7 +141,7 @@ impl Attestation {
             String::from("sha-256"),
         );
 
-        let claims_map: Vec<(Value, Value)> = vec![
+        let mut claims_map: Vec<(Value, Value)> = vec![
             claims.challenge.into(),
             claims.personalization_value.into(),
             claims.rim.into(),
@@ -116,10 +150,16 @@ impl Attestation {
             claims.rak_pub.into(),
             claims.rak_pub_hash_algo.into(),
         ];
+        if let Some(granules) = committed_ram {
+            claims_map.push(claims::committed_ram(granules).into());
+        }
+        if debug {
+            claims_map.push(claims::realm_debug().into());
+        }
 
         let mut realm_token = Vec::new();
-        ser::into_writer(&Value::Map(claims_map), &mut realm_token)
-            .expect("Failed to serialize realm token");
+        ser::into_writer(&canonical_map(claims_map), &mut realm_token)
+            .or(Err(SigningError::Encoding))?;
 
         let protected = HeaderBuilder::new()
             .algorithm(coset::iana::Algorithm::ES384)
@@ -128,43 +168,697 @@ impl Attestation {
         let sign1 = CoseSign1Builder::new()
             .protected(protected)
             .payload(realm_token)
-            .create_signature(b"", |payload| Self::sign(secret_key, payload))
+            .try_create_signature(b"", |payload| Self::sign(secret_key, payload))?
             .build();
 
-        sign1
-            .to_tagged_vec()
-            .expect("Failed to create tagged signed token")
+        sign1.to_tagged_vec().or(Err(SigningError::Encoding))
     }
 
-    fn sign(secret_key: p384::SecretKey, data: &[u8]) -> Vec<u8> {
+    fn sign(secret_key: p384::SecretKey, data: &[u8]) -> Result<Vec<u8>, SigningError> {
         let signing_key = p384::ecdsa::SigningKey::from_bytes(&secret_key.to_bytes())
-            .expect("Failed to generate signing key");
+            .or(Err(SigningError::BadKey))?;
 
         let signature: p384::ecdsa::Signature = signing_key
             .try_sign(data)
-            .expect("Failed to create P384 signature");
-        signature.to_vec()
+            .or(Err(SigningError::Signature))?;
+        Ok(signature.to_vec())
     }
 }
 
+/// Reasons the realm token can't be created.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SigningError {
+    /// The challenge is larger than the challenge claim.
+    BadChallenge,
+    /// The RAK is not a valid EC-P384 private key.
+    BadKey,
+    /// The signature over the realm claims can't be created.
+    Signature,
+    /// The token can't be encoded in CBOR.
+    Encoding,
+}
+
+/// Returns the CBOR encoding of a map key, which orders the keys of a canonical map.
+fn encoded_key(key: &Value) -> (usize, Vec<u8>) {
+    let mut encoded = Vec::new();
+    ser::into_writer(key, &mut encoded).expect("Failed to serialize map key");
+    (encoded.len(), encoded)
+}
+
+/// Builds a map in the canonical CBOR order (RFC 8949, Section 4.2.3),
+/// i.e., keys sorted by the length of their encodings and then bytewise,
+/// so that the tokens are byte-reproducible.
+fn canonical_map(mut entries: Vec<(Value, Value)>) -> Value {
+    entries.sort_by_cached_key(|(key, _)| encoded_key(key));
+    Value::Map(entries)
+}
+
 pub fn get_token(
-    attest_pa: usize,
     challenge: &[u8],
     measurements: &[Measurement],
-    hash_algo: u8,
-) -> usize {
+    hash_algo: HashAlgo,
+    committed_ram: Option<usize>,
+    debug: bool,
+) -> Result<Vec<u8>, SigningError> {
     // TODO: consider storing attestation object somewhere,
     // as RAK and token do not change during rmm lifetime.
-    let token = Attestation::new(&plat_token(), &realm_attest_key()).create_attestation_token(
+    Attestation::new(&plat_token(), &realm_attest_key()).create_attestation_token(
         challenge,
         measurements,
         hash_algo,
-    );
+        committed_ram,
+        debug,
+    )
+}
+
+/// The token is larger than the maximum size which the RMM hands out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TokenTooLarge(pub usize);
+
+/// The token copied to the realm chunk by chunk over RSI_ATTEST_TOKEN_CONTINUE,
+/// with the progress of the copy.
+#[derive(Debug, PartialEq)]
+pub struct Continuation {
+    token: Vec<u8>,
+    offset: usize,
+    chunk_size: usize,
+}
+
+impl Continuation {
+    /// Keeps `token` to copy it in the chunk size the realm prefers, bounded to a granule.
+    /// A granule is used if the realm has no preference (i.e., 0).
+    /// It fails if the token exceeds `max_size`.
+    pub fn new(token: Vec<u8>, preferred: usize, max_size: usize) -> Result<Self, TokenTooLarge> {
+        if token.len() > max_size {
+            return Err(TokenTooLarge(token.len()));
+        }
+
+        let chunk_size = match preferred {
+            0 => GRANULE_SIZE,
+            size => core::cmp::min(size, GRANULE_SIZE),
+        };
+        Ok(Self {
+            token,
+            offset: 0,
+            chunk_size,
+        })
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Copies the next chunk of the token to `dst`.
+    /// Returns the number of the bytes copied and whether the whole token is copied.
+    pub fn copy_next(&mut self, dst: &mut [u8]) -> (usize, bool) {
+        let remaining = self.token.get(self.offset..).unwrap_or(&[]);
+        let len = core::cmp::min(remaining.len(), core::cmp::min(self.chunk_size, dst.len()));
+        dst[..len].copy_from_slice(&remaining[..len]);
+        self.offset += len;
+        (len, self.offset >= self.token.len())
+    }
+}
+
+/// Copies the platform-only token to the granule at `attest_pa`,
+/// failing if it can't be encoded or doesn't fit in the granule.
+pub fn get_platform_token(attest_pa: usize) -> Result<usize, AttestFailure> {
+    let res = Attestation::new(&plat_token(), &realm_attest_key()).create_platform_token();
+    let token = match res {
+        Ok(token) => token,
+        Err(err) => {
+            warn!("Failed to create platform token: {:?}", err);
+            return Err(AttestFailure::Signing);
+        }
+    };
+    if token.len() > GRANULE_SIZE {
+        warn!("Platform token too large: {} bytes", token.len());
+        return Err(AttestFailure::TokenTooLarge);
+    }
 
     unsafe {
         let pa_ptr = attest_pa as *mut u8;
         core::ptr::copy(token.as_ptr(), pa_ptr, token.len());
     }
 
-    token.len()
+    Ok(token.len())
+}
+
+pub fn token_init(
+    _arg: &[usize],
+    ret: &mut [usize],
+    _rmm: &Monitor,
+    rec: &mut Rec<'_>,
+    _run: &mut Run,
+) -> Result<(), Error> {
+    let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
+    init_token(rd_granule.content::<Rd>(), rec)?;
+    ret[0] = rmi::SUCCESS_REC_ENTER;
+    Ok(())
+}
+
+pub fn token_continue(
+    _arg: &[usize],
+    ret: &mut [usize],
+    _rmm: &Monitor,
+    rec: &mut Rec<'_>,
+    _run: &mut Run,
+) -> Result<(), Error> {
+    let mut rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
+    continue_token(rd_granule.content_mut::<Rd>(), rec)?;
+    ret[0] = rmi::SUCCESS_REC_ENTER;
+    Ok(())
+}
+
+pub fn platform_continue(
+    _arg: &[usize],
+    ret: &mut [usize],
+    _rmm: &Monitor,
+    rec: &mut Rec<'_>,
+    _run: &mut Run,
+) -> Result<(), Error> {
+    let mut rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
+    continue_platform_token(rd_granule.content_mut::<Rd>(), rec)?;
+    ret[0] = rmi::SUCCESS_REC_ENTER;
+    Ok(())
+}
+
+/// Starts the attestation of `rec` with the challenge in x2-x9.
+fn init_token(rd: &Rd, rec: &mut Rec<'_>) -> Result<(), Error> {
+    let vcpuid = rec.vcpuid();
+    let mut challenge: [u8; 64] = [0; 64];
+
+    for i in 0..8 {
+        let challenge_part = get_reg(rd, vcpuid, i + 2)?;
+        let start_idx = i * 8;
+        let end_idx = start_idx + 8;
+        challenge[start_idx..end_idx].copy_from_slice(&challenge_part.to_le_bytes());
+    }
+
+    rec.set_attest_challenge(&challenge);
+    rec.set_attest_state(RmmRecAttestState::AttestInProgress);
+    rec.set_attest_continuation(None);
+
+    set_reg(rd, vcpuid, 0, SUCCESS)?;
+    // the upper bound of the token size
+    set_reg(rd, vcpuid, 1, MAX_ATTEST_TOKEN_SIZE)
+}
+
+/// Counts `failure` in the attestation metrics of the realm and returns its error to the realm.
+fn fail(rd: &mut Rd, vcpuid: usize, failure: AttestFailure) -> Result<(), Error> {
+    rd.attest_metrics.failure(failure);
+    set_reg(rd, vcpuid, 0, failure.rsi_error())
+}
+
+/// Copies the next chunk of the token to the granule at the IPA in x1.
+fn continue_token(rd: &mut Rd, rec: &mut Rec<'_>) -> Result<(), Error> {
+    let vcpuid = rec.vcpuid();
+
+    let attest_ipa = get_reg(rd, vcpuid, 1)?;
+    if let Err(failure) = check_continue(rec.attest_state(), attest_ipa, rd.ipa_bits()) {
+        warn!(
+            "Rejected attest token continue at {:X}: {:?}",
+            attest_ipa, failure
+        );
+        return fail(rd, vcpuid, failure);
+    }
+
+    let res = rd
+        .s2_table()
+        .lock()
+        .ipa_to_pa(GuestPhysAddr::from(attest_ipa), RTT_PAGE_LEVEL);
+    let Some(pa) = res else {
+        warn!("Unmapped attest token buffer {:X}", attest_ipa);
+        return fail(rd, vcpuid, AttestFailure::BadInput);
+    };
+
+    // x2: the offset in the granule to copy the chunk to, x3: the preferred chunk size.
+    // The chunk size is negotiated on the first call and kept until the token is copied.
+    let offset = get_reg(rd, vcpuid, 2)?;
+
+    // The granule is a protected data granule mapped to the realm
+    // which is validated by the above stage 2 translation.
+    // An offset at the end of the granule leaves no room for the chunk to make progress.
+    if offset >= GRANULE_SIZE {
+        warn!("Wrong offset passed {:X}", offset);
+        return fail(rd, vcpuid, AttestFailure::BadInput);
+    }
+    let pa: usize = pa.into();
+    let buffer = unsafe { core::slice::from_raw_parts_mut(pa as *mut u8, GRANULE_SIZE) };
+    let chunk = &mut buffer[offset..];
+
+    // The token is generated and signed once at the first call,
+    // and the following calls copy the rest of it.
+    let mut continuation = match rec.take_attest_continuation() {
+        Some(continuation) => continuation,
+        None => {
+            let committed_ram = CLAIM_COMMITTED_RAM.then(|| rd.committed_ram.granules());
+            #[cfg(not(kani))]
+            // `rsi` is currently not reachable in model checking harnesses
+            let res = get_token(
+                rec.attest_challenge(),
+                &rd.measurements,
+                rd.hash_algo(),
+                committed_ram,
+                rd.debug(),
+            );
+            #[cfg(kani)]
+            let res: Result<_, SigningError> = Ok(Vec::new());
+
+            let token = match res {
+                Ok(token) => token,
+                Err(err) => {
+                    warn!("Failed to create attestation token: {:?}", err);
+                    rec.set_attest_state(RmmRecAttestState::NoAttestInProgress);
+                    return fail(rd, vcpuid, AttestFailure::Signing);
+                }
+            };
+
+            let preferred = get_reg(rd, vcpuid, 3)?;
+            match Continuation::new(token, preferred, MAX_ATTEST_TOKEN_SIZE) {
+                Ok(continuation) => continuation,
+                Err(TokenTooLarge(size)) => {
+                    warn!("Attestation token too large: {} bytes", size);
+                    rec.set_attest_state(RmmRecAttestState::NoAttestInProgress);
+                    return fail(rd, vcpuid, AttestFailure::TokenTooLarge);
+                }
+            }
+        }
+    };
+
+    let (len, done) = continuation.copy_next(chunk);
+    if done {
+        set_reg(rd, vcpuid, 0, SUCCESS)?;
+        rd.attest_metrics.success();
+    } else {
+        rec.set_attest_continuation(Some(continuation));
+        set_reg(rd, vcpuid, 0, INCOMPLETE)?;
+    }
+    set_reg(rd, vcpuid, 1, len)
+}
+
+/// Copies the platform-only token to the granule at the IPA in x1.
+fn continue_platform_token(rd: &mut Rd, rec: &mut Rec<'_>) -> Result<(), Error> {
+    let vcpuid = rec.vcpuid();
+
+    let attest_ipa = get_reg(rd, vcpuid, 1)?;
+    if let Err(failure) = check_continue(rec.attest_state(), attest_ipa, rd.ipa_bits()) {
+        warn!(
+            "Rejected attest platform token continue at {:X}: {:?}",
+            attest_ipa, failure
+        );
+        return fail(rd, vcpuid, failure);
+    }
+
+    let res = rd
+        .s2_table()
+        .lock()
+        .ipa_to_pa(GuestPhysAddr::from(attest_ipa), RTT_PAGE_LEVEL);
+    let Some(pa) = res else {
+        warn!("Unmapped attest token buffer {:X}", attest_ipa);
+        return fail(rd, vcpuid, AttestFailure::BadInput);
+    };
+
+    #[cfg(not(kani))]
+    // `rsi` is currently not reachable in model checking harnesses
+    let res = get_platform_token(pa.into());
+    #[cfg(kani)]
+    let res: Result<usize, AttestFailure> = Ok(0);
+
+    match res {
+        Ok(attest_size) => {
+            set_reg(rd, vcpuid, 0, SUCCESS)?;
+            set_reg(rd, vcpuid, 1, attest_size)?;
+            rd.attest_metrics.success();
+            Ok(())
+        }
+        Err(failure) => {
+            rec.set_attest_state(RmmRecAttestState::NoAttestInProgress);
+            fail(rd, vcpuid, failure)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ciborium::de;
+
+    fn realm_claims(token: &[u8]) -> Vec<(Value, Value)> {
+        let Value::Tag(CCA_TOKEN_COLLECTION, map) = de::from_reader(token).unwrap() else {
+            panic!("Not a CCA token collection");
+        };
+        let Value::Map(map) = *map else {
+            panic!("Not a map");
+        };
+        let Value::Bytes(realm_token) = &map[1].1 else {
+            panic!("No realm token");
+        };
+        let sign1 = coset::CoseSign1::from_tagged_slice(realm_token).unwrap();
+        let Value::Map(claims) = de::from_reader(&sign1.payload.unwrap()[..]).unwrap() else {
+            panic!("Realm claims not a map");
+        };
+        claims
+    }
+
+    fn is_canonical(map: &[(Value, Value)]) -> bool {
+        map.windows(2)
+            .all(|pair| encoded_key(&pair[0].0) < encoded_key(&pair[1].0))
+    }
+
+    #[test]
+    fn canonical_token() {
+        let platform_token = [0xd2u8, 0x84, 0x40, 0xa0, 0x40, 0x40];
+        let measurements = [Measurement::empty(); crate::measurement::MEASUREMENTS_SLOT_NR];
+        let token = Attestation::new(&platform_token, &[1; 48])
+            .create_attestation_token(&[0; 64], &measurements, HashAlgo::Sha256, None, false)
+            .unwrap();
+
+        let Value::Tag(CCA_TOKEN_COLLECTION, map) = de::from_reader(&token[..]).unwrap() else {
+            panic!("Not a CCA token collection");
+        };
+        let Value::Map(map) = *map else {
+            panic!("Not a map");
+        };
+        assert!(is_canonical(&map));
+
+        let claims = realm_claims(&token);
+        assert_eq!(claims.len(), 7);
+        assert!(is_canonical(&claims));
+
+        // Claims with labels of different lengths are sorted by the length first
+        let map = canonical_map(vec![
+            (Value::Integer(44235.into()), Value::Null),
+            (Value::Integer(10.into()), Value::Null),
+            (Value::Integer(256.into()), Value::Null),
+        ]);
+        let Value::Map(map) = map else {
+            panic!("Not a map");
+        };
+        assert!(is_canonical(&map));
+        assert_eq!(map[0].0, Value::Integer(10.into()));
+    }
+
+    #[test]
+    fn committed_ram_claim() {
+        use crate::granule::GRANULE_SIZE;
+        use crate::rmi::realm::commitment::RamCommitment;
+
+        let platform_token = [0xd2u8, 0x84, 0x40, 0xa0, 0x40, 0x40];
+        let measurements = [Measurement::empty(); crate::measurement::MEASUREMENTS_SLOT_NR];
+        let attestation = Attestation::new(&platform_token, &[1; 48]);
+        let token = |committed: &RamCommitment| {
+            attestation
+                .create_attestation_token(
+                    &[0; 64],
+                    &measurements,
+                    HashAlgo::Sha256,
+                    Some(committed.granules()),
+                    false,
+                )
+                .unwrap()
+        };
+        let claim = |claims: Vec<(Value, Value)>| {
+            assert!(is_canonical(&claims));
+            claims
+                .into_iter()
+                .find(|(label, _)| *label == Value::Integer(claims::COMMITTED_RAM_LABEL.into()))
+                .map(|(_, value)| value)
+        };
+
+        let mut committed = RamCommitment::new();
+        committed.account(16 * GRANULE_SIZE, false, true);
+        let claims = realm_claims(&token(&committed));
+        assert_eq!(claims.len(), 8);
+        assert_eq!(claim(claims), Some(Value::Integer(16.into())));
+
+        // a RIPAS RAM assignment by the realm
+        committed.account(GRANULE_SIZE, false, true);
+        let claims = realm_claims(&token(&committed));
+        assert_eq!(claim(claims), Some(Value::Integer(17.into())));
+    }
+
+    #[test]
+    fn realm_debug_claim() {
+        let platform_token = [0xd2u8, 0x84, 0x40, 0xa0, 0x40, 0x40];
+        let measurements = [Measurement::empty(); crate::measurement::MEASUREMENTS_SLOT_NR];
+        let attestation = Attestation::new(&platform_token, &[1; 48]);
+        let claim = |debug: bool| {
+            let token = attestation
+                .create_attestation_token(&[0; 64], &measurements, HashAlgo::Sha256, None, debug)
+                .unwrap();
+            let claims = realm_claims(&token);
+            assert!(is_canonical(&claims));
+            claims
+                .into_iter()
+                .find(|(label, _)| *label == Value::Integer(claims::REALM_DEBUG_LABEL.into()))
+                .map(|(_, value)| value)
+        };
+
+        assert_eq!(claim(true), Some(Value::Bool(true)));
+        assert_eq!(claim(false), None);
+    }
+
+    #[test]
+    fn event_log_replays_rim() {
+        use crate::host::DataPage;
+        use crate::measurement::{HashContext, Hasher, MEASUREMENTS_SLOT_RIM, RMI_MEASURE_CONTENT};
+        use crate::rmi::realm::params::Params as RealmParams;
+        use crate::rmi::rec::params::Params as RecParams;
+        use crate::test_support::OneGranuleTable;
+
+        let mut rd = crate::rmi::realm::rd::test::rd(1, IPA_BITS, OneGranuleTable::new());
+        let mut ctx = HashContext::new(&mut rd).unwrap();
+        ctx.measure_realm_create(&RealmParams::default()).unwrap();
+        ctx.measure_ripas_granule(0x1000, 3).unwrap();
+        ctx.measure_data_granule(&DataPage::default(), 0x1000, RMI_MEASURE_CONTENT)
+            .unwrap();
+        ctx.measure_rec_params(&RecParams::default()).unwrap();
+        ctx.extend_measurement(&[0x33; 32], 1).unwrap();
+
+        let platform_token = [0xd2u8, 0x84, 0x40, 0xa0, 0x40, 0x40];
+        let token = Attestation::new(&platform_token, &[1; 48])
+            .create_attestation_token(&[0; 64], &rd.measurements, HashAlgo::Sha256, None, false)
+            .unwrap();
+        let rim = realm_claims(&token)
+            .into_iter()
+            .find(|(label, _)| *label == Value::Integer(claims::INITIAL_MEASUREMENT_LABEL.into()))
+            .map(|(_, value)| value);
+        let Some(Value::Bytes(rim)) = rim else {
+            panic!("No RIM claim");
+        };
+
+        let hasher = Hasher::from_hash_algo(HashAlgo::Sha256).unwrap();
+        let replayed = rd.event_log.replay(&hasher, MEASUREMENTS_SLOT_RIM).unwrap();
+        assert_eq!(rim, replayed.as_slice()[..rim.len()]);
+        assert_ne!(rim, Measurement::empty().as_slice()[..rim.len()]);
+    }
+
+    #[test]
+    fn signing_failure() {
+        let platform_token = [0xd2u8, 0x84, 0x40, 0xa0, 0x40, 0x40];
+        let measurements = [Measurement::empty(); crate::measurement::MEASUREMENTS_SLOT_NR];
+        let token = |rak_priv: &[u8], challenge: &[u8]| {
+            Attestation::new(&platform_token, rak_priv).create_attestation_token(
+                challenge,
+                &measurements,
+                HashAlgo::Sha256,
+                None,
+                false,
+            )
+        };
+
+        assert_eq!(
+            token(&[1; 48], &[0; claims::CHALLENGE_SIZE + 1]),
+            Err(SigningError::BadChallenge)
+        );
+        // zero is not a valid scalar of EC-P384
+        assert_eq!(
+            token(&[0; 48], &[0; claims::CHALLENGE_SIZE]),
+            Err(SigningError::BadKey)
+        );
+        assert!(token(&[1; 48], &[0; claims::CHALLENGE_SIZE]).is_ok());
+    }
+
+    #[test]
+    fn small_chunks() {
+        let token: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        let mut continuation = Continuation::new(token.clone(), 300, 1000).unwrap();
+        assert_eq!(continuation.chunk_size(), 300);
+
+        let mut copied = Vec::new();
+        let mut chunk = [0u8; GRANULE_SIZE];
+        let lens: Vec<usize> = core::iter::from_fn(|| {
+            let (len, done) = continuation.copy_next(&mut chunk);
+            copied.extend_from_slice(&chunk[..len]);
+            (!done).then_some(len)
+        })
+        .collect();
+        assert_eq!(lens, vec![300, 300, 300]);
+        assert_eq!(copied, token);
+
+        // bounded to a granule
+        let chunk_size = |preferred| {
+            Continuation::new(token.clone(), preferred, 1000)
+                .unwrap()
+                .chunk_size()
+        };
+        assert_eq!(chunk_size(0), GRANULE_SIZE);
+        assert_eq!(chunk_size(GRANULE_SIZE + 1), GRANULE_SIZE);
+    }
+
+    #[test]
+    fn oversized_token() {
+        let token = vec![0u8; 0x100];
+        assert_eq!(
+            Continuation::new(token.clone(), 0, 0xff),
+            Err(TokenTooLarge(0x100))
+        );
+
+        let mut dst = [0xffu8; GRANULE_SIZE];
+        let mut continuation = Continuation::new(token, 0, 0x100).unwrap();
+        assert_eq!(continuation.copy_next(&mut dst), (0x100, true));
+    }
+
+    #[test]
+    fn platform_token_only() {
+        let platform_token = [0xd2u8, 0x84, 0x40, 0xa0, 0x40, 0x40];
+        let token = Attestation::new(&platform_token, &[0; 48])
+            .create_platform_token()
+            .unwrap();
+
+        let Value::Tag(CCA_TOKEN_COLLECTION, map) = de::from_reader(&token[..]).unwrap() else {
+            panic!("Not a CCA token collection");
+        };
+        assert_eq!(
+            *map,
+            Value::Map(vec![(
+                Value::Integer(CCA_PLATFORM_TOKEN.into()),
+                Value::Bytes(platform_token.to_vec())
+            )])
+        );
+    }
+
+    const IPA_BITS: usize = 33;
+
+    fn x0(rd: &Rd) -> usize {
+        get_reg(rd, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn failed_continue() {
+        use crate::rsi::{ERROR_INPUT, ERROR_STATE};
+        use crate::test_support::OneGranuleTable;
+
+        let mut rd = crate::rmi::realm::rd::test::rd(1, IPA_BITS, OneGranuleTable::new());
+        let mut rec = crate::rmi::rec::test::rec();
+
+        // without ATTEST_TOKEN_INIT
+        set_reg(&rd, 0, 1, 0x1000).unwrap();
+        continue_token(&mut rd, &mut rec).unwrap();
+        assert_eq!(x0(&rd), ERROR_STATE);
+        assert_eq!(rd.attest_metrics.failures(AttestFailure::BadState), 1);
+
+        init_token(&rd, &mut rec).unwrap();
+        assert_eq!(x0(&rd), SUCCESS);
+
+        // not aligned
+        set_reg(&rd, 0, 1, 0x1001).unwrap();
+        continue_token(&mut rd, &mut rec).unwrap();
+        assert_eq!(x0(&rd), ERROR_INPUT);
+        // unprotected
+        set_reg(&rd, 0, 1, 1 << (IPA_BITS - 1)).unwrap();
+        continue_platform_token(&mut rd, &mut rec).unwrap();
+        assert_eq!(x0(&rd), ERROR_INPUT);
+        assert_eq!(rd.attest_metrics.failures(AttestFailure::BadInput), 2);
+
+        // no RAK is provisioned to sign the token with
+        set_reg(&rd, 0, 1, 0x1000).unwrap();
+        continue_token(&mut rd, &mut rec).unwrap();
+        assert_eq!(x0(&rd), ERROR_STATE);
+        assert_eq!(rd.attest_metrics.failures(AttestFailure::Signing), 1);
+        assert!(rec.attest_state() == RmmRecAttestState::NoAttestInProgress);
+
+        assert_eq!(rd.attest_metrics.successes(), 0);
+        assert_eq!(rd.attest_metrics.to_regs(), [0, 2, 1, 1, 0]);
+    }
+
+    #[test]
+    fn bad_token_buffer() {
+        use crate::realm::mm::address::PhysAddr;
+        use crate::realm::mm::IPATranslation;
+        use crate::rsi::ERROR_INPUT;
+        use crate::test_support::OneGranuleTable;
+
+        // A stage 2 table without any mapping
+        #[derive(Debug)]
+        struct Unmapped;
+
+        impl IPATranslation for Unmapped {
+            fn get_base_address(&self) -> *const core::ffi::c_void {
+                core::ptr::null()
+            }
+            fn ipa_to_pa(&mut self, _guest: GuestPhysAddr, _level: usize) -> Option<PhysAddr> {
+                None
+            }
+            fn ipa_to_pte(&mut self, _guest: GuestPhysAddr, _level: usize) -> Option<(u64, usize)> {
+                None
+            }
+            fn ipa_to_pte_set(
+                &mut self,
+                _guest: GuestPhysAddr,
+                _level: usize,
+                _val: u64,
+            ) -> Result<(), Error> {
+                Err(Error::RmiErrorRtt(0))
+            }
+            fn clean(&mut self) {}
+        }
+
+        // not mapped, which is returned to the realm rather than to the host
+        let mut rd = crate::rmi::realm::rd::test::rd(1, IPA_BITS, Box::new(Unmapped));
+        let mut rec = crate::rmi::rec::test::rec();
+        init_token(&rd, &mut rec).unwrap();
+        set_reg(&rd, 0, 1, 0x1000).unwrap();
+        assert!(continue_token(&mut rd, &mut rec).is_ok());
+        assert_eq!(x0(&rd), ERROR_INPUT);
+        assert!(continue_platform_token(&mut rd, &mut rec).is_ok());
+        assert_eq!(x0(&rd), ERROR_INPUT);
+        assert_eq!(rd.attest_metrics.failures(AttestFailure::BadInput), 2);
+
+        // the offset at the end of the granule, which would copy nothing forever
+        let mut rd = crate::rmi::realm::rd::test::rd(1, IPA_BITS, OneGranuleTable::new());
+        let mut rec = crate::rmi::rec::test::rec();
+        init_token(&rd, &mut rec).unwrap();
+        set_reg(&rd, 0, 1, 0x1000).unwrap();
+        for offset in [GRANULE_SIZE, GRANULE_SIZE + 1] {
+            set_reg(&rd, 0, 2, offset).unwrap();
+            continue_token(&mut rd, &mut rec).unwrap();
+            assert_eq!(x0(&rd), ERROR_INPUT);
+        }
+        assert_eq!(rd.attest_metrics.failures(AttestFailure::BadInput), 2);
+        assert!(rec.take_attest_continuation().is_none());
+    }
+
+    #[test]
+    fn platform_continue() {
+        use crate::test_support::OneGranuleTable;
+
+        let table = OneGranuleTable::new();
+        let data = table.data().as_ptr();
+        let mut rd = crate::rmi::realm::rd::test::rd(1, IPA_BITS, table);
+        let mut rec = crate::rmi::rec::test::rec();
+
+        init_token(&rd, &mut rec).unwrap();
+        set_reg(&rd, 0, 1, 0x1000).unwrap();
+        continue_platform_token(&mut rd, &mut rec).unwrap();
+        assert_eq!(x0(&rd), SUCCESS);
+
+        let len = get_reg(&rd, 0, 1).unwrap();
+        let token = unsafe { core::slice::from_raw_parts(data, len) };
+        assert!(matches!(
+            de::from_reader(token),
+            Ok(Value::Tag(CCA_TOKEN_COLLECTION, _))
+        ));
+        assert_eq!(rd.attest_metrics.to_regs(), [1, 0, 0, 0, 0]);
+    }
 }
@@ -0,0 +1,90 @@
+use crate::granule::{is_granule_aligned, GRANULE_SIZE};
+use crate::rmi::error::Error;
+use crate::rmi::rtt::is_protected_range;
+
+/// The maximum size of a range which can be maintained in a single call.
+pub const MAX_FLUSH_SIZE: usize = 16 * GRANULE_SIZE;
+
+/// Performs data cache clean and invalidate on the IPA range `[ipa, ipa + size)`.
+///
+/// `translate` returns the PA mapped to a protected IPA granule,
+/// and `maintain` performs the cache operation on a PA range
+/// (i.e., `crate::asm::dcache_flush` on aarch64, which can be stubbed out in tests).
+/// The whole range is validated before any maintenance takes place.
+pub fn flush(
+    ipa: usize,
+    size: usize,
+    ipa_bits: usize,
+    mut translate: impl FnMut(usize) -> Option<usize>,
+    mut maintain: impl FnMut(usize, usize),
+) -> Result<(), Error> {
+    if size == 0 || size > MAX_FLUSH_SIZE || !is_granule_aligned(ipa) || !is_granule_aligned(size) {
+        return Err(Error::RmiErrorInput);
+    }
+
+    let ipa_end = ipa.checked_add(size).ok_or(Error::RmiErrorInput)?;
+    if !is_protected_range(ipa, ipa_end, ipa_bits) {
+        return Err(Error::RmiErrorInput);
+    }
+
+    if (ipa..ipa_end)
+        .step_by(GRANULE_SIZE)
+        .any(|ipa| translate(ipa).is_none())
+    {
+        return Err(Error::RmiErrorInput);
+    }
+
+    for ipa in (ipa..ipa_end).step_by(GRANULE_SIZE) {
+        let pa = translate(ipa).ok_or(Error::RmiErrorInput)?;
+        maintain(pa, GRANULE_SIZE);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec::Vec;
+
+    const IPA_BITS: usize = 33;
+    const PA_BASE: usize = 0x8800_0000;
+    const MAPPED: core::ops::Range<usize> = 0x0..0x4000;
+
+    fn translate(ipa: usize) -> Option<usize> {
+        MAPPED.contains(&ipa).then_some(PA_BASE + ipa)
+    }
+
+    fn flushed(ipa: usize, size: usize) -> Result<Vec<(usize, usize)>, Error> {
+        let mut ranges = Vec::new();
+        flush(ipa, size, IPA_BITS, translate, |pa, len| {
+            ranges.push((pa, len))
+        })?;
+        Ok(ranges)
+    }
+
+    #[test]
+    fn flush_in_bounds() {
+        let ranges = flushed(0x1000, 2 * GRANULE_SIZE).unwrap();
+        assert_eq!(
+            ranges,
+            [
+                (PA_BASE + 0x1000, GRANULE_SIZE),
+                (PA_BASE + 0x2000, GRANULE_SIZE)
+            ]
+        );
+    }
+
+    #[test]
+    fn flush_invalid_range() {
+        // empty, too large and unaligned
+        assert!(flushed(0x1000, 0).is_err());
+        assert!(flushed(0x0, MAX_FLUSH_SIZE + GRANULE_SIZE).is_err());
+        assert!(flushed(0x1001, GRANULE_SIZE).is_err());
+        assert!(flushed(0x1000, 0x800).is_err());
+        // unprotected
+        assert!(flushed(1 << (IPA_BITS - 1), GRANULE_SIZE).is_err());
+        assert!(flushed((1 << (IPA_BITS - 1)) - GRANULE_SIZE, 2 * GRANULE_SIZE).is_err());
+        // partially unmapped
+        assert!(flushed(0x3000, 2 * GRANULE_SIZE).is_err());
+    }
+}
@@ -4,6 +4,7 @@ use crate::measurement::MeasurementError;
 pub enum Error {
     RealmDoesNotExists,
     InvalidMeasurementIndex,
+    InvalidMeasurementSize,
     MeasurementError(MeasurementError),
 }
 
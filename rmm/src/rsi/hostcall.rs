@@ -1,11 +1,27 @@
+use crate::asm::system_counter;
+use crate::config::HOST_CALL_IMMS;
 use crate::const_assert_eq;
-use crate::granule::GRANULE_SIZE;
+use crate::granule::{is_granule_aligned, GranuleState, GRANULE_SIZE};
+use crate::realm::context::{get_reg, set_reg};
+use crate::realm::mm::address::GuestPhysAddr;
+use crate::rmi;
 use crate::rmi::error::Error;
+use crate::rmi::realm::Rd;
+use crate::rmi::rec::run::{marshal_exit, ExitPayload, ExitReason, Run};
+use crate::rmi::rec::Rec;
+use crate::rmi::rtt::RTT_PAGE_LEVEL;
+use crate::rsi::{ERROR_INPUT, SUCCESS};
+use crate::Monitor;
+use crate::{get_granule, get_granule_if};
+
+use core::ops::RangeInclusive;
+use safe_abstraction::raw_ptr::assume_safe_permitted;
 
 pub const HOST_CALL_NR_GPRS: usize = 7;
 const PADDING: [usize; 2] = [6, 4032];
 
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct HostCall {
     imm: u16,
     padding0: [u8; PADDING[0]],
@@ -27,9 +43,145 @@ impl HostCall {
         Ok(())
     }
 
+    /// Copies all the GPRs of the structure from the GPRs the host has given
+    /// on REC entry, completing the host call.
+    pub fn load_from_run(&mut self, run: &Run) -> Result<(), Error> {
+        for idx in 0..HOST_CALL_NR_GPRS {
+            self.set_gpr(idx, run.entry_gpr(idx)?)?;
+        }
+        Ok(())
+    }
+
     pub fn imm(&self) -> u16 {
         self.imm
     }
+
+    /// Exits to the host with the immediate and the GPRs of the host call.
+    /// Call it on a snapshot to avoid TOCTOU.
+    pub fn exit_to_host(&self, run: &mut Run) -> Result<(), Error> {
+        let payload = ExitPayload::HostCall {
+            imm: self.imm,
+            gprs: self.gprs,
+        };
+        marshal_exit(run, &ExitReason::HostCall, &payload)
+    }
+
+    /// Checks whether the immediate is at most `max`.
+    /// Call it on a snapshot to avoid TOCTOU.
+    pub fn imm_in_range(&self, max: u16) -> bool {
+        self.imm <= max
+    }
+
+    /// Copies the structure out of the realm's memory
+    /// so that it can't be changed between being validated and used.
+    pub fn snapshot(&self) -> Self {
+        // Safety: `self` is a valid reference to the structure.
+        unsafe { core::ptr::read_volatile(self) }
+    }
+
+    /// Checks whether the reserved padding is zero as required by the specification.
+    /// Call it on a snapshot to avoid TOCTOU.
+    pub fn validate_padding(&self) -> bool {
+        self.padding0
+            .iter()
+            .chain(self.padding1.iter())
+            .all(|b| *b == 0)
+    }
+
+    /// Checks whether the immediate is one of `recognized` (i.e., `config::HOST_CALL_IMMS`).
+    /// Call it on a snapshot to avoid TOCTOU.
+    pub fn validate_imm(&self, recognized: &RangeInclusive<u16>) -> bool {
+        recognized.contains(&self.imm)
+    }
+}
+
+/// Handles RSI_HOST_CALL, whose structure is at the IPA in x1.
+///
+/// The first call exits to the host with the immediate and the GPRs of the structure,
+/// blocking the REC on the host call. On the next REC entry, it is called again to copy
+/// the GPRs given by the host back to the structure, completing the host call.
+pub fn host_call(
+    _arg: &[usize],
+    ret: &mut [usize],
+    _rmm: &Monitor,
+    rec: &mut Rec<'_>,
+    run: &mut Run,
+) -> core::result::Result<(), Error> {
+    let vcpuid = rec.vcpuid();
+    let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
+    let rd = rd_granule.content::<Rd>();
+
+    let ipa = get_reg(rd, vcpuid, 1).unwrap_or(0x0);
+    if !is_granule_aligned(ipa) {
+        warn!("HOST_CALL: unaligned structure at {:#X}", ipa);
+        return Err(Error::RmiErrorInput);
+    }
+
+    let pa = rd
+        .s2_table()
+        .lock()
+        .ipa_to_pa(GuestPhysAddr::from(ipa), RTT_PAGE_LEVEL)
+        .ok_or(Error::RmiErrorInput)?;
+
+    // fails unless the structure is in a data granule (see `PermissionChecked`)
+    let mut host_call = assume_safe_permitted::<HostCall>(pa.into())?;
+    // accessed through `with` and `mut_with`, which fail
+    // if the granule has stopped being a data granule in the meantime
+    let snapshot = host_call.with(|host_call| host_call.snapshot())?;
+
+    handle_host_call(rd, rec, run, ret, &snapshot, |run| {
+        host_call.mut_with(|host_call| {
+            host_call.load_from_run(run)?;
+            trace!("HOST_CALL param: {:#X?}", host_call);
+            Ok::<(), Error>(())
+        })?
+    })
+}
+
+/// Handles RSI_HOST_CALL on the `snapshot` of its structure,
+/// where `complete` copies the GPRs given by the host back to the structure.
+fn handle_host_call(
+    rd: &Rd,
+    rec: &mut Rec<'_>,
+    run: &mut Run,
+    ret: &mut [usize],
+    snapshot: &HostCall,
+    complete: impl FnOnce(&Run) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let vcpuid = rec.vcpuid();
+    let imm = snapshot.imm();
+
+    // Checked on the completion as well, as the other RECs of the realm
+    // can change the structure while the host call is pending.
+    if !snapshot.validate_padding() {
+        warn!("HOST_CALL: the reserved padding is not zero");
+        rec.set_host_call_pending(false);
+        set_reg(rd, vcpuid, 0, ERROR_INPUT)?;
+        ret[0] = rmi::SUCCESS_REC_ENTER;
+        return Ok(());
+    }
+
+    if !rec.host_call_pending() && !snapshot.validate_imm(&HOST_CALL_IMMS) {
+        warn!("HOST_CALL: unrecognized immediate {:#X}", imm);
+        set_reg(rd, vcpuid, 0, ERROR_INPUT)?;
+        ret[0] = rmi::SUCCESS_REC_ENTER;
+        return Ok(());
+    }
+
+    if rec.host_call_pending() {
+        complete(run)?;
+        rec.set_host_call_pending(false);
+        set_reg(rd, vcpuid, 0, SUCCESS)?;
+    } else {
+        trace!("HOST_CALL param: {:#X?}", snapshot);
+        snapshot.exit_to_host(run)?;
+        // the realm can't retry a host call, so it is only flagged when throttled
+        run.set_throttled(rec.throttle_host_exit(system_counter()));
+        rec.set_host_call_pending(true);
+    }
+
+    ret[0] = rmi::SUCCESS;
+    Ok(())
 }
 
 impl core::fmt::Debug for HostCall {
@@ -43,7 +195,17 @@ impl core::fmt::Debug for HostCall {
 
 impl safe_abstraction::raw_ptr::RawPtr for HostCall {}
 
-impl safe_abstraction::raw_ptr::SafetyChecked for HostCall {}
+impl safe_abstraction::raw_ptr::SafetyChecked for HostCall {
+    fn is_readable(&self) -> bool {
+        use safe_abstraction::raw_ptr::PermissionChecked;
+        self.has_read_permission_for_span(core::mem::size_of::<Self>())
+    }
+
+    fn is_writable(&self) -> bool {
+        use safe_abstraction::raw_ptr::PermissionChecked;
+        self.has_write_permission_for_span(core::mem::size_of::<Self>())
+    }
+}
 
 impl safe_abstraction::raw_ptr::SafetyAssured for HostCall {
     fn is_initialized(&self) -> bool {
@@ -63,3 +225,208 @@ impl safe_abstraction::raw_ptr::SafetyAssured for HostCall {
         true
     }
 }
+
+impl safe_abstraction::raw_ptr::PermissionChecked for HostCall {
+    const GRANULE_SIZE: usize = GRANULE_SIZE;
+
+    fn has_granule_read_permission(granule_addr: usize) -> bool {
+        // The structure has to be located in the realm's data granules.
+        get_granule_if!(granule_addr, GranuleState::Data).is_ok()
+    }
+
+    fn has_granule_write_permission(granule_addr: usize) -> bool {
+        // The RMM writes the results of the host call back to the same data granules.
+        Self::has_granule_read_permission(granule_addr)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::boxed::Box;
+
+    fn host_call() -> Box<HostCall> {
+        Box::new(HostCall {
+            imm: 0x1234,
+            padding0: [0; PADDING[0]],
+            gprs: [0xffff_ffff; HOST_CALL_NR_GPRS],
+            padding1: [0; PADDING[1]],
+        })
+    }
+
+    #[test]
+    fn round_trip() {
+        use crate::rmi::rec::run::RunHint;
+
+        let mut host_call = host_call();
+        host_call.gprs = [1, 2, 3, 4, 5, 6, 7];
+        let mut run = Run::default();
+        host_call.snapshot().exit_to_host(&mut run).unwrap();
+
+        assert_eq!(run.exit_reason(), rmi::EXIT_HOST_CALL);
+        assert_eq!(run.run_hint(), RunHint::BlockedHostCall as u8);
+        assert_eq!(run.imm(), 0x1234);
+        for idx in 0..HOST_CALL_NR_GPRS {
+            assert_eq!(run.exit_gpr(idx).unwrap(), idx as u64 + 1);
+        }
+
+        // the host returns the results on the next REC entry
+        for idx in 0..HOST_CALL_NR_GPRS {
+            let val = run.exit_gpr(idx).unwrap();
+            run.set_entry_gpr(idx, val * 0x10).unwrap();
+        }
+        host_call.load_from_run(&run).unwrap();
+        assert_eq!(host_call.gprs, [0x10, 0x20, 0x30, 0x40, 0x50, 0x60, 0x70]);
+    }
+
+    #[test]
+    #[cfg(not(feature = "gst_page_table"))]
+    fn granule_state() {
+        use crate::granule::set_granule;
+        use crate::test_support::{restore_granules, snapshot_granules};
+        use safe_abstraction::raw_ptr::PermissionChecked;
+
+        // a granule which no other test uses
+        const ADDR: usize = 0x8811_0000;
+        let snapshot = snapshot_granules(ADDR..ADDR + GRANULE_SIZE);
+        let set_state = |state| {
+            let mut granule = get_granule!(ADDR).unwrap();
+            set_granule(&mut granule, state).unwrap();
+        };
+
+        assert!(!HostCall::has_granule_read_permission(ADDR));
+        set_state(GranuleState::Delegated);
+        assert!(!HostCall::has_granule_read_permission(ADDR));
+        set_state(GranuleState::Data);
+        assert!(HostCall::has_granule_read_permission(ADDR));
+        assert!(HostCall::has_granule_write_permission(ADDR));
+
+        restore_granules(snapshot);
+    }
+
+    #[test]
+    #[cfg(not(feature = "gst_page_table"))]
+    fn run_as_host_call() {
+        use crate::test_support::{restore_granules, snapshot_granules};
+
+        // a granule which no other test uses
+        const ADDR: usize = 0x8812_0000;
+        let snapshot = snapshot_granules(ADDR..ADDR + GRANULE_SIZE);
+
+        // the realm's IPA resolves to a granule of the host, where a `Run` is,
+        // which RSI_HOST_CALL doesn't access as `HostCall`
+        get_granule!(ADDR)
+            .unwrap()
+            .restore_state(GranuleState::Undelegated);
+        assert!(assume_safe_permitted::<HostCall>(ADDR).is_err());
+
+        restore_granules(snapshot);
+    }
+
+    #[test]
+    fn zero_padding() {
+        let host_call = host_call();
+        let snapshot = host_call.snapshot();
+        assert!(snapshot.validate_padding());
+        assert_eq!(snapshot.imm(), 0x1234);
+    }
+
+    #[test]
+    fn non_zero_padding() {
+        let mut host_call = host_call();
+        host_call.padding0[PADDING[0] - 1] = 1;
+        assert!(!host_call.snapshot().validate_padding());
+
+        let mut host_call = self::host_call();
+        host_call.padding1[0] = 0x80;
+        assert!(!host_call.snapshot().validate_padding());
+    }
+
+    #[test]
+    fn recognized_imm() {
+        let snapshot = host_call().snapshot();
+        assert!(snapshot.validate_imm(&(0..=u16::MAX)));
+        assert!(snapshot.validate_imm(&(0x1000..=0x1fff)));
+    }
+
+    #[test]
+    fn imm_in_range() {
+        let snapshot = host_call().snapshot();
+        assert!(snapshot.imm_in_range(0x1234));
+        assert!(snapshot.imm_in_range(u16::MAX));
+        assert!(!snapshot.imm_in_range(0x1233));
+    }
+
+    #[test]
+    fn load_gprs() {
+        let mut run = Run::default();
+        for idx in 0..HOST_CALL_NR_GPRS {
+            run.set_entry_gpr(idx, idx as u64 + 1).unwrap();
+        }
+
+        let mut host_call = host_call();
+        host_call.load_from_run(&run).unwrap();
+        assert_eq!(host_call.gprs, [1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(host_call.imm(), 0x1234);
+
+        assert!(host_call.set_gpr(HOST_CALL_NR_GPRS, 0).is_err());
+        assert_eq!(host_call.gprs, [1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn unrecognized_imm() {
+        let snapshot = host_call().snapshot();
+        assert!(!snapshot.validate_imm(&(0..=0xff)));
+        assert!(!snapshot.validate_imm(&(0x1235..=u16::MAX)));
+    }
+
+    #[test]
+    fn default_imms() {
+        // CMD_GET_SHARED_BUF and CMD_SUCCESS of realm/rsi-test
+        for imm in [1, 2] {
+            let mut host_call = host_call();
+            host_call.imm = imm;
+            assert!(host_call.snapshot().validate_imm(&HOST_CALL_IMMS));
+        }
+        for imm in [0x100, 0x1234, u16::MAX] {
+            let mut host_call = host_call();
+            host_call.imm = imm;
+            assert!(!host_call.snapshot().validate_imm(&HOST_CALL_IMMS));
+        }
+    }
+
+    #[test]
+    fn reject_unrecognized_imm() {
+        use crate::test_support::OneGranuleTable;
+
+        // `host_call` reads the structure from a data granule of the realm,
+        // so its snapshot is passed to `handle_host_call` directly.
+        let rd = crate::rmi::realm::rd::test::rd(1, 33, OneGranuleTable::new());
+        let mut rec = crate::rmi::rec::test::rec();
+        let mut run = Run::default();
+        let mut ret = [0; 8];
+        let x0 = |rd: &Rd| get_reg(rd, 0, 0).unwrap();
+
+        let mut host_call = host_call();
+        host_call.imm = *HOST_CALL_IMMS.end() + 1;
+        handle_host_call(&rd, &mut rec, &mut run, &mut ret, &host_call, |_| {
+            panic!("not pending")
+        })
+        .unwrap();
+        assert_eq!(x0(&rd), ERROR_INPUT);
+        assert_eq!(ret[0], rmi::SUCCESS_REC_ENTER);
+        assert!(!rec.host_call_pending());
+        assert_ne!(run.exit_reason(), rmi::EXIT_HOST_CALL);
+
+        // a recognized immediate exits to the host
+        host_call.imm = 1;
+        handle_host_call(&rd, &mut rec, &mut run, &mut ret, &host_call, |_| {
+            panic!("not pending")
+        })
+        .unwrap();
+        assert_eq!(ret[0], rmi::SUCCESS);
+        assert!(rec.host_call_pending());
+        assert_eq!(run.exit_reason(), rmi::EXIT_HOST_CALL);
+        assert_eq!(run.imm(), 1);
+    }
+}
@@ -1,19 +1,130 @@
-use crate::measurement::MeasurementError;
+use crate::granule::GranuleState;
+use crate::measurement::{
+    HashContext, Measurement, MeasurementError, MEASUREMENTS_SLOT_MAX_SIZE, MEASUREMENTS_SLOT_NR,
+    MEASUREMENTS_SLOT_RIM,
+};
+use crate::realm::context::{get_reg, set_reg};
+use crate::rmi;
+use crate::rmi::realm::Rd;
+use crate::rmi::rec::run::Run;
+use crate::rmi::rec::Rec;
 use crate::rsi::error::Error;
-use crate::rsi::Rd;
+use crate::rsi::{ERROR_INPUT, SUCCESS};
+use crate::Monitor;
+use crate::{get_granule, get_granule_if};
 
-pub fn read(
-    rd: &Rd,
+/// The number of the registers which a measurement is returned in, from x1.
+pub const MEASUREMENT_NR_GPRS: usize = MEASUREMENTS_SLOT_MAX_SIZE / core::mem::size_of::<usize>();
+
+/// Packs the measurement at `index`, 0 for RIM and the others for REMs,
+/// into the values of the registers in little-endian.
+pub fn to_regs(
+    measurements: &[Measurement],
     index: usize,
-    out: &mut crate::measurement::Measurement,
-) -> Result<(), crate::rsi::error::Error> {
-    let measurement = rd
-        .measurements
-        .iter()
-        .nth(index)
+) -> Result<[usize; MEASUREMENT_NR_GPRS], Error> {
+    let measurement = measurements
+        .get(index)
         .ok_or(Error::InvalidMeasurementIndex)?;
 
-    out.as_mut_slice().copy_from_slice(measurement.as_slice());
+    let mut regs = [0; MEASUREMENT_NR_GPRS];
+    for (reg, chunk) in regs.iter_mut().zip(
+        measurement
+            .as_slice()
+            .chunks_exact(core::mem::size_of::<usize>()),
+    ) {
+        *reg = usize::from_le_bytes(chunk.try_into().unwrap());
+    }
+    Ok(regs)
+}
+
+pub fn read_measurement(
+    _arg: &[usize],
+    ret: &mut [usize],
+    _rmm: &Monitor,
+    rec: &mut Rec<'_>,
+    _run: &mut Run,
+) -> core::result::Result<(), rmi::error::Error> {
+    let vcpuid = rec.vcpuid();
+    let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
+    let rd = rd_granule.content::<Rd>();
+    let index = get_reg(rd, vcpuid, 1)?;
+
+    match to_regs(&rd.measurements, index) {
+        Ok(regs) => {
+            set_reg(rd, vcpuid, 0, SUCCESS)?;
+            for (i, value) in regs.into_iter().enumerate() {
+                set_reg(rd, vcpuid, i + 1, value)?;
+            }
+        }
+        Err(_) => {
+            warn!("Wrong index passed: {}", index);
+            set_reg(rd, vcpuid, 0, ERROR_INPUT)?;
+        }
+    }
+
+    ret[0] = rmi::SUCCESS_REC_ENTER;
+    Ok(())
+}
+
+/// Packs the values of the registers into the data to extend the REM at `index` with,
+/// of which only the first `size` bytes are measured.
+/// The RIM is read-only to the realm.
+pub fn extend_data(index: usize, size: usize, regs: &[usize]) -> Result<Measurement, Error> {
+    if index == MEASUREMENTS_SLOT_RIM || index >= MEASUREMENTS_SLOT_NR {
+        return Err(Error::InvalidMeasurementIndex);
+    }
+    if size > MEASUREMENTS_SLOT_MAX_SIZE {
+        return Err(Error::InvalidMeasurementSize);
+    }
+
+    let mut data = Measurement::empty();
+    for (chunk, reg) in data
+        .as_mut_slice()
+        .chunks_exact_mut(core::mem::size_of::<usize>())
+        .zip(regs)
+    {
+        chunk.copy_from_slice(&reg.to_le_bytes());
+    }
+    Ok(data)
+}
+
+pub fn extend_measurement(
+    _arg: &[usize],
+    ret: &mut [usize],
+    _rmm: &Monitor,
+    rec: &mut Rec<'_>,
+    _run: &mut Run,
+) -> core::result::Result<(), rmi::error::Error> {
+    let vcpuid = rec.vcpuid();
+    let mut rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
+    let rd = rd_granule.content_mut::<Rd>();
+
+    let index = get_reg(rd, vcpuid, 1)?;
+    let size = get_reg(rd, vcpuid, 2)?;
+    let mut regs = [0; MEASUREMENT_NR_GPRS];
+    for (i, reg) in regs.iter_mut().enumerate() {
+        *reg = get_reg(rd, vcpuid, i + 3)?;
+    }
+
+    let data = match extend_data(index, size, &regs) {
+        Ok(data) => data,
+        Err(_) => {
+            warn!(
+                "Wrong index or buffer size passed: idx: {}, size: {}",
+                index, size
+            );
+            set_reg(rd, vcpuid, 0, ERROR_INPUT)?;
+            ret[0] = rmi::SUCCESS_REC_ENTER;
+            return Ok(());
+        }
+    };
+
+    #[cfg(not(kani))]
+    // `rsi` is currently not reachable in model checking harnesses
+    HashContext::new(rd)?.extend_measurement(&data.as_slice()[..size], index)?;
+
+    set_reg(rd, vcpuid, 0, SUCCESS)?;
+    ret[0] = rmi::SUCCESS_REC_ENTER;
     Ok(())
 }
 
@@ -31,3 +142,93 @@ pub fn extend(
     f(measurement)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::measurement::event_log::EVENT_TYPE_EXTEND;
+    use crate::measurement::HashAlgo;
+    use crate::measurement::{EventLogEntry, Hasher};
+
+    fn extend_with(hasher: &Hasher, rem: &mut Measurement, index: usize, data: &[u8]) {
+        let mut regs = [0; MEASUREMENT_NR_GPRS];
+        for (reg, chunk) in regs
+            .iter_mut()
+            .zip(data.chunks(core::mem::size_of::<usize>()))
+        {
+            let mut bytes = [0; core::mem::size_of::<usize>()];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            *reg = usize::from_le_bytes(bytes);
+        }
+        let size = data.len();
+        let data = extend_data(index, size, &regs).unwrap();
+        EventLogEntry::new(EVENT_TYPE_EXTEND, index, 0, size, data)
+            .extend_into(hasher, rem)
+            .unwrap();
+    }
+
+    #[test]
+    fn read_rem() {
+        let mut measurements = [Measurement::empty(); MEASUREMENTS_SLOT_NR];
+        measurements[2]
+            .as_mut_slice()
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, b)| *b = i as u8);
+
+        let regs = to_regs(&measurements, 2).unwrap();
+        assert_eq!(regs[0], usize::from_le_bytes([0, 1, 2, 3, 4, 5, 6, 7]));
+        assert_eq!(
+            regs[MEASUREMENT_NR_GPRS - 1],
+            usize::from_le_bytes([56, 57, 58, 59, 60, 61, 62, 63])
+        );
+        assert_eq!(to_regs(&measurements, 1).unwrap(), [0; MEASUREMENT_NR_GPRS]);
+    }
+
+    #[test]
+    fn extend_in_order() {
+        let hasher = Hasher::from_hash_algo(HashAlgo::Sha256).unwrap();
+        let (first, second) = ([0x11u8; 32], [0x22u8; 16]);
+
+        let mut rem = Measurement::empty();
+        extend_with(&hasher, &mut rem, 1, &first);
+        extend_with(&hasher, &mut rem, 1, &second);
+        // SHA-256(SHA-256(0^32 || 0x11^32) || 0x22^16)
+        assert_eq!(
+            hex::encode(&rem.as_slice()[..32]),
+            "c864581eaa0d29688fa9d1630e23a017403cf9cd884d37935052fb3449bc19ad"
+        );
+
+        let mut reversed = Measurement::empty();
+        extend_with(&hasher, &mut reversed, 1, &second);
+        extend_with(&hasher, &mut reversed, 1, &first);
+        assert_ne!(reversed.as_slice(), rem.as_slice());
+    }
+
+    #[test]
+    fn extend_invalid() {
+        let regs = [0; MEASUREMENT_NR_GPRS];
+        assert!(extend_data(1, MEASUREMENTS_SLOT_MAX_SIZE, &regs).is_ok());
+        assert!(matches!(
+            extend_data(MEASUREMENTS_SLOT_RIM, 32, &regs),
+            Err(Error::InvalidMeasurementIndex)
+        ));
+        assert!(matches!(
+            extend_data(MEASUREMENTS_SLOT_NR, 32, &regs),
+            Err(Error::InvalidMeasurementIndex)
+        ));
+        assert!(matches!(
+            extend_data(1, MEASUREMENTS_SLOT_MAX_SIZE + 1, &regs),
+            Err(Error::InvalidMeasurementSize)
+        ));
+    }
+
+    #[test]
+    fn read_out_of_range() {
+        let measurements = [Measurement::empty(); MEASUREMENTS_SLOT_NR];
+        assert!(matches!(
+            to_regs(&measurements, MEASUREMENTS_SLOT_NR),
+            Err(Error::InvalidMeasurementIndex)
+        ));
+    }
+}
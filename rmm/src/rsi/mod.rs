@@ -1,42 +1,60 @@
 pub mod attestation;
+pub mod cache;
 pub mod constraint;
 pub mod error;
 pub mod hostcall;
 pub mod measurement;
+#[cfg(feature = "profiling")]
+pub mod profile;
 pub mod psci;
+pub mod random;
+pub mod ripas;
+pub mod throttle;
+pub mod version;
 
+use crate::asm::system_counter;
+use crate::config::{Penalty, HOST_EXIT_PENALTY, RIPAS_AUTO_ACCEPT};
 use crate::define_interface;
 use crate::event::RsiHandle;
-use crate::granule::{is_granule_aligned, GranuleState};
+use crate::granule::{is_granule_aligned, GranuleState, GRANULE_SIZE};
+use crate::host::DataPage;
 use crate::listen;
-use crate::measurement::{HashContext, Measurement, MEASUREMENTS_SLOT_NR, MEASUREMENTS_SLOT_RIM};
 use crate::realm::config::realm_config;
-use crate::realm::context::{get_reg, set_reg};
+use crate::realm::context::{get_reg, set_reg, set_result_reg, write_result_regs};
 use crate::realm::mm::address::GuestPhysAddr;
-use crate::realm::mm::stage2_tte::invalid_ripas;
 use crate::rmi;
-use crate::rmi::error::Error;
+use crate::rmi::error::{Error, InternalError};
 use crate::rmi::realm::Rd;
-use crate::rmi::rec::run::Run;
-use crate::rmi::rec::{Rec, RmmRecAttestState};
-use crate::rmi::rtt::{is_protected_ipa, validate_ipa, RTT_PAGE_LEVEL};
-use crate::rsi::hostcall::{HostCall, HOST_CALL_NR_GPRS};
-use crate::Monitor;
+use crate::rmi::rec::run::DiagCheck;
+use crate::rmi::rec::RmmRecAttestState;
+use crate::rmi::rtt::{is_protected_range, validate_ipa, RTT_PAGE_LEVEL};
+use crate::rsi::ripas::RipasChangeFlags;
 use crate::{get_granule, get_granule_if};
 
-use safe_abstraction::raw_ptr::assume_safe;
+/// The base of the commands which are our own extensions, not part of the specification.
+///
+/// They are in the range of the vendor-specific hypervisor service calls of SMCCC (OEN 6)
+/// apart from 0xc400_0190 ~ 0xc400_01af, which the specification reserves for RSI.
+pub const VENDOR_BASE: usize = 0xc600_0000;
 
 define_interface! {
     command {
-        ABI_VERSION             = 0xc400_0190,
-        MEASUREMENT_READ        = 0xc400_0192,
-        MEASUREMENT_EXTEND      = 0xc400_0193,
-        ATTEST_TOKEN_INIT       = 0xc400_0194,
-        ATTEST_TOKEN_CONTINUE   = 0xc400_0195,
-        REALM_CONFIG            = 0xc400_0196,
-        IPA_STATE_SET           = 0xc400_0197,
-        IPA_STATE_GET           = 0xc400_0198,
-        HOST_CALL               = 0xc400_0199,
+        ABI_VERSION               = 0xc400_0190,
+        MEASUREMENT_READ          = 0xc400_0192,
+        MEASUREMENT_EXTEND        = 0xc400_0193,
+        ATTEST_TOKEN_INIT         = 0xc400_0194,
+        ATTEST_TOKEN_CONTINUE     = 0xc400_0195,
+        REALM_CONFIG              = 0xc400_0196,
+        IPA_STATE_SET             = 0xc400_0197,
+        IPA_STATE_GET             = 0xc400_0198,
+        HOST_CALL                 = 0xc400_0199,
+        IPA_STATE_GET_BATCH       = VENDOR_BASE,
+        RANDOM                    = VENDOR_BASE + 0x1,
+        IPA_STATE_CHECK           = VENDOR_BASE + 0x2,
+        ATTEST_EVENT_LOG_CONTINUE = VENDOR_BASE + 0x3,
+        VCPU_COUNT                = VENDOR_BASE + 0x4,
+        CACHE_FLUSH               = VENDOR_BASE + 0x5,
+        ATTEST_PLATFORM_CONTINUE  = VENDOR_BASE + 0x6,
     }
 }
 
@@ -47,215 +65,127 @@ pub const INCOMPLETE: usize = 3;
 
 const ABI_VERSION_MAJOR: usize = 1;
 const ABI_VERSION_MINOR: usize = 0;
-pub const VERSION: usize = (ABI_VERSION_MAJOR << 16) | ABI_VERSION_MINOR;
+pub const VERSION: usize = version::encode(ABI_VERSION_MAJOR, ABI_VERSION_MINOR);
 
 extern crate alloc;
 
-pub fn do_host_call(
-    _arg: &[usize],
-    ret: &mut [usize],
-    _rmm: &Monitor,
-    rec: &mut Rec<'_>,
-    run: &mut Run,
-) -> core::result::Result<(), Error> {
-    let vcpuid = rec.vcpuid();
-    let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
-    let rd = rd_granule.content::<Rd>();
-
-    let ipa = get_reg(rd, vcpuid, 1).unwrap_or(0x0);
-
-    let pa = rd
-        .s2_table()
-        .lock()
-        .ipa_to_pa(
-            crate::realm::mm::address::GuestPhysAddr::from(ipa),
-            RTT_PAGE_LEVEL,
-        )
-        .ok_or(Error::RmiErrorInput)?;
-
-    let mut host_call = assume_safe::<HostCall>(pa.into())?;
-    let imm = host_call.imm();
-
-    if rec.host_call_pending() {
-        for i in 0..HOST_CALL_NR_GPRS {
-            let val = run.entry_gpr(i)?;
-            host_call.set_gpr(i, val)?
-        }
-        rec.set_host_call_pending(false);
-    } else {
-        run.set_imm(imm);
-        run.set_exit_reason(rmi::EXIT_HOST_CALL);
-        rec.set_host_call_pending(true);
-    }
-
-    trace!("HOST_CALL param: {:#X?}", *host_call);
-
-    ret[0] = rmi::SUCCESS;
-    Ok(())
-}
-
 pub fn set_event_handler(rsi: &mut RsiHandle) {
-    listen!(rsi, ATTEST_TOKEN_INIT, |_arg, ret, _rmm, rec, _| {
-        let vcpuid = rec.vcpuid();
-        let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
-        let rd = rd_granule.content::<Rd>();
-
-        let mut challenge: [u8; 64] = [0; 64];
-
-        for i in 0..8 {
-            let challenge_part = get_reg(rd, vcpuid, i + 2)?;
-            let start_idx = i * 8;
-            let end_idx = start_idx + 8;
-            challenge[start_idx..end_idx].copy_from_slice(&challenge_part.to_le_bytes());
-        }
-
-        rec.set_attest_challenge(&challenge);
-        rec.set_attest_state(RmmRecAttestState::AttestInProgress);
-
-        set_reg(rd, vcpuid, 0, SUCCESS)?;
-
-        // TODO: Calculate real token size
-        set_reg(rd, vcpuid, 1, 4096)?;
-
-        ret[0] = rmi::SUCCESS_REC_ENTER;
-        Ok(())
-    });
-
-    listen!(rsi, ATTEST_TOKEN_CONTINUE, |_arg, ret, _rmm, rec, _| {
+    listen!(rsi, ATTEST_TOKEN_INIT, attestation::token_init);
+    listen!(rsi, ATTEST_TOKEN_CONTINUE, attestation::token_continue);
+    // Called instead of ATTEST_TOKEN_CONTINUE after ATTEST_TOKEN_INIT
+    // when the realm needs only the platform token.
+    listen!(
+        rsi,
+        ATTEST_PLATFORM_CONTINUE,
+        attestation::platform_continue
+    );
+
+    listen!(rsi, ATTEST_EVENT_LOG_CONTINUE, |_arg, ret, _rmm, rec, _| {
         let ipa_bits = rec.ipa_bits()?;
 
         let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
         let rd = rd_granule.content::<Rd>();
-        let hash_algo = rd.hash_algo();
 
         let vcpuid = rec.vcpuid();
 
         if rec.attest_state() != RmmRecAttestState::AttestInProgress {
-            warn!("Calling attest token continue without init");
+            warn!("Calling attest event log continue without init");
             set_reg(rd, vcpuid, 0, ERROR_STATE)?;
             ret[0] = rmi::SUCCESS_REC_ENTER;
             return Ok(());
         }
 
-        let attest_ipa = get_reg(rd, vcpuid, 1)?;
-        if validate_ipa(attest_ipa, ipa_bits).is_err() {
-            warn!("Wrong ipa passed {}", attest_ipa);
+        let log_ipa = get_reg(rd, vcpuid, 1)?;
+        let offset = get_reg(rd, vcpuid, 2)?;
+        if validate_ipa(log_ipa, ipa_bits).is_err() || offset > rd.event_log.size() {
+            warn!("Wrong ipa or offset passed {:X} {:X}", log_ipa, offset);
             set_reg(rd, vcpuid, 0, ERROR_INPUT)?;
             ret[0] = rmi::SUCCESS_REC_ENTER;
             return Ok(());
         }
 
-        let pa = rd
+        let pa: usize = rd
             .s2_table()
             .lock()
-            .ipa_to_pa(GuestPhysAddr::from(attest_ipa), RTT_PAGE_LEVEL)
-            .ok_or(Error::RmiErrorInput)?;
-
-        let measurements = rd.measurements;
-
-        #[cfg(not(kani))]
-        // `rsi` is currently not reachable in model checking harnesses
-        let attest_size = crate::rsi::attestation::get_token(
-            pa.into(),
-            rec.attest_challenge(),
-            &measurements,
-            hash_algo,
-        );
-
-        set_reg(rd, vcpuid, 0, SUCCESS)?;
-        #[cfg(not(kani))]
-        // `rsi` is currently not reachable in model checking harnesses
-        set_reg(rd, vcpuid, 1, attest_size)?;
+            .ipa_to_pa(GuestPhysAddr::from(log_ipa), RTT_PAGE_LEVEL)
+            .ok_or(Error::RmiErrorInput)?
+            .into();
+
+        // the granule mapped by the above stage 2 translation,
+        // which is locked while the log is copied to it.
+        let mut data_granule = get_granule_if!(pa, GranuleState::Data)?;
+        let buffer = data_granule.content_mut::<DataPage>();
+        let len = rd.event_log.read(offset, buffer.as_mut_slice());
+
+        if offset + len < rd.event_log.size() {
+            set_reg(rd, vcpuid, 0, INCOMPLETE)?;
+        } else {
+            set_reg(rd, vcpuid, 0, SUCCESS)?;
+        }
+        set_reg(rd, vcpuid, 1, len)?;
+        // the events which were measured after the log got full,
+        // with which the log doesn't reproduce the measurements.
+        set_reg(rd, vcpuid, 2, rd.event_log.dropped())?;
 
         ret[0] = rmi::SUCCESS_REC_ENTER;
         Ok(())
     });
 
-    listen!(rsi, HOST_CALL, do_host_call);
-
-    listen!(rsi, ABI_VERSION, |_arg, ret, _rmm, rec, _| {
-        let vcpuid = rec.vcpuid();
-        let realmid = rec.realmid()?;
-        let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
-        let rd = rd_granule.content::<Rd>();
+    listen!(rsi, HOST_CALL, hostcall::host_call);
 
-        if set_reg(rd, vcpuid, 0, VERSION).is_err() {
-            warn!(
-                "Unable to set register 0. realmid: {:?} vcpuid: {:?}",
-                realmid, vcpuid
-            );
-        }
-        trace!("RSI_ABI_VERSION: {:#X?}", VERSION);
-        ret[0] = rmi::SUCCESS_REC_ENTER;
-        Ok(())
-    });
+    listen!(rsi, ABI_VERSION, version::get_version);
 
-    listen!(rsi, MEASUREMENT_READ, |_arg, ret, _rmm, rec, _| {
+    listen!(rsi, VCPU_COUNT, |_arg, ret, _rmm, rec, _| {
         let vcpuid = rec.vcpuid();
         let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
         let rd = rd_granule.content::<Rd>();
-        let mut measurement = Measurement::empty();
-        let index = get_reg(rd, vcpuid, 1)?;
-
-        if index >= MEASUREMENTS_SLOT_NR {
-            warn!("Wrong index passed: {}", index);
-            set_reg(rd, vcpuid, 0, ERROR_INPUT)?;
-            ret[0] = rmi::SUCCESS_REC_ENTER;
-            return Ok(());
-        }
+        let vcpu_count = rd.vcpu_count();
 
-        #[cfg(not(kani))]
-        // `rsi` is currently not reachable in model checking harnesses
-        crate::rsi::measurement::read(rd, index, &mut measurement)?;
         set_reg(rd, vcpuid, 0, SUCCESS)?;
-        for (ind, chunk) in measurement
-            .as_slice()
-            .chunks_exact(core::mem::size_of::<usize>())
-            .enumerate()
-        {
-            let reg_value = usize::from_le_bytes(chunk.try_into().unwrap());
-            set_reg(rd, vcpuid, ind + 1, reg_value)?;
-        }
-
+        set_reg(rd, vcpuid, 1, vcpu_count)?;
+        trace!("RSI_VCPU_COUNT: {}", vcpu_count);
         ret[0] = rmi::SUCCESS_REC_ENTER;
         Ok(())
     });
 
-    listen!(rsi, MEASUREMENT_EXTEND, |_arg, ret, _rmm, rec, _| {
+    listen!(rsi, CACHE_FLUSH, |_arg, ret, _rmm, rec, _| {
         let vcpuid = rec.vcpuid();
         let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
         let rd = rd_granule.content::<Rd>();
-
-        let index = get_reg(rd, vcpuid, 1)?;
+        let ipa = get_reg(rd, vcpuid, 1)?;
         let size = get_reg(rd, vcpuid, 2)?;
-        let mut buffer = [0u8; 64];
-
-        for i in 0..8 {
-            buffer[i * 8..i * 8 + 8]
-                .copy_from_slice(get_reg(rd, vcpuid, i + 3)?.to_le_bytes().as_slice());
-        }
 
-        if size > buffer.len() || index == MEASUREMENTS_SLOT_RIM || index >= MEASUREMENTS_SLOT_NR {
+        let res = {
+            let mut s2_table = rd.s2_table().lock();
+            cache::flush(
+                ipa,
+                size,
+                rd.ipa_bits(),
+                |ipa| {
+                    s2_table
+                        .ipa_to_pa(GuestPhysAddr::from(ipa), RTT_PAGE_LEVEL)
+                        .map(|pa| pa.into())
+                },
+                crate::asm::dcache_flush,
+            )
+        };
+        if let Err(e) = res {
             warn!(
-                "Wrong index or buffer size passed: idx: {}, size: {}",
-                index, size
+                "RSI_CACHE_FLUSH: invalid range {:X}+{:X}: {:?}",
+                ipa, size, e
             );
             set_reg(rd, vcpuid, 0, ERROR_INPUT)?;
-            ret[0] = rmi::SUCCESS_REC_ENTER;
-            return Ok(());
+        } else {
+            set_reg(rd, vcpuid, 0, SUCCESS)?;
         }
-
-        let mut rd = get_granule_if!(rec.owner()?, GranuleState::RD)?;
-        let rd = rd.content_mut::<Rd>();
-        #[cfg(not(kani))]
-        // `rsi` is currently not reachable in model checking harnesses
-        HashContext::new(rd)?.extend_measurement(&buffer[0..size], index)?;
-
-        set_reg(rd, vcpuid, 0, SUCCESS)?;
         ret[0] = rmi::SUCCESS_REC_ENTER;
         Ok(())
     });
 
+    listen!(rsi, MEASUREMENT_READ, measurement::read_measurement);
+
+    listen!(rsi, MEASUREMENT_EXTEND, measurement::extend_measurement);
+
     listen!(rsi, REALM_CONFIG, |_arg, ret, _rmm, rec, _| {
         let vcpuid = rec.vcpuid();
         let ipa_bits = rec.ipa_bits()?;
@@ -282,51 +212,144 @@ pub fn set_event_handler(rsi: &mut RsiHandle) {
         Ok(())
     });
 
-    listen!(rsi, IPA_STATE_GET, |_arg, ret, _rmm, rec, _| {
+    // Returns random bytes from the platform entropy.
+    //   x1: the size of the request in bytes
+    //   x2: the IPA of the granule to fill, unless the request fits in x1-x4
+    listen!(rsi, RANDOM, |_arg, ret, _rmm, rec, _| {
         let vcpuid = rec.vcpuid();
         let ipa_bits = rec.ipa_bits()?;
-        let realmid = rec.realmid()?;
         let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
         let rd = rd_granule.content::<Rd>();
 
-        let ipa_page = get_reg(rd, vcpuid, 1)?;
-        if validate_ipa(ipa_page, ipa_bits).is_err() {
-            if set_reg(rd, vcpuid, 0, ERROR_INPUT).is_err() {
-                warn!(
-                    "Unable to set register 0. realmid: {:?} vcpuid: {:?}",
-                    realmid, vcpuid
-                );
+        let size = get_reg(rd, vcpuid, 1)?;
+        let dst_ipa = get_reg(rd, vcpuid, 2)?;
+
+        let res = if size <= random::MAX_RANDOM_REGS_SIZE {
+            random::random_regs(size, &mut random::Rndr).and_then(|regs| {
+                for (idx, reg) in regs.iter().enumerate() {
+                    set_reg(rd, vcpuid, idx + 1, *reg)?;
+                }
+                Ok(())
+            })
+        } else if !is_granule_aligned(dst_ipa) || validate_ipa(dst_ipa, ipa_bits).is_err() {
+            Err(Error::RmiErrorInput)
+        } else {
+            let dst_pa: Option<usize> = rd
+                .s2_table()
+                .lock()
+                .ipa_to_pa(GuestPhysAddr::from(dst_ipa), RTT_PAGE_LEVEL)
+                .map(|pa| pa.into());
+            match dst_pa {
+                Some(dst_pa) => {
+                    // The granule is a protected data granule mapped to the realm
+                    // which is validated by the above stage 2 translation.
+                    let dst =
+                        unsafe { core::slice::from_raw_parts_mut(dst_pa as *mut u8, GRANULE_SIZE) };
+                    random::random_granule(size, dst, &mut random::Rndr)
+                }
+                None => Err(Error::RmiErrorInput),
             }
+        };
+
+        let result = match res {
+            Ok(()) => SUCCESS,
+            Err(Error::RmiErrorOthers(InternalError::NoEntropy)) => ERROR_STATE,
+            Err(Error::RmiErrorOthers(InternalError::NoRng)) => ERROR_INPUT,
+            Err(e) => {
+                warn!("RSI_RANDOM: invalid request of {:X} bytes: {:?}", size, e);
+                ERROR_INPUT
+            }
+        };
+        set_reg(rd, vcpuid, 0, result)?;
+        ret[0] = rmi::SUCCESS_REC_ENTER;
+        Ok(())
+    });
+
+    listen!(rsi, IPA_STATE_GET, |_arg, ret, _rmm, rec, _| {
+        let vcpuid = rec.vcpuid();
+        let ipa_bits = rec.ipa_bits()?;
+        let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
+        let rd = rd_granule.content::<Rd>();
+
+        // x1: base, x2: end of the query, which must lie in the protected IPA space
+        let ipa_base = get_reg(rd, vcpuid, 1)?;
+        let ipa_end = get_reg(rd, vcpuid, 2)?;
+        if validate_ipa(ipa_base, ipa_bits).is_err()
+            || !is_granule_aligned(ipa_end)
+            || !is_protected_range(ipa_base, ipa_end, ipa_bits)
+        {
+            set_result_reg(rd, vcpuid, 0, ERROR_INPUT)?;
             ret[0] = rmi::SUCCESS_REC_ENTER;
             return Ok(());
         }
 
-        let ripas = crate::rtt::get_ripas(rd, ipa_page, RTT_PAGE_LEVEL)? as usize;
+        let (ripas, top) =
+            ripas::ripas_run(ipa_base, ipa_end, |ipa| crate::rtt::ripas_at(rd, ipa))?;
 
         debug!(
-            "RSI_IPA_STATE_GET: ipa_page: {:X} ripas: {:X}",
-            ipa_page, ripas
+            "RSI_IPA_STATE_GET: {:X} ~ {:X} ripas: {:X}",
+            ipa_base, top, ripas
         );
 
-        if set_reg(rd, vcpuid, 0, SUCCESS).is_err() {
-            warn!(
-                "Unable to set register 0. realmid: {:?} vcpuid: {:?}",
-                realmid, vcpuid
-            );
+        // x1: top of the run sharing the RIPAS of the base, x2: the RIPAS
+        write_result_regs(
+            rd,
+            vcpuid,
+            &[(0, SUCCESS as u64), (1, top as u64), (2, ripas as u64)],
+        )?;
+        ret[0] = rmi::SUCCESS_REC_ENTER;
+        Ok(())
+    });
+
+    listen!(rsi, IPA_STATE_GET_BATCH, |_arg, ret, _rmm, rec, _| {
+        let vcpuid = rec.vcpuid();
+        let ipa_bits = rec.ipa_bits()?;
+        let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
+        let rd = rd_granule.content::<Rd>();
+
+        let base = get_reg(rd, vcpuid, 1)?;
+        let count = get_reg(rd, vcpuid, 2)?;
+        let dst_ipa = get_reg(rd, vcpuid, 3)?;
+
+        if !is_granule_aligned(dst_ipa) || validate_ipa(dst_ipa, ipa_bits).is_err() {
+            set_result_reg(rd, vcpuid, 0, ERROR_INPUT)?;
+            ret[0] = rmi::SUCCESS_REC_ENTER;
+            return Ok(());
         }
 
-        if set_reg(rd, vcpuid, 1, ripas).is_err() {
+        let dst_pa: Option<usize> = rd
+            .s2_table()
+            .lock()
+            .ipa_to_pa(GuestPhysAddr::from(dst_ipa), RTT_PAGE_LEVEL)
+            .map(|pa| pa.into());
+        let Some(dst_pa) = dst_pa else {
+            set_result_reg(rd, vcpuid, 0, ERROR_INPUT)?;
+            ret[0] = rmi::SUCCESS_REC_ENTER;
+            return Ok(());
+        };
+
+        // The granule is a protected data granule mapped to the realm
+        // which is validated by the above stage 2 translation.
+        let dst = unsafe { core::slice::from_raw_parts_mut(dst_pa as *mut u8, GRANULE_SIZE) };
+        let res = ripas::read_ripas_batch(base, count, ipa_bits, dst, |ipa| {
+            crate::rtt::get_ripas(rd, ipa, RTT_PAGE_LEVEL)
+        });
+        if let Err(e) = res {
             warn!(
-                "Unable to set register 1. realmid: {:?} vcpuid: {:?}",
-                realmid, vcpuid
+                "RSI_IPA_STATE_GET_BATCH: invalid range {:X}+{:X} pages: {:?}",
+                base, count, e
             );
+            set_result_reg(rd, vcpuid, 0, ERROR_INPUT)?;
+        } else {
+            set_result_reg(rd, vcpuid, 0, SUCCESS)?;
         }
-
         ret[0] = rmi::SUCCESS_REC_ENTER;
         Ok(())
     });
 
-    listen!(rsi, IPA_STATE_SET, |_arg, ret, _rmm, rec, run| {
+    // It validates the RIPAS change IPA_STATE_SET would make with the same x1 ~ x4,
+    // returning the first offending IPA in x1, without changing anything nor exiting.
+    listen!(rsi, IPA_STATE_CHECK, |_arg, ret, _rmm, rec, run| {
         let vcpuid = rec.vcpuid();
         let ipa_bits = rec.ipa_bits()?;
         let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
@@ -335,47 +358,119 @@ pub fn set_event_handler(rsi: &mut RsiHandle) {
         let ipa_start = get_reg(rd, vcpuid, 1)?;
         let ipa_size = get_reg(rd, vcpuid, 2)?;
         let ipa_state = get_reg(rd, vcpuid, 3)? as u8;
-        let ipa_end = ipa_start + ipa_size;
+        let Ok(flags) = RipasChangeFlags::parse(get_reg(rd, vcpuid, 4)?) else {
+            set_result_reg(rd, vcpuid, 0, ERROR_INPUT)?;
+            ret[0] = rmi::SUCCESS_REC_ENTER;
+            return Ok(());
+        };
+
+        let offending =
+            ripas::check_ripas_change(ipa_start, ipa_size, ipa_state, flags, ipa_bits, |ipa| {
+                crate::rtt::ripas_at(rd, ipa)
+            });
+        if let Some((check, ipa)) = offending {
+            debug!(
+                "RSI_IPA_STATE_CHECK: {:X}+{:X} {:X} fails {:?} at {:X}",
+                ipa_start, ipa_size, ipa_state, check, ipa
+            );
+            run.set_diag(check, ipa as u64);
+            set_result_reg(rd, vcpuid, 0, ERROR_INPUT)?;
+            set_result_reg(rd, vcpuid, 1, ipa)?;
+        } else {
+            set_result_reg(rd, vcpuid, 0, SUCCESS)?;
+        }
+        ret[0] = rmi::SUCCESS_REC_ENTER;
+        Ok(())
+    });
+
+    listen!(rsi, IPA_STATE_SET, |_arg, ret, _rmm, rec, run| {
+        let vcpuid = rec.vcpuid();
+        let ipa_bits = rec.ipa_bits()?;
+        let mut rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
+        let rd = rd_granule.content_mut::<Rd>();
+
+        let ipa_start = get_reg(rd, vcpuid, 1)?;
+        let ipa_size = get_reg(rd, vcpuid, 2)?;
+        let ipa_state = get_reg(rd, vcpuid, 3)? as u8;
+        let flags = get_reg(rd, vcpuid, 4)?;
+
+        let Ok(flags) = RipasChangeFlags::parse(flags) else {
+            set_result_reg(rd, vcpuid, 0, ERROR_INPUT)?;
+            ret[0] = rmi::SUCCESS_REC_ENTER;
+            return Ok(());
+        };
 
-        if ipa_end <= ipa_start {
-            return Err(Error::RmiErrorInput); // integer overflows or size is zero
+        let Ok(ipa_end) = ripas::ripas_range(ipa_start, ipa_size) else {
+            // integer overflows
+            run.set_diag(DiagCheck::Overflow, ipa_start as u64);
+            set_result_reg(rd, vcpuid, 0, ERROR_INPUT)?;
+            ret[0] = rmi::SUCCESS_REC_ENTER;
+            return Ok(());
+        };
+
+        let Some(ipa_end) = ipa_end else {
+            // zero-length range, nothing to change
+            set_result_reg(rd, vcpuid, 0, SUCCESS)?;
+            set_result_reg(rd, vcpuid, 1, ipa_start)?;
+            ret[0] = rmi::SUCCESS_REC_ENTER;
+            return Ok(());
+        };
+
+        // the range must be valid and the current RIPAS of all of it must allow the change
+        let offending =
+            ripas::check_ripas_change(ipa_start, ipa_size, ipa_state, flags, ipa_bits, |ipa| {
+                crate::rtt::ripas_at(rd, ipa)
+            });
+        if let Some((check, ipa)) = offending {
+            run.set_diag(check, ipa as u64);
+            set_result_reg(rd, vcpuid, 0, ERROR_INPUT)?;
+            ret[0] = rmi::SUCCESS_REC_ENTER;
+            return Ok(());
         }
 
-        if !is_granule_aligned(ipa_start)
-            || !is_granule_aligned(ipa_size)
-            || !is_ripas_valid(ipa_state)
-            || !is_protected_ipa(ipa_start, ipa_bits)
-            || !is_protected_ipa(ipa_end - 1, ipa_bits)
-        {
-            set_reg(rd, vcpuid, 0, ERROR_INPUT)?;
+        if RIPAS_AUTO_ACCEPT {
+            let next = ripas::apply_ripas(ipa_start, ipa_end, |ipa| {
+                crate::rmi::rtt::set_ripas(rd, ipa, RTT_PAGE_LEVEL, ipa_state as u64)
+            });
+            // exits to the host as usual unless any page is changed
+            if next > ipa_start {
+                write_result_regs(
+                    rd,
+                    vcpuid,
+                    &[
+                        (0, SUCCESS as u64),
+                        (1, next as u64),
+                        (2, ripas::RSI_ACCEPT as u64),
+                    ],
+                )?;
+                ret[0] = rmi::SUCCESS_REC_ENTER;
+                return Ok(());
+            }
+        }
+
+        let throttled = rec.throttle_host_exit(system_counter());
+        if throttled && HOST_EXIT_PENALTY == Penalty::Retry {
+            // return to the realm without progress, telling it to retry
+            write_result_regs(rd, vcpuid, &[(0, INCOMPLETE as u64), (1, ipa_start as u64)])?;
             ret[0] = rmi::SUCCESS_REC_ENTER;
             return Ok(());
         }
 
-        // TODO: check ipa_state value, ipa address granularity
-        run.set_exit_reason(rmi::EXIT_RIPAS_CHANGE);
-        run.set_ripas(ipa_start as u64, ipa_size as u64, ipa_state);
+        ripas::set_ripas_exit(run, ipa_start as u64, ipa_size as u64, ipa_state)?;
+        run.set_throttled(throttled);
         rec.set_ripas(
             ipa_start as u64,
             ipa_end as u64,
             ipa_start as u64,
             ipa_state,
+            flags,
         );
         ret[0] = rmi::SUCCESS;
         debug!(
             "RSI_IPA_STATE_SET: {:X} ~ {:X} {:X}",
-            ipa_start,
-            ipa_start + ipa_size,
-            ipa_state
+            ipa_start, ipa_end, ipa_state
         );
         super::rmi::dummy();
         Ok(())
     });
 }
-
-fn is_ripas_valid(ripas: u8) -> bool {
-    match ripas as u64 {
-        invalid_ripas::EMPTY | invalid_ripas::RAM => true,
-        _ => false,
-    }
-}
@@ -0,0 +1,88 @@
+use crate::asm::cycle_counter;
+use crate::event::Mainloop;
+use crate::listen;
+use crate::rmi;
+
+use alloc::collections::btree_map::BTreeMap;
+use spin::mutex::Mutex;
+
+extern crate alloc;
+
+/// Cycles spent in the handler of an RSI command, accumulated over its invocations.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Accumulator {
+    pub calls: u64,
+    pub cycles: u64,
+}
+
+impl Accumulator {
+    /// Encodes the accumulator into the output registers of RMI_RSI_PROFILE_GET:
+    ///   x1: number of invocations
+    ///   x2: total cycles
+    pub fn to_regs(&self) -> [usize; 2] {
+        [self.calls as usize, self.cycles as usize]
+    }
+}
+
+/// Per-command accumulators of the RSI handlers.
+pub struct Profile {
+    accumulators: BTreeMap<usize, Accumulator>,
+}
+
+impl Profile {
+    pub const fn new() -> Self {
+        Self {
+            accumulators: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, cmd: usize, cycles: u64) {
+        let acc = self.accumulators.entry(cmd).or_default();
+        acc.calls = acc.calls.wrapping_add(1);
+        acc.cycles = acc.cycles.wrapping_add(cycles);
+    }
+
+    pub fn get(&self, cmd: usize) -> Accumulator {
+        self.accumulators.get(&cmd).copied().unwrap_or_default()
+    }
+}
+
+pub static PROFILE: Mutex<Profile> = Mutex::new(Profile::new());
+
+/// Runs the handler of the RSI command `cmd`, accumulating the cycles it takes.
+///
+/// The lock of the accumulators is not held while the handler runs.
+pub fn measure<T>(cmd: usize, handler: impl FnOnce() -> T) -> T {
+    let start = cycle_counter();
+    let res = handler();
+    let cycles = cycle_counter().wrapping_sub(start);
+    PROFILE.lock().record(cmd, cycles);
+    res
+}
+
+pub fn set_event_handler(mainloop: &mut Mainloop) {
+    listen!(mainloop, rmi::RSI_PROFILE_GET, |arg, ret, _| {
+        ret[1..3].copy_from_slice(&PROFILE.lock().get(arg[0]).to_regs());
+        Ok(())
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::asm::CYCLE_COUNTER_STEP;
+    use crate::rsi;
+
+    #[test]
+    fn handler_increments_accumulator() {
+        let before = PROFILE.lock().get(rsi::VCPU_COUNT);
+
+        let ret = measure(rsi::VCPU_COUNT, || rsi::SUCCESS);
+        assert_eq!(ret, rsi::SUCCESS);
+
+        let after = PROFILE.lock().get(rsi::VCPU_COUNT);
+        assert_eq!(after.calls, before.calls + 1);
+        assert!(after.cycles >= before.cycles + CYCLE_COUNTER_STEP);
+        assert_eq!(PROFILE.lock().get(rsi::HOST_CALL).calls, 0);
+    }
+}
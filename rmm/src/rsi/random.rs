@@ -0,0 +1,127 @@
+use crate::granule::GRANULE_SIZE;
+use crate::rmi::error::{Error, InternalError};
+
+use armv9a::regs::{AA64ISAR0, ID_AA64ISAR0_EL1};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// The number of registers (x1-x4) returning the random bytes of a small request.
+pub const RANDOM_REGS: usize = 4;
+/// The maximum size of a request returned in the registers.
+pub const MAX_RANDOM_REGS_SIZE: usize = RANDOM_REGS * core::mem::size_of::<usize>();
+/// The maximum size of a request, which fills a granule of the realm.
+pub const MAX_RANDOM_SIZE: usize = GRANULE_SIZE;
+
+/// A source of entropy (e.g., the platform TRNG), which can be stubbed in tests.
+pub trait Entropy {
+    /// Fills `dst` with random bytes. It fails if no entropy is available for now.
+    fn fill(&mut self, dst: &mut [u8]) -> Result<(), Error>;
+}
+
+/// Whether the PE implements FEAT_RNG, which is detected once at boot.
+static RNDR_SUPPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Detects FEAT_RNG from ID_AA64ISAR0_EL1.RNDR.
+/// Until it is called, RNDR is regarded as absent.
+pub fn setup_rndr() {
+    let isar0 = AA64ISAR0::new(unsafe { ID_AA64ISAR0_EL1.get() });
+    let supported = isar0.get_masked_value(AA64ISAR0::RNDR) != 0;
+    if !supported {
+        warn!("FEAT_RNG is not implemented, RSI_RANDOM is unavailable");
+    }
+    RNDR_SUPPORTED.store(supported, Ordering::Relaxed);
+}
+
+/// The entropy from the RNDR register of FEAT_RNG.
+pub struct Rndr;
+
+impl Entropy for Rndr {
+    fn fill(&mut self, dst: &mut [u8]) -> Result<(), Error> {
+        // RNDR is undefined without FEAT_RNG
+        if !RNDR_SUPPORTED.load(Ordering::Relaxed) {
+            return Err(Error::RmiErrorOthers(InternalError::NoRng));
+        }
+        for chunk in dst.chunks_mut(core::mem::size_of::<u64>()) {
+            let random =
+                crate::asm::rndr().ok_or(Error::RmiErrorOthers(InternalError::NoEntropy))?;
+            chunk.copy_from_slice(&random.to_le_bytes()[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
+
+/// Returns `size` random bytes in the registers, little-endian from x1.
+pub fn random_regs(size: usize, entropy: &mut impl Entropy) -> Result<[usize; RANDOM_REGS], Error> {
+    if size == 0 || size > MAX_RANDOM_REGS_SIZE {
+        return Err(Error::RmiErrorInput);
+    }
+
+    let mut bytes = [0u8; MAX_RANDOM_REGS_SIZE];
+    entropy.fill(&mut bytes[..size])?;
+
+    let mut regs = [0; RANDOM_REGS];
+    for (reg, chunk) in regs
+        .iter_mut()
+        .zip(bytes.chunks(core::mem::size_of::<usize>()))
+    {
+        *reg = usize::from_le_bytes(chunk.try_into().or(Err(Error::RmiErrorInput))?);
+    }
+    Ok(regs)
+}
+
+/// Fills the first `size` bytes of `dst` (the granule given by the realm) with random bytes.
+pub fn random_granule(
+    size: usize,
+    dst: &mut [u8],
+    entropy: &mut impl Entropy,
+) -> Result<(), Error> {
+    if size == 0 || size > MAX_RANDOM_SIZE || size > dst.len() {
+        return Err(Error::RmiErrorInput);
+    }
+    entropy.fill(&mut dst[..size])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+
+    /// Yields 1, 2, 3, ... as random bytes
+    struct Counter(u8);
+
+    impl Entropy for Counter {
+        fn fill(&mut self, dst: &mut [u8]) -> Result<(), Error> {
+            for byte in dst {
+                self.0 = self.0.wrapping_add(1);
+                *byte = self.0;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn fill_requested_bytes() {
+        let regs = random_regs(10, &mut Counter(0)).unwrap();
+        assert_eq!(regs[0], usize::from_le_bytes([1, 2, 3, 4, 5, 6, 7, 8]));
+        assert_eq!(regs[1], usize::from_le_bytes([9, 10, 0, 0, 0, 0, 0, 0]));
+        assert_eq!(regs[2..], [0, 0]);
+
+        let mut granule = vec![0xffu8; GRANULE_SIZE];
+        random_granule(100, &mut granule, &mut Counter(0)).unwrap();
+        assert!(granule[..100]
+            .iter()
+            .enumerate()
+            .all(|(i, b)| *b == i as u8 + 1));
+        // the rest is left untouched
+        assert!(granule[100..].iter().all(|b| *b == 0xff));
+    }
+
+    #[test]
+    fn oversized_request() {
+        assert!(random_regs(MAX_RANDOM_REGS_SIZE + 1, &mut Counter(0)).is_err());
+        assert!(random_regs(0, &mut Counter(0)).is_err());
+
+        let mut granule = vec![0u8; GRANULE_SIZE];
+        assert!(random_granule(MAX_RANDOM_SIZE + 1, &mut granule, &mut Counter(0)).is_err());
+        assert!(granule.iter().all(|b| *b == 0));
+    }
+}
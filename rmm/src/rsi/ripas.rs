@@ -0,0 +1,706 @@
+use crate::granule::{is_granule_aligned, GRANULE_SIZE};
+use crate::mm::protected_ipa_boundary;
+use crate::realm::mm::stage2_tte::invalid_ripas;
+use crate::rmi::error::Error;
+use crate::rmi::rec::run::{
+    marshal_exit, DiagCheck, ExitPayload, ExitReason, Run, REC_ENTRY_FLAG_RIPAS_RESPONSE,
+};
+use crate::rmi::rtt::is_protected_range;
+
+/// The host has applied the RIPAS change.
+pub const RSI_ACCEPT: usize = 0;
+/// The host has rejected the RIPAS change.
+pub const RSI_REJECT: usize = 1;
+
+/// The RIPAS of a page whose contents are lost (e.g., by RMI_DATA_DESTROY),
+/// in the encoding of RSI_IPA_STATE_GET.
+pub const RIPAS_DESTROYED: u8 = 2;
+
+/// Flags of RSI_IPA_STATE_SET passed in x4.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RipasChangeFlags(u64);
+
+impl RipasChangeFlags {
+    /// Whether the RIPAS of a destroyed page can be changed.
+    ///  val 0: RSI_NO_CHANGE_DESTROYED, a destroyed page stops the change.
+    ///  val 1: RSI_CHANGE_DESTROYED, a destroyed page is changed like the others.
+    pub const CHANGE_DESTROYED: u64 = 1 << 0;
+    const ALLOWED: u64 = Self::CHANGE_DESTROYED;
+
+    pub fn parse(flags: usize) -> Result<Self, Error> {
+        let flags = flags as u64;
+        if flags & !Self::ALLOWED != 0 {
+            return Err(Error::RmiErrorInput);
+        }
+        Ok(Self(flags))
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+
+    pub fn change_destroyed(&self) -> bool {
+        self.0 & Self::CHANGE_DESTROYED != 0
+    }
+}
+
+/// Returns the response to the realm's RIPAS change
+/// (i.e., `RSI_ACCEPT` or `RSI_REJECT`) from the host's REC entry flags.
+///
+/// The host can reject any change, which the realm finds in x2.
+pub fn ripas_response(entry_flags: u64) -> usize {
+    if entry_flags & REC_ENTRY_FLAG_RIPAS_RESPONSE == 0 {
+        RSI_ACCEPT
+    } else {
+        RSI_REJECT
+    }
+}
+
+/// How a RIPAS change proceeds once the host re-enters the REC.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RipasProgress {
+    /// Returns to the realm with the next IPA to be changed (x1) and the response (x2).
+    Complete { next: u64, response: usize },
+    /// Exits to the host again for the rest of the range, `[base, base + size)`.
+    Resume { base: u64, size: u64 },
+}
+
+/// Decides how the RIPAS change of the range ending at `end` proceeds
+/// after the host has applied `[exit_base, addr)` and re-entered with `response`.
+///
+/// The change resumes with the rest of the range only if the host accepted it
+/// and applied at least a page since the last exit, so that a host applying nothing
+/// can't keep the realm from running. A rejection stops the change at `addr`,
+/// the first page the host didn't apply.
+pub fn ripas_progress(exit_base: u64, addr: u64, end: u64, response: usize) -> RipasProgress {
+    if response == RSI_ACCEPT && exit_base < addr && addr < end {
+        RipasProgress::Resume {
+            base: addr,
+            size: end - addr,
+        }
+    } else {
+        RipasProgress::Complete {
+            next: addr,
+            response,
+        }
+    }
+}
+
+/// Returns whether the realm can change a page in `current` RIPAS to `target`.
+///
+/// Only EMPTY and RAM can be changed into each other. A destroyed page can be
+/// brought back only if the realm passed `RSI_CHANGE_DESTROYED`,
+/// and the realm can't destroy a page itself.
+pub fn is_ripas_transition_allowed(current: u8, target: u8, flags: RipasChangeFlags) -> bool {
+    let changeable = |ripas: u8| matches!(ripas as u64, invalid_ripas::EMPTY | invalid_ripas::RAM);
+    let destroyed = current == RIPAS_DESTROYED && flags.change_destroyed();
+    (changeable(current) || destroyed) && changeable(target)
+}
+
+/// Returns whether every page in `[base, end)` can be changed to `target`,
+/// walking the RTT entries covering the range.
+///
+/// `ripas_at` returns the RIPAS of the last-level entry covering an IPA and the size
+/// of the range it covers (i.e., `crate::rtt::ripas_at`, which can be stubbed out in tests),
+/// so that a block entry is checked at once. An entry which can't be read fails the check.
+pub fn is_ripas_change_allowed(
+    base: usize,
+    end: usize,
+    target: u8,
+    flags: RipasChangeFlags,
+    ripas_at: impl FnMut(usize) -> Result<(u8, usize), Error>,
+) -> bool {
+    first_disallowed_ripas(base, end, target, flags, ripas_at).is_none()
+}
+
+/// Returns the first IPA in `[base, end)` which can't be changed to `target`,
+/// or `None` if the whole range can. See `is_ripas_change_allowed` for `ripas_at`.
+fn first_disallowed_ripas(
+    base: usize,
+    end: usize,
+    target: u8,
+    flags: RipasChangeFlags,
+    mut ripas_at: impl FnMut(usize) -> Result<(u8, usize), Error>,
+) -> Option<usize> {
+    let mut ipa = base;
+    while ipa < end {
+        match ripas_at(ipa) {
+            Ok((ripas, size)) if is_ripas_transition_allowed(ripas, target, flags) => {
+                ipa = (ipa & !(size - 1)) + size;
+            }
+            _ => return Some(ipa),
+        }
+    }
+    None
+}
+
+/// Validates the RIPAS change of `[base, base + size)` to `target` under `flags` as a whole,
+/// without changing anything, so that the realm can find out beforehand
+/// whether a bulk change would stop midway.
+///
+/// Returns the failed check and the first offending IPA, or `None` if every page
+/// can be changed:
+///   - `base` itself if the range is malformed (unaligned base, unknown target, overflow),
+///   - the partial page at the end if `size` isn't aligned,
+///   - the first IPA out of the protected range,
+///   - the first page whose current RIPAS doesn't allow the change.
+pub fn check_ripas_change(
+    base: usize,
+    size: usize,
+    target: u8,
+    flags: RipasChangeFlags,
+    ipa_bits: usize,
+    ripas_at: impl FnMut(usize) -> Result<(u8, usize), Error>,
+) -> Option<(DiagCheck, usize)> {
+    if !is_granule_aligned(base) {
+        return Some((DiagCheck::Alignment, base));
+    }
+    if !matches!(target as u64, invalid_ripas::EMPTY | invalid_ripas::RAM) {
+        return Some((DiagCheck::InvalidValue, base));
+    }
+    let end = match ripas_range(base, size) {
+        Ok(Some(end)) => end,
+        Ok(None) => return None,
+        Err(_) => return Some((DiagCheck::Overflow, base)),
+    };
+    if !is_granule_aligned(size) {
+        return Some((DiagCheck::Alignment, end & !(GRANULE_SIZE - 1)));
+    }
+    if !is_protected_range(base, end, ipa_bits) {
+        let ipa = core::cmp::max(base, protected_ipa_boundary(ipa_bits));
+        return Some((DiagCheck::ProtectedRange, ipa));
+    }
+    first_disallowed_ripas(base, end, target, flags, ripas_at)
+        .map(|ipa| (DiagCheck::RipasTransition, ipa))
+}
+
+/// Returns the RIPAS of `base` and the top of the run from `base` sharing it,
+/// bounded by `end`, for RSI_IPA_STATE_GET.
+///
+/// `ripas_at` returns the RIPAS of the entry covering an IPA and the size of the range
+/// the entry covers (i.e., `crate::rtt::ripas_at`, which can be stubbed out in tests).
+/// The run steps over whole entries (e.g., a 2MB block at once), so it ends at an entry
+/// boundary: the first entry whose RIPAS differs or which can't be read, or `end`.
+pub fn ripas_run(
+    base: usize,
+    end: usize,
+    mut ripas_at: impl FnMut(usize) -> Result<(u8, usize), Error>,
+) -> Result<(u8, usize), Error> {
+    let next_entry = |ipa: usize, size: usize| {
+        let size = size.max(GRANULE_SIZE);
+        (ipa & !(size - 1)).saturating_add(size)
+    };
+
+    let (ripas, size) = ripas_at(base)?;
+    let mut top = next_entry(base, size);
+    while top < end {
+        match ripas_at(top) {
+            Ok((next, size)) if next == ripas => top = next_entry(top, size),
+            _ => break,
+        }
+    }
+    Ok((ripas, core::cmp::min(top, end)))
+}
+
+/// Returns the end of the RIPAS change range `[ipa_start, ipa_start + ipa_size)`,
+/// or `None` for a zero-length range.
+///
+/// The end must not wrap around, so a range reaching the top of the address space
+/// is rejected, and a non-empty range always ends after `ipa_start`.
+///
+/// A zero-length range changes nothing, so RSI_IPA_STATE_SET completes it
+/// without exiting to the host, returning `ipa_start` in x1 as the next address
+/// like the completion of a non-empty change does.
+pub fn ripas_range(ipa_start: usize, ipa_size: usize) -> Result<Option<usize>, Error> {
+    if ipa_size == 0 {
+        return Ok(None);
+    }
+    ipa_start
+        .checked_add(ipa_size)
+        .map(Some)
+        .ok_or(Error::RmiErrorInput)
+}
+
+/// Exits to the host to request the RIPAS change of `[base, base + size)` to `state`.
+pub fn set_ripas_exit(run: &mut Run, base: u64, size: u64, state: u8) -> Result<(), Error> {
+    let payload = ExitPayload::RipasChange { base, size, state };
+    marshal_exit(run, &ExitReason::RipasChange, &payload)
+}
+
+/// Applies the RIPAS change of `[base, end)` in the RMM, page by page with `set_ripas`,
+/// instead of exiting to the host (see `RIPAS_AUTO_ACCEPT`).
+///
+/// `set_ripas` changes the RIPAS of a protected IPA page
+/// (i.e., `crate::rmi::rtt::set_ripas` at the page level, which can be stubbed out in tests).
+/// Returns the address where the change stopped, which is `end` once all pages are changed.
+/// It stops at the first page which can't be changed (e.g., its RTT is missing),
+/// leaving the rest to the host.
+pub fn apply_ripas(
+    base: usize,
+    end: usize,
+    mut set_ripas: impl FnMut(usize) -> Result<(), Error>,
+) -> usize {
+    let mut next = base;
+    while next < end && set_ripas(next).is_ok() {
+        next += GRANULE_SIZE;
+    }
+    next
+}
+
+/// The maximum number of pages whose RIPAS can be read in a single call,
+/// one byte per page in the destination granule.
+pub const MAX_RIPAS_BATCH: usize = GRANULE_SIZE;
+
+/// Writes the RIPAS of `count` pages from `base` into `dst`, one byte per page.
+///
+/// `get_ripas` returns the RIPAS of a protected IPA page
+/// (i.e., `crate::rtt::get_ripas` at the page level, which can be stubbed out in tests).
+/// The contents of `dst` are unspecified on an error.
+pub fn read_ripas_batch(
+    base: usize,
+    count: usize,
+    ipa_bits: usize,
+    dst: &mut [u8],
+    mut get_ripas: impl FnMut(usize) -> Result<u64, Error>,
+) -> Result<(), Error> {
+    if count == 0 || count > MAX_RIPAS_BATCH || count > dst.len() || !is_granule_aligned(base) {
+        return Err(Error::RmiErrorInput);
+    }
+
+    let end = count
+        .checked_mul(GRANULE_SIZE)
+        .and_then(|size| base.checked_add(size))
+        .ok_or(Error::RmiErrorInput)?;
+    if !is_protected_range(base, end, ipa_bits) {
+        return Err(Error::RmiErrorInput);
+    }
+
+    for (ipa, ripas) in (base..end).step_by(GRANULE_SIZE).zip(dst.iter_mut()) {
+        *ripas = get_ripas(ipa)? as u8;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rmi;
+    use crate::rmi::rec::run::RunHint;
+
+    #[test]
+    fn valid_flags() {
+        let flags = RipasChangeFlags::parse(0).unwrap();
+        assert!(!flags.change_destroyed());
+
+        let flags = RipasChangeFlags::parse(RipasChangeFlags::CHANGE_DESTROYED as usize).unwrap();
+        assert!(flags.change_destroyed());
+        assert_eq!(flags.get(), RipasChangeFlags::CHANGE_DESTROYED);
+    }
+
+    #[test]
+    fn unknown_flags() {
+        assert!(RipasChangeFlags::parse(1 << 1).is_err());
+        assert!(RipasChangeFlags::parse(usize::MAX).is_err());
+    }
+
+    #[test]
+    fn zero_length_range() {
+        assert_eq!(ripas_range(0x8000_0000, 0).unwrap(), None);
+        assert_eq!(ripas_range(usize::MAX, 0).unwrap(), None);
+        assert_eq!(ripas_range(0x8000_0000, 0x1000).unwrap(), Some(0x8000_1000));
+        assert!(ripas_range(usize::MAX - 0xfff, 0x1000).is_err());
+    }
+
+    #[test]
+    fn overflowing_range() {
+        assert_eq!(ripas_range(0, 0).unwrap(), None);
+        assert!(ripas_range(usize::MAX, 1).is_err());
+        assert!(ripas_range(1, usize::MAX).is_err());
+        assert!(ripas_range(usize::MAX, usize::MAX).is_err());
+
+        // no wrap-around, but beyond any protected range
+        let end = ripas_range(0, usize::MAX).unwrap().unwrap();
+        assert_eq!(end, usize::MAX);
+        assert!(!is_protected_range(0, end, 48));
+        assert!(!is_protected_range(0, 0, 48));
+    }
+
+    #[test]
+    fn ripas_exit_hint() {
+        let mut run = Run::default();
+        assert_eq!(run.run_hint(), RunHint::Runnable as u8);
+
+        set_ripas_exit(&mut run, 0x8000_0000, 0x1000, 1).unwrap();
+        assert_eq!(run.exit_reason(), rmi::EXIT_RIPAS_CHANGE);
+        assert_eq!(run.run_hint(), RunHint::BlockedRipas as u8);
+    }
+
+    const IPA_BITS: usize = 33;
+
+    fn get_ripas(ipa: usize) -> Result<u64, Error> {
+        match ipa {
+            0x0..=0x3fff => Ok((ipa / GRANULE_SIZE % 2) as u64),
+            _ => Err(Error::RmiErrorRtt(2)),
+        }
+    }
+
+    #[test]
+    fn batch_matches_per_page() {
+        let mut dst = [0xff; GRANULE_SIZE];
+        read_ripas_batch(0x0, 4, IPA_BITS, &mut dst, get_ripas).unwrap();
+        for (i, &ripas) in dst[..4].iter().enumerate() {
+            assert_eq!(ripas as u64, get_ripas(i * GRANULE_SIZE).unwrap());
+        }
+        assert!(dst[4..].iter().all(|&b| b == 0xff));
+    }
+
+    #[test]
+    fn batch_out_of_bounds() {
+        let mut dst = [0; GRANULE_SIZE];
+        let read =
+            |base, count, dst: &mut [u8]| read_ripas_batch(base, count, IPA_BITS, dst, get_ripas);
+
+        assert!(read(0x0, 0, &mut dst).is_err());
+        assert!(read(0x0, MAX_RIPAS_BATCH + 1, &mut dst).is_err());
+        assert!(read(0x0, 4, &mut dst[..3]).is_err());
+        assert!(read(0x10, 1, &mut dst).is_err()); // unaligned
+        assert!(read(0x1_0000_0000 - 0x1000, 2, &mut dst).is_err()); // unprotected
+        assert!(read(0x3000, 2, &mut dst).is_err()); // unmapped
+    }
+
+    #[test]
+    fn auto_accept_in_one_call() {
+        use crate::realm::mm::stage2_tte::{invalid_hipas, S2TTE};
+        use crate::rmi::rtt::RTT_PAGE_LEVEL;
+        use crate::rtt::{ripas_entry, walk_entry};
+        use armv9a::bits_in_reg;
+        use core::cell::RefCell;
+
+        // an RTT of level 3 as the root, whose pages from 0x0 are
+        //   unassigned EMPTY, unassigned EMPTY, assigned EMPTY, valid (RAM), destroyed
+        let destroyed = bits_in_reg(S2TTE::INVALID_HIPAS, invalid_hipas::DESTROYED);
+        let rtt = RefCell::new([
+            0,
+            0,
+            S2TTE::data(0x8800_2000, RTT_PAGE_LEVEL, invalid_ripas::EMPTY),
+            S2TTE::data(0x8800_3000, RTT_PAGE_LEVEL, invalid_ripas::RAM),
+            destroyed,
+        ]);
+        let read = |_, index: usize| rtt.borrow().get(index).copied().unwrap_or(destroyed);
+        let entry = |ipa| walk_entry(0, RTT_PAGE_LEVEL, ipa, read).unwrap();
+        // changes the RTT as `crate::rmi::rtt::set_ripas` does at the page level
+        let set_ripas = |ipa: usize| {
+            let (s2tte, level) = walk_entry(0, RTT_PAGE_LEVEL, ipa, read)?;
+            if let Some(new_s2tte) = ripas_entry(&s2tte, level, invalid_ripas::RAM)? {
+                let mut rtt = rtt.borrow_mut();
+                let slot = rtt
+                    .get_mut(ipa / GRANULE_SIZE)
+                    .ok_or(Error::RmiErrorRtt(3))?;
+                *slot = new_s2tte;
+            }
+            Ok(())
+        };
+
+        assert_eq!(apply_ripas(0x1000, 0x4000, set_ripas), 0x4000);
+        // the destroyed page is changed as well, since the realm must have passed
+        // RSI_CHANGE_DESTROYED to get here (see `check_ripas_change`),
+        // but it stops past the RTT
+        assert_eq!(apply_ripas(0x3000, 0x6000, set_ripas), 0x5000);
+
+        // untouched outside the range
+        assert_eq!(entry(0x0).0.get_ripas(), invalid_ripas::EMPTY);
+        for ipa in [0x1000, 0x2000] {
+            let (s2tte, _) = entry(ipa);
+            assert!(s2tte.is_unassigned() || s2tte.is_assigned());
+            assert_eq!(s2tte.get_ripas(), invalid_ripas::RAM);
+        }
+        // the assigned page keeps its output address
+        let (s2tte, level) = entry(0x2000);
+        assert!(s2tte.is_assigned());
+        let pa: usize = s2tte.address(level).unwrap().into();
+        assert_eq!(pa, 0x8800_2000);
+        // the valid page is left as it is
+        let (s2tte, level) = entry(0x3000);
+        assert!(s2tte.is_valid(level, false));
+        assert_eq!(
+            s2tte.get(),
+            S2TTE::data(0x8800_3000, RTT_PAGE_LEVEL, invalid_ripas::RAM)
+        );
+        // the destroyed page is brought back as an unassigned one
+        let (s2tte, _) = entry(0x4000);
+        assert!(s2tte.is_unassigned());
+        assert_eq!(s2tte.get_ripas(), invalid_ripas::RAM);
+    }
+
+    #[test]
+    fn ram_to_empty() {
+        use crate::rmi::rtt::level_to_size;
+
+        // a 2MB block of RAM at 0x0, followed by RAM pages
+        let rtt = |ipa: usize| match ipa {
+            0x0..=0x1f_ffff => Ok((invalid_ripas::RAM as u8, level_to_size(2) as usize)),
+            _ => Ok((invalid_ripas::RAM as u8, GRANULE_SIZE)),
+        };
+        let empty = invalid_ripas::EMPTY as u8;
+        let flags = RipasChangeFlags::default();
+
+        assert!(is_ripas_change_allowed(
+            0x1f_e000, 0x20_4000, empty, flags, rtt
+        ));
+        assert!(is_ripas_change_allowed(0x0, 0x1000, empty, flags, rtt));
+        assert!(is_ripas_transition_allowed(
+            empty,
+            invalid_ripas::RAM as u8,
+            flags
+        ));
+    }
+
+    #[test]
+    fn disallowed_transition() {
+        // the third page is destroyed
+        let rtt = |ipa: usize| match ipa {
+            0x2000..=0x2fff => Ok((RIPAS_DESTROYED, GRANULE_SIZE)),
+            0x5000.. => Err(Error::RmiErrorRtt(2)),
+            _ => Ok((invalid_ripas::RAM as u8, GRANULE_SIZE)),
+        };
+        let empty = invalid_ripas::EMPTY as u8;
+        let flags = RipasChangeFlags::default();
+
+        assert!(is_ripas_change_allowed(0x0, 0x2000, empty, flags, rtt));
+        assert!(!is_ripas_change_allowed(0x0, 0x4000, empty, flags, rtt));
+        assert!(!is_ripas_change_allowed(
+            0x2000,
+            0x3000,
+            invalid_ripas::RAM as u8,
+            flags,
+            rtt
+        ));
+        // a destroyed target, even for pages which could be changed otherwise
+        assert!(!is_ripas_change_allowed(
+            0x0,
+            0x2000,
+            RIPAS_DESTROYED,
+            flags,
+            rtt
+        ));
+        // unreadable entries
+        assert!(!is_ripas_change_allowed(0x4000, 0x6000, empty, flags, rtt));
+    }
+
+    #[test]
+    fn change_destroyed() {
+        // the third page is destroyed
+        let rtt = |ipa: usize| match ipa {
+            0x2000..=0x2fff => Ok((RIPAS_DESTROYED, GRANULE_SIZE)),
+            _ => Ok((invalid_ripas::RAM as u8, GRANULE_SIZE)),
+        };
+        let empty = invalid_ripas::EMPTY as u8;
+        let flags = RipasChangeFlags::parse(RipasChangeFlags::CHANGE_DESTROYED as usize).unwrap();
+
+        assert!(is_ripas_change_allowed(0x0, 0x4000, empty, flags, rtt));
+        assert!(is_ripas_change_allowed(
+            0x2000,
+            0x3000,
+            invalid_ripas::RAM as u8,
+            flags,
+            rtt
+        ));
+        // the realm still can't destroy a page itself
+        assert!(!is_ripas_change_allowed(
+            0x0,
+            0x2000,
+            RIPAS_DESTROYED,
+            flags,
+            rtt
+        ));
+        assert_eq!(check_ripas_change(0x0, 0x4000, empty, flags, 48, rtt), None);
+        assert_eq!(
+            check_ripas_change(0x0, 0x4000, empty, RipasChangeFlags::default(), 48, rtt),
+            Some((DiagCheck::RipasTransition, 0x2000))
+        );
+    }
+
+    #[test]
+    fn check_whole_range() {
+        use core::cell::RefCell;
+
+        let empty = invalid_ripas::EMPTY as u8;
+        // RAM pages, but the fourth one is destroyed
+        let rtt = RefCell::new([invalid_ripas::RAM as u8; 8]);
+        rtt.borrow_mut()[3] = RIPAS_DESTROYED;
+        let before = *rtt.borrow();
+        let ripas_at = |ipa: usize| {
+            rtt.borrow()
+                .get(ipa / GRANULE_SIZE)
+                .map(|ripas| (*ripas, GRANULE_SIZE))
+                .ok_or(Error::RmiErrorRtt(3))
+        };
+
+        let check = |base, size, target, ipa_bits| {
+            check_ripas_change(
+                base,
+                size,
+                target,
+                RipasChangeFlags::default(),
+                ipa_bits,
+                ripas_at,
+            )
+        };
+
+        assert_eq!(check(0x0, 0x3000, empty, 48), None);
+        assert_eq!(
+            check(0x1000, 0x6000, empty, 48),
+            Some((DiagCheck::RipasTransition, 0x3000))
+        );
+        // nothing is changed by the check
+        assert_eq!(*rtt.borrow(), before);
+
+        // malformed ranges
+        assert_eq!(check(0x1000, 0, empty, 48), None);
+        assert_eq!(
+            check(0x1800, 0x1000, empty, 48),
+            Some((DiagCheck::Alignment, 0x1800))
+        );
+        assert_eq!(
+            check(0x0, 0x1800, empty, 48),
+            Some((DiagCheck::Alignment, 0x1000))
+        );
+        assert_eq!(
+            check(0x0, 0x1000, RIPAS_DESTROYED, 48),
+            Some((DiagCheck::InvalidValue, 0x0))
+        );
+        assert_eq!(
+            check(0x1000, usize::MAX, empty, 48),
+            Some((DiagCheck::Overflow, 0x1000))
+        );
+        // beyond the protected range of a 16-bit IPA space
+        assert_eq!(
+            check(0x0, 0x9000, empty, 16),
+            Some((DiagCheck::ProtectedRange, 0x8000))
+        );
+        assert_eq!(
+            check(0x9000, 0x1000, empty, 16),
+            Some((DiagCheck::ProtectedRange, 0x9000))
+        );
+    }
+
+    #[test]
+    fn uniform_run() {
+        const BLOCK: usize = 2 << 20;
+        let ram = invalid_ripas::RAM as u8;
+        // RAM pages up to a 2MB block of RAM, then EMPTY pages
+        let rtt = |ipa: usize| match ipa {
+            0..=0x1f_ffff => Ok((ram, GRANULE_SIZE)),
+            BLOCK..=0x3f_ffff => Ok((ram, BLOCK)),
+            0x40_0000..=0x40_ffff => Ok((invalid_ripas::EMPTY as u8, GRANULE_SIZE)),
+            _ => Err(Error::RmiErrorRtt(3)),
+        };
+
+        // several pages, bounded by the end
+        assert_eq!(ripas_run(0x1000, 0x5000, rtt).unwrap(), (ram, 0x5000));
+        // across the block, stepping over it at once
+        assert_eq!(ripas_run(0x1000, 0x80_0000, rtt).unwrap(), (ram, 0x40_0000));
+        // from the middle of the block, ending at its boundary
+        assert_eq!(
+            ripas_run(BLOCK + 0x3000, BLOCK + 0x5000, rtt).unwrap(),
+            (ram, BLOCK + 0x5000)
+        );
+    }
+
+    #[test]
+    fn run_ends_at_differing_entry() {
+        let ram = invalid_ripas::RAM as u8;
+        let empty = invalid_ripas::EMPTY as u8;
+        // RAM pages, but the fourth one is EMPTY and the sixth one is destroyed
+        let rtt = |ipa: usize| match ipa / GRANULE_SIZE {
+            3 => Ok((empty, GRANULE_SIZE)),
+            5 => Ok((RIPAS_DESTROYED, GRANULE_SIZE)),
+            0..=7 => Ok((ram, GRANULE_SIZE)),
+            _ => Err(Error::RmiErrorRtt(3)),
+        };
+
+        assert_eq!(ripas_run(0x0, 0x8000, rtt).unwrap(), (ram, 0x3000));
+        assert_eq!(ripas_run(0x3000, 0x8000, rtt).unwrap(), (empty, 0x4000));
+        assert_eq!(
+            ripas_run(0x5000, 0x8000, rtt).unwrap(),
+            (RIPAS_DESTROYED, 0x6000)
+        );
+        // up to an entry which can't be read
+        assert_eq!(ripas_run(0x6000, 0xa000, rtt).unwrap(), (ram, 0x8000));
+        assert!(ripas_run(0x8000, 0xa000, rtt).is_err());
+    }
+
+    #[test]
+    fn diagnostics() {
+        // the second page is destroyed
+        let rtt = |ipa: usize| match ipa {
+            0x1000..=0x1fff => Ok((RIPAS_DESTROYED, GRANULE_SIZE)),
+            _ => Ok((invalid_ripas::RAM as u8, GRANULE_SIZE)),
+        };
+        let empty = invalid_ripas::EMPTY as u8;
+
+        let mut run = Run::default();
+        let (check, ipa) =
+            check_ripas_change(0x0, 0x4000, empty, RipasChangeFlags::default(), 48, rtt).unwrap();
+        run.set_diag(check, ipa as u64);
+
+        if cfg!(feature = "diagnostics") {
+            assert_eq!(run.diag(), (DiagCheck::RipasTransition as u8, 0x1000));
+        } else {
+            assert_eq!(run.diag(), (DiagCheck::None as u8, 0));
+        }
+    }
+
+    #[test]
+    fn reject_response() {
+        assert_eq!(ripas_response(0), RSI_ACCEPT);
+        assert_eq!(ripas_response(REC_ENTRY_FLAG_RIPAS_RESPONSE), RSI_REJECT);
+    }
+
+    #[test]
+    fn accept_two_pages_reject_third() {
+        const BASE: u64 = 0x8000_0000;
+        const END: u64 = BASE + 4 * GRANULE_SIZE as u64;
+        let page = |n: u64| BASE + n * GRANULE_SIZE as u64;
+
+        // the host applies the first two pages of [BASE, END) and accepts
+        assert_eq!(
+            ripas_progress(BASE, page(2), END, RSI_ACCEPT),
+            RipasProgress::Resume {
+                base: page(2),
+                size: 2 * GRANULE_SIZE as u64
+            }
+        );
+        // then rejects the third, where the change stops
+        assert_eq!(
+            ripas_progress(page(2), page(2), END, RSI_REJECT),
+            RipasProgress::Complete {
+                next: page(2),
+                response: RSI_REJECT
+            }
+        );
+    }
+
+    #[test]
+    fn complete_without_progress() {
+        const BASE: u64 = 0x8000_0000;
+        const END: u64 = BASE + 2 * GRANULE_SIZE as u64;
+
+        // all pages applied
+        assert_eq!(
+            ripas_progress(BASE, END, END, RSI_ACCEPT),
+            RipasProgress::Complete {
+                next: END,
+                response: RSI_ACCEPT
+            }
+        );
+        // accepted without applying any page since the last exit
+        assert_eq!(
+            ripas_progress(BASE, BASE, END, RSI_ACCEPT),
+            RipasProgress::Complete {
+                next: BASE,
+                response: RSI_ACCEPT
+            }
+        );
+    }
+}
@@ -0,0 +1,47 @@
+/// Counts the host exits of a REC within a window of the system counter.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Throttle {
+    window_start: u64,
+    exits: u32,
+}
+
+impl Throttle {
+    /// Records a host exit at `now` and returns whether it goes beyond
+    /// `burst` exits within `window` ticks. Throttled exits aren't counted.
+    pub fn hit(&mut self, now: u64, burst: u32, window: u64) -> bool {
+        if now.wrapping_sub(self.window_start) >= window {
+            self.window_start = now;
+            self.exits = 0;
+        }
+
+        if self.exits >= burst {
+            return true;
+        }
+        self.exits += 1;
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{HOST_EXIT_BURST, HOST_EXIT_WINDOW};
+
+    #[test]
+    fn ripas_burst_throttled() {
+        let mut throttle = Throttle::default();
+        let start = HOST_EXIT_WINDOW;
+
+        // a burst of RIPAS changes within the window
+        for i in 0..HOST_EXIT_BURST {
+            assert!(!throttle.hit(start + i as u64, HOST_EXIT_BURST, HOST_EXIT_WINDOW));
+        }
+        let now = start + HOST_EXIT_BURST as u64;
+        assert!(throttle.hit(now, HOST_EXIT_BURST, HOST_EXIT_WINDOW));
+        assert!(throttle.hit(now + 1, HOST_EXIT_BURST, HOST_EXIT_WINDOW));
+
+        // the next window starts afresh
+        let now = start + HOST_EXIT_WINDOW;
+        assert!(!throttle.hit(now, HOST_EXIT_BURST, HOST_EXIT_WINDOW));
+    }
+}
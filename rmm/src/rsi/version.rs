@@ -0,0 +1,98 @@
+use crate::get_granule;
+use crate::get_granule_if;
+use crate::granule::GranuleState;
+use crate::realm::context::set_result_reg;
+use crate::rmi;
+use crate::rmi::error::Error;
+use crate::rmi::realm::Rd;
+use crate::rmi::rec::run::Run;
+use crate::rmi::rec::Rec;
+use crate::rsi::{ERROR_INPUT, SUCCESS, VERSION};
+use crate::Monitor;
+
+/// Encodes the revision `major.minor` as RSI_VERSION does.
+pub const fn encode(major: usize, minor: usize) -> usize {
+    (major << 16) | minor
+}
+
+/// Negotiates the RSI revision with the realm which requests `requested`
+/// of the RMM implementing `implemented`, returning the status
+/// with the lower and the higher revisions of RSI_VERSION.
+///
+/// The request is compatible if it's of the same major revision as `implemented`
+/// and no later minor one, which is returned as the lower revision.
+/// Otherwise, it fails with `ERROR_INPUT` and both are `implemented`.
+pub fn negotiate(requested: usize, implemented: usize) -> [usize; 3] {
+    let compatible = requested >> 16 == implemented >> 16 && requested <= implemented;
+    if compatible {
+        [SUCCESS, requested, implemented]
+    } else {
+        [ERROR_INPUT, implemented, implemented]
+    }
+}
+
+/// Handles RSI_VERSION with the requested revision in x1, returning
+/// the status in x0 and the lower and the higher revisions in x1 and x2.
+pub fn get_version(
+    _arg: &[usize],
+    ret: &mut [usize],
+    _rmm: &Monitor,
+    rec: &mut Rec<'_>,
+    _run: &mut Run,
+) -> Result<(), Error> {
+    let vcpuid = rec.vcpuid();
+    let rd_granule = get_granule_if!(rec.owner()?, GranuleState::RD)?;
+    let rd = rd_granule.content::<Rd>();
+
+    let requested = crate::realm::context::get_reg(rd, vcpuid, 1)?;
+    let [status, lower, higher] = negotiate(requested, VERSION);
+    trace!(
+        "RSI_VERSION: requested {:#X} lower {:#X} higher {:#X}",
+        requested,
+        lower,
+        higher
+    );
+
+    set_result_reg(rd, vcpuid, 0, status)?;
+    set_result_reg(rd, vcpuid, 1, lower)?;
+    set_result_reg(rd, vcpuid, 2, higher)?;
+    ret[0] = rmi::SUCCESS_REC_ENTER;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const IMPLEMENTED: usize = encode(1, 2);
+
+    #[test]
+    fn exact_match() {
+        assert_eq!(
+            negotiate(IMPLEMENTED, IMPLEMENTED),
+            [SUCCESS, IMPLEMENTED, IMPLEMENTED]
+        );
+        assert_eq!(negotiate(VERSION, VERSION), [SUCCESS, VERSION, VERSION]);
+    }
+
+    #[test]
+    fn older_request() {
+        let older = encode(1, 1);
+        assert_eq!(negotiate(older, IMPLEMENTED), [SUCCESS, older, IMPLEMENTED]);
+        // a previous major revision isn't compatible
+        assert_eq!(
+            negotiate(encode(0, 9), IMPLEMENTED),
+            [ERROR_INPUT, IMPLEMENTED, IMPLEMENTED]
+        );
+    }
+
+    #[test]
+    fn newer_request() {
+        for newer in [encode(1, 3), encode(2, 0)] {
+            assert_eq!(
+                negotiate(newer, IMPLEMENTED),
+                [ERROR_INPUT, IMPLEMENTED, IMPLEMENTED]
+            );
+        }
+    }
+}
@@ -1,19 +1,24 @@
-use crate::granule::GRANULE_SIZE;
-use crate::granule::{set_granule, GranuleState};
+use crate::config::{MAX_UNACCOUNTED_REGIONS, ZERO_ON_RIPAS_EMPTY};
+use crate::granule::{assign_granule, release_granule, GranuleState};
+use crate::granule::{GRANULE_SHIFT, GRANULE_SIZE};
+use crate::mm::protected_ipa_boundary;
 use crate::mm::translation::PageTable;
 use crate::realm::mm::address::GuestPhysAddr;
-use crate::realm::mm::page_table::pte::attribute;
-use crate::realm::mm::page_table::pte::{permission, shareable};
 use crate::realm::mm::stage2_tte::{desc_type, invalid_hipas, invalid_ripas};
 use crate::realm::mm::stage2_tte::{RttPage, INVALID_UNPROTECTED, S2TTE};
+use crate::realm::mm::translate::translate_ipa;
 use crate::rmi::error::Error;
 use crate::rmi::realm::Rd;
-use crate::rmi::rtt::RTT_PAGE_LEVEL;
 use crate::rmi::rtt::S2TTE_STRIDE;
+use crate::rmi::rtt::{level_to_size, RTT_PAGE_LEVEL};
 use crate::rmi::rtt_entry_state;
+use crate::rsi::ripas::RIPAS_DESTROYED;
 use crate::{get_granule, get_granule_if};
 use armv9a::bits_in_reg;
 
+use alloc::vec::Vec;
+use core::ops::Range;
+
 pub fn create(rd: &Rd, rtt_addr: usize, ipa: usize, level: usize) -> Result<(), Error> {
     let mut rtt_granule = get_granule_if!(rtt_addr, GranuleState::Delegated)?;
     let s2tt = rtt_granule.content_mut::<RttPage>();
@@ -51,44 +56,28 @@ pub fn create(rd: &Rd, rtt_addr: usize, ipa: usize, level: usize) -> Result<(),
                 *elem = new_s2tte;
             }
         }
-    } else if parent_s2tte.is_assigned() {
-        let mut pa: usize = parent_s2tte
-            .address(level - 1)
-            .ok_or(Error::RmiErrorRtt(0))?
-            .into(); //XXX: check this again
-        let map_size = match level {
-            3 => GRANULE_SIZE, // 4096
-            2 => GRANULE_SIZE << S2TTE_STRIDE,
-            1 => GRANULE_SIZE << (S2TTE_STRIDE * 2),
-            0 => GRANULE_SIZE << (S2TTE_STRIDE * 3),
-            _ => unreachable!(),
-        };
-        let mut flags = bits_in_reg(S2TTE::INVALID_HIPAS, invalid_hipas::ASSIGNED);
-        flags |= bits_in_reg(S2TTE::INVALID_RIPAS, invalid_ripas::EMPTY);
-        let mut new_s2tte = pa as u64 | flags;
-        for i in 0..s2tt_len {
-            if let Some(elem) = s2tt.get_mut(i) {
-                *elem = new_s2tte;
-            }
-            pa += map_size;
-            new_s2tte = pa as u64 | flags;
-        }
-    } else if parent_s2tte.is_valid(level - 1, false) {
-        unimplemented!();
-    } else if parent_s2tte.is_valid(level - 1, true) {
-        unimplemented!();
+    } else if parent_s2tte.is_assigned()
+        || parent_s2tte.is_valid(level - 1, false)
+        || parent_s2tte.is_valid(level - 1, true)
+    {
+        parent_s2tte.unfold(level - 1, s2tt)?;
     } else if parent_s2tte.is_table(level - 1) {
         return Err(Error::RmiErrorRtt(level - 1));
     } else {
         panic!("Unexpected s2tte value:{:X}", parent_s2tte.get());
     }
 
-    set_granule(&mut rtt_granule, GranuleState::RTT)?;
+    assign_granule(&mut rtt_granule, GranuleState::RTT, rd.id())?;
 
+    // breaks the unfolded block, if any, before the table is made
     let parent_s2tte = rtt_addr as u64 | bits_in_reg(S2TTE::DESC_TYPE, desc_type::L012_TABLE);
-    rd.s2_table()
-        .lock()
-        .ipa_to_pte_set(GuestPhysAddr::from(ipa), level - 1, parent_s2tte)?;
+    S2TTE::update(
+        &mut **rd.s2_table().lock(),
+        rd.id(),
+        ipa,
+        level - 1,
+        parent_s2tte,
+    )?;
 
     // The below is added to avoid a fault regarding the RTT entry
     PageTable::get_ref().map(rtt_addr, true);
@@ -124,14 +113,46 @@ pub fn destroy(rd: &Rd, rtt_addr: usize, ipa: usize, level: usize) -> Result<(),
         INVALID_UNPROTECTED
     };
 
-    rd.s2_table()
-        .lock()
-        .ipa_to_pte_set(GuestPhysAddr::from(ipa), level - 1, parent_s2tte)?;
+    // the walk caches of the realm may still point at the RTT being released
+    S2TTE::update(
+        &mut **rd.s2_table().lock(),
+        rd.id(),
+        ipa,
+        level - 1,
+        parent_s2tte,
+    )?;
 
-    set_granule(&mut g_rtt, GranuleState::Delegated)?;
+    release_granule(&mut g_rtt)?;
     Ok(())
 }
 
+/// Folds the RTT at `level` covering `ipa` back into a single entry of its parent
+/// (e.g., 512 pages into a 2MB block), the reverse of unfolding it in `create`.
+/// Returns the address of the RTT granule, which is no longer used.
+pub fn fold(rd: &Rd, ipa: usize, level: usize) -> Result<usize, Error> {
+    let (parent_s2tte, last_level) = S2TTE::get_s2tte(rd, ipa, level - 1, Error::RmiErrorRtt(0))?;
+
+    if last_level != level - 1 {
+        return Err(Error::RmiErrorRtt(last_level));
+    }
+
+    if !parent_s2tte.is_table(level - 1) {
+        return Err(Error::RmiErrorRtt(level - 1));
+    }
+
+    let rtt_addr: usize = parent_s2tte
+        .address(RTT_PAGE_LEVEL)
+        .ok_or(Error::RmiErrorInput)?
+        .into();
+    let mut g_rtt = get_granule_if!(rtt_addr, GranuleState::RTT)?;
+    let folded = S2TTE::fold(g_rtt.content::<RttPage>(), level).ok_or(Error::RmiErrorRtt(level))?;
+
+    S2TTE::update(&mut **rd.s2_table().lock(), rd.id(), ipa, level - 1, folded)?;
+
+    release_granule(&mut g_rtt)?;
+    Ok(rtt_addr)
+}
+
 pub fn init_ripas(rd: &Rd, ipa: usize, level: usize) -> Result<(), Error> {
     let (s2tte, last_level) = S2TTE::get_s2tte(rd, ipa, level, Error::RmiErrorRtt(0))?;
 
@@ -153,17 +174,48 @@ pub fn init_ripas(rd: &Rd, ipa: usize, level: usize) -> Result<(), Error> {
     Ok(())
 }
 
-pub fn get_ripas(rd: &Rd, ipa: usize, level: usize) -> Result<u64, Error> {
+/// Returns whether the RIPAS of the entry at `ipa` is RAM. A valid entry is always RAM.
+pub fn is_ram(rd: &Rd, ipa: usize, level: usize) -> Result<bool, Error> {
     let (s2tte, last_level) = S2TTE::get_s2tte(rd, ipa, level, Error::RmiErrorRtt(0))?;
 
     if level != last_level {
         return Err(Error::RmiErrorRtt(last_level));
     }
 
+    let ram =
+        (s2tte.is_unassigned() || s2tte.is_assigned()) && s2tte.get_ripas() == invalid_ripas::RAM;
+    Ok(ram || s2tte.is_valid(level, false))
+}
+
+/// Returns the RIPAS of the last-level entry covering `ipa` (`RIPAS_DESTROYED` for
+/// a destroyed one) and the size of the range the entry covers.
+pub fn ripas_at(rd: &Rd, ipa: usize) -> Result<(u8, usize), Error> {
+    let (s2tte, last_level) = S2TTE::get_s2tte(rd, ipa, RTT_PAGE_LEVEL, Error::RmiErrorRtt(0))?;
+
+    let ripas = if s2tte.is_destroyed() {
+        RIPAS_DESTROYED
+    } else if s2tte.is_unassigned() || s2tte.is_assigned() {
+        s2tte.get_ripas() as u8
+    } else if s2tte.is_valid(last_level, false) {
+        invalid_ripas::RAM as u8
+    } else {
+        return Err(Error::RmiErrorRtt(last_level));
+    };
+    Ok((ripas, level_to_size(last_level) as usize))
+}
+
+/// Returns the RIPAS of `ipa`, which is that of the entry covering it
+/// even if the walk ends before `level` (e.g., at a 2MB block).
+pub fn get_ripas(rd: &Rd, ipa: usize, level: usize) -> Result<u64, Error> {
+    let (s2tte, last_level) = S2TTE::get_s2tte(rd, ipa, level, Error::RmiErrorRtt(0))?;
+
     if s2tte.is_destroyed() {
         error!("The s2tte is destroyed: {:x}", s2tte.get());
         return Err(Error::RmiErrorRtt(last_level));
     }
+    if s2tte.is_valid(last_level, false) {
+        return Ok(invalid_ripas::RAM);
+    }
     Ok(s2tte.get_ripas())
 }
 
@@ -265,15 +317,14 @@ pub fn unmap_unprotected(rd: &Rd, ipa: usize, level: usize) -> Result<(), Error>
 
     let new_s2tte: u64 = INVALID_UNPROTECTED;
 
-    rd.s2_table()
-        .lock()
-        .ipa_to_pte_set(GuestPhysAddr::from(ipa), level, new_s2tte)?;
-
-    //TODO: add page/block invalidation
+    S2TTE::update(&mut **rd.s2_table().lock(), rd.id(), ipa, level, new_s2tte)?;
 
     Ok(())
 }
 
+/// Changes the RIPAS of the entry at `level` covering `ipa` to EMPTY.
+/// It fails with `RmiErrorRtt` at the level of a larger entry (e.g., a 2MB block)
+/// covering `ipa`, which the host needs to unfold with RTT_CREATE first.
 pub fn make_shared(rd: &Rd, ipa: usize, level: usize) -> Result<(), Error> {
     let (s2tte, last_level) = S2TTE::get_s2tte(rd, ipa, level, Error::RmiErrorRtt(0))?;
 
@@ -285,22 +336,18 @@ pub fn make_shared(rd: &Rd, ipa: usize, level: usize) -> Result<(), Error> {
     //           (realm-linux) : __set_memory_encrypted() in arch/arm64/mm/pageattr.c
     //           (nw-linux)    : set_ipa_state() and kvm_realm_unmap_range() in arch/arm64/kvm/rme.c
     //           (rmm-spec)    : Figure D2.1 Realm shared memory protocol flow
+    let Some(new_s2tte) = ripas_entry(&s2tte, level, invalid_ripas::EMPTY)? else {
+        return Ok(());
+    };
     if s2tte.is_valid(level, false) {
         // the case for ipa's range 0x8840_0000 - in realm-linux booting
         let pa: usize = s2tte.address(level).ok_or(Error::RmiErrorRtt(0))?.into(); //XXX: check this again
-        let mut flags = 0;
-        flags |= bits_in_reg(S2TTE::INVALID_HIPAS, invalid_hipas::ASSIGNED);
-        flags |= bits_in_reg(S2TTE::INVALID_RIPAS, invalid_ripas::EMPTY);
-        let new_s2tte = pa as u64 | flags;
-
-        rd.s2_table()
-            .lock()
-            .ipa_to_pte_set(GuestPhysAddr::from(ipa), level, new_s2tte)?;
-    } else if s2tte.is_unassigned() || s2tte.is_assigned() {
-        let pa: usize = s2tte.address(level).ok_or(Error::RmiErrorRtt(0))?.into(); //XXX: check this again
-        let flags = bits_in_reg(S2TTE::INVALID_RIPAS, invalid_ripas::EMPTY);
-        let new_s2tte = pa as u64 | flags;
+        S2TTE::update(&mut **rd.s2_table().lock(), rd.id(), ipa, level, new_s2tte)?;
 
+        // Zeroed only after the entry is invalidated along with the TLB entries,
+        // so that no REC can write to the granules through the stale mapping afterwards.
+        scrub_on_ripas_empty(pa, level_to_size(level) as usize);
+    } else {
         rd.s2_table()
             .lock()
             .ipa_to_pte_set(GuestPhysAddr::from(ipa), level, new_s2tte)?;
@@ -309,6 +356,60 @@ pub fn make_shared(rd: &Rd, ipa: usize, level: usize) -> Result<(), Error> {
     Ok(())
 }
 
+/// Returns the entry replacing `s2tte` at `level` to change its RIPAS to `ripas`,
+/// or `None` if the entry is left as it is (e.g., a valid one, which is RAM).
+///
+/// A valid entry changed to EMPTY becomes an assigned one keeping its output address.
+/// A destroyed entry becomes an unassigned one, which the callers allow only if
+/// the realm passed `RSI_CHANGE_DESTROYED`.
+/// Otherwise, only an unassigned, assigned or valid entry can be changed to RAM.
+pub fn ripas_entry(s2tte: &S2TTE, level: usize, ripas: u64) -> Result<Option<u64>, Error> {
+    let valid = s2tte.is_valid(level, false);
+    let assignable = s2tte.is_unassigned() || s2tte.is_assigned();
+    let pa = || -> Result<u64, Error> {
+        let pa: usize = s2tte.address(level).ok_or(Error::RmiErrorRtt(0))?.into();
+        Ok(pa as u64)
+    };
+
+    match ripas {
+        invalid_ripas::EMPTY | invalid_ripas::RAM if s2tte.is_destroyed() => {
+            Ok(Some(bits_in_reg(S2TTE::INVALID_RIPAS, ripas)))
+        }
+        invalid_ripas::EMPTY if valid => {
+            let mut flags = 0;
+            flags |= bits_in_reg(S2TTE::INVALID_HIPAS, invalid_hipas::ASSIGNED);
+            flags |= bits_in_reg(S2TTE::INVALID_RIPAS, invalid_ripas::EMPTY);
+            Ok(Some(pa()? | flags))
+        }
+        invalid_ripas::EMPTY if assignable => Ok(Some(
+            pa()? | bits_in_reg(S2TTE::INVALID_RIPAS, invalid_ripas::EMPTY),
+        )),
+        invalid_ripas::EMPTY => Ok(None),
+        invalid_ripas::RAM if valid => Ok(None),
+        invalid_ripas::RAM if assignable => Ok(Some(
+            s2tte.get() | bits_in_reg(S2TTE::INVALID_RIPAS, invalid_ripas::RAM),
+        )),
+        invalid_ripas::RAM => Err(Error::RmiErrorRtt(level)),
+        _ => Err(Error::RmiErrorInput),
+    }
+}
+
+/// Zeroes the granules backing `[pa, pa + size)` whose RIPAS is changed from RAM to EMPTY
+/// so that the stale realm data can't be exposed by any later reuse.
+/// It does nothing unless `ZERO_ON_RIPAS_EMPTY` is configured.
+fn scrub_on_ripas_empty(pa: usize, size: usize) {
+    if !ZERO_ON_RIPAS_EMPTY {
+        return;
+    }
+    for granule in (pa..pa + size).step_by(GRANULE_SIZE) {
+        // Safety: data granules assigned to the realm are mapped in the RMM's page table
+        //         from DATA_CREATE until DATA_DESTROY.
+        unsafe {
+            core::ptr::write_bytes(granule as *mut u8, 0x0, GRANULE_SIZE);
+        }
+    }
+}
+
 pub fn make_exclusive(rd: &Rd, ipa: usize, level: usize) -> Result<(), Error> {
     let (s2tte, last_level) = S2TTE::get_s2tte(rd, ipa, level, Error::RmiErrorRtt(0))?;
 
@@ -316,17 +417,10 @@ pub fn make_exclusive(rd: &Rd, ipa: usize, level: usize) -> Result<(), Error> {
         return Err(Error::RmiErrorRtt(last_level)); //XXX: check this again
     }
 
-    if s2tte.is_valid(level, false) {
-        // This condition is added with no-op for handling the `else` case
-    } else if s2tte.is_unassigned() || s2tte.is_assigned() {
-        let flags = bits_in_reg(S2TTE::INVALID_RIPAS, invalid_ripas::RAM);
-        let new_s2tte = s2tte.get() | flags;
-
+    if let Some(new_s2tte) = ripas_entry(&s2tte, level, invalid_ripas::RAM)? {
         rd.s2_table()
             .lock()
             .ipa_to_pte_set(GuestPhysAddr::from(ipa), level, new_s2tte)?;
-    } else {
-        return Err(Error::RmiErrorRtt(level)); //XXX: check this again
     }
 
     Ok(())
@@ -341,28 +435,13 @@ pub fn data_create(rd: &Rd, ipa: usize, target_pa: usize) -> Result<(), Error> {
     }
 
     if !s2tte.is_unassigned() {
-        return Err(Error::RmiErrorRtt(RTT_PAGE_LEVEL));
+        return Err(Error::RmiErrorRtt(level));
     }
 
-    let mut new_s2tte = target_pa as u64;
     if s2tte.is_invalid_ripas() {
         panic!("invalid ripas");
     }
-    let ripas = s2tte.get_ripas();
-    if ripas == invalid_ripas::EMPTY {
-        new_s2tte |= bits_in_reg(S2TTE::INVALID_HIPAS, invalid_hipas::ASSIGNED);
-        new_s2tte |= bits_in_reg(S2TTE::INVALID_RIPAS, invalid_ripas::EMPTY);
-    } else if ripas == invalid_ripas::RAM {
-        // S2TTE_PAGE  : S2TTE_ATTRS | S2TTE_L3_PAGE
-        new_s2tte |= bits_in_reg(S2TTE::DESC_TYPE, desc_type::L3_PAGE);
-        // S2TTE_ATTRS : S2TTE_MEMATTR_FWB_NORMAL_WB | S2TTE_AP_RW | S2TTE_SH_IS | S2TTE_AF
-        new_s2tte |= bits_in_reg(S2TTE::MEMATTR, attribute::NORMAL_FWB);
-        new_s2tte |= bits_in_reg(S2TTE::AP, permission::RW);
-        new_s2tte |= bits_in_reg(S2TTE::SH, shareable::INNER);
-        new_s2tte |= bits_in_reg(S2TTE::AF, 1);
-    } else {
-        panic!("Unexpected ripas: {}", ripas);
-    }
+    let new_s2tte = S2TTE::data(target_pa, level, s2tte.get_ripas());
 
     rd.s2_table()
         .lock()
@@ -397,9 +476,277 @@ pub fn data_destroy(rd: &Rd, ipa: usize) -> Result<usize, Error> {
         flags |= bits_in_reg(S2TTE::INVALID_RIPAS, invalid_ripas::EMPTY);
     }
     let new_s2tte = flags;
-    rd.s2_table()
-        .lock()
-        .ipa_to_pte_set(GuestPhysAddr::from(ipa), level, new_s2tte)?;
+    S2TTE::update(&mut **rd.s2_table().lock(), rd.id(), ipa, level, new_s2tte)?;
 
     Ok(pa)
 }
+
+/// Reads the entry at `index` of the RTT granule at `table` of a realm.
+///
+/// The caller holds the lock of the stage 2 table of the realm,
+/// so that the RTT doesn't change during the walk.
+pub fn read_rtt(table: usize, index: usize) -> u64 {
+    // Safety: the RTT granules are mapped in the RMM's page table
+    //         from RTT_CREATE until RTT_DESTROY.
+    unsafe { *(table as *const u64).add(index) }
+}
+
+/// Walks the RTT from the table at `root` of `start_level` down to the entry mapping `ipa`,
+/// reading the entry at `index` of the table at `table` with `read(table, index)`.
+///
+/// Returns the entry where the walk ends with its level,
+/// or `RmiErrorRtt` with the level of a table entry without an output address.
+pub fn walk_entry(
+    root: usize,
+    start_level: usize,
+    ipa: usize,
+    read: impl Fn(usize, usize) -> u64,
+) -> Result<(S2TTE, usize), Error> {
+    let mut table = root;
+    for level in start_level..=RTT_PAGE_LEVEL {
+        let shift = GRANULE_SHIFT + S2TTE_STRIDE * (RTT_PAGE_LEVEL - level);
+        // the starting level may consist of concatenated tables
+        let index = if level == start_level {
+            ipa >> shift
+        } else {
+            (ipa >> shift) & ((1 << S2TTE_STRIDE) - 1)
+        };
+        let s2tte = S2TTE::from(read(table, index) as usize);
+
+        if s2tte.is_table(level) {
+            table = s2tte
+                .address(RTT_PAGE_LEVEL)
+                .ok_or(Error::RmiErrorRtt(level))?
+                .into();
+            continue;
+        }
+        return Ok((s2tte, level));
+    }
+    Err(Error::RmiErrorRtt(RTT_PAGE_LEVEL))
+}
+
+/// Finds the regions of the protected IPA space, `[start, protected_ipa_boundary(ipa_bits))`,
+/// whose RIPAS isn't defined by the RTT, reading it as `walk_entry` does.
+///
+/// Each page must end the walk at an entry which is unassigned, assigned or valid,
+/// which carries the RIPAS. Any other entry (e.g., a destroyed one or a block at
+/// an unsupported level) leaves the region it covers unaccounted for.
+/// The walk steps over the whole region covered by each entry,
+/// and adjacent unaccounted regions are merged.
+///
+/// The walk stops before the region following `MAX_UNACCOUNTED_REGIONS` regions,
+/// returning the found regions with the IPA to continue from, which is
+/// the protected IPA boundary once the whole space is walked.
+pub fn unaccounted_ripas(
+    root: usize,
+    start_level: usize,
+    ipa_bits: usize,
+    start: usize,
+    read: impl Fn(usize, usize) -> u64,
+) -> (Vec<Range<usize>>, usize) {
+    let end = protected_ipa_boundary(ipa_bits);
+    let mut gaps: Vec<Range<usize>> = Vec::with_capacity(MAX_UNACCOUNTED_REGIONS);
+    let mut ipa = start;
+    while ipa < end {
+        let (level, accounted) = match walk_entry(root, start_level, ipa, &read) {
+            Ok((s2tte, level)) => (
+                level,
+                s2tte.is_unassigned() || s2tte.is_assigned() || s2tte.is_valid(level, false),
+            ),
+            Err(Error::RmiErrorRtt(level)) => (level, false),
+            Err(_) => (RTT_PAGE_LEVEL, false),
+        };
+        let size = (level_to_size(level) as usize).max(GRANULE_SIZE);
+        let next = (ipa & !(size - 1)) + size;
+
+        if !accounted {
+            let full = gaps.len() == MAX_UNACCOUNTED_REGIONS;
+            match gaps.last_mut() {
+                Some(gap) if gap.end == ipa => gap.end = next,
+                _ if full => return (gaps, ipa),
+                _ => gaps.push(ipa..next),
+            }
+        }
+        ipa = next;
+    }
+    (gaps, end)
+}
+
+/// Translates `ipa` of the realm to the PA backing it with the level of the mapping,
+/// which must be valid (i.e., RAM).
+pub fn translate(rd: &Rd, ipa: usize) -> Result<(usize, usize), Error> {
+    translate_ipa(rd, ipa)?.valid()
+}
+
+/// Finds the regions of the protected IPA space of the realm whose RIPAS isn't defined,
+/// from `start` up to `MAX_UNACCOUNTED_REGIONS` regions (see `unaccounted_ripas`).
+pub fn unaccounted(rd: &Rd, start: usize) -> Result<(Vec<Range<usize>>, usize), Error> {
+    let start_level = usize::try_from(rd.s2_starting_level()).or(Err(Error::RmiErrorInput))?;
+    if start % GRANULE_SIZE != 0 || start >= protected_ipa_boundary(rd.ipa_bits()) {
+        return Err(Error::RmiErrorInput);
+    }
+
+    // The RTT doesn't change during the walk while holding the lock of the stage 2 table.
+    let _s2_table = rd.s2_table().lock();
+    Ok(unaccounted_ripas(
+        rd.rtt_base(),
+        start_level,
+        rd.ipa_bits(),
+        start,
+        read_rtt,
+    ))
+}
+
+#[cfg(test)]
+pub mod walk_test {
+    use super::*;
+    use crate::rmi::rtt::RTT_MIN_BLOCK_LEVEL;
+    use alloc::vec;
+
+    pub const L2_TABLE: usize = 0x1000;
+    pub const L3_TABLE: usize = 0x2000;
+    // 8MB of the protected IPA space, i.e., four entries of the level 2 table
+    pub const IPA_BITS: usize = 24;
+
+    // A synthetic RTT starting at level 2:
+    //   0x0000_0000 (2MB) -> L3_TABLE, whose 0x0000_1000 (4KB) -> 0x8800_0000
+    //   0x0020_0000 (2MB) -> 0x8860_0000 (a block)
+    //   0x0040_0000 (2MB) -> 0x8880_0000 (an assigned block, whose RIPAS is EMPTY)
+    pub fn read(table: usize, index: usize) -> u64 {
+        match (table, index) {
+            (L2_TABLE, 0) => L3_TABLE as u64 | desc_type::L012_TABLE,
+            (L2_TABLE, 1) => S2TTE::data(0x8860_0000, RTT_MIN_BLOCK_LEVEL, invalid_ripas::RAM),
+            (L2_TABLE, 2) => S2TTE::data(0x8880_0000, RTT_MIN_BLOCK_LEVEL, invalid_ripas::EMPTY),
+            (L3_TABLE, 1) => S2TTE::data(0x8800_0000, RTT_PAGE_LEVEL, invalid_ripas::RAM),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn unaccounted_gap() {
+        let end = protected_ipa_boundary(IPA_BITS);
+        let (gaps, next) = unaccounted_ripas(L2_TABLE, RTT_MIN_BLOCK_LEVEL, IPA_BITS, 0, read);
+        assert!(gaps.is_empty());
+        assert_eq!(next, end);
+
+        // The RTT above with the pages at 0x0000_5000 and 0x0000_6000 and
+        // the 2MB at 0x0040_0000 destroyed
+        let destroyed = bits_in_reg(S2TTE::INVALID_HIPAS, invalid_hipas::DESTROYED);
+        let with_gaps = |table, index| match (table, index) {
+            (L3_TABLE, 5) | (L3_TABLE, 6) | (L2_TABLE, 2) => destroyed,
+            _ => read(table, index),
+        };
+        assert_eq!(
+            unaccounted_ripas(L2_TABLE, RTT_MIN_BLOCK_LEVEL, IPA_BITS, 0, with_gaps),
+            (vec![0x5000..0x7000, 0x0040_0000..0x0060_0000], end)
+        );
+        // continued from the middle of the first region
+        assert_eq!(
+            unaccounted_ripas(L2_TABLE, RTT_MIN_BLOCK_LEVEL, IPA_BITS, 0x6000, with_gaps),
+            (vec![0x6000..0x7000, 0x0040_0000..0x0060_0000], end)
+        );
+    }
+
+    #[test]
+    fn unaccounted_paging() {
+        let end = protected_ipa_boundary(IPA_BITS);
+        // every other page of the level 3 table destroyed
+        let destroyed = bits_in_reg(S2TTE::INVALID_HIPAS, invalid_hipas::DESTROYED);
+        let sparse = |table, index| match (table, index) {
+            (L3_TABLE, index) if index % 2 == 0 => destroyed,
+            _ => read(table, index),
+        };
+        let gap = |i: usize| 2 * i * GRANULE_SIZE..(2 * i + 1) * GRANULE_SIZE;
+
+        let mut start = 0;
+        let mut found = Vec::new();
+        while start < end {
+            let (gaps, next) =
+                unaccounted_ripas(L2_TABLE, RTT_MIN_BLOCK_LEVEL, IPA_BITS, start, sparse);
+            assert!(gaps.len() <= MAX_UNACCOUNTED_REGIONS);
+            assert!(next > start);
+            found.extend(gaps);
+            start = next;
+        }
+        assert_eq!(found, (0..128).map(gap).collect::<Vec<_>>());
+    }
+}
+
+#[cfg(all(test, feature = "zero_ripas_empty"))]
+mod test {
+    use super::*;
+    use crate::realm::mm::address::PhysAddr;
+    use crate::realm::mm::IPATranslation;
+    use alloc::boxed::Box;
+    use alloc::sync::Arc;
+    use spin::mutex::Mutex;
+
+    const RTT_BASE: usize = 0x8890_0000;
+
+    #[repr(C, align(4096))]
+    struct Granules([u8; 2 * GRANULE_SIZE]);
+
+    // A stage 2 table with a single page entry for any IPA,
+    // which checks the granule mapped by the old entry when the entry is replaced
+    #[derive(Debug)]
+    struct OneEntry {
+        pte: u64,
+        granule: usize,
+        // whether the granule still had the realm data when the entry was replaced
+        intact_on_write: Arc<Mutex<Option<bool>>>,
+    }
+
+    impl IPATranslation for OneEntry {
+        fn get_base_address(&self) -> *const core::ffi::c_void {
+            RTT_BASE as *const core::ffi::c_void
+        }
+        fn ipa_to_pa(&mut self, _guest: GuestPhysAddr, _level: usize) -> Option<PhysAddr> {
+            None
+        }
+        fn ipa_to_pte(&mut self, _guest: GuestPhysAddr, _level: usize) -> Option<(u64, usize)> {
+            Some((self.pte, RTT_PAGE_LEVEL))
+        }
+        fn ipa_to_pte_set(
+            &mut self,
+            _guest: GuestPhysAddr,
+            _level: usize,
+            val: u64,
+        ) -> Result<(), Error> {
+            let data = unsafe { &*(self.granule as *const [u8; GRANULE_SIZE]) };
+            *self.intact_on_write.lock() = Some(data.iter().all(|b| *b == 0xab));
+            self.pte = val;
+            Ok(())
+        }
+        fn clean(&mut self) {}
+    }
+
+    #[test]
+    fn ram_to_empty_zeroes_granule() {
+        const VMID: u16 = 0x7a5;
+        let ipa = 0x1000;
+        let mut granules = Box::new(Granules([0xab; 2 * GRANULE_SIZE]));
+        let pa = granules.0.as_mut_ptr() as usize;
+        let intact_on_write = Arc::new(Mutex::new(None));
+        let s2_table = Box::new(OneEntry {
+            pte: S2TTE::data(pa, RTT_PAGE_LEVEL, invalid_ripas::RAM),
+            granule: pa,
+            intact_on_write: intact_on_write.clone(),
+        });
+        let rd = crate::rmi::realm::rd::test::rd(VMID, 33, s2_table);
+
+        make_shared(&rd, ipa, RTT_PAGE_LEVEL).unwrap();
+
+        let (s2tte, _) = S2TTE::get_s2tte(&rd, ipa, RTT_PAGE_LEVEL, Error::RmiErrorRtt(0)).unwrap();
+        assert!(s2tte.is_assigned());
+        assert_eq!(s2tte.get_ripas(), invalid_ripas::EMPTY);
+        // invalidated while the granule kept the data, then zeroed
+        assert_eq!(*intact_on_write.lock(), Some(true));
+        let vttbr = crate::realm::mm::vttbr(VMID as usize, RTT_BASE);
+        assert!(crate::asm::TLBI_IPA_CALLS
+            .lock()
+            .contains(&(vttbr, ipa, RTT_PAGE_LEVEL)));
+        assert!(granules.0[..GRANULE_SIZE].iter().all(|b| *b == 0));
+        // The next granule is not part of the range
+        assert!(granules.0[GRANULE_SIZE..].iter().all(|b| *b == 0xab));
+    }
+}
@@ -51,7 +51,7 @@ impl Stats {
     fn new() -> Self {
         let mut stats = Stats {
             collected_stat_cnt: 0,
-            list: [Stat::default(); MAX_KIND],
+            list: [Stat::new(Kind::Undefined); MAX_KIND],
         };
 
         for i in 0..MAX_KIND {
@@ -120,7 +120,7 @@ impl Stats {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug)]
 struct Stat {
     kind: Kind,
     overflowed: bool,
@@ -133,9 +133,14 @@ struct Stat {
 
 impl Stat {
     fn new(kind: Kind) -> Self {
+        // Default isn't derived for arrays longer than 32
         Stat {
             kind,
-            ..Default::default()
+            overflowed: false,
+            cur_cmd: None,
+            call_cnt: [0; MAX_CMD_CNT],
+            mem_used_before: None,
+            total_mem_used: [0; MAX_CMD_CNT],
         }
     }
     fn cmd_to_str(&self, cmd: usize) -> &'static str {
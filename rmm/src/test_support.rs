@@ -0,0 +1,254 @@
+//! Helpers to set up the objects which the host passes to the handlers in tests.
+
+use crate::const_assert_eq;
+#[cfg(not(feature = "gst_page_table"))]
+use crate::get_granule;
+#[cfg(not(feature = "gst_page_table"))]
+use crate::granule::GranuleState;
+use crate::granule::{is_granule_aligned, GRANULE_SIZE};
+use crate::realm::mm::address::{GuestPhysAddr, PhysAddr};
+use crate::realm::mm::IPATranslation;
+use crate::rmi::error::Error;
+use crate::rmi::rec::run::{Run, RunHint};
+
+use alloc::boxed::Box;
+#[cfg(not(feature = "gst_page_table"))]
+use alloc::vec::Vec;
+#[cfg(not(feature = "gst_page_table"))]
+use core::ops::Range;
+use safe_abstraction::raw_ptr::assume_safe;
+
+extern crate alloc;
+
+/// A granule holding a `Run`, as the host provides on RMI_REC_ENTER.
+#[repr(C, align(4096))]
+pub struct RunGranule {
+    run: Run,
+}
+const_assert_eq!(
+    core::mem::size_of::<RunGranule>(),
+    core::mem::size_of::<Run>()
+);
+const_assert_eq!(core::mem::align_of::<RunGranule>(), GRANULE_SIZE);
+
+impl RunGranule {
+    pub fn run(&self) -> &Run {
+        &self.run
+    }
+
+    pub fn run_mut(&mut self) -> &mut Run {
+        &mut self.run
+    }
+
+    /// Returns the address of the `Run`, validated as the handlers do.
+    pub fn addr(&self) -> usize {
+        let addr = &self.run as *const Run as usize;
+        assert!(is_granule_aligned(addr));
+        assert!(assume_safe::<Run>(addr).is_ok());
+        addr
+    }
+}
+
+/// Builds a `Run` with typed setters instead of raw writes to a granule.
+#[derive(Default)]
+pub struct RunBuilder {
+    run: Run,
+}
+
+impl RunBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entry_flags(mut self, flags: u64) -> Self {
+        self.run.set_entry_flags(flags);
+        self
+    }
+
+    pub fn exit_reason(mut self, exit_reason: u8) -> Self {
+        self.run.set_exit_reason(exit_reason);
+        self
+    }
+
+    pub fn ripas(mut self, base: u64, size: u64, state: u8) -> Self {
+        self.run.set_ripas(base, size, state);
+        self
+    }
+
+    pub fn run_hint(mut self, hint: RunHint) -> Self {
+        self.run.set_run_hint(hint);
+        self
+    }
+
+    pub fn build(self) -> Box<RunGranule> {
+        Box::new(RunGranule { run: self.run })
+    }
+}
+
+/// A granule of the realm memory, e.g., the token buffer of an attestation request.
+#[repr(C, align(4096))]
+pub struct DataGranule([u8; GRANULE_SIZE]);
+
+/// A stage 2 table which maps every IPA to the same data granule,
+/// so that the handlers copying to the realm memory can run without RTTs.
+#[derive(Debug)]
+pub struct OneGranuleTable {
+    granule: usize,
+}
+
+impl OneGranuleTable {
+    pub fn new() -> Box<Self> {
+        let granule = Box::leak(Box::new(DataGranule([0; GRANULE_SIZE])));
+        Box::new(Self {
+            granule: granule as *mut DataGranule as usize,
+        })
+    }
+
+    /// Returns the contents of the data granule.
+    pub fn data(&self) -> &[u8] {
+        unsafe { &(*(self.granule as *const DataGranule)).0 }
+    }
+}
+
+impl IPATranslation for OneGranuleTable {
+    fn get_base_address(&self) -> *const core::ffi::c_void {
+        core::ptr::null()
+    }
+    fn ipa_to_pa(&mut self, _guest: GuestPhysAddr, _level: usize) -> Option<PhysAddr> {
+        Some(PhysAddr::from(self.granule))
+    }
+    fn ipa_to_pte(&mut self, _guest: GuestPhysAddr, _level: usize) -> Option<(u64, usize)> {
+        None
+    }
+    fn ipa_to_pte_set(
+        &mut self,
+        _guest: GuestPhysAddr,
+        _level: usize,
+        _val: u64,
+    ) -> Result<(), Error> {
+        Err(Error::RmiErrorRtt(0))
+    }
+    fn clean(&mut self) {}
+}
+
+/// The states of the granules in a range of the granule status table,
+/// taken by `snapshot_granules()`.
+///
+/// Only the granules not in `Undelegated` are recorded, in the order of
+/// their addresses. Granules don't keep a reference count in this table,
+/// so the state is all there is to capture.
+#[cfg(not(feature = "gst_page_table"))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct GranuleSnapshot {
+    range: Range<usize>,
+    states: Vec<(usize, u8)>,
+}
+
+/// Takes a snapshot of the granules in `range` (physical addresses).
+///
+/// The table is shared by the tests running in parallel, so each test
+/// passes the range of granules which only it uses.
+/// Each granule is read while holding its lock, so the caller must not
+/// hold the lock of any granule in the range.
+#[cfg(not(feature = "gst_page_table"))]
+pub fn snapshot_granules(range: Range<usize>) -> GranuleSnapshot {
+    let states = range
+        .clone()
+        .step_by(GRANULE_SIZE)
+        .filter_map(|addr| {
+            let state = get_granule!(addr).ok()?.state();
+            (state != GranuleState::Undelegated).then_some((addr, state))
+        })
+        .collect();
+    GranuleSnapshot { range, states }
+}
+
+/// Restores the granules in the range of `snapshot`, leaving the others as they are.
+///
+/// The granules are not zeroized as the handlers do on delegation,
+/// as only the states are restored. The caller must not hold the lock
+/// of any granule in the range.
+#[cfg(not(feature = "gst_page_table"))]
+pub fn restore_granules(snapshot: GranuleSnapshot) {
+    let mut states = snapshot.states.into_iter().peekable();
+    for addr in snapshot.range.step_by(GRANULE_SIZE) {
+        let state = match states.next_if(|&(a, _)| a == addr) {
+            Some((_, state)) => state,
+            None => GranuleState::Undelegated,
+        };
+        if let Ok(mut granule) = get_granule!(addr) {
+            granule.restore_state(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rmi;
+    use crate::rmi::rec::run::REC_ENTRY_FLAG_RIPAS_RESPONSE;
+
+    #[test]
+    fn run_builder_round_trip() {
+        let granule = RunBuilder::new()
+            .entry_flags(REC_ENTRY_FLAG_RIPAS_RESPONSE)
+            .exit_reason(rmi::EXIT_RIPAS_CHANGE)
+            .ripas(0x8000_0000, 0x4000, 1)
+            .run_hint(RunHint::BlockedRipas)
+            .build();
+
+        let addr = granule.addr();
+        assert_eq!(addr % GRANULE_SIZE, 0);
+
+        // what the handlers see at the address
+        let run = unsafe { &*(addr as *const Run) };
+        assert_eq!(run.entry_flags(), REC_ENTRY_FLAG_RIPAS_RESPONSE);
+        assert_eq!(run.exit_reason(), rmi::EXIT_RIPAS_CHANGE);
+        assert_eq!(run.ripas(), (0x8000_0000, 0x4000, 1));
+        assert_eq!(run.run_hint(), RunHint::BlockedRipas as u8);
+        assert_eq!(run.entry_gpr(0).unwrap(), 0);
+    }
+
+    #[cfg(not(feature = "gst_page_table"))]
+    #[test]
+    fn restore_reverts_scenario() {
+        // granules which no other test uses
+        const BASE: usize = 0x8810_0000;
+        const RD: usize = BASE;
+        const REC: usize = BASE + GRANULE_SIZE;
+        const DATA: usize = BASE + 2 * GRANULE_SIZE;
+        const OUTSIDE: usize = BASE + 3 * GRANULE_SIZE;
+
+        let snapshot = snapshot_granules(BASE..OUTSIDE);
+
+        // a scenario creating a realm with a REC and a data granule
+        for (addr, state) in [
+            (RD, GranuleState::RD),
+            (REC, GranuleState::Rec),
+            (DATA, GranuleState::Data),
+        ] {
+            get_granule!(addr).unwrap().restore_state(state);
+        }
+        assert_ne!(snapshot_granules(BASE..OUTSIDE), snapshot);
+        // a granule out of the range is left as it is
+        get_granule!(OUTSIDE)
+            .unwrap()
+            .restore_state(GranuleState::Delegated);
+
+        restore_granules(snapshot.clone());
+        assert_eq!(snapshot_granules(BASE..OUTSIDE), snapshot);
+        for addr in [RD, REC, DATA] {
+            assert_eq!(
+                get_granule!(addr).unwrap().state(),
+                GranuleState::Undelegated
+            );
+        }
+        assert_eq!(
+            get_granule!(OUTSIDE).unwrap().state(),
+            GranuleState::Delegated
+        );
+        get_granule!(OUTSIDE)
+            .unwrap()
+            .restore_state(GranuleState::Undelegated);
+    }
+}
@@ -4,8 +4,19 @@ use crate::report::Report;
 #[cfg(target_arch = "x86_64")]
 fn attest_x86_64(user_data: &[u8]) -> Result<Report, Error> {
     println!("Simulated attestation operation on x86_64.");
+
+    let report = crate::mock::report();
+    let (header, token) = crate::mock::verify(&report)?;
+    if user_data.len() > header.challenge_len as usize {
+        println!(
+            "Length of user_data cannot over CHALLENGE_LEN[{}]",
+            header.challenge_len
+        );
+        return Err(Error::InvalidArgument);
+    }
+
     Ok(Report {
-        buffer: crate::mock::REPORT.to_vec(),
+        buffer: token.to_vec(),
         user_data: user_data.to_vec(), // Hold user_data temporarily
     })
 }
@@ -16,6 +16,8 @@ pub const TAG_REALM_PUB_KEY_HASH_ALGO_ID: u16 = 44236;
 pub const TAG_REALM_PUB_KEY: u16 = 44237;
 pub const TAG_REALM_INITIAL_MEASUREMENT: u16 = 44238;
 pub const TAG_REALM_EXTENTIBLE_MEASUREMENTS: u16 = 44239;
+pub const TAG_REALM_COMMITTED_RAM: u16 = 44300;
+pub const TAG_REALM_DEBUG: u16 = 44301;
 
 pub const TAG_PLAT_CHALLENGE: u16 = 10;
 pub const TAG_PLAT_VERIFICATION_SERVICE: u16 = 2400;
@@ -39,6 +41,8 @@ pub const STR_REALM_PUB_KEY_HASH_ALGO_ID: &str = "Realm public key hash algo id"
 pub const STR_REALM_PUB_KEY: &str = "Realm signing public key";
 pub const STR_REALM_INITIAL_MEASUREMENT: &str = "Realm initial measurement";
 pub const STR_REALM_EXTENTIBLE_MEASUREMENTS: &str = "Realm extentible measurements";
+pub const STR_REALM_COMMITTED_RAM: &str = "Realm committed RAM";
+pub const STR_REALM_DEBUG: &str = "Realm debug";
 
 pub const STR_PLAT_CHALLENGE: &str = "Challenge";
 pub const STR_PLAT_VERIFICATION_SERVICE: &str = "Verification service";
@@ -50,6 +54,11 @@ pub const STR_PLAT_CONFIGURATION: &str = "Configuration";
 pub const STR_PLAT_HASH_ALGO_ID: &str = "Platform hash algo";
 pub const STR_PLAT_SW_COMPONENTS: &str = "Platform sw components";
 
+pub const STR_SW_COMP_TYPE: &str = "SW Type";
+pub const STR_SW_COMP_VERSION: &str = "Version";
+
+pub const SW_TYPE_RMM: &str = "RMM";
+
 pub fn to_label(title: &'static str) -> u16 {
     match title {
         STR_USER_DATA | STR_REALM_CHALLENGE => TAG_REALM_CHALLENGE,
@@ -59,6 +68,8 @@ pub fn to_label(title: &'static str) -> u16 {
         STR_REALM_PUB_KEY => TAG_REALM_PUB_KEY,
         STR_REALM_INITIAL_MEASUREMENT => TAG_REALM_INITIAL_MEASUREMENT,
         STR_REALM_EXTENTIBLE_MEASUREMENTS => TAG_REALM_EXTENTIBLE_MEASUREMENTS,
+        STR_REALM_COMMITTED_RAM => TAG_REALM_COMMITTED_RAM,
+        STR_REALM_DEBUG => TAG_REALM_DEBUG,
         STR_PLAT_CHALLENGE => TAG_PLAT_CHALLENGE,
         STR_PLAT_VERIFICATION_SERVICE => TAG_PLAT_VERIFICATION_SERVICE,
         STR_PLAT_PROFILE => TAG_PLAT_PROFILE,
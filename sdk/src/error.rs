@@ -5,12 +5,17 @@ pub enum Error {
     CCAToken(TokenError),
     Claims,
     Decoding,
+    InsufficientLifecycleState,
     InvalidArgument,
     NotSupported,
+    PolicyMismatch,
+    RealmIdentityMismatch,
     Report,
     Sealing,
     SealingKey,
     Serialize,
+    UnknownKeyId,
+    VersionTooOld,
 }
 
 impl From<TokenError> for Error {
@@ -7,6 +7,8 @@ pub mod c_api;
 /// cbindgen:ignore
 pub mod config;
 pub mod error;
+pub mod log;
+pub mod policy;
 pub mod prelude;
 pub mod report;
 pub mod sealing;
@@ -19,6 +21,50 @@ mod parser;
 #[cfg(test)]
 mod tests {
     use super::prelude::*;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::nid::Nid;
+    use openssl::pkey::Private;
+
+    fn es384_key() -> EcKey<Private> {
+        let group = EcGroup::from_curve_name(Nid::SECP384R1).unwrap();
+        EcKey::generate(&group).unwrap()
+    }
+
+    /// Returns the SEC1-encoded public key of `key`.
+    fn public_key(key: &EcKey<Private>) -> Vec<u8> {
+        use openssl::bn::BigNumContext;
+        use openssl::ec::PointConversionForm;
+
+        let mut ctx = BigNumContext::new().unwrap();
+        key.public_key()
+            .to_bytes(key.group(), PointConversionForm::UNCOMPRESSED, &mut ctx)
+            .unwrap()
+    }
+
+    /// Returns the tagged COSE_Sign1 of `payload` signed with `key` in ES384.
+    fn sign_es384(kid: &[u8], payload: Vec<u8>, key: &EcKey<Private>) -> Vec<u8> {
+        use coset::{iana, CoseSign1Builder, HeaderBuilder, TaggedCborSerializable};
+        use openssl::ecdsa::EcdsaSig;
+        use openssl::sha::sha384;
+
+        CoseSign1Builder::new()
+            .protected(
+                HeaderBuilder::new()
+                    .algorithm(iana::Algorithm::ES384)
+                    .key_id(kid.to_vec())
+                    .build(),
+            )
+            .payload(payload)
+            .create_signature(b"", |data| {
+                let sig = EcdsaSig::sign(&sha384(data), key).unwrap();
+                let mut raw = sig.r().to_vec_padded(48).unwrap();
+                raw.extend(sig.s().to_vec_padded(48).unwrap());
+                raw
+            })
+            .build()
+            .to_tagged_vec()
+            .unwrap()
+    }
 
     #[test]
     fn attest_verify() {
@@ -39,6 +85,756 @@ mod tests {
         }
     }
 
+    #[test]
+    fn signed_material() {
+        use super::verifier::extract_signed_material;
+        use openssl::bn::BigNum;
+        use openssl::ec::EcPoint;
+        use openssl::ecdsa::EcdsaSig;
+        use openssl::sha::sha384;
+
+        const ES384: i64 = -35;
+
+        let report = attest(b"User data").unwrap();
+        let claims = verify(&report).unwrap();
+        let (sig_structure, signature, alg) = extract_signed_material(&report).unwrap();
+        assert_eq!(alg, ES384);
+
+        let pub_key = match parse(&claims, config::STR_REALM_PUB_KEY) {
+            Some(ClaimData::Bstr(key)) => key,
+            _ => panic!("Claims parsing error."),
+        };
+
+        // Verify the signed material with openssl rather than the verifier of this crate.
+        let group = EcGroup::from_curve_name(Nid::SECP384R1).unwrap();
+        let mut ctx = openssl::bn::BigNumContext::new().unwrap();
+        let point = EcPoint::from_bytes(&group, pub_key, &mut ctx).unwrap();
+        let key = EcKey::from_public_key(&group, &point).unwrap();
+
+        let (r, s) = signature.split_at(signature.len() / 2);
+        let sig = EcdsaSig::from_private_components(
+            BigNum::from_slice(r).unwrap(),
+            BigNum::from_slice(s).unwrap(),
+        )
+        .unwrap();
+        assert!(sig.verify(&sha384(&sig_structure), &key).unwrap());
+
+        // A single modified byte of Sig_structure breaks the verification.
+        let mut tampered = sig_structure.clone();
+        *tampered.last_mut().unwrap() ^= 0xff;
+        assert!(!sig.verify(&sha384(&tampered), &key).unwrap());
+    }
+
+    #[test]
+    fn detached_signature() {
+        use super::verifier::{extract_signed_material, verify_detached};
+        use cca_token::verifier::split_token;
+        use cose::signing::{detached_parts, detached_sig_structure, DetachedParts};
+
+        let report = attest(b"User data").unwrap();
+        let claims = verify(&report).unwrap();
+        let pub_key = match parse(&claims, config::STR_REALM_PUB_KEY) {
+            Some(ClaimData::Bstr(key)) => key,
+            _ => panic!("Claims parsing error."),
+        };
+
+        let (_platform_token, realm_token) = split_token(&report.buffer).unwrap();
+        let DetachedParts {
+            payload,
+            signature,
+            protected,
+        } = detached_parts(&realm_token).unwrap();
+        assert!(verify_detached(&payload, &signature, &protected, pub_key).is_ok());
+
+        // The Sig_structure reconstructed from the parts is the attached one.
+        let (sig_structure, _, _) = extract_signed_material(&report).unwrap();
+        let (detached, _) = detached_sig_structure(&payload, &protected).unwrap();
+        assert_eq!(detached, sig_structure);
+
+        let mut tampered = payload.clone();
+        *tampered.last_mut().unwrap() ^= 0xff;
+        assert!(verify_detached(&tampered, &signature, &protected, pub_key).is_err());
+    }
+
+    #[test]
+    fn trust_anchors() {
+        use super::error::Error;
+        use super::verifier::Verifier;
+        use std::collections::BTreeMap;
+
+        let keys: Vec<_> = (0..3).map(|_| es384_key()).collect();
+        let token =
+            |kid: &[u8], key: &EcKey<Private>| sign_es384(kid, b"Platform claims".to_vec(), key);
+
+        // The anchors before and after a key rotation
+        let verifier = Verifier::with_anchors(BTreeMap::from([
+            (b"cpak-2023".to_vec(), public_key(&keys[0])),
+            (b"cpak-2024".to_vec(), public_key(&keys[1])),
+        ]));
+        assert!(verifier
+            .verify_sign1(&token(b"cpak-2024", &keys[1]))
+            .is_ok());
+        assert!(verifier
+            .verify_sign1(&token(b"cpak-2023", &keys[0]))
+            .is_ok());
+
+        // Signed by a key other than the anchor of its kid
+        assert!(matches!(
+            verifier.verify_sign1(&token(b"cpak-2023", &keys[1])),
+            Err(Error::CCAToken(_))
+        ));
+        assert!(matches!(
+            verifier.verify_sign1(&token(b"cpak-2025", &keys[2])),
+            Err(Error::UnknownKeyId)
+        ));
+        assert!(matches!(
+            verifier.verify_sign1(&token(b"", &keys[0])),
+            Err(Error::UnknownKeyId)
+        ));
+    }
+
+    #[test]
+    fn claims_equal() {
+        use super::report::{claims_equal, claims_equal_except};
+
+        let token = attest(b"User data").unwrap().buffer;
+
+        // The last byte of the token belongs to the signature of the realm token.
+        let mut resigned = token.clone();
+        *resigned.last_mut().unwrap() ^= 0xff;
+        assert!(verify(&Report {
+            buffer: resigned.clone(),
+            user_data: Vec::new(),
+        })
+        .is_err());
+        assert!(claims_equal(&token, &resigned).unwrap());
+
+        // Modify the platform profile claim, "http://arm.com/CCA-SSD/1.0.0".
+        let profile = b"CCA-SSD/1.0.0";
+        let pos = token
+            .windows(profile.len())
+            .position(|w| w == profile)
+            .unwrap();
+        let mut different = token.clone();
+        different[pos + profile.len() - 1] = b'1';
+        assert!(!claims_equal(&token, &different).unwrap());
+        assert!(claims_equal_except(&token, &different, &[config::STR_PLAT_PROFILE]).unwrap());
+
+        assert!(claims_equal(&token, &token[1..]).is_err());
+    }
+
+    #[test]
+    fn platform_token_only() {
+        use super::error::Error;
+        use super::verifier::Verifier;
+        use cca_token::verifier::split_token;
+        use coset::{CoseSign1, TaggedCborSerializable};
+        use std::collections::BTreeMap;
+
+        // tag(399) { 44234: bstr(platform token) }
+        let platform_only = |platform_token: &[u8]| {
+            let mut buffer = vec![0xd9, 0x01, 0x8f, 0xa1, 0x19, 0xac, 0xca, 0x59];
+            buffer.extend((platform_token.len() as u16).to_be_bytes());
+            buffer.extend(platform_token);
+            Report {
+                buffer,
+                user_data: Vec::new(),
+            }
+        };
+
+        // The platform claims of the mock token, signed again with a key of the test
+        let report = attest(b"User data").unwrap();
+        let (platform_token, _realm_token) = split_token(&report.buffer).unwrap();
+        let payload = CoseSign1::from_tagged_slice(&platform_token)
+            .unwrap()
+            .payload
+            .unwrap();
+        let key = es384_key();
+        let report = platform_only(&sign_es384(b"cpak", payload.clone(), &key));
+
+        let verifier =
+            Verifier::with_anchors(BTreeMap::from([(b"cpak".to_vec(), public_key(&key))]));
+        let claims = verifier.verify_platform(&report).unwrap();
+        if let Some(ClaimData::Text(data)) = parse(&claims, config::STR_PLAT_PROFILE) {
+            assert_eq!(data, "http://arm.com/CCA-SSD/1.0.0");
+        } else {
+            assert!(false, "Claims parsing error.");
+        }
+        assert!(claims.claim(config::STR_PLAT_INSTANCE_ID).unwrap().present);
+        assert!(!claims.claim(config::STR_REALM_PUB_KEY).unwrap().present);
+
+        // Signed by a key other than the anchor
+        let forged = platform_only(&sign_es384(b"cpak", payload, &es384_key()));
+        assert!(matches!(
+            verifier.verify_platform(&forged),
+            Err(Error::CCAToken(_))
+        ));
+        // The mock platform token has no kid
+        assert!(matches!(
+            verifier.verify_platform(&platform_only(&platform_token)),
+            Err(Error::UnknownKeyId)
+        ));
+        // The full token is not a platform-only token
+        assert!(verifier
+            .verify_platform(&attest(b"User data").unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn verify_with_progress() {
+        use super::verifier::verify_with_progress;
+        use cca_token::verifier::Milestone;
+
+        let report = attest(b"User data").unwrap();
+        let mut milestones = Vec::new();
+        let claims = verify_with_progress(&report, &mut |m| milestones.push(m)).unwrap();
+
+        assert_eq!(milestones.len(), 3);
+        assert_eq!(
+            milestones[0],
+            Milestone::HeaderParsed {
+                bytes: report.buffer.len()
+            }
+        );
+        assert!(matches!(milestones[1], Milestone::ClaimsExtracted { claims } if claims > 0));
+        assert_eq!(milestones[2], Milestone::SignatureVerified);
+        // the same claims as without observing
+        let unobserved = verify(&report).unwrap();
+        assert!(claims
+            .plat_token_claims
+            .iter()
+            .chain(claims.realm_token_claims.iter())
+            .zip(
+                unobserved
+                    .plat_token_claims
+                    .iter()
+                    .chain(unobserved.realm_token_claims.iter())
+            )
+            .all(|(a, b)| a.present == b.present && a.data == b.data));
+
+        // a bad signature stops before the last milestone
+        let mut tampered = report.buffer.clone();
+        *tampered.last_mut().unwrap() ^= 0xff;
+        let tampered = Report {
+            buffer: tampered,
+            user_data: Vec::new(),
+        };
+        milestones.clear();
+        assert!(verify_with_progress(&tampered, &mut |m| milestones.push(m)).is_err());
+        assert_eq!(milestones.len(), 2);
+    }
+
+    #[test]
+    fn verify_strict() {
+        use super::verifier::verify_strict;
+        use cca_token::TokenError;
+
+        // The mock realm token carries its claims in the order of the former attester,
+        // which isn't the canonical order.
+        let report = attest(b"User data").unwrap();
+        assert!(verify(&report).is_ok());
+        assert!(matches!(
+            verify_strict(&report),
+            Err(TokenError::NonCanonicalCbor)
+        ));
+
+        // Swap the platform and the realm token in the token collection
+        let (head, entries) = report.buffer.split_at(4);
+        let platform_len = 6 + u16::from_be_bytes([entries[4], entries[5]]) as usize;
+        let (platform, realm) = entries.split_at(platform_len);
+        let reordered = Report {
+            buffer: [head, realm, platform].concat(),
+            user_data: Vec::new(),
+        };
+        assert!(matches!(
+            verify_strict(&reordered),
+            Err(TokenError::NonCanonicalCbor)
+        ));
+        assert!(verify(&reordered).is_err());
+    }
+
+    /// Returns `val` with the keys of every map sorted in the canonical order.
+    fn canonical(val: coset::cbor::Value) -> coset::cbor::Value {
+        use coset::cbor::{ser, Value};
+
+        let encoded = |key: &Value| {
+            let mut buf = Vec::new();
+            ser::into_writer(key, &mut buf).unwrap();
+            (buf.len(), buf)
+        };
+        match val {
+            Value::Map(map) => {
+                let mut map: Vec<_> = map.into_iter().map(|(k, v)| (k, canonical(v))).collect();
+                map.sort_by_cached_key(|(key, _)| encoded(key));
+                Value::Map(map)
+            }
+            Value::Array(vec) => Value::Array(vec.into_iter().map(canonical).collect()),
+            Value::Tag(tag, val) => Value::Tag(tag, Box::new(canonical(*val))),
+            val => val,
+        }
+    }
+
+    fn encode(val: &coset::cbor::Value) -> Vec<u8> {
+        let mut buf = Vec::new();
+        coset::cbor::ser::into_writer(val, &mut buf).unwrap();
+        buf
+    }
+
+    /// Returns the mock CCA token with the claims of both tokens in the canonical order,
+    /// and the realm token signed by a key of the test after `edit` on its claims.
+    fn resigned_token(
+        edit: impl FnOnce(&mut Vec<(coset::cbor::Value, coset::cbor::Value)>),
+    ) -> Vec<u8> {
+        use cca_token::verifier::split_token;
+        use coset::cbor::{de, Value};
+        use coset::{CoseSign1, TaggedCborSerializable};
+
+        let claims = |token: &[u8]| -> Value {
+            let sign1 = CoseSign1::from_tagged_slice(token).unwrap();
+            canonical(de::from_reader(&sign1.payload.unwrap()[..]).unwrap())
+        };
+
+        let (platform_token, realm_token) =
+            split_token(&attest(b"User data").unwrap().buffer).unwrap();
+        let key = es384_key();
+        let Value::Map(mut realm_claims) = claims(&realm_token) else {
+            panic!("Realm claims not a map");
+        };
+        for (label, value) in realm_claims.iter_mut() {
+            if *label == Value::Integer(config::TAG_REALM_PUB_KEY.into()) {
+                *value = Value::Bytes(public_key(&key));
+            }
+        }
+        edit(&mut realm_claims);
+        let realm_token = sign_es384(b"", encode(&Value::Map(realm_claims)), &key);
+        let platform_token = sign_es384(b"", encode(&claims(&platform_token)), &es384_key());
+
+        encode(&Value::Tag(
+            399,
+            Box::new(Value::Map(vec![
+                (Value::Integer(44234.into()), Value::Bytes(platform_token)),
+                (Value::Integer(44241.into()), Value::Bytes(realm_token)),
+            ])),
+        ))
+    }
+
+    #[test]
+    fn verify_strict_canonical() {
+        use super::verifier::verify_strict;
+        use cca_token::TokenError;
+        use coset::cbor::{de, Value};
+
+        let report = |buffer: Vec<u8>| Report {
+            buffer,
+            user_data: Vec::new(),
+        };
+
+        let collection = resigned_token(|_| {});
+        assert!(verify_strict(&report(collection.clone())).is_ok());
+
+        // tag(399), map(2), then the label of the platform token (44234)
+        assert_eq!(collection[..7], [0xd9, 0x01, 0x8f, 0xa2, 0x19, 0xac, 0xca]);
+
+        // The label encoded in 4 bytes instead of 2
+        let wide_label = [
+            &collection[..4],
+            &[0x1a, 0, 0, 0xac, 0xca],
+            &collection[7..],
+        ]
+        .concat();
+        assert!(verify(&report(wide_label.clone())).is_ok());
+        assert!(matches!(
+            verify_strict(&report(wide_label)),
+            Err(TokenError::NonCanonicalCbor)
+        ));
+
+        // The map of an indefinite length
+        let indefinite = [&collection[..3], &[0xbf], &collection[4..], &[0xff]].concat();
+        assert!(verify(&report(indefinite.clone())).is_ok());
+        assert!(matches!(
+            verify_strict(&report(indefinite)),
+            Err(TokenError::NonCanonicalCbor)
+        ));
+
+        // The platform token twice
+        let Value::Tag(tag, map) = de::from_reader(&collection[..]).unwrap() else {
+            panic!("Not a CCA token collection");
+        };
+        let Value::Map(map) = *map else {
+            panic!("Not a map");
+        };
+        let duplicate = encode(&Value::Tag(
+            tag,
+            Box::new(Value::Map(vec![map[0].clone(), map[0].clone()])),
+        ));
+        assert!(matches!(
+            verify_strict(&report(duplicate)),
+            Err(TokenError::NonCanonicalCbor)
+        ));
+    }
+
+    #[test]
+    fn verify_batch() {
+        use super::verifier::verify_batch;
+
+        let token = attest(b"User data").unwrap().buffer;
+        let mut tampered = token.clone();
+        *tampered.last_mut().unwrap() ^= 0xff;
+
+        let results = verify_batch(&[&token, &tampered, &token[..token.len() / 2], &[], &token]);
+        assert_eq!(results.len(), 5);
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1],
+            Err(Error::CCAToken(cca_token::TokenError::Signature))
+        ));
+        assert!(results[2].is_err());
+        assert!(results[3].is_err());
+        assert!(results[4].is_ok());
+
+        // Malformed tokens are reported as errors rather than panics
+        let no_key = resigned_token(|claims| {
+            claims.retain(|(label, _)| {
+                *label != coset::cbor::Value::Integer(config::TAG_REALM_PUB_KEY.into())
+            })
+        });
+        let text_key = resigned_token(|claims| {
+            for (label, value) in claims.iter_mut() {
+                if *label == coset::cbor::Value::Integer(config::TAG_REALM_PUB_KEY.into()) {
+                    *value = coset::cbor::Value::Text("key".to_string());
+                }
+            }
+        });
+        let results = verify_batch(&[&no_key, &text_key]);
+        assert!(matches!(
+            results[0],
+            Err(Error::CCAToken(cca_token::TokenError::InvalidTokenFormat(
+                _
+            )))
+        ));
+        assert!(matches!(
+            results[1],
+            Err(Error::CCAToken(cca_token::TokenError::InvalidTokenFormat(
+                _
+            )))
+        ));
+    }
+
+    #[test]
+    fn min_rmm_version() {
+        use super::policy::{check_policy, rmm_version, verify_with_policy, Policy, Version};
+
+        let report = attest(b"User data").unwrap();
+        let mut claims = verify(&report).unwrap();
+        let set_rmm_version = |claims: &mut cca_token::AttestationClaims, version: &str| {
+            let rmm = claims
+                .sw_component_claims
+                .iter_mut()
+                .find(|c| c.claims[0].data == ClaimData::Text(config::SW_TYPE_RMM.to_string()))
+                .unwrap();
+            rmm.claims[3].data = ClaimData::Text(version.to_string());
+        };
+        let policy = Policy {
+            min_rmm_version: Some(Version::new(1, 2, 0)),
+            ..Default::default()
+        };
+
+        set_rmm_version(&mut claims, "1.2.0");
+        assert_eq!(rmm_version(&claims), Some("1.2.0"));
+        assert!(check_policy(&claims, &policy).is_ok());
+
+        set_rmm_version(&mut claims, "1.10.0+3");
+        assert!(check_policy(&claims, &policy).is_ok());
+
+        set_rmm_version(&mut claims, "1.1.9");
+        assert!(matches!(
+            check_policy(&claims, &policy),
+            Err(Error::VersionTooOld)
+        ));
+
+        // The RMM of the mock token doesn't report its version
+        assert!(verify_with_policy(&report, &Policy::default()).is_ok());
+        assert!(matches!(
+            verify_with_policy(&report, &policy),
+            Err(Error::Claims)
+        ));
+    }
+
+    #[test]
+    fn structured_log() {
+        use super::log::{decode_frames, LogFrame};
+
+        let frame = LogFrame {
+            level: "WARN",
+            target: "islet_rmm::rmi",
+            msg: "msg=3:\nnot a frame",
+        };
+        let encoded = frame.encode();
+        let (decoded, rest) = LogFrame::decode(&encoded).unwrap();
+        assert_eq!(decoded, frame);
+        assert!(rest.is_empty());
+
+        // frames among human-readable text
+        let output = format!("[INFO]islet_rmm -- booting\n{}done\n{}", encoded, encoded);
+        assert_eq!(decode_frames(&output), [frame.clone(), frame]);
+
+        // truncated
+        assert!(LogFrame::decode(&encoded[..encoded.len() - 2]).is_err());
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn mock_report_version() {
+        use super::mock::{self, Header};
+
+        let report = mock::report();
+        let (header, token) = mock::verify(&report).unwrap();
+        assert_eq!(header.version, mock::VERSION);
+        assert_eq!(header.challenge_len, mock::CHALLENGE_LEN);
+        assert_eq!(token, &mock::REPORT[..]);
+
+        let older = mock::encode(
+            Header {
+                version: mock::VERSION - 1,
+                challenge_len: mock::CHALLENGE_LEN,
+            },
+            &mock::REPORT,
+        );
+        assert!(matches!(mock::verify(&older), Err(Error::VersionTooOld)));
+
+        // A bare token without the header
+        assert!(matches!(mock::verify(&mock::REPORT), Err(Error::Report)));
+        assert!(matches!(
+            mock::verify(&report[..mock::HEADER_LEN]),
+            Err(Error::Report)
+        ));
+    }
+
+    #[test]
+    fn peek_unverified() {
+        let user_data = b"User data";
+        let report = attest(user_data).unwrap();
+        let peeked: UnverifiedClaims = peek_claims(&report).unwrap();
+        let claims = verify(&report).unwrap();
+
+        for title in [
+            config::STR_USER_DATA,
+            config::STR_REALM_PUB_KEY,
+            config::STR_PLAT_PROFILE,
+            config::STR_PLAT_INSTANCE_ID,
+        ] {
+            assert_eq!(peeked.parse(title), parse(&claims, title));
+        }
+        if let Some(ClaimData::Bstr(data)) = peeked.parse(config::STR_USER_DATA) {
+            assert_eq!(&data[..user_data.len()], user_data);
+        } else {
+            assert!(false, "Claims parsing error.");
+        }
+
+        // A bad signature isn't noticed by peeking
+        let mut tampered = report.buffer.clone();
+        *tampered.last_mut().unwrap() ^= 0xff;
+        let tampered = Report {
+            buffer: tampered,
+            user_data: report.user_data.clone(),
+        };
+        assert!(verify(&tampered).is_err());
+        let peeked = peek_claims(&tampered).unwrap();
+        assert_eq!(
+            peeked.parse(config::STR_PLAT_PROFILE),
+            parse(&claims, config::STR_PLAT_PROFILE)
+        );
+    }
+
+    #[test]
+    fn reject_debug() {
+        use super::policy::{check_policy, verify_with_policy, Policy};
+
+        let report = attest(b"User data").unwrap();
+        let mut claims = verify(&report).unwrap();
+        let policy = Policy {
+            reject_debug: true,
+            ..Default::default()
+        };
+
+        // The mock realm isn't debuggable
+        assert!(!claims.realm_debug());
+        assert!(verify_with_policy(&report, &policy).is_ok());
+
+        // As the realm token of a debuggable realm carries
+        let debug = claims.claim_mut(config::STR_REALM_DEBUG).unwrap();
+        debug.data = ClaimData::Bool(true);
+        debug.present = true;
+        assert!(claims.realm_debug());
+        assert!(matches!(
+            check_policy(&claims, &policy),
+            Err(Error::PolicyMismatch)
+        ));
+        assert!(check_policy(&claims, &Policy::default()).is_ok());
+    }
+
+    #[test]
+    fn committed_ram() {
+        use coset::cbor::Value;
+
+        let report = |buffer: Vec<u8>| Report {
+            buffer,
+            user_data: Vec::new(),
+        };
+
+        // The mock RMM doesn't report it
+        let claims = verify(&attest(b"User data").unwrap()).unwrap();
+        assert_eq!(claims.committed_ram(), None);
+
+        // As the realm token of an RMM built with `committed_ram_claim` carries
+        let token = resigned_token(|claims| {
+            claims.push((
+                Value::Integer(config::TAG_REALM_COMMITTED_RAM.into()),
+                Value::Integer(0x1234.into()),
+            ))
+        });
+        let claims = verify(&report(token)).unwrap();
+        assert_eq!(claims.committed_ram(), Some(0x1234));
+        assert_eq!(
+            config::to_label(config::STR_REALM_COMMITTED_RAM),
+            config::TAG_REALM_COMMITTED_RAM
+        );
+    }
+
+    #[test]
+    fn min_lifecycle() {
+        use super::policy::{check_policy, Policy};
+        use cca_token::SecurityLifecycle;
+
+        let report = attest(b"User data").unwrap();
+        let policy = Policy {
+            min_lifecycle: Some(SecurityLifecycle::Secured),
+            ..Default::default()
+        };
+
+        // As the platform tokens of a secured and a debug platform carry
+        let lifecycle = |value: i64| {
+            let mut claims = verify(&report).unwrap();
+            let claim = claims
+                .claim_mut(config::STR_PLAT_SECURITY_LIFECYCLE)
+                .unwrap();
+            claim.data = ClaimData::Int64(value);
+            claim.present = true;
+            claims
+        };
+
+        let secured = lifecycle(0x3001);
+        assert_eq!(
+            secured.security_lifecycle(),
+            Some(SecurityLifecycle::Secured)
+        );
+        assert!(check_policy(&secured, &policy).is_ok());
+
+        let debug = lifecycle(0x4000);
+        assert_eq!(
+            debug.security_lifecycle(),
+            Some(SecurityLifecycle::NonPsaRotDebug)
+        );
+        assert!(matches!(
+            check_policy(&debug, &policy),
+            Err(Error::InsufficientLifecycleState)
+        ));
+        assert!(check_policy(&debug, &Policy::default()).is_ok());
+
+        let decommissioned = lifecycle(0x6000);
+        assert!(matches!(
+            check_policy(&decommissioned, &policy),
+            Err(Error::InsufficientLifecycleState)
+        ));
+
+        // out of the defined states
+        let invalid = lifecycle(0x7000);
+        assert_eq!(invalid.security_lifecycle(), None);
+        assert!(matches!(
+            check_policy(&invalid, &policy),
+            Err(Error::Claims)
+        ));
+    }
+
+    #[test]
+    fn realm_identity() {
+        use super::verifier::verify_realm_identity;
+
+        let report = attest(b"User data").unwrap();
+        let claims = verify(&report).unwrap();
+        let bstr = |title| match parse(&claims, title) {
+            Some(ClaimData::Bstr(data)) => data.clone(),
+            _ => panic!("Claims parsing error."),
+        };
+        let rim = bstr(config::STR_REALM_INITIAL_MEASUREMENT);
+        let rpv = bstr(config::STR_REALM_PERSONALIZATION_VALUE);
+
+        assert!(verify_realm_identity(&report, &rim, &rpv).is_ok());
+
+        let mut wrong_rim = rim.clone();
+        wrong_rim[0] ^= 0xff;
+        assert!(matches!(
+            verify_realm_identity(&report, &wrong_rim, &rpv),
+            Err(Error::RealmIdentityMismatch)
+        ));
+
+        // a clone of the realm personalized differently
+        let mut wrong_rpv = rpv.clone();
+        wrong_rpv[0] ^= 0xff;
+        assert!(matches!(
+            verify_realm_identity(&report, &rim, &wrong_rpv),
+            Err(Error::RealmIdentityMismatch)
+        ));
+        assert!(matches!(
+            verify_realm_identity(&report, &rim, &rpv[1..]),
+            Err(Error::RealmIdentityMismatch)
+        ));
+    }
+
+    #[test]
+    fn key_generation() {
+        let report = attest(b"User data").unwrap();
+
+        // The same RAK signs every token of the mock RMM
+        let generation = verify(&report).unwrap().key_generation();
+        assert!(generation.is_some());
+        assert_eq!(verify(&report).unwrap().key_generation(), generation);
+
+        // As if the realm token was signed by a rotated RAK
+        let mut claims = verify(&report).unwrap();
+        match claims.claim_mut(config::STR_REALM_PUB_KEY).unwrap().data {
+            ClaimData::Bstr(ref mut key) => key[1] ^= 0xff,
+            _ => panic!("the realm public key isn't a bstr"),
+        }
+        assert!(claims.key_generation().is_some());
+        assert_ne!(claims.key_generation(), generation);
+    }
+
+    #[test]
+    fn verification_service() {
+        use cca_token::verifier::parse_token;
+
+        let report = attest(b"User data").unwrap();
+        let claims = verify(&report).unwrap();
+        assert_eq!(claims.verification_service(), Some("http://whatever.com"));
+
+        // A URL which isn't valid UTF-8
+        let url = b"whatever.com";
+        let pos = report
+            .buffer
+            .windows(url.len())
+            .position(|w| w == url)
+            .unwrap();
+        let mut malformed = report.buffer.clone();
+        malformed[pos] = 0xff;
+        assert!(parse_token(&malformed).is_err());
+        assert!(peek_claims(&Report {
+            buffer: malformed,
+            user_data: Vec::new(),
+        })
+        .is_err());
+    }
+
     #[test]
     fn sealing() {
         use super::sealing::{seal, unseal};
@@ -47,4 +843,49 @@ mod tests {
         let unsealed = unseal(&sealed).unwrap();
         assert_eq!(plaintext, &unsealed[..]);
     }
+
+    #[test]
+    fn sealing_with_label() {
+        use super::sealing::{seal_with_label, unseal_with_label};
+        let plaintext = b"Plaintext";
+        let sealed_a = seal_with_label(plaintext, b"label-a").unwrap();
+        let sealed_b = seal_with_label(plaintext, b"label-b").unwrap();
+
+        let unsealed = unseal_with_label(&sealed_a, b"label-a").unwrap();
+        assert_eq!(plaintext, &unsealed[..]);
+        let unsealed = unseal_with_label(&sealed_b, b"label-b").unwrap();
+        assert_eq!(plaintext, &unsealed[..]);
+
+        // Blobs don't cross-unseal under another label
+        assert!(matches!(
+            unseal_with_label(&sealed_a, b"label-b"),
+            Err(Error::PolicyMismatch)
+        ));
+        assert!(matches!(
+            unseal_with_label(&sealed_b, b"label-a"),
+            Err(Error::PolicyMismatch)
+        ));
+        assert!(matches!(
+            unseal_with_label(&sealed_a, b""),
+            Err(Error::PolicyMismatch)
+        ));
+    }
+
+    #[test]
+    fn sealing_key_derivation() {
+        use super::sealing::{derive_key, seal_with_key, unseal_with_key};
+        let plaintext = b"Plaintext";
+        let key = derive_key(b"secret-a", b"label").unwrap();
+        let sealed = seal_with_key(&key, plaintext, b"label").unwrap();
+        let unsealed = unseal_with_key(&key, &sealed, b"label").unwrap();
+        assert_eq!(plaintext, &unsealed[..]);
+
+        // a key derived from another secret doesn't unseal the blob
+        let other = derive_key(b"secret-b", b"label").unwrap();
+        assert_ne!(key, other);
+        assert!(matches!(
+            unseal_with_key(&other, &sealed, b"label"),
+            Err(Error::PolicyMismatch)
+        ));
+    }
 }
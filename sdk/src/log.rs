@@ -0,0 +1,70 @@
+use crate::error::Error;
+
+/// Prefix of a structured log frame emitted by the RMM built with `structured_log`.
+pub const FRAME_PREFIX: char = '\x1e';
+
+/// A log record of the RMM decoded from a structured log frame:
+///   \x1elevel=<level> target=<len>:<target> msg=<len>:<msg>\n
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogFrame<'a> {
+    pub level: &'a str,
+    pub target: &'a str,
+    pub msg: &'a str,
+}
+
+impl<'a> LogFrame<'a> {
+    /// Encodes the record in the same way as the RMM does.
+    pub fn encode(&self) -> String {
+        format!(
+            "{}level={} target={}:{} msg={}:{}\n",
+            FRAME_PREFIX,
+            self.level,
+            self.target.len(),
+            self.target,
+            self.msg.len(),
+            self.msg
+        )
+    }
+
+    /// Decodes a frame at the start of `buf`
+    /// and returns it with the rest of `buf` following the frame.
+    pub fn decode(buf: &'a str) -> Result<(Self, &'a str), Error> {
+        let buf = buf.strip_prefix(FRAME_PREFIX).ok_or(Error::Decoding)?;
+        let buf = buf.strip_prefix("level=").ok_or(Error::Decoding)?;
+        let (level, buf) = buf.split_once(' ').ok_or(Error::Decoding)?;
+        let (target, buf) = sized_value(buf, "target=")?;
+        let buf = buf.strip_prefix(' ').ok_or(Error::Decoding)?;
+        let (msg, buf) = sized_value(buf, "msg=")?;
+        let buf = buf.strip_prefix('\n').ok_or(Error::Decoding)?;
+        Ok((Self { level, target, msg }, buf))
+    }
+}
+
+// Splits `<key><len>:<value>` off the start of `buf`.
+fn sized_value<'a>(buf: &'a str, key: &str) -> Result<(&'a str, &'a str), Error> {
+    let buf = buf.strip_prefix(key).ok_or(Error::Decoding)?;
+    let (len, buf) = buf.split_once(':').ok_or(Error::Decoding)?;
+    let len: usize = len.parse().or(Err(Error::Decoding))?;
+    if len > buf.len() || !buf.is_char_boundary(len) {
+        return Err(Error::Decoding);
+    }
+    Ok(buf.split_at(len))
+}
+
+/// Returns the structured log frames in the console output of the RMM,
+/// skipping the human-readable text in between.
+pub fn decode_frames(output: &str) -> Vec<LogFrame<'_>> {
+    let mut frames = Vec::new();
+    let mut rest = output;
+    while let Some(pos) = rest.find(FRAME_PREFIX) {
+        rest = &rest[pos..];
+        match LogFrame::decode(rest) {
+            Ok((frame, next)) => {
+                frames.push(frame);
+                rest = next;
+            }
+            Err(_) => rest = &rest[FRAME_PREFIX.len_utf8()..],
+        }
+    }
+    frames
+}
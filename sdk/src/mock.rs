@@ -1,3 +1,69 @@
+use crate::error::Error;
+
+/// Magic at the beginning of a mock report.
+pub const MAGIC: [u8; 4] = *b"IMCK";
+/// Version of the mock report format.
+///   1: header followed by the token of `REPORT_LEN` bytes
+pub const VERSION: u16 = 1;
+/// Length of the header: magic, version (le16) and challenge length (le16).
+pub const HEADER_LEN: usize = 8;
+/// Length of the realm challenge claim in `REPORT`.
+pub const CHALLENGE_LEN: u16 = 64;
+
+/// Header of a mock report.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Header {
+    pub version: u16,
+    pub challenge_len: u16,
+}
+
+/// Returns the mock report of the current version, i.e., the header followed by `REPORT`.
+pub fn report() -> Vec<u8> {
+    encode(
+        Header {
+            version: VERSION,
+            challenge_len: CHALLENGE_LEN,
+        },
+        &REPORT,
+    )
+}
+
+/// Builds a mock report of `header` carrying `token`.
+pub fn encode(header: Header, token: &[u8]) -> Vec<u8> {
+    let mut report = Vec::with_capacity(HEADER_LEN + token.len());
+    report.extend_from_slice(&MAGIC);
+    report.extend_from_slice(&header.version.to_le_bytes());
+    report.extend_from_slice(&header.challenge_len.to_le_bytes());
+    report.extend_from_slice(token);
+    report
+}
+
+/// Checks the header of the mock report and returns it with the token following it.
+///
+/// A report of an older version is rejected with `Error::VersionTooOld`
+/// rather than being read with the current format.
+pub fn verify(report: &[u8]) -> Result<(Header, &[u8]), Error> {
+    if report.len() < HEADER_LEN || report[..4] != MAGIC {
+        return Err(Error::Report);
+    }
+    let header = Header {
+        version: u16::from_le_bytes([report[4], report[5]]),
+        challenge_len: u16::from_le_bytes([report[6], report[7]]),
+    };
+    if header.version < VERSION {
+        return Err(Error::VersionTooOld);
+    }
+    if header.version > VERSION {
+        return Err(Error::NotSupported);
+    }
+
+    let token = &report[HEADER_LEN..];
+    if token.len() != REPORT_LEN {
+        return Err(Error::Report);
+    }
+    Ok((header, token))
+}
+
 const REPORT_LEN: usize = 1737;
 pub const REPORT: [u8; REPORT_LEN] = [
     0xD9, 0x01, 0x8F, 0xA2, 0x19, 0xAC, 0xCA, 0x59, 0x04, 0x96, 0xD2, 0x84, 0x44, 0xA1, 0x01, 0x38,
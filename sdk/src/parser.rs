@@ -1,4 +1,8 @@
 use crate::config;
+use crate::error::Error;
+use crate::report::Report;
+use crate::verifier::with_user_data;
+use cca_token::verifier::parse_token;
 use cca_token::{dumper::print_token, AttestationClaims, ClaimData};
 
 pub fn parse<'a>(claims: &'a AttestationClaims, title: &'static str) -> Option<&'a ClaimData> {
@@ -10,6 +14,26 @@ pub fn print_claims(claims: &AttestationClaims) {
     print_token(&claims);
 }
 
+/// The claims of a report whose signatures are NOT verified.
+///
+/// They are only for what doesn't need to trust them (e.g., routing or logging
+/// before the verification) and can't be taken where the verified claims are expected.
+#[derive(Debug)]
+pub struct UnverifiedClaims(AttestationClaims);
+
+impl UnverifiedClaims {
+    /// Returns the unverified data of the claim as `parse` does.
+    pub fn parse(&self, title: &'static str) -> Option<&ClaimData> {
+        parse(&self.0, title)
+    }
+}
+
+/// Parses the claims of the report without verifying its signatures.
+pub fn peek_claims(report: &Report) -> Result<UnverifiedClaims, Error> {
+    let claims = parse_token(&report.buffer)?;
+    Ok(UnverifiedClaims(with_user_data(report, claims)))
+}
+
 // The requirement of Certifier
 fn support_user_data(title: &'static str) -> &'static str {
     if title == config::STR_USER_DATA {
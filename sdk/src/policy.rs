@@ -0,0 +1,109 @@
+use crate::config;
+use crate::error::Error;
+use crate::report::Report;
+use crate::verifier::verify;
+
+use cca_token::{AttestationClaims as Claims, ClaimData, SecurityLifecycle, SwComponent};
+
+/// A version of a software component, e.g., "1.9.0+0".
+/// The build metadata following '+' doesn't take part in the comparison.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    pub fn parse(version: &str) -> Result<Self, Error> {
+        let version = version.split('+').next().unwrap_or_default();
+        let mut numbers = version
+            .split('.')
+            .map(|n| n.parse::<u32>().or(Err(Error::InvalidArgument)));
+        let mut next = || numbers.next().unwrap_or(Err(Error::InvalidArgument));
+        let parsed = Self::new(next()?, next()?, next()?);
+        if numbers.next().is_some() {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(parsed)
+    }
+}
+
+/// Requirements of the relying party on top of the token verification.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Policy {
+    /// The oldest RMM accepted, e.g., the first version with a security fix.
+    pub min_rmm_version: Option<Version>,
+    /// Rejects a realm created with debug access, as in production.
+    pub reject_debug: bool,
+    /// The earliest lifecycle state of the platform accepted, e.g., `Secured` in production.
+    /// A platform in a debug or decommissioned state never meets it.
+    pub min_lifecycle: Option<SecurityLifecycle>,
+}
+
+fn sw_component_data<'a>(component: &'a SwComponent, title: &str) -> Option<&'a ClaimData> {
+    component
+        .claims
+        .iter()
+        .find(|claim| claim.title == title && claim.present)
+        .map(|claim| &claim.data)
+}
+
+/// Returns the version claim of the RMM in the platform sw components.
+pub fn rmm_version(claims: &Claims) -> Option<&str> {
+    let rmm = ClaimData::Text(config::SW_TYPE_RMM.to_string());
+    claims
+        .sw_component_claims
+        .iter()
+        .filter(|component| component.present)
+        .find(|component| sw_component_data(component, config::STR_SW_COMP_TYPE) == Some(&rmm))
+        .and_then(
+            |component| match sw_component_data(component, config::STR_SW_COMP_VERSION) {
+                Some(ClaimData::Text(version)) => Some(version.as_str()),
+                _ => None,
+            },
+        )
+}
+
+/// Checks the verified claims against `policy`.
+///
+/// A token without a valid RMM version claim is rejected with `Error::Claims`
+/// when a minimum RMM version is required.
+/// A token of a debuggable realm is rejected with `Error::PolicyMismatch`
+/// when debuggable realms are rejected.
+/// A token of a platform short of the minimum lifecycle state is rejected with
+/// `Error::InsufficientLifecycleState`, or with `Error::Claims` without a valid lifecycle claim.
+pub fn check_policy(claims: &Claims, policy: &Policy) -> Result<(), Error> {
+    if policy.reject_debug && claims.realm_debug() {
+        return Err(Error::PolicyMismatch);
+    }
+    if let Some(min) = policy.min_lifecycle {
+        let lifecycle = claims.security_lifecycle().ok_or(Error::Claims)?;
+        if !lifecycle.satisfies(min) {
+            return Err(Error::InsufficientLifecycleState);
+        }
+    }
+    if let Some(min) = policy.min_rmm_version {
+        let version = rmm_version(claims).ok_or(Error::Claims)?;
+        let version = Version::parse(version).or(Err(Error::Claims))?;
+        if version < min {
+            return Err(Error::VersionTooOld);
+        }
+    }
+    Ok(())
+}
+
+/// Verifies the report and then checks its claims against `policy`.
+pub fn verify_with_policy(report: &Report, policy: &Policy) -> Result<Claims, Error> {
+    let claims = verify(report)?;
+    check_policy(&claims, policy)?;
+    Ok(claims)
+}
@@ -1,9 +1,9 @@
 pub use crate::attester::attest;
 pub use crate::config;
 pub use crate::error::Error;
-pub use crate::parser::{parse, print_claims};
+pub use crate::parser::{parse, peek_claims, print_claims, UnverifiedClaims};
 pub use crate::report::Report;
-pub use crate::sealing::{seal, unseal};
+pub use crate::sealing::{seal, seal_with_label, unseal, unseal_with_label};
 pub use crate::verifier::verify;
 
 pub use cca_token::ClaimData;
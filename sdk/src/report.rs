@@ -1,3 +1,6 @@
+use crate::error::Error;
+use cca_token::verifier::parse_token;
+use cca_token::Claim;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -5,3 +8,32 @@ pub struct Report {
     pub buffer: Vec<u8>,
     pub user_data: Vec<u8>,
 }
+
+/// Returns whether the tokens `a` and `b` carry the same claims,
+/// regardless of their signatures.
+pub fn claims_equal(a: &[u8], b: &[u8]) -> Result<bool, Error> {
+    claims_equal_except(a, b, &[])
+}
+
+/// Returns whether the tokens `a` and `b` carry the same claims
+/// except for the claims titled in `excluded` (e.g., `config::STR_REALM_CHALLENGE`).
+pub fn claims_equal_except(a: &[u8], b: &[u8], excluded: &[&str]) -> Result<bool, Error> {
+    let a = parse_token(a)?;
+    let b = parse_token(b)?;
+
+    let same = |a: &[Claim], b: &[Claim]| {
+        a.iter().zip(b).all(|(a, b)| {
+            excluded.contains(&a.title.as_str()) || (a.present == b.present && a.data == b.data)
+        })
+    };
+    let same_sw_components = a
+        .sw_component_claims
+        .iter()
+        .zip(&b.sw_component_claims)
+        .all(|(a, b)| a.present == b.present && same(&a.claims, &b.claims));
+
+    Ok(same(&a.realm_token_claims, &b.realm_token_claims)
+        && same(&a.realm_measurement_claims, &b.realm_measurement_claims)
+        && same(&a.plat_token_claims, &b.plat_token_claims)
+        && same_sw_components)
+}
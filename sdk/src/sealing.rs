@@ -1,10 +1,20 @@
 use crate::error::Error;
 
+use openssl::md::Md;
+use openssl::pkey::Id;
+use openssl::pkey_ctx::PkeyCtx;
+use openssl::rand::rand_bytes;
 use openssl::rsa::Padding;
 use openssl::rsa::Rsa;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
 
 pub const DEBUG_KEY: &[u8] = include_bytes!("../debug/rsa2048-priv.der");
 
+const LABEL_KEY_CONTEXT: &[u8] = b"islet-sealing-label";
+const KEY_LEN: usize = 32;
+const IV_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
 pub fn seal(plaintext: &[u8]) -> Result<Vec<u8>, Error> {
     let pri_key = Rsa::private_key_from_der(DEBUG_KEY).or(Err(Error::SealingKey))?;
     let padding = Padding::PKCS1;
@@ -28,3 +38,75 @@ pub fn unseal(sealed: &[u8]) -> Result<Vec<u8>, Error> {
     unsealed.truncate(len);
     Ok(unsealed)
 }
+
+// Returns the secret sealing key which the labelled keys are derived from.
+//
+// NOTE: This is the hard-coded debug key, to simulate the sealing key.
+// In future, this will be replaced by the sealing key derived from HES.
+fn sealing_key() -> &'static [u8] {
+    DEBUG_KEY
+}
+
+// Derives a key which is distinct for each label from the secret sealing key (HKDF-SHA256).
+pub(crate) fn derive_key(secret: &[u8], label: &[u8]) -> Result<Vec<u8>, Error> {
+    let derive = || {
+        let mut ctx = PkeyCtx::new_id(Id::HKDF)?;
+        ctx.derive_init()?;
+        ctx.set_hkdf_md(Md::sha256())?;
+        ctx.set_hkdf_key(secret)?;
+        ctx.add_hkdf_info(LABEL_KEY_CONTEXT)?;
+        ctx.add_hkdf_info(label)?;
+        let mut key = vec![0u8; KEY_LEN];
+        ctx.derive(Some(&mut key))?;
+        Ok::<_, openssl::error::ErrorStack>(key)
+    };
+    derive().or(Err(Error::SealingKey))
+}
+
+pub(crate) fn seal_with_key(key: &[u8], plaintext: &[u8], label: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut iv = [0u8; IV_LEN];
+    rand_bytes(&mut iv).or(Err(Error::Sealing))?;
+    let mut tag = [0u8; TAG_LEN];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(&iv),
+        label,
+        plaintext,
+        &mut tag,
+    )
+    .or(Err(Error::Sealing))?;
+
+    let mut sealed = Vec::with_capacity(IV_LEN + TAG_LEN + ciphertext.len());
+    sealed.extend_from_slice(&iv);
+    sealed.extend_from_slice(&tag);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+pub(crate) fn unseal_with_key(key: &[u8], sealed: &[u8], label: &[u8]) -> Result<Vec<u8>, Error> {
+    if sealed.len() < IV_LEN + TAG_LEN {
+        return Err(Error::InvalidArgument);
+    }
+    let (iv, rest) = sealed.split_at(IV_LEN);
+    let (tag, ciphertext) = rest.split_at(TAG_LEN);
+    decrypt_aead(Cipher::aes_256_gcm(), key, Some(iv), label, ciphertext, tag)
+        .or(Err(Error::PolicyMismatch))
+}
+
+/// Seals the plaintext with a key derived from the sealing key and `label`,
+/// so that blobs sealed for different purposes use distinct keys.
+///
+/// The sealed blob is laid out as `iv || tag || ciphertext`.
+pub fn seal_with_label(plaintext: &[u8], label: &[u8]) -> Result<Vec<u8>, Error> {
+    let key = derive_key(sealing_key(), label)?;
+    seal_with_key(&key, plaintext, label)
+}
+
+/// Unseals the blob sealed by `seal_with_label()` under the same `label`.
+///
+/// A blob sealed under another label fails with `Error::PolicyMismatch`.
+pub fn unseal_with_label(sealed: &[u8], label: &[u8]) -> Result<Vec<u8>, Error> {
+    let key = derive_key(sealing_key(), label)?;
+    unseal_with_key(&key, sealed, label)
+}
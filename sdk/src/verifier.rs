@@ -1,26 +1,173 @@
+use crate::config;
+use crate::error::Error;
+use crate::parser::parse;
 use crate::report::Report;
 
-use cca_token::{verifier::verify_token, AttestationClaims as Claims, TokenError};
+use cca_token::verifier::{
+    parse_platform_token_only, split_platform_token, split_token, verify_token,
+    verify_token_strict, verify_token_with_progress, Milestone,
+};
+use cca_token::{AttestationClaims as Claims, ClaimData, TokenError};
+use std::collections::BTreeMap;
+
+/// The serialized COSE Sig_structure, i.e., the exact bytes that were signed.
+pub type SigStructure = Vec<u8>;
+/// The raw signature of the COSE_Sign1 object.
+pub type Signature = Vec<u8>;
+/// The COSE algorithm identifier (e.g., -35 for ES384).
+pub type AlgId = i64;
+/// The COSE key identifier (`kid`) of a trust anchor.
+pub type KeyId = Vec<u8>;
+/// The SEC1-encoded public key of a trust anchor.
+pub type PublicKey = Vec<u8>;
 
 #[cfg(target_arch = "x86_64")]
 fn replace_user_data(claims: &mut Claims, user_data: Vec<u8>) {
-    let claim = claims
-        .claim_mut(crate::config::STR_REALM_CHALLENGE)
-        .expect("CCA Token should include Realm challenge.");
-    claim.data = cca_token::ClaimData::Bstr(user_data);
+    if let Some(claim) = claims.claim_mut(crate::config::STR_REALM_CHALLENGE) {
+        claim.data = cca_token::ClaimData::Bstr(user_data);
+    }
 }
 
 pub fn verify(report: &Report) -> Result<Claims, TokenError> {
-    let claims = verify_token(&report.buffer)?;
+    verify_with_progress(report, &mut |_| {})
+}
+
+/// Verifies the report, reporting the milestones of the verification to `progress`
+/// (e.g., for UI feedback). `progress` only observes and can't affect the result.
+pub fn verify_with_progress(
+    report: &Report,
+    progress: &mut dyn FnMut(Milestone),
+) -> Result<Claims, TokenError> {
+    let claims = verify_token_with_progress(&report.buffer, progress)?;
+    Ok(with_user_data(report, claims))
+}
+
+/// Verifies the report as `verify` does, additionally rejecting a token
+/// which isn't encoded in canonical CBOR with `TokenError::NonCanonicalCbor`.
+pub fn verify_strict(report: &Report) -> Result<Claims, TokenError> {
+    let claims = verify_token_strict(&report.buffer)?;
+    Ok(with_user_data(report, claims))
+}
 
+pub(crate) fn with_user_data(report: &Report, claims: Claims) -> Claims {
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "x86_64")] {
           let mut claims = claims;
           if !report.user_data.is_empty() {
               replace_user_data(&mut claims, report.user_data.clone());
           }
+        } else {
+          let _ = report;
         }
     }
 
+    claims
+}
+
+/// Checks that the claims come from the realm instance of `expected_rim`
+/// (Realm Initial Measurement) and `expected_rpv` (Realm Personalization Value),
+/// rejecting a token of any other realm, including a clone with another RPV,
+/// with `Error::RealmIdentityMismatch`.
+///
+/// A token missing either claim is rejected with `Error::Claims`.
+pub fn check_realm_identity(
+    claims: &Claims,
+    expected_rim: &[u8],
+    expected_rpv: &[u8],
+) -> Result<(), Error> {
+    let bstr = |title| match parse(claims, title) {
+        Some(ClaimData::Bstr(data)) => Ok(data),
+        _ => Err(Error::Claims),
+    };
+    let rim = bstr(config::STR_REALM_INITIAL_MEASUREMENT)?;
+    let rpv = bstr(config::STR_REALM_PERSONALIZATION_VALUE)?;
+    if rim.as_slice() != expected_rim || rpv.as_slice() != expected_rpv {
+        return Err(Error::RealmIdentityMismatch);
+    }
+    Ok(())
+}
+
+/// Verifies the report as `verify` does and then checks that it was produced
+/// by the expected realm instance (see `check_realm_identity`).
+pub fn verify_realm_identity(
+    report: &Report,
+    expected_rim: &[u8],
+    expected_rpv: &[u8],
+) -> Result<Claims, Error> {
+    let claims = verify(report)?;
+    check_realm_identity(&claims, expected_rim, expected_rpv)?;
     Ok(claims)
 }
+
+/// Verifies each of `tokens` independently and returns the result per token,
+/// so that a bad token doesn't abort the others.
+/// The verification returns an error for any malformed token instead of panicking.
+pub fn verify_batch(tokens: &[&[u8]]) -> Vec<Result<Claims, Error>> {
+    tokens
+        .iter()
+        .map(|token| verify_token(token).map_err(Error::from))
+        .collect()
+}
+
+/// Returns the signed material of the realm token in the report
+/// without verifying the signature,
+/// so that it can be verified with an external crypto library.
+pub fn extract_signed_material(report: &Report) -> Result<(SigStructure, Signature, AlgId), Error> {
+    let (_platform_token, realm_token) = split_token(&report.buffer)?;
+    cose::signing::signed_material(&realm_token, b"").or(Err(Error::Decoding))
+}
+
+/// Verifies a detached signature of the realm token, supplied as its parts
+/// (see `cose::signing::detached_parts`), with the realm public key `trust_anchor`.
+pub fn verify_detached(
+    payload: &[u8],
+    signature: &[u8],
+    protected: &[u8],
+    trust_anchor: &[u8],
+) -> Result<(), Error> {
+    cose::signing::verify_detached(payload, signature, protected, trust_anchor)
+        .or(Err(Error::CCAToken(TokenError::Signature)))
+}
+
+/// Verifies COSE_Sign1 objects (e.g., platform tokens) against several trust anchors,
+/// selecting the anchor by the `kid` header of each object.
+///
+/// It lets the signing keys be rotated without pinning a single key.
+pub struct Verifier {
+    anchors: BTreeMap<KeyId, PublicKey>,
+}
+
+impl Verifier {
+    pub fn with_anchors(anchors: BTreeMap<KeyId, PublicKey>) -> Self {
+        Self { anchors }
+    }
+
+    /// Verifies the COSE_Sign1 `object` with the anchor matching its `kid`.
+    ///
+    /// An object without a `kid` or with one matching no anchor
+    /// is rejected with `Error::UnknownKeyId`.
+    pub fn verify_sign1(&self, object: &[u8]) -> Result<(), Error> {
+        let kid = cose::signing::key_id(object).or(Err(Error::Decoding))?;
+        let key = kid
+            .and_then(|kid| self.anchors.get(&kid))
+            .ok_or(Error::UnknownKeyId)?;
+        cose::signing::verify(object, key, b"").or(Err(Error::CCAToken(TokenError::Signature)))
+    }
+
+    /// Verifies the report as `verify` does,
+    /// additionally verifying the platform token with the anchor matching its `kid`.
+    pub fn verify(&self, report: &Report) -> Result<Claims, Error> {
+        let claims = verify(report)?;
+        let (platform_token, _realm_token) = split_token(&report.buffer)?;
+        self.verify_sign1(&platform_token)?;
+        Ok(claims)
+    }
+
+    /// Verifies the report which carries only the platform token
+    /// with the anchor matching its `kid`, and returns the platform claims.
+    pub fn verify_platform(&self, report: &Report) -> Result<Claims, Error> {
+        let platform_token = split_platform_token(&report.buffer)?;
+        self.verify_sign1(&platform_token)?;
+        Ok(parse_platform_token_only(&report.buffer)?)
+    }
+}